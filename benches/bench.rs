@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use std::{
     sync::{Arc, Barrier, RwLock},
     thread,
@@ -176,5 +176,98 @@ fn insert_remove_single_thread(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, insert_remove_local, insert_remove_single_thread);
+/// Shuffles `keys` in place using a fixed-seed xorshift generator, so that
+/// every run accesses the slab in the same (cache-unfriendly) order.
+fn shuffle<T>(keys: &mut [T]) {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    for i in (1..keys.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        keys.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+const N_GETS: &[usize] = &[100, 1000, 10_000, 100_000];
+
+fn get_random(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_random");
+
+    for i in N_GETS {
+        group.bench_with_input(BenchmarkId::new("sharded_slab", i), i, |b, &i| {
+            let slab = sharded_slab::Slab::new();
+            let mut keys: Vec<_> = (0..i).map(|i| slab.insert(i).unwrap()).collect();
+            shuffle(&mut keys);
+            b.iter(|| {
+                for &key in &keys {
+                    black_box(slab.get(key).map(|item| *item));
+                }
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("slab_no_lock", i), i, |b, &i| {
+            let mut slab = slab::Slab::new();
+            let mut keys: Vec<_> = (0..i).map(|i| slab.insert(i)).collect();
+            shuffle(&mut keys);
+            b.iter(|| {
+                for &key in &keys {
+                    black_box(slab.get(key).copied());
+                }
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("slab_uncontended", i), i, |b, &i| {
+            let slab = RwLock::new(slab::Slab::new());
+            let mut keys: Vec<_> = (0..i).map(|i| slab.write().unwrap().insert(i)).collect();
+            shuffle(&mut keys);
+            b.iter(|| {
+                for &key in &keys {
+                    black_box(slab.read().unwrap().get(key).copied());
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Compares lookups in a slab constructed in a `static` with lookups in one
+/// constructed at runtime, to check that a `const`-constructed slab doesn't
+/// make every access more expensive.
+fn get_static(c: &mut Criterion) {
+    static SLAB: sharded_slab::Slab<usize> = sharded_slab::Slab::new();
+
+    let mut group = c.benchmark_group("get_static");
+
+    for i in N_GETS {
+        group.bench_with_input(BenchmarkId::new("owned", i), i, |b, &i| {
+            let slab = sharded_slab::Slab::new();
+            let mut keys: Vec<_> = (0..i).map(|i| slab.insert(i).unwrap()).collect();
+            shuffle(&mut keys);
+            b.iter(|| {
+                for &key in &keys {
+                    black_box(slab.get(key).map(|item| *item));
+                }
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("static", i), i, |b, &i| {
+            let mut keys: Vec<_> = (0..i).map(|i| SLAB.insert(i).unwrap()).collect();
+            shuffle(&mut keys);
+            b.iter(|| {
+                for &key in &keys {
+                    black_box(SLAB.get(key).map(|item| *item));
+                }
+            });
+            for key in keys {
+                SLAB.remove(key);
+            }
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    insert_remove_local,
+    insert_remove_single_thread,
+    get_random,
+    get_static
+);
 criterion_main!(benches);
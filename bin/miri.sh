@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+# Runs the library's unit tests under Miri, using the Tree Borrows aliasing
+# model and strict provenance checking.
+#
+# `DefaultConfig` uses much smaller limits when compiled under Miri, so that
+# the tests finish in a reasonable amount of time. Tests that are too slow to
+# run under Miri are ignored.
+#
+# Any arguments to this script are passed to the `cargo miri test` invocation.
+
+MIRIFLAGS="${MIRIFLAGS} -Zmiri-tree-borrows -Zmiri-strict-provenance" \
+    cargo +nightly miri test --lib "$@"
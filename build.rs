@@ -0,0 +1,40 @@
+use std::{env, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let minor = match rustc_minor_version() {
+        Some(minor) => minor,
+        None => return,
+    };
+
+    // MSRV: `OsString::clear` was stabilized in 1.46.0.
+    if minor >= 46 {
+        println!("cargo:rustc-cfg=slab_os_string_clear");
+    }
+
+    // MSRV: `shrink_to` was stabilized for the standard library's collections
+    // in 1.56.0.
+    if minor >= 56 {
+        println!("cargo:rustc-cfg=slab_shrink_to");
+    }
+
+    // MSRV: `std::thread::available_parallelism` was stabilized in 1.59.0.
+    if minor >= 59 {
+        println!("cargo:rustc-cfg=slab_available_parallelism");
+    }
+
+    // MSRV: trait bounds and function pointers in `const fn` were stabilized
+    // in 1.61.0, and `Mutex::new` became a `const fn` in 1.63.0.
+    if minor >= 63 {
+        println!("cargo:rustc-cfg=slab_const_fn");
+    }
+}
+
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    // e.g. "rustc 1.59.0 (9d1b2106e 2022-02-23)"
+    version.split('.').nth(1)?.parse().ok()
+}
@@ -0,0 +1,162 @@
+//! Derive macro for [`sharded_slab::Clear`].
+//!
+//! This crate should not be used directly; instead, enable the `derive`
+//! feature of `sharded-slab`, which re-exports the macro as
+//! `sharded_slab::Clear`.
+//!
+//! [`sharded_slab::Clear`]: https://docs.rs/sharded-slab/latest/sharded_slab/trait.Clear.html
+#![warn(missing_debug_implementations, missing_docs)]
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use std::collections::HashSet;
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Error, Ident, Index,
+    Member, Meta, NestedMeta, Type,
+};
+
+/// Derives `Clear` for a struct by clearing each of its fields.
+///
+/// Fields may be annotated with one of the following attributes:
+///
+/// - `#[clear(skip)]`: the field is left as-is.
+/// - `#[clear(default)]`: the field is reset to its type's `Default` value,
+///   rather than being cleared in place. This is useful for fields whose type
+///   does not implement `Clear`.
+///
+/// Any field whose type mentions one of the struct's type parameters adds a
+/// `Clear` (or `Default`) bound on that type to the generated impl.
+#[proc_macro_derive(Clear, attributes(clear))]
+pub fn derive_clear(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// How a single field is reset when the struct is cleared.
+enum Action {
+    Clear,
+    Default,
+    Skip,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match input.data {
+        Data::Struct(data) => data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "`Clear` can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "`Clear` can only be derived for structs",
+            ))
+        }
+    };
+
+    let params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<HashSet<_>>();
+    let mut generics = input.generics.clone();
+    let mut body = TokenStream2::new();
+    for (i, field) in fields.iter().enumerate() {
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index {
+                index: i as u32,
+                span: field.span(),
+            }),
+        };
+        let ty = &field.ty;
+        let needs_bound = mentions_any(ty, &params);
+        match field_action(&field.attrs)? {
+            Action::Clear => {
+                if needs_bound {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(parse_quote!(#ty: ::sharded_slab::Clear));
+                }
+                body.extend(quote! {
+                    ::sharded_slab::Clear::clear(&mut self.#member);
+                });
+            }
+            Action::Default => {
+                if needs_bound {
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(parse_quote!(#ty: ::core::default::Default));
+                }
+                body.extend(quote! {
+                    self.#member = ::core::default::Default::default();
+                });
+            }
+            Action::Skip => {}
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::sharded_slab::Clear for #name #ty_generics #where_clause {
+            #[inline]
+            fn clear(&mut self) {
+                #body
+            }
+        }
+    })
+}
+
+/// Parses a field's `#[clear(...)]` attributes.
+fn field_action(attrs: &[syn::Attribute]) -> syn::Result<Action> {
+    let mut action = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("clear")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new(meta.span(), "expected `#[clear(...)]`")),
+        };
+        for nested in list.nested {
+            let next = match nested {
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("skip") => Action::Skip,
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("default") => {
+                    Action::Default
+                }
+                nested => {
+                    return Err(Error::new(
+                        nested.span(),
+                        "unknown `clear` attribute; expected `skip` or `default`",
+                    ))
+                }
+            };
+            if action.is_some() {
+                return Err(Error::new(
+                    attr.span(),
+                    "only one `clear` attribute may be applied to a field",
+                ));
+            }
+            action = Some(next);
+        }
+    }
+    Ok(action.unwrap_or(Action::Clear))
+}
+
+/// Returns `true` if `ty` refers to any of the identifiers in `params`.
+fn mentions_any(ty: &Type, params: &HashSet<Ident>) -> bool {
+    fn visit(tokens: TokenStream2, params: &HashSet<Ident>) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ref ident) => params.contains(ident),
+            TokenTree::Group(group) => visit(group.stream(), params),
+            _ => false,
+        })
+    }
+    !params.is_empty() && visit(ty.to_token_stream(), params)
+}
@@ -0,0 +1,244 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    shard, Entry, Pack,
+};
+use std::{fmt, marker::PhantomData, ptr};
+
+/// A slab of reference-counted values.
+///
+/// Inserting a value into an `ArcSlab` returns an [`ArcEntry`], an owning
+/// handle to that value. Additional handles are created by cloning the
+/// `ArcEntry`, and the value is removed from the slab when the last handle to
+/// it is dropped. In other words, an `ArcEntry` behaves like an
+/// [`Arc`](std::sync::Arc), except that the value is stored in the slab's
+/// pages rather than in its own heap allocation.
+///
+/// Since an `ArcEntry`'s reference count is stored in the same word as its
+/// slot's lifecycle state, this requires no additional space per value.
+///
+/// Unlike a [`Slab`], the values in an `ArcSlab` cannot be looked up by key:
+/// the only way to access a value is through a handle to it.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::ArcSlab;
+/// let slab = ArcSlab::new();
+///
+/// let hello = slab.insert(String::from("hello world")).unwrap();
+/// let hello2 = hello.clone();
+/// assert_eq!(*hello2, "hello world");
+///
+/// // Dropping one handle doesn't remove the value...
+/// drop(hello);
+/// assert_eq!(*hello2, "hello world");
+///
+/// // ...but dropping the last one does.
+/// drop(hello2);
+/// ```
+///
+/// Handles may be sent to other threads, and the last one may be dropped on
+/// any thread:
+///
+/// ```
+/// # use sharded_slab::ArcSlab;
+/// use std::thread;
+///
+/// let slab = ArcSlab::new();
+/// let hello = slab.insert("hello world").unwrap();
+///
+/// thread::scope(|s| {
+///     for _ in 0..4 {
+///         let hello = hello.clone();
+///         s.spawn(move || assert_eq!(hello, "hello world"));
+///     }
+/// });
+/// ```
+///
+/// [`Slab`]: crate::Slab
+pub struct ArcSlab<T, C: cfg::Config = DefaultConfig> {
+    shards: shard::Array<Option<T>, C>,
+    _cfg: PhantomData<C>,
+}
+
+/// An owning, reference-counted handle to a value in an [`ArcSlab`].
+///
+/// Cloning an `ArcEntry` creates another handle to the same value. When the
+/// last handle is dropped, the value is dropped and its slot is returned to
+/// the slab.
+pub struct ArcEntry<'a, T, C: cfg::Config = DefaultConfig> {
+    inner: Entry<'a, T, C>,
+}
+
+impl<T> ArcSlab<T> {
+    /// Returns a new `ArcSlab` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `ArcSlab` with the provided configuration parameters.
+    pub fn new_with_config<C: cfg::Config>() -> ArcSlab<T, C> {
+        C::validate();
+        ArcSlab {
+            shards: shard::Array::new(),
+            _cfg: PhantomData,
+        }
+    }
+}
+
+impl<T, C: cfg::Config> ArcSlab<T, C> {
+    /// Inserts a value into the slab, returning a handle to it.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no items can be added until some are removed, or the maximum
+    /// number of shards has been reached.
+    ///
+    /// `None` is also returned if the current thread is exiting, and its
+    /// thread-local storage has already been destroyed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::ArcSlab;
+    /// let slab = ArcSlab::new();
+    ///
+    /// let entry = slab.insert("hello world").unwrap();
+    /// assert_eq!(entry, "hello world");
+    /// ```
+    pub fn insert(&self, value: T) -> Option<ArcEntry<'_, T, C>> {
+        let (tid, shard) = self.shards.current()?;
+        test_println!("ArcSlab::insert {:?}", tid);
+        let mut value = Some(value);
+        shard.init_with(|idx, slot| {
+            let mut init = slot.init()?;
+            let key = shard.tag(tid.pack(init.generation().pack(idx)));
+            let inner = unsafe {
+                // Safety: the `InitGuard` is dropped before this closure
+                // returns, and the returned guard borrows the shard.
+                *init.value_mut() = value.take();
+                // The slot is marked for removal immediately, so that it is
+                // cleared as soon as the last handle is dropped.
+                init.downgrade_marked()
+            };
+            let value = ptr::NonNull::from(slot.value().as_ref().unwrap());
+            Some(ArcEntry {
+                inner: Entry {
+                    inner,
+                    value,
+                    shard,
+                    key,
+                },
+            })
+        })
+    }
+}
+
+impl<T> Default for ArcSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for ArcSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArcSlab")
+            .field("shards", &self.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+unsafe impl<T: Send, C: cfg::Config> Send for ArcSlab<T, C> {}
+unsafe impl<T: Sync, C: cfg::Config> Sync for ArcSlab<T, C> {}
+
+// === impl ArcEntry ===
+
+impl<T, C: cfg::Config> ArcEntry<'_, T, C> {
+    /// Returns `true` if both handles refer to the same value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::{ArcEntry, ArcSlab};
+    /// let slab = ArcSlab::new();
+    ///
+    /// let hello = slab.insert("hello").unwrap();
+    /// let hello2 = hello.clone();
+    /// let also_hello = slab.insert("hello").unwrap();
+    ///
+    /// assert!(ArcEntry::ptr_eq(&hello, &hello2));
+    /// assert!(!ArcEntry::ptr_eq(&hello, &also_hello));
+    /// ```
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.inner.value == other.inner.value
+    }
+}
+
+impl<T, C: cfg::Config> Clone for ArcEntry<'_, T, C> {
+    /// Returns another handle to the same value.
+    ///
+    /// # Panics
+    ///
+    /// If the maximum number of concurrent references to the slot has been
+    /// reached.
+    fn clone(&self) -> Self {
+        let inner = unsafe {
+            // Safety: the returned guard borrows the same shard as `self`.
+            self.inner.inner.try_clone()
+        }
+        .expect("maximum number of `ArcEntry` handles to a value exceeded");
+        ArcEntry {
+            inner: Entry {
+                inner,
+                value: self.inner.value,
+                shard: self.inner.shard,
+                key: self.inner.key,
+            },
+        }
+    }
+}
+
+impl<T, C: cfg::Config> std::ops::Deref for ArcEntry<'_, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for ArcEntry<'_, T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C> fmt::Debug for ArcEntry<'_, T, C>
+where
+    T: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl<T, C> PartialEq<T> for ArcEntry<'_, T, C>
+where
+    T: PartialEq<T>,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        *self.inner == *other
+    }
+}
+
+// Like `Arc<T>`, handles may be sent between threads if the value may be both
+// shared and dropped on another thread.
+unsafe impl<T: Send + Sync, C: cfg::Config> Send for ArcEntry<'_, T, C> {}
+unsafe impl<T: Send + Sync, C: cfg::Config> Sync for ArcEntry<'_, T, C> {}
@@ -0,0 +1,223 @@
+use crate::Clear;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ffi::OsString,
+    fmt, hash,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+/// A wrapper around a collection which limits the capacity it retains when
+/// it is [cleared].
+///
+/// Pooling collections such as `Vec`s and `String`s allows their heap
+/// allocations to be reused. However, if one use of a pooled collection
+/// grows it much larger than usual (say, to buffer an unusually large
+/// request), that memory is retained for as long as the pool exists, even if
+/// it is never needed again. When a `Bounded` collection is cleared, it is
+/// also shrunk to the ceiling configured by its [`Ceiling`] type parameter,
+/// if its capacity exceeds that ceiling.
+///
+/// Because a [`Pool`] constructs its values using `Default`, the ceiling is
+/// configured by a type, rather than a value.
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::{Bounded, Ceiling, Clear};
+///
+/// struct FourKb;
+///
+/// impl Ceiling for FourKb {
+///     const MAX_CAPACITY: usize = 4096;
+/// }
+///
+/// let mut buf: Bounded<Vec<u8>, FourKb> = Bounded::default();
+///
+/// buf.extend_from_slice(&[0; 1024]);
+/// let capacity = buf.capacity();
+/// buf.clear();
+/// // Small buffers retain their capacity...
+/// assert_eq!(buf.capacity(), capacity);
+///
+/// buf.extend_from_slice(&[0; 1024 * 1024]);
+/// buf.clear();
+/// // ...but large ones are shrunk.
+/// assert!(buf.capacity() <= 4096);
+/// ```
+///
+/// [cleared]: Clear
+/// [`Pool`]: crate::Pool
+pub struct Bounded<T, L> {
+    inner: T,
+    _ceiling: PhantomData<fn(L)>,
+}
+
+/// Configures the maximum capacity retained by a [`Bounded`] collection when
+/// it is cleared.
+pub trait Ceiling {
+    /// The maximum capacity a [`Bounded`] collection retains when it is
+    /// cleared.
+    ///
+    /// This is in the units of the collection's `capacity` method: elements
+    /// for a `Vec`, or bytes for a `String`.
+    const MAX_CAPACITY: usize;
+}
+
+/// Trait implemented by collections whose capacity may be reduced.
+///
+/// This is used by [`Bounded`] to shrink collections when they are cleared.
+pub trait Shrink: Clear {
+    /// Returns the number of elements the collection can hold without
+    /// reallocating.
+    fn capacity(&self) -> usize;
+
+    /// Shrinks the capacity of the collection to at least `min_capacity`.
+    ///
+    /// The capacity may be left higher than `min_capacity`, or be reduced
+    /// below it if the collection is empty.
+    fn shrink_to(&mut self, min_capacity: usize);
+}
+
+// === impl Bounded ===
+
+impl<T: Shrink, L: Ceiling> Bounded<T, L> {
+    /// Wraps `inner` so that its retained capacity is limited when it is
+    /// cleared.
+    ///
+    /// If `inner`'s capacity already exceeds the ceiling, it is not shrunk
+    /// until it is cleared.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            _ceiling: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped collection.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Shrink, L: Ceiling> Clear for Bounded<T, L> {
+    #[inline]
+    fn clear(&mut self) {
+        self.inner.clear();
+        if self.inner.capacity() > L::MAX_CAPACITY {
+            self.inner.shrink_to(L::MAX_CAPACITY);
+        }
+    }
+}
+
+impl<T: Shrink + Default, L: Ceiling> Default for Bounded<T, L> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, L> Deref for Bounded<T, L> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T, L> DerefMut for Bounded<T, L> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: fmt::Debug, L: Ceiling> fmt::Debug for Bounded<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bounded")
+            .field("inner", &self.inner)
+            .field("max_capacity", &L::MAX_CAPACITY)
+            .finish()
+    }
+}
+
+// === impl Shrink ===
+
+// Before Rust 1.56, only `shrink_to_fit` is available. Since collections are
+// shrunk after they are cleared, this deallocates them entirely.
+macro_rules! impl_shrink {
+    (impl<$($param:ident),*> Shrink for $ty:ty $(where $($bounds:tt)+)?) => {
+        impl<$($param),*> Shrink for $ty $(where $($bounds)+)? {
+            #[inline]
+            fn capacity(&self) -> usize {
+                <$ty>::capacity(self)
+            }
+
+            #[inline]
+            #[cfg(slab_shrink_to)]
+            #[allow(clippy::incompatible_msrv)] // only built on Rust 1.56+; see build.rs
+            fn shrink_to(&mut self, min_capacity: usize) {
+                <$ty>::shrink_to(self, min_capacity)
+            }
+
+            #[inline]
+            #[cfg(not(slab_shrink_to))]
+            fn shrink_to(&mut self, _: usize) {
+                <$ty>::shrink_to_fit(self)
+            }
+        }
+    };
+}
+
+impl_shrink!(impl<T> Shrink for Vec<T>);
+impl_shrink!(impl<T> Shrink for VecDeque<T>);
+impl_shrink!(impl<> Shrink for String);
+impl_shrink!(impl<> Shrink for OsString);
+impl_shrink!(
+    impl<K, V, S> Shrink for HashMap<K, V, S>
+    where
+        K: hash::Hash + Eq,
+        S: hash::BuildHasher,
+);
+impl_shrink!(
+    impl<T, S> Shrink for HashSet<T, S>
+    where
+        T: hash::Hash + Eq,
+        S: hash::BuildHasher,
+);
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Pool;
+
+    struct Small;
+
+    impl Ceiling for Small {
+        const MAX_CAPACITY: usize = 64;
+    }
+
+    #[test]
+    fn pooled_buffers_are_shrunk() {
+        let pool: Pool<Bounded<String, Small>> = Pool::new();
+        let key = pool
+            .create_with(|buf| buf.push_str(&"a".repeat(1024)))
+            .unwrap();
+        assert!(pool.get(key).unwrap().capacity() >= 1024);
+        assert!(pool.clear(key));
+
+        let key = pool.create_with(|buf| buf.push('a')).unwrap();
+        let buf = pool.get(key).unwrap();
+        assert_eq!(buf.as_str(), "a");
+        assert!(buf.capacity() <= Small::MAX_CAPACITY);
+    }
+
+    #[test]
+    fn shrinks_maps() {
+        let mut map: Bounded<HashMap<usize, usize>, Small> = Bounded::default();
+        map.extend((0..1024).map(|i| (i, i)));
+        map.clear();
+        assert!(map.is_empty());
+        assert!(map.capacity() <= Small::MAX_CAPACITY * 2);
+    }
+}
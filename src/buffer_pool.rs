@@ -0,0 +1,346 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    pool::{Ref, RefMut},
+    Clear, Pool,
+};
+use std::{
+    alloc::{self, Layout},
+    fmt, io,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    slice,
+};
+
+/// A [`Pool`] of fixed-size, aligned byte buffers for I/O.
+///
+/// A `Pool<Vec<u8>>` retains each buffer's allocation, but a buffer may grow
+/// past the size it was created with, and is moved to a new allocation when
+/// it does. A `BufferPool` instead hands out [`AlignedBuf`]s, each of which
+/// is a single allocation of exactly the pool's buffer size and alignment,
+/// which is never reallocated:
+///
+/// - Every buffer has the same capacity. Clearing a buffer only resets its
+///   length, so a buffer is returned to the pool at the configured size no
+///   matter how it was used.
+/// - Buffers are aligned to the pool's alignment, as required by direct
+///   I/O (such as files opened with `O_DIRECT`), which usually needs
+///   buffers aligned to the device's block size.
+/// - A buffer's address is fixed from the first time its slot is used until
+///   the pool is dropped, since buffers are never taken out of a
+///   `BufferPool`, or dropped when they are cleared. This allows buffers to
+///   be registered with the kernel once, such as with io_uring's fixed
+///   buffers, and then reused for as long as the pool exists.
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::BufferPool;
+/// use std::io::Write;
+///
+/// let pool = BufferPool::new(4096, 512);
+///
+/// let mut buf = pool.create().unwrap();
+/// assert_eq!(buf.capacity(), 4096);
+/// assert_eq!(buf.as_ptr() as usize % 512, 0);
+/// buf.write_all(b"hello world").unwrap();
+///
+/// let key = buf.key();
+/// let ptr = buf.as_ptr();
+/// drop(buf);
+/// assert_eq!(&pool.get(key).unwrap()[..], b"hello world");
+///
+/// // Once cleared, the same buffer is reused, at its original size.
+/// pool.clear(key);
+/// let buf = pool.create().unwrap();
+/// assert_eq!(buf.as_ptr(), ptr);
+/// assert!(buf.is_empty());
+/// assert_eq!(buf.capacity(), 4096);
+/// ```
+pub struct BufferPool<C: cfg::Config = DefaultConfig> {
+    pool: Pool<AlignedBuf, C>,
+    layout: Layout,
+}
+
+/// A fixed-size, aligned byte buffer in a [`BufferPool`].
+///
+/// An `AlignedBuf` dereferences to the bytes that have been written to it,
+/// like a `Vec<u8>`, but its capacity is fixed when it is created. Bytes may
+/// be appended with its [`io::Write`] implementation, or written directly
+/// into its [spare capacity], for instance by a read, and then committed
+/// with [`set_len`].
+///
+/// Every byte of the buffer is initialized when it is allocated, so bytes
+/// past those that have been written hold whatever was last written there.
+///
+/// [spare capacity]: AlignedBuf::spare_capacity_mut
+/// [`set_len`]: AlignedBuf::set_len
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+impl BufferPool {
+    /// Returns a new `BufferPool` of buffers of `buffer_size` bytes, aligned
+    /// to `align` bytes, with the default configuration parameters.
+    ///
+    /// # Panics
+    ///
+    /// If `buffer_size` is zero, or if `align` is not a power of two, or if
+    /// `buffer_size`, rounded up to a multiple of `align`, overflows an
+    /// `isize`.
+    pub fn new(buffer_size: usize, align: usize) -> Self {
+        Self::new_with_config(buffer_size, align)
+    }
+
+    /// Returns a new `BufferPool` of buffers of `buffer_size` bytes, aligned
+    /// to `align` bytes, with the provided configuration parameters.
+    ///
+    /// # Panics
+    ///
+    /// Under the same conditions as [`BufferPool::new`], or if the
+    /// configuration is invalid.
+    pub fn new_with_config<C: cfg::Config>(buffer_size: usize, align: usize) -> BufferPool<C> {
+        assert!(buffer_size > 0, "buffers must not be empty");
+        let layout = Layout::from_size_align(buffer_size, align)
+            .expect("buffer alignment must be a power of two");
+        BufferPool {
+            pool: Pool::with_factory_and_config(move || AlignedBuf::new(layout)),
+            layout,
+        }
+    }
+}
+
+impl<C: cfg::Config> BufferPool<C> {
+    /// Returns the capacity of every buffer in the pool.
+    pub fn buffer_size(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns the alignment of every buffer in the pool.
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+
+    /// Creates a new, empty buffer in the pool, returning a [`RefMut`] guard
+    /// that may be used to write to it.
+    ///
+    /// As with [`Pool::create`], this returns `None` if the shard for the
+    /// current thread is full, or its thread-local storage has already been
+    /// destroyed.
+    pub fn create(&self) -> Option<RefMut<'_, AlignedBuf, C>> {
+        self.pool.create()
+    }
+
+    /// Returns a reference to the buffer associated with the given key.
+    ///
+    /// If the pool does not contain a buffer for the key, `None` is returned
+    /// instead.
+    pub fn get(&self, key: usize) -> Option<Ref<'_, AlignedBuf, C>> {
+        self.pool.get(key)
+    }
+
+    /// Clears the buffer associated with the given key, returning `true` if
+    /// it was cleared.
+    ///
+    /// As with [`Pool::clear`], if the buffer is being accessed, it is
+    /// cleared once the last guard referencing it is dropped. Either way, its
+    /// allocation is kept, and reused by a later call to [`create`].
+    ///
+    /// [`create`]: BufferPool::create
+    pub fn clear(&self, key: usize) -> bool {
+        self.pool.clear(key)
+    }
+}
+
+impl<C: cfg::Config> fmt::Debug for BufferPool<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &self.layout.size())
+            .field("align", &self.layout.align())
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl AlignedBuf ===
+
+impl AlignedBuf {
+    fn new(layout: Layout) -> Self {
+        let ptr = unsafe {
+            // Safety: `BufferPool` doesn't allow empty buffers, so the layout
+            // has a non-zero size.
+            alloc::alloc_zeroed(layout)
+        };
+        let ptr = match NonNull::new(ptr) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        };
+        Self {
+            ptr,
+            len: 0,
+            layout,
+        }
+    }
+
+    /// Returns the number of bytes which have been written to the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no bytes have been written to the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes the buffer can hold.
+    ///
+    /// This is the buffer size of the [`BufferPool`] it was created in.
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// Returns a pointer to the start of the buffer.
+    ///
+    /// The pointer is aligned to the alignment of the [`BufferPool`] the
+    /// buffer was created in, and remains valid, at the same address, until
+    /// that pool is dropped.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns a mutable pointer to the start of the buffer.
+    ///
+    /// See [`as_ptr`](AlignedBuf::as_ptr) for details.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Returns the part of the buffer past the bytes which have been written
+    /// to it.
+    ///
+    /// Once bytes have been written here, they can be added to the buffer's
+    /// contents with [`set_len`](AlignedBuf::set_len).
+    pub fn spare_capacity_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.full_mut()[len..]
+    }
+
+    /// Sets the number of bytes which have been written to the buffer.
+    ///
+    /// Since every byte of the buffer is initialized, this is safe, although
+    /// it may expose bytes left over from a previous use of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// If `len` is greater than the buffer's capacity.
+    pub fn set_len(&mut self, len: usize) {
+        assert!(
+            len <= self.capacity(),
+            "length {} exceeds buffer capacity {}",
+            len,
+            self.capacity()
+        );
+        self.len = len;
+    }
+
+    fn full_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            // Safety: the allocation is `capacity` bytes long, and was
+            // zeroed when it was allocated.
+            slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity())
+        }
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: `len` never exceeds the length of the allocation,
+            // which is always initialized.
+            slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+        }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let len = self.len;
+        &mut self.full_mut()[..len]
+    }
+}
+
+impl io::Write for AlignedBuf {
+    /// Appends as many bytes from `buf` as the buffer has room for.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let spare = self.spare_capacity_mut();
+        let n = std::cmp::min(spare.len(), buf.len());
+        spare[..n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Clear for AlignedBuf {
+    /// Empties the buffer, keeping its allocation.
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe {
+            // Safety: the buffer was allocated with this layout.
+            alloc::dealloc(self.ptr.as_ptr(), self.layout)
+        }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .field("align", &self.layout.align())
+            .finish()
+    }
+}
+
+// The buffer owns its allocation, like a `Vec<u8>`.
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::BufferPool;
+    use std::io::Write;
+
+    #[test]
+    fn write_past_capacity() {
+        let pool = BufferPool::new(8, 64);
+        let mut buf = pool.create().unwrap();
+        assert_eq!(buf.write(b"hello world").unwrap(), 8);
+        assert_eq!(&buf[..], b"hello wo");
+        assert_eq!(buf.write(b"!").unwrap(), 0);
+        assert!(buf.spare_capacity_mut().is_empty());
+
+        buf.set_len(2);
+        buf.spare_capacity_mut()[0] = b'y';
+        buf.set_len(3);
+        assert_eq!(&buf[..], b"hey");
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_len_past_capacity() {
+        let pool = BufferPool::new(8, 8);
+        pool.create().unwrap().set_len(9);
+    }
+}
@@ -12,6 +12,10 @@ pub trait Config: Sized {
     /// in the slab. If a thread is created, accesses the slab, and then terminates,
     /// its shard may be reused and thus does not count against the maximum
     /// number of threads once the thread has terminated.
+    ///
+    /// Space for shards is allocated as threads access the slab, so setting
+    /// this higher than necessary costs little memory. However, it reduces the
+    /// number of bits available for each index's generation counter.
     const MAX_THREADS: usize = DefaultConfig::MAX_THREADS;
     /// The maximum number of pages in each shard in the slab.
     ///
@@ -37,13 +41,248 @@ pub trait Config: Sized {
     /// counter. These should thus be used relatively sparingly, to ensure that
     /// generation counters are able to effectively prevent the ABA problem.
     const RESERVED_BITS: usize = 0;
+    /// If `true`, the slab records when each slot was last used.
+    ///
+    /// This is required by [`Pool::evict_idle`], which drops pooled objects
+    /// that have not been used for a while. It costs one word of storage per
+    /// slot, plus an additional atomic operation and a clock read each time a
+    /// slot is checked out or cleared, so it is disabled by default.
+    ///
+    /// [`Pool::evict_idle`]: crate::Pool::evict_idle
+    const TRACK_IDLE: bool = false;
+    /// If `true`, the slab records when the value in each slot was inserted.
+    ///
+    /// This allows [`Entry::inserted_at`] to return the time a value was
+    /// inserted, and [`Slab::keys_older_than`] to find values which have been
+    /// in the slab for longer than some duration, without storing a timestamp
+    /// in each value. It costs one word of storage per slot, plus a clock
+    /// read each time a value is inserted, so it is disabled by default.
+    ///
+    /// [`Entry::inserted_at`]: crate::Entry::inserted_at
+    /// [`Slab::keys_older_than`]: crate::Slab::keys_older_than
+    const TRACK_INSERTED: bool = false;
+    /// If `true`, the slab records when the value in each slot was last
+    /// accessed.
+    ///
+    /// This allows [`Entry::last_accessed`] to return the time a value was
+    /// last accessed, and [`Slab::idle_keys`] to find values which haven't
+    /// been accessed for a while. Inserting a value, and each call to
+    /// [`Slab::get`] or [`Slab::get_owned`] that returns it, counts as an
+    /// access. Since this reads the clock on every access, it is considerably
+    /// more expensive than [`TRACK_INSERTED`], and is disabled by default.
+    ///
+    /// This shares its storage with [`TRACK_IDLE`], so enabling both costs
+    /// only one word per slot, but a [`Pool`]'s objects are then also
+    /// considered used when they are accessed.
+    ///
+    /// [`Entry::last_accessed`]: crate::Entry::last_accessed
+    /// [`Slab::idle_keys`]: crate::Slab::idle_keys
+    /// [`Slab::get`]: crate::Slab::get
+    /// [`Slab::get_owned`]: crate::Slab::get_owned
+    /// [`TRACK_INSERTED`]: Config::TRACK_INSERTED
+    /// [`TRACK_IDLE`]: Config::TRACK_IDLE
+    /// [`Pool`]: crate::Pool
+    const TRACK_ACCESS: bool = false;
+    /// If `true`, each page records which of its slots are occupied.
+    ///
+    /// This allows iterating over a slab to visit only the slots that hold
+    /// values, rather than every slot that has ever been used, which is much
+    /// faster when a slab has grown large but is now sparsely occupied. It
+    /// costs one bit of storage per slot, plus an additional atomic operation
+    /// each time a value is inserted or removed, so it is disabled by default.
+    const TRACK_OCCUPANCY: bool = false;
+    /// If `true`, values are inserted into the free slot with the lowest
+    /// address.
+    ///
+    /// By default, the most recently freed slot in a page is reused first.
+    /// With this option, each page's free list is kept sorted, so live values
+    /// stay packed into the lowest slots of each shard's earliest pages. This
+    /// keeps iteration fast and leaves the high pages empty, so that they can
+    /// be released by [`Slab::compact`]. However, freeing a slot takes time
+    /// proportional to the number of free slots below it in its page, so it
+    /// is disabled by default.
+    ///
+    /// [`Slab::compact`]: crate::Slab::compact
+    const PREFER_LOW_ADDRESSES: bool = false;
+    /// If `true`, the slab reserves space up front for as many shards as the
+    /// machine can run threads in parallel.
+    ///
+    /// A slab's array of shards grows as threads with higher IDs access it,
+    /// but accessing a shard past the space reserved when the slab was
+    /// created costs an additional pointer dereference. By default, space is
+    /// reserved for 32 shards. With this option, it is instead reserved for
+    /// the [available parallelism], rounded up to a power of two, so that
+    /// threads in a pool with one worker per CPU core all take the fast path.
+    ///
+    /// The available parallelism can only be determined when this crate is
+    /// built with Rust 1.59 or later. Otherwise, this option has no effect.
+    ///
+    /// [available parallelism]: https://doc.rust-lang.org/std/thread/fn.available_parallelism.html
+    const AUTO_SIZE_SHARDS: bool = false;
+    /// If `true`, values are inserted into each of the slab's shards in turn,
+    /// rather than into the inserting thread's own shard.
+    ///
+    /// By default, each thread inserts values into its own shard. If most
+    /// values are inserted by one thread, such as a single producer handing
+    /// work off to a pool of consumers, they all end up in that thread's
+    /// shard, while the others sit empty. With this option, inserts are
+    /// spread across as many shards as the slab reserves space for up front
+    /// (see [`AUTO_SIZE_SHARDS`]), regardless of which thread performs them.
+    ///
+    /// Since any thread may insert into any shard, each insert must lock the
+    /// shard, and slots are always freed through the synchronized free list
+    /// that is otherwise only used by other threads. Inserts and removals are
+    /// therefore somewhat slower, so this is disabled by default.
+    ///
+    /// [`AUTO_SIZE_SHARDS`]: Config::AUTO_SIZE_SHARDS
+    const BALANCED_INSERTS: bool = false;
+    /// What to do when a slot's generation counter wraps around.
+    ///
+    /// Each time a slot is freed, its generation is advanced, so that keys
+    /// referring to the value that was removed no longer match the slot. Once
+    /// a slot has been reused as many times as its generation can count, the
+    /// generation wraps back to zero, and a key from a much earlier generation
+    /// may match a new value. This is more likely when [`RESERVED_BITS`] leaves
+    /// fewer bits for the generation.
+    ///
+    /// By default, wrapping is ignored. See [`GenerationWrap`] for the other
+    /// options.
+    ///
+    /// [`RESERVED_BITS`]: Config::RESERVED_BITS
+    const GENERATION_WRAP: GenerationWrap = GenerationWrap::Ignore;
+    /// The number of bits in each key used to identify the slab that issued
+    /// it.
+    ///
+    /// Keys from one slab are structurally valid for any other slab with the
+    /// same configuration, so using a key with the wrong slab silently
+    /// accesses an unrelated value. If this is nonzero, each slab (or pool)
+    /// tags the keys it issues with an ID, taking these bits from the
+    /// generation counter. A key tagged by a different slab is never found;
+    /// if debug assertions or the `strict` feature are enabled, using one
+    /// panics.
+    ///
+    /// Since slabs are tagged by counting them modulo `2^TAG_BITS - 1`, two
+    /// slabs may share a tag, so this can't detect every misused key. A
+    /// handful of bits is enough to catch most mixups, though. Keys issued by
+    /// a [`FixedSlab`] are never tagged.
+    ///
+    /// [`FixedSlab`]: crate::FixedSlab
+    const TAG_BITS: usize = 0;
+    /// If `true`, every thread uses the first shard, so that the keys issued
+    /// by a slab depend only on the sequence of operations performed on it.
+    ///
+    /// By default, the keys a slab issues depend on which thread inserted
+    /// each value, and on whether each value was removed by the thread that
+    /// inserted it. Tests which assert on specific key values can therefore
+    /// fail depending on which worker thread the test harness happens to run
+    /// them on. With this option, all values are inserted into shard 0, and
+    /// slots are always freed through the synchronized free list, so the same
+    /// operations always produce the same keys, on any thread.
+    ///
+    /// This is intended for tests: since every thread shares one shard,
+    /// inserts must lock it, and concurrent threads contend on its free
+    /// lists. Keys are only reproducible if [`TAG_BITS`] is zero, since tags
+    /// depend on how many slabs the process has created. Keys issued by a
+    /// [`FixedSlab`] are not affected.
+    ///
+    /// [`TAG_BITS`]: Config::TAG_BITS
+    /// [`FixedSlab`]: crate::FixedSlab
+    const DETERMINISTIC: bool = false;
+    /// If `true`, values are inserted into the shard identified by the
+    /// current shard hint, if one is set, rather than into the inserting
+    /// thread's own shard.
+    ///
+    /// A hint is set for the duration of a closure by [`with_shard_hint`], or
+    /// for each poll of a future by wrapping it in a [`ShardHinted`]. On a
+    /// work-stealing async runtime, a task may be polled by a different
+    /// worker thread each time it runs, so the values it inserts are
+    /// otherwise scattered across every worker's shard. Giving each task (or
+    /// each connection) its own hint keeps its values together in one shard,
+    /// regardless of which worker polls it.
+    ///
+    /// Hints are taken modulo the maximum number of shards. Without a hint,
+    /// values are inserted into the current thread's shard as usual. Values
+    /// may always be removed from any thread.
+    ///
+    /// Since any thread may insert into any shard, each insert must lock the
+    /// shard, and slots are always freed through the synchronized free list,
+    /// as with [`BALANCED_INSERTS`]. If both are set, hinted inserts use the
+    /// hinted shard, and the rest are balanced. [`DETERMINISTIC`] overrides
+    /// this option.
+    ///
+    /// [`with_shard_hint`]: crate::with_shard_hint
+    /// [`ShardHinted`]: crate::ShardHinted
+    /// [`BALANCED_INSERTS`]: Config::BALANCED_INSERTS
+    /// [`DETERMINISTIC`]: Config::DETERMINISTIC
+    const SHARD_HINTS: bool = false;
+    /// If `true`, keys don't include a thread ID, so that a slab's keys are
+    /// small and dense.
+    ///
+    /// By default, each key packs the ID of the shard that issued it above
+    /// the slot's address, so even the first value inserted by a thread other
+    /// than the first may have a key in the thousands, or larger. Tools that
+    /// store keys on disk, or show them to users, would rather the keys were
+    /// `0, 1, 2, ...`. With this option, the slab has a single shard, which
+    /// every thread inserts into, and the thread ID is left out of keys
+    /// entirely. Until a slot is reused, its key is just its index.
+    ///
+    /// This requires [`MAX_THREADS`] to be 1; otherwise, the configuration is
+    /// invalid. Since every thread shares one shard, inserts must lock it, as
+    /// with [`DETERMINISTIC`], so a slab with this option may still be
+    /// accessed from any thread, but is best suited to a slab which is mostly
+    /// used by one. Switching to a sharded configuration later doesn't
+    /// require any changes to code using the slab, although the keys it
+    /// issues then have a different layout. This option is not supported by
+    /// [`FixedSlab`].
+    ///
+    /// [`MAX_THREADS`]: Config::MAX_THREADS
+    /// [`DETERMINISTIC`]: Config::DETERMINISTIC
+    /// [`FixedSlab`]: crate::FixedSlab
+    const SINGLE_THREADED: bool = false;
+}
+
+/// What a slab or pool does when a slot's generation counter wraps around.
+///
+/// This is configured by [`Config::GENERATION_WRAP`]. Every option other than
+/// `Ignore` counts the number of times a generation has wrapped, which is
+/// reported by [`ShardStats::generation_wraps`] and
+/// [`PoolMetrics::generation_wraps`].
+///
+/// [`ShardStats::generation_wraps`]: crate::ShardStats::generation_wraps
+/// [`PoolMetrics::generation_wraps`]: crate::PoolMetrics::generation_wraps
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GenerationWrap {
+    /// Reuse the slot as usual, without recording that its generation
+    /// wrapped.
+    Ignore,
+    /// Reuse the slot as usual, but count the wrap.
+    Count,
+    /// Count the wrap, and panic if debug assertions are enabled.
+    ///
+    /// This is intended for tests which should never reuse a slot often
+    /// enough for its generation to wrap.
+    DebugAssert,
+    /// Count the wrap, and never reuse the slot.
+    ///
+    /// This ensures that a stale key can never refer to a new value, at the
+    /// cost of one slot of storage each time a generation wraps. The slot is
+    /// only released along with the rest of its page.
+    Quarantine,
 }
 
 pub(crate) trait CfgPrivate: Config {
-    const USED_BITS: usize = Generation::<Self>::LEN + Generation::<Self>::SHIFT;
+    const TAG_SHIFT: usize = Generation::<Self>::LEN + Generation::<Self>::SHIFT;
+    const USED_BITS: usize = Self::TAG_SHIFT + Self::TAG_BITS;
+    // If there are no tag bits, the generation may use the rest of the word,
+    // so the shift must wrap.
+    const TAG_MASK: usize = ((1usize << Self::TAG_BITS) - 1).wrapping_shl(Self::TAG_SHIFT as u32);
     const INITIAL_SZ: usize = next_pow2(Self::INITIAL_PAGE_SIZE);
     const MAX_SHARDS: usize = next_pow2(Self::MAX_THREADS - 1);
     const ADDR_INDEX_SHIFT: usize = Self::INITIAL_SZ.trailing_zeros() as usize + 1;
+    /// Whether any thread may insert into any shard, in which case inserts
+    /// lock the shard and slots are always freed to the remote free lists.
+    const SHARED_INSERTS: bool =
+        Self::BALANCED_INSERTS || Self::DETERMINISTIC || Self::SHARD_HINTS || Self::SINGLE_THREADED;
 
     fn page_size(n: usize) -> usize {
         Self::INITIAL_SZ * 2usize.pow(n as _)
@@ -54,41 +293,55 @@ pub(crate) trait CfgPrivate: Config {
     }
 
     fn validate() {
-        assert!(
-            Self::INITIAL_SZ.is_power_of_two(),
-            "invalid Config: {:#?}",
-            Self::debug(),
-        );
-        assert!(
-            Self::INITIAL_SZ <= Addr::<Self>::BITS,
-            "invalid Config: {:#?}",
-            Self::debug()
-        );
-
-        assert!(
-            Generation::<Self>::BITS >= 3,
-            "invalid Config: {:#?}\ngeneration counter should be at least 3 bits!",
-            Self::debug()
-        );
-
-        assert!(
-            Self::USED_BITS <= WIDTH,
-            "invalid Config: {:#?}\ntotal number of bits per index is too large to fit in a word!",
-            Self::debug()
-        );
-
-        assert!(
-            WIDTH - Self::USED_BITS >= Self::RESERVED_BITS,
-            "invalid Config: {:#?}\nindices are too large to fit reserved bits!",
-            Self::debug()
-        );
-
-        assert!(
-            RefCount::<Self>::MAX > 1,
-            "invalid config: {:#?}\n maximum concurrent references would be {}",
-            Self::debug(),
-            RefCount::<Self>::MAX,
-        );
+        if let Err(error) = Self::check() {
+            panic!("invalid Config: {:#?}\n{}", Self::debug(), error);
+        }
+    }
+
+    fn check() -> Result<(), ConfigError> {
+        // `next_pow2` always returns a power of two.
+        debug_assert!(Self::INITIAL_SZ.is_power_of_two());
+
+        if Self::SINGLE_THREADED && Self::MAX_THREADS != 1 {
+            return Err(ConfigError::TooManyThreads {
+                max_threads: Self::MAX_THREADS,
+            });
+        }
+
+        if Self::INITIAL_SZ > Addr::<Self>::BITS {
+            return Err(ConfigError::InitialPageSizeTooLarge {
+                initial_page_size: Self::INITIAL_SZ,
+                max: Addr::<Self>::BITS,
+            });
+        }
+
+        if Generation::<Self>::BITS < 3 {
+            return Err(ConfigError::GenerationTooSmall {
+                generation_bits: Generation::<Self>::LEN,
+            });
+        }
+
+        if Self::USED_BITS > WIDTH {
+            return Err(ConfigError::IndexTooLarge {
+                used_bits: Self::USED_BITS,
+                width: WIDTH,
+            });
+        }
+
+        if WIDTH - Self::USED_BITS < Self::RESERVED_BITS {
+            return Err(ConfigError::ReservedBitsTooLarge {
+                reserved_bits: Self::RESERVED_BITS,
+                available: WIDTH - Self::USED_BITS,
+            });
+        }
+
+        if RefCount::<Self>::MAX <= 1 {
+            return Err(ConfigError::TooFewReferences {
+                max_references: RefCount::<Self>::MAX,
+            });
+        }
+
+        Ok(())
     }
 
     #[inline(always)]
@@ -110,27 +363,245 @@ pub(crate) trait CfgPrivate: Config {
     fn unpack_gen(packed: usize) -> Generation<Self> {
         Self::unpack(packed)
     }
+
+    /// Clears the bits of `key` which are reserved for user code, so that
+    /// keys referring to the same value always compare equal.
+    #[inline(always)]
+    fn strip_reserved(key: usize) -> usize {
+        key & !(!0usize).checked_shl(Self::USED_BITS as u32).unwrap_or(0)
+    }
 }
 impl<C: Config> CfgPrivate for C {}
 
+/// An error returned when a [`Config`] is invalid.
+///
+/// This is returned by [`Slab::try_new_with_config`]; each variant describes
+/// which constraint the config violates.
+///
+/// [`Slab::try_new_with_config`]: crate::Slab::try_new_with_config
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The initial page size, rounded up to a power of two, is larger than
+    /// the number of slots that can be addressed.
+    InitialPageSizeTooLarge {
+        /// The initial page size, rounded up to a power of two.
+        initial_page_size: usize,
+        /// The largest number of slots that can be addressed.
+        max: usize,
+    },
+    /// Too few bits are left for the generation counter, which requires at
+    /// least 2 bits.
+    GenerationTooSmall {
+        /// The number of bits left for the generation counter.
+        generation_bits: usize,
+    },
+    /// The page index, thread ID, and generation together require more bits
+    /// than fit in a `usize`.
+    IndexTooLarge {
+        /// The number of bits required.
+        used_bits: usize,
+        /// The number of bits in a `usize`.
+        width: usize,
+    },
+    /// More bits are reserved than are left over by the slab.
+    ReservedBitsTooLarge {
+        /// The value of [`Config::RESERVED_BITS`].
+        reserved_bits: usize,
+        /// The number of bits left over by the slab.
+        available: usize,
+    },
+    /// The generation counter is so large that too few bits are left in a
+    /// slot for its reference count.
+    TooFewReferences {
+        /// The maximum number of concurrent references to a slot.
+        max_references: usize,
+    },
+    /// [`Config::SINGLE_THREADED`] is set, but [`Config::MAX_THREADS`] is not
+    /// 1.
+    TooManyThreads {
+        /// The value of [`Config::MAX_THREADS`].
+        max_threads: usize,
+    },
+}
+
 /// Default slab configuration values.
+///
+/// When compiled under [Miri], the default configuration allows far fewer
+/// pages per shard, since every shard eagerly allocates metadata for each of
+/// its pages, and Miri makes even tiny tests unbearably slow with the default
+/// limit. Keys from such a build are not compatible with those from a regular
+/// build.
+///
+/// [Miri]: https://github.com/rust-lang/miri
 #[derive(Copy, Clone)]
 pub struct DefaultConfig {
     _p: (),
 }
 
+/// The effective values of a [`Config`]'s parameters.
+///
+/// These are returned by [`Slab::config`]. Unlike the constants in the
+/// `Config` implementation, these are the values a slab actually uses, after
+/// rounding up to powers of two and deriving the layout of its keys.
+///
+/// [`Slab::config`]: crate::Slab::config
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConfigInfo {
+    initial_page_size: usize,
+    max_pages: usize,
+    max_shards: usize,
+    reserved_bits: usize,
+    used_bits: usize,
+    generation_bits: usize,
+    shard_capacity: usize,
+}
+
 pub(crate) struct DebugConfig<C: Config> {
     _cfg: PhantomData<fn(C)>,
 }
 
 pub(crate) const WIDTH: usize = std::mem::size_of::<usize>() * 8;
 
+/// The number of bits in each slot's lifecycle word, which packs the slot's
+/// state, reference count, and generation.
+#[cfg(not(feature = "lifecycle-u32"))]
+pub(crate) const LIFECYCLE_WIDTH: usize = WIDTH;
+#[cfg(feature = "lifecycle-u32")]
+pub(crate) const LIFECYCLE_WIDTH: usize = 32;
+
+/// The maximum number of bits of each index used for the generation counter.
+///
+/// With a full-word lifecycle, the generation may use every bit left over in
+/// the index. With a 32-bit lifecycle, the generation is capped at 15 bits,
+/// leaving 15 bits for the reference count (plus 2 for the lifecycle state).
+#[cfg(not(feature = "lifecycle-u32"))]
+pub(crate) const MAX_GENERATION_BITS: usize = WIDTH;
+#[cfg(feature = "lifecycle-u32")]
+pub(crate) const MAX_GENERATION_BITS: usize = 15;
+
+pub(crate) const fn min(a: usize, b: usize) -> usize {
+    // MSRV: `if` in `const fn` requires Rust 1.46.
+    let a_is_less = (a < b) as usize;
+    a * a_is_less + b * (1 - a_is_less)
+}
+
 pub(crate) const fn next_pow2(n: usize) -> usize {
     let pow2 = n.count_ones() == 1;
     let zeros = n.leading_zeros();
     1 << (WIDTH - zeros as usize - pow2 as usize)
 }
 
+// === impl ConfigError ===
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ConfigError::InitialPageSizeTooLarge {
+                initial_page_size,
+                max,
+            } => write!(
+                f,
+                "initial page size {} is larger than the maximum of {}",
+                initial_page_size, max
+            ),
+            ConfigError::GenerationTooSmall { generation_bits } => write!(
+                f,
+                "generation counter should be at least 2 bits, but only {} are left",
+                generation_bits
+            ),
+            ConfigError::IndexTooLarge { used_bits, width } => write!(
+                f,
+                "total number of bits per index ({}) is too large to fit in a word ({} bits)",
+                used_bits, width
+            ),
+            ConfigError::ReservedBitsTooLarge {
+                reserved_bits,
+                available,
+            } => write!(
+                f,
+                "indices are too large to fit {} reserved bits; only {} are available",
+                reserved_bits, available
+            ),
+            ConfigError::TooFewReferences { max_references } => write!(
+                f,
+                "maximum concurrent references would be {}",
+                max_references
+            ),
+            ConfigError::TooManyThreads { max_threads } => write!(
+                f,
+                "a single-threaded config must allow 1 thread, but allows {}",
+                max_threads
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// === impl ConfigInfo ===
+
+impl ConfigInfo {
+    pub(crate) fn new<C: Config>() -> Self {
+        let shard_capacity = (0..C::MAX_PAGES)
+            .map(C::page_size)
+            .fold(0usize, usize::saturating_add);
+        Self {
+            initial_page_size: C::INITIAL_SZ,
+            max_pages: C::MAX_PAGES,
+            max_shards: C::MAX_SHARDS,
+            reserved_bits: C::RESERVED_BITS,
+            used_bits: C::USED_BITS,
+            generation_bits: Generation::<C>::LEN,
+            shard_capacity,
+        }
+    }
+
+    /// Returns the number of slots in each shard's first page.
+    ///
+    /// This is [`Config::INITIAL_PAGE_SIZE`], rounded up to a power of two.
+    pub fn initial_page_size(&self) -> usize {
+        self.initial_page_size
+    }
+
+    /// Returns the maximum number of pages in each shard.
+    pub fn max_pages(&self) -> usize {
+        self.max_pages
+    }
+
+    /// Returns the maximum number of shards, and so the maximum number of
+    /// threads which may insert into the slab at once.
+    ///
+    /// This is [`Config::MAX_THREADS`], rounded up to a power of two.
+    pub fn max_shards(&self) -> usize {
+        self.max_shards
+    }
+
+    /// Returns the number of high bits of each key which are reserved for
+    /// the user.
+    pub fn reserved_bits(&self) -> usize {
+        self.reserved_bits
+    }
+
+    /// Returns the number of low bits of each key which are used by the slab.
+    pub fn used_bits(&self) -> usize {
+        self.used_bits
+    }
+
+    /// Returns the number of bits of each key used for the slot's generation.
+    ///
+    /// A slot may be reused `2^generation_bits` times before its keys repeat.
+    pub fn generation_bits(&self) -> usize {
+        self.generation_bits
+    }
+
+    /// Returns the maximum number of values each shard can hold, once all of
+    /// its pages are allocated.
+    pub fn shard_capacity(&self) -> usize {
+        self.shard_capacity
+    }
+}
+
 // === impl DefaultConfig ===
 
 impl Config for DefaultConfig {
@@ -142,7 +613,10 @@ impl Config for DefaultConfig {
     // TODO(eliza): can we find enough bits to give 32-bit platforms more threads?
     const MAX_THREADS: usize = 128;
 
+    #[cfg(not(miri))]
     const MAX_PAGES: usize = WIDTH / 2;
+    #[cfg(miri)]
+    const MAX_PAGES: usize = 8;
 }
 
 impl fmt::Debug for DefaultConfig {
@@ -159,7 +633,20 @@ impl<C: Config> fmt::Debug for DebugConfig<C> {
             .field("max_pages", &C::MAX_PAGES)
             .field("used_bits", &C::USED_BITS)
             .field("reserved_bits", &C::RESERVED_BITS)
+            .field("track_idle", &C::TRACK_IDLE)
+            .field("track_inserted", &C::TRACK_INSERTED)
+            .field("track_access", &C::TRACK_ACCESS)
+            .field("track_occupancy", &C::TRACK_OCCUPANCY)
+            .field("prefer_low_addresses", &C::PREFER_LOW_ADDRESSES)
+            .field("auto_size_shards", &C::AUTO_SIZE_SHARDS)
+            .field("balanced_inserts", &C::BALANCED_INSERTS)
+            .field("generation_wrap", &C::GENERATION_WRAP)
+            .field("tag_bits", &C::TAG_BITS)
+            .field("deterministic", &C::DETERMINISTIC)
+            .field("shard_hints", &C::SHARD_HINTS)
+            .field("single_threaded", &C::SINGLE_THREADED)
             .field("pointer_width", &WIDTH)
+            .field("lifecycle_width", &LIFECYCLE_WIDTH)
             .field("max_concurrent_references", &RefCount::<C>::MAX)
             .finish()
     }
@@ -173,6 +660,9 @@ mod tests {
 
     #[test]
     #[cfg_attr(loom, ignore)]
+    // With a 32-bit lifecycle, the generation is capped so that it always
+    // leaves room for the ref count, so this config is valid.
+    #[cfg_attr(feature = "lifecycle-u32", ignore)]
     #[should_panic]
     fn validates_max_refs() {
         struct GiantGenConfig;
@@ -191,6 +681,39 @@ mod tests {
 
     #[test]
     #[cfg_attr(loom, ignore)]
+    fn try_new_returns_errors() {
+        struct GiantGenConfig;
+
+        impl Config for GiantGenConfig {
+            const INITIAL_PAGE_SIZE: usize = 1;
+            const MAX_THREADS: usize = 1;
+            const MAX_PAGES: usize = 1;
+        }
+
+        struct TinyGenConfig;
+
+        // Reserve every bit but one above the thread ID.
+        impl Config for TinyGenConfig {
+            const RESERVED_BITS: usize = WIDTH - Generation::<Self>::SHIFT - 1;
+        }
+
+        let result = Slab::<usize>::try_new_with_config::<GiantGenConfig>();
+        if cfg!(feature = "lifecycle-u32") {
+            assert!(result.is_ok());
+        } else {
+            assert!(matches!(result, Err(ConfigError::TooFewReferences { .. })));
+        }
+
+        let result = Slab::<usize>::try_new_with_config::<TinyGenConfig>();
+        assert!(matches!(
+            result,
+            Err(ConfigError::GenerationTooSmall { generation_bits: 1 })
+        ));
+        assert!(Slab::<usize>::try_new_with_config::<DefaultConfig>().is_ok());
+    }
+
+    #[test]
+    #[cfg_attr(any(loom, miri), ignore)]
     fn big() {
         let slab = Slab::new();
 
@@ -202,7 +725,7 @@ mod tests {
     }
 
     #[test]
-    #[cfg_attr(loom, ignore)]
+    #[cfg_attr(any(loom, miri), ignore)]
     fn custom_page_sz() {
         let slab = Slab::new_with_config::<test_util::TinyConfig>();
 
@@ -0,0 +1,238 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    page, Pack, Shard, Slab, Tid,
+};
+use std::fmt;
+
+/// A value that has been moved out of a [`Slab`], while its slot remains
+/// reserved.
+///
+/// This is returned by [`Slab::checkout`]. While the `Checkout` exists, the
+/// entry's key remains valid, but the value can't be accessed through the
+/// slab: [`Slab::get`] returns `None` for that key. Calling [`checkin`] puts a
+/// value back into the slot, after which it is accessible by the same key as
+/// before.
+///
+/// If a `Checkout` is dropped without being checked in, the value it holds
+/// (if it still holds one) is put back, as though [`checkin`] had been called
+/// with it. If the value was [taken] from the `Checkout` and not checked in,
+/// the entry is removed from the slab instead.
+///
+/// If the entry is removed from the slab while it is checked out, it is
+/// removed once the `Checkout` is released, and the value that was checked
+/// in is dropped.
+///
+/// [`checkin`]: Checkout::checkin
+/// [taken]: Checkout::take
+pub struct Checkout<'a, T, C: cfg::Config = DefaultConfig> {
+    inner: page::slot::WriteGuard<Option<T>, C>,
+    value: Option<T>,
+    slab: &'a Slab<T, C>,
+    shard: &'a Shard<Option<T>, C>,
+    key: usize,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Moves the value associated with the given key out of the slab, without
+    /// removing its entry.
+    ///
+    /// This allows taking ownership of the value temporarily, such as to pass
+    /// it to a function which consumes it, without invalidating the key that
+    /// other code may hold for it. See [`Checkout`] for details.
+    ///
+    /// If the slab does not contain a value for the given key, or any guards
+    /// to that value currently exist, `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert(String::from("hello")).unwrap();
+    ///
+    /// let mut checkout = slab.checkout(key).unwrap();
+    /// let value = checkout.take().unwrap();
+    ///
+    /// // While the value is checked out, it can't be accessed...
+    /// assert!(slab.get(key).is_none());
+    ///
+    /// // ...until it (or a replacement) is checked back in.
+    /// checkout.checkin(value + " world");
+    /// assert_eq!(*slab.get(key).unwrap(), "hello world");
+    /// ```
+    pub fn checkout(&self, key: usize) -> Option<Checkout<'_, T, C>> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("checkout {:?}; current={:?}", tid, Tid::<C>::current());
+        let shard = self.shards.get(tid.as_usize())?;
+        shard.with_slot(key, |slot| {
+            let inner = slot.get_mut(C::unpack_gen(key))?;
+            let value = unsafe {
+                // Safety: the slot is locked for writing, so no other
+                // references to its value may exist until the guard is
+                // released.
+                (*inner.value_mut()).take()
+            };
+            Some(Checkout {
+                inner,
+                value,
+                slab: self,
+                shard,
+                key,
+            })
+        })
+    }
+}
+
+// === impl Checkout ===
+
+impl<T, C: cfg::Config> Checkout<'_, T, C> {
+    /// Returns the key of the checked out entry.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Takes the checked out value, returning `None` if it was already taken.
+    ///
+    /// Once the value has been taken, the entry is removed when the
+    /// `Checkout` is dropped, unless a value is [checked in].
+    ///
+    /// [checked in]: Checkout::checkin
+    pub fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+
+    /// Puts `value` into the checked out entry's slot, making it accessible
+    /// by the entry's key again.
+    ///
+    /// If the `Checkout` still holds the value that was checked out, that
+    /// value is dropped.
+    pub fn checkin(mut self, value: T) {
+        self.value = Some(value);
+    }
+}
+
+impl<T, C: cfg::Config> Drop for Checkout<'_, T, C> {
+    fn drop(&mut self) {
+        match self.value.take() {
+            Some(value) => unsafe {
+                // Safety: the slot is still locked for writing.
+                *self.inner.value_mut() = Some(value);
+            },
+            None => {
+                test_println!("-> checkout dropped without a value; removing");
+                // The slot is locked, so this only marks it to be removed
+                // when the lock is released below.
+                self.slab.remove(self.key);
+            }
+        }
+        let should_remove = unsafe {
+            // Safety: the `Checkout` borrows the slab, so the slot will not be
+            // dropped while this `WriteGuard` exists.
+            self.inner.release()
+        };
+        self.shard.on_release(self.key);
+        if should_remove {
+            self.shard.clear_after_release(self.key)
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for Checkout<'_, T, C>
+where
+    T: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkout")
+            .field("key", &format_args!("{:#x}", self.key))
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+// Like `EntryMut`, the checked out value may be mutated (and dropped) from
+// whichever thread holds the `Checkout`.
+unsafe impl<T: Send, C: cfg::Config> Send for Checkout<'_, T, C> {}
+unsafe impl<T: Sync, C: cfg::Config> Sync for Checkout<'_, T, C> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::{LookupError, Slab};
+
+    #[test]
+    fn checkout_keeps_key_valid() {
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let other = slab.insert(2).unwrap();
+
+        // Guards prevent checking out.
+        let entry = slab.get(key).unwrap();
+        assert!(slab.checkout(key).is_none());
+        drop(entry);
+
+        let mut checkout = slab.checkout(key).unwrap();
+        assert!(slab.checkout(key).is_none());
+        assert!(!slab.contains(key));
+        assert_eq!(slab.get_detailed(key).unwrap_err(), LookupError::Locked);
+        assert_eq!(checkout.take(), Some(1));
+        assert_eq!(checkout.take(), None);
+        checkout.checkin(3);
+
+        assert_eq!(slab.get(key).unwrap(), 3);
+        assert_eq!(slab.get(other).unwrap(), 2);
+
+        // Dropping a `Checkout` which still holds its value puts it back.
+        drop(slab.checkout(key).unwrap());
+        assert_eq!(slab.get(key).unwrap(), 3);
+    }
+
+    #[test]
+    fn dropped_empty_checkout_removes_entry() {
+        let slab = Slab::new();
+        let key = slab.insert(String::from("hello")).unwrap();
+        let mut checkout = slab.checkout(key).unwrap();
+        assert_eq!(checkout.take().unwrap(), "hello");
+        drop(checkout);
+
+        assert!(!slab.contains(key));
+        let key2 = slab.insert(String::from("world")).unwrap();
+        assert_ne!(key, key2);
+    }
+
+    #[test]
+    fn removed_while_checked_out() {
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let checkout = slab.checkout(key).unwrap();
+        assert!(slab.remove(key));
+        checkout.checkin(2);
+        assert!(!slab.contains(key));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn dropped_checkout_wakes_take_async() {
+        use crate::tests::util::counting_waker;
+        use std::{
+            future::Future,
+            pin::Pin,
+            sync::atomic::{AtomicUsize, Ordering},
+            task::{Context, Poll},
+        };
+
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let checkout = slab.checkout(key).unwrap();
+
+        let mut take = slab.take_async(key);
+        assert!(Pin::new(&mut take).poll(&mut cx).is_pending());
+        drop(checkout);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert_eq!(Pin::new(&mut take).poll(&mut cx), Poll::Ready(Some(1)));
+    }
+}
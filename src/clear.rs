@@ -1,4 +1,4 @@
-use std::{collections, hash, ops::DerefMut, sync};
+use std::{collections, ffi::OsString, hash, mem, ops::DerefMut, path::PathBuf, sync};
 
 /// Trait implemented by types which can be cleared in place, retaining any
 /// allocated memory.
@@ -18,9 +18,63 @@ use std::{collections, hash, ops::DerefMut, sync};
 /// no-op. In that case, it essentially serves as a marker indicating that the
 /// type may be reused to store new data.
 ///
+/// # Third-Party Types
+///
+/// `Clear` is also implemented for collection types from the following
+/// crates, when the crate-level feature of the same name is enabled:
+///
+/// - [`bytes`], for `BytesMut`
+/// - [`hashbrown`], for `HashMap` and `HashSet`
+/// - [`indexmap`], for `IndexMap` and `IndexSet`
+/// - [`smallvec`], for `SmallVec`
+///
+/// # Deriving
+///
+/// When the `derive` feature is enabled, `Clear` may be derived for structs
+/// whose fields implement `Clear`. The derived implementation clears each
+/// field in turn. A field annotated with `#[clear(skip)]` is left unchanged,
+/// and one annotated with `#[clear(default)]` is reset to its `Default`
+/// value instead; this may be used for fields whose types do not implement
+/// `Clear`.
+///
+/// ```
+/// # #[cfg(feature = "derive")]
+/// # mod example {
+/// use sharded_slab::Clear;
+/// use std::collections::HashMap;
+///
+/// #[derive(Clear, Default)]
+/// struct RequestState {
+///     headers: HashMap<String, String>,
+///     body: Vec<u8>,
+///     #[clear(default)]
+///     status: u16,
+///     #[clear(skip)]
+///     worker: usize,
+/// }
+/// # pub fn run() {
+///
+/// let mut state = RequestState::default();
+/// state.body.push(1);
+/// state.status = 404;
+/// state.worker = 2;
+/// state.clear();
+/// assert!(state.body.is_empty());
+/// assert_eq!(state.status, 0);
+/// assert_eq!(state.worker, 2);
+/// # }
+/// # }
+/// # #[cfg(feature = "derive")]
+/// # example::run();
+/// ```
+///
 /// [`Vec::clear`]: https://doc.rust-lang.org/stable/std/vec/struct.Vec.html#method.clear
 /// [`String::clear`]: https://doc.rust-lang.org/stable/std/string/struct.String.html#method.clear
 /// [`HashMap::clear`]: https://doc.rust-lang.org/stable/std/collections/struct.HashMap.html#method.clear
+/// [`bytes`]: https://crates.io/crates/bytes
+/// [`hashbrown`]: https://crates.io/crates/hashbrown
+/// [`indexmap`]: https://crates.io/crates/indexmap
+/// [`smallvec`]: https://crates.io/crates/smallvec
 pub trait Clear {
     /// Clear all data in `self`, retaining the allocated capacithy.
     fn clear(&mut self);
@@ -42,6 +96,19 @@ where
     }
 }
 
+/// Clears each element of the slice, retaining its allocation.
+impl<T> Clear for Box<[T]>
+where
+    T: Clear,
+{
+    #[inline]
+    fn clear(&mut self) {
+        for item in self.iter_mut() {
+            item.clear();
+        }
+    }
+}
+
 impl<T> Clear for Vec<T> {
     #[inline]
     fn clear(&mut self) {
@@ -49,6 +116,20 @@ impl<T> Clear for Vec<T> {
     }
 }
 
+impl<T> Clear for collections::VecDeque<T> {
+    #[inline]
+    fn clear(&mut self) {
+        collections::VecDeque::clear(self)
+    }
+}
+
+impl<T> Clear for collections::BinaryHeap<T> {
+    #[inline]
+    fn clear(&mut self) {
+        collections::BinaryHeap::clear(self)
+    }
+}
+
 impl<K, V, S> Clear for collections::HashMap<K, V, S>
 where
     K: hash::Hash + Eq,
@@ -78,6 +159,32 @@ impl Clear for String {
     }
 }
 
+impl Clear for OsString {
+    #[inline]
+    #[cfg(slab_os_string_clear)]
+    #[allow(clippy::incompatible_msrv)] // only built on Rust 1.46+; see build.rs
+    fn clear(&mut self) {
+        OsString::clear(self)
+    }
+
+    #[inline]
+    #[cfg(not(slab_os_string_clear))]
+    fn clear(&mut self) {
+        // `OsString::clear` isn't available, so the best we can do is to keep
+        // the same capacity.
+        *self = OsString::with_capacity(self.capacity());
+    }
+}
+
+impl Clear for PathBuf {
+    #[inline]
+    fn clear(&mut self) {
+        let mut path = mem::replace(self, PathBuf::new()).into_os_string();
+        Clear::clear(&mut path);
+        *self = path.into();
+    }
+}
+
 impl<T: Clear> Clear for sync::Mutex<T> {
     #[inline]
     fn clear(&mut self) {
@@ -92,9 +199,111 @@ impl<T: Clear> Clear for sync::RwLock<T> {
     }
 }
 
+#[cfg(feature = "bytes")]
+impl Clear for bytes::BytesMut {
+    #[inline]
+    fn clear(&mut self) {
+        bytes::BytesMut::clear(self)
+    }
+}
+
+#[cfg(feature = "hashbrown")]
+impl<K, V, S> Clear for hashbrown::HashMap<K, V, S> {
+    #[inline]
+    fn clear(&mut self) {
+        hashbrown::HashMap::clear(self)
+    }
+}
+
+#[cfg(feature = "hashbrown")]
+impl<T, S> Clear for hashbrown::HashSet<T, S> {
+    #[inline]
+    fn clear(&mut self) {
+        hashbrown::HashSet::clear(self)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> Clear for indexmap::IndexMap<K, V, S> {
+    #[inline]
+    fn clear(&mut self) {
+        indexmap::IndexMap::clear(self)
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<T, S> Clear for indexmap::IndexSet<T, S> {
+    #[inline]
+    fn clear(&mut self) {
+        indexmap::IndexSet::clear(self)
+    }
+}
+
+/// Clears the vector, retaining its heap allocation if it has spilled.
+#[cfg(feature = "smallvec")]
+impl<A: smallvec::Array> Clear for smallvec::SmallVec<A> {
+    #[inline]
+    fn clear(&mut self) {
+        smallvec::SmallVec::clear(self)
+    }
+}
+
 #[cfg(all(loom, test))]
 impl<T: Clear> Clear for crate::sync::alloc::Track<T> {
     fn clear(&mut self) {
         self.get_mut().clear()
     }
 }
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    fn assert_retains_capacity<T: Clear>(mut value: T, capacity: impl Fn(&T) -> usize) {
+        let before = capacity(&value);
+        value.clear();
+        assert_eq!(capacity(&value), before);
+    }
+
+    #[test]
+    fn retains_capacity() {
+        assert_retains_capacity(
+            collections::VecDeque::from(vec![1, 2, 3]),
+            collections::VecDeque::capacity,
+        );
+        assert_retains_capacity(
+            collections::BinaryHeap::from(vec![1, 2, 3]),
+            collections::BinaryHeap::capacity,
+        );
+        assert_retains_capacity(OsString::from("hello"), OsString::capacity);
+        assert_retains_capacity(PathBuf::from("/hello/world"), PathBuf::capacity);
+
+        #[cfg(feature = "bytes")]
+        assert_retains_capacity(bytes::BytesMut::from("hello"), bytes::BytesMut::capacity);
+        #[cfg(feature = "hashbrown")]
+        assert_retains_capacity(
+            (0..10)
+                .map(|i| (i, i))
+                .collect::<hashbrown::HashMap<_, _, collections::hash_map::RandomState>>(),
+            hashbrown::HashMap::capacity,
+        );
+        #[cfg(feature = "indexmap")]
+        assert_retains_capacity(
+            (0..10).collect::<indexmap::IndexSet<_>>(),
+            indexmap::IndexSet::capacity,
+        );
+        #[cfg(feature = "smallvec")]
+        assert_retains_capacity(
+            (0..10).collect::<smallvec::SmallVec<[_; 4]>>(),
+            smallvec::SmallVec::capacity,
+        );
+    }
+
+    #[test]
+    fn clears_boxed_slice_elements() {
+        let mut slice = vec![String::from("hello"), String::from("world")].into_boxed_slice();
+        slice.clear();
+        assert_eq!(slice.len(), 2);
+        assert!(slice.iter().all(|s| s.is_empty() && s.capacity() == 5));
+    }
+}
@@ -0,0 +1,433 @@
+//! Secondary storage indexed by a [`Slab`]'s keys.
+//!
+//! A [`Column`] associates additional values with the entries in a slab,
+//! without storing them in the slab itself. This is useful when only some
+//! entries have a particular kind of associated data, when the associated
+//! data is accessed separately from the primary values, or when it is defined
+//! by code that doesn't own the slab.
+//!
+//! [`Slab`]: crate::Slab
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    page::{self, slot, Slot},
+    sync::atomic::{AtomicPtr, Ordering},
+    Pack, Slab,
+};
+use std::{fmt, marker::PhantomData, ptr};
+
+/// Values associated with the entries in a [`Slab`], indexed by the same
+/// keys.
+///
+/// A column's storage has the same layout as the slab's: the value for a key
+/// is stored at the same page and offset in the column as the key's entry is
+/// in the slab. Storage for a page is allocated the first time a value is
+/// inserted in that page.
+///
+/// Each value is stored along with the generation of the key it was inserted
+/// with, so it is only accessible through that key. When the slab reuses a
+/// slot for a new entry, inserting a value for the new entry's key replaces
+/// any value left over from the slot's previous entry. This means that the
+/// column's values track the slab's entries without the column having to be
+/// told about every removal; however, a value is not dropped until it is
+/// replaced or [removed], or the column is dropped.
+///
+/// In particular, removing an entry from the slab does not drop its values
+/// in any column. If the slab's entries are removed and never replaced, their
+/// values stay allocated for as long as the column exists. Code which removes
+/// entries from a long-lived slab should also call [`Column::remove`] with
+/// the same key, for every column indexed by that slab.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// let slab = Slab::new();
+/// let names = slab.column();
+///
+/// let key = slab.insert(1.5f32).unwrap();
+/// names.insert(key, "position").unwrap();
+/// assert_eq!(names.get(key).unwrap(), "position");
+///
+/// // Once the slab reuses the slot, the old value is no longer accessible...
+/// slab.remove(key);
+/// let key2 = slab.insert(2.5).unwrap();
+/// assert!(names.get(key2).is_none());
+///
+/// // ...and is replaced by the value for the new entry.
+/// names.insert(key2, "velocity").unwrap();
+/// assert_eq!(names.get(key2).unwrap(), "velocity");
+/// assert!(names.get(key).is_none());
+/// ```
+///
+/// [`Slab`]: crate::Slab
+/// [removed]: Column::remove
+pub struct Column<U, C: cfg::Config = DefaultConfig> {
+    /// Each shard's page table, allocated the first time a value is inserted
+    /// for a key in that shard.
+    shards: Box<[AtomicPtr<Pages<U, C>>]>,
+    _cfg: PhantomData<C>,
+}
+
+/// A guard that allows access to a value in a [`Column`].
+///
+/// While the guard exists, the value will not be dropped, even if it is
+/// removed from the column.
+pub struct Entry<'a, U, C: cfg::Config = DefaultConfig> {
+    inner: slot::Guard<Option<U>, C>,
+    value: ptr::NonNull<U>,
+    slot: &'a Slot<Option<U>, C>,
+    key: usize,
+}
+
+/// A pointer to each page of slots in a shard.
+struct Pages<U, C: cfg::Config>(Box<[PagePtr<U, C>]>);
+
+/// A pointer to the first slot in a page, or null if the page has not been
+/// allocated.
+type PagePtr<U, C> = AtomicPtr<Slot<Option<U>, C>>;
+
+impl<U> Column<U> {
+    /// Returns a new `Column` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `Column` with the provided configuration parameters.
+    ///
+    /// This must be the same configuration as the slab whose keys will be
+    /// used to index the column.
+    pub fn new_with_config<C: cfg::Config>() -> Column<U, C> {
+        C::validate();
+        Column {
+            shards: (0..C::MAX_SHARDS)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            _cfg: PhantomData,
+        }
+    }
+}
+
+impl<U, C: cfg::Config> Column<U, C> {
+    /// Associates `value` with the given key.
+    ///
+    /// Any value left over from a previous entry at the same slot in the slab
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// If a value is already associated with this key, or a value left over
+    /// from a previous entry is still being accessed, `value` is returned.
+    pub fn insert(&self, key: usize, value: U) -> Result<(), U> {
+        test_println!("Column::insert {:#x}", key);
+        let slot = match self.slot_or_alloc(key) {
+            Some(slot) => slot,
+            None => return Err(value),
+        };
+        let prev = slot.insert_at(C::unpack_gen(key), value)?;
+        drop(prev);
+        Ok(())
+    }
+
+    /// Returns a guard for the value associated with the given key, if there
+    /// is one.
+    pub fn get(&self, key: usize) -> Option<Entry<'_, U, C>> {
+        test_println!("Column::get {:#x}", key);
+        let slot = self.slot(key)?;
+        let inner = slot.get(C::unpack_gen(key))?;
+        let value = ptr::NonNull::from(slot.value().as_ref().unwrap());
+        Some(Entry {
+            inner,
+            value,
+            slot,
+            key,
+        })
+    }
+
+    /// Removes the value associated with the given key, returning `true` if
+    /// there was one.
+    ///
+    /// If the value is currently being accessed, it is dropped when the last
+    /// guard to it is dropped.
+    ///
+    /// This should be called whenever the key's entry is removed from the
+    /// slab, since the column isn't notified of removals.
+    pub fn remove(&self, key: usize) -> bool {
+        test_println!("Column::remove {:#x}", key);
+        match self.slot(key) {
+            Some(slot) => slot.remove_at(C::unpack_gen(key)),
+            None => false,
+        }
+    }
+
+    /// Returns `true` if a value is associated with the given key.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    fn slot(&self, key: usize) -> Option<&Slot<Option<U>, C>> {
+        let pages = self.shards.get(C::unpack_tid(key).as_usize())?;
+        let pages = unsafe {
+            // Safety: page tables are only deallocated when the column is
+            // dropped.
+            pages.load(Ordering::Acquire).as_ref()?
+        };
+        let (addr, page_index) = page::indices::<C>(key);
+        let slots = pages.0.get(page_index)?.load(Ordering::Acquire);
+        if slots.is_null() {
+            return None;
+        }
+        let offset = addr.offset() - prev_sz::<C>(page_index);
+        Some(unsafe {
+            // Safety: pages are only deallocated when the column is dropped,
+            // and every slot in a page is initialized when it is allocated.
+            &*slots.add(offset)
+        })
+    }
+
+    fn slot_or_alloc(&self, key: usize) -> Option<&Slot<Option<U>, C>> {
+        let pages = self.shards.get(C::unpack_tid(key).as_usize())?;
+        let pages = unsafe {
+            // Safety: page tables are only deallocated when the column is
+            // dropped.
+            &*install(
+                pages,
+                || Box::into_raw(Box::new(Pages::new())),
+                |pages| drop(Box::from_raw(pages)),
+            )
+        };
+        let (addr, page_index) = page::indices::<C>(key);
+        let slots = install(
+            pages.0.get(page_index)?,
+            || alloc_page(page_index),
+            |slots| unsafe { dealloc_page(slots, page_index) },
+        );
+        let offset = addr.offset() - prev_sz::<C>(page_index);
+        Some(unsafe {
+            // Safety: pages are only deallocated when the column is dropped,
+            // and every slot in a page is initialized when it is allocated.
+            &*slots.add(offset)
+        })
+    }
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Returns a new, empty [`Column`] which may be indexed by this slab's
+    /// keys.
+    ///
+    /// This is equivalent to [`Column::new_with_config`], but infers the
+    /// configuration from the slab.
+    pub fn column<U>(&self) -> Column<U, C> {
+        Column::new_with_config()
+    }
+}
+
+impl<U> Default for Column<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U, C: cfg::Config> Drop for Column<U, C> {
+    fn drop(&mut self) {
+        for pages in self.shards.iter() {
+            let pages = pages.load(Ordering::Acquire);
+            if pages.is_null() {
+                continue;
+            }
+            let pages = unsafe {
+                // Safety: we have exclusive access to the column, and the page
+                // table was allocated by `slot_or_alloc`.
+                Box::from_raw(pages)
+            };
+            for (page_index, slots) in pages.0.iter().enumerate() {
+                let slots = slots.load(Ordering::Acquire);
+                if slots.is_null() {
+                    continue;
+                }
+                unsafe {
+                    // Safety: we have exclusive access to the column.
+                    dealloc_page(slots, page_index);
+                }
+            }
+        }
+    }
+}
+
+impl<U, C: cfg::Config> fmt::Debug for Column<U, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shards = self
+            .shards
+            .iter()
+            .filter(|pages| !pages.load(Ordering::Relaxed).is_null())
+            .count();
+        f.debug_struct("Column")
+            .field("shards", &shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// Values may be inserted on one thread and dropped on another.
+unsafe impl<U: Send, C: cfg::Config> Send for Column<U, C> {}
+unsafe impl<U: Send + Sync, C: cfg::Config> Sync for Column<U, C> {}
+
+// === impl Pages ===
+
+impl<U, C: cfg::Config> Pages<U, C> {
+    fn new() -> Self {
+        Self(
+            (0..C::MAX_PAGES)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+        )
+    }
+}
+
+/// Returns the pointer stored in `ptr`, first storing a newly allocated value
+/// if it is null.
+///
+/// If another thread stores a value first, ours is deallocated.
+fn install<T>(
+    ptr: &AtomicPtr<T>,
+    alloc: impl FnOnce() -> *mut T,
+    dealloc: impl FnOnce(*mut T),
+) -> *mut T {
+    let current = ptr.load(Ordering::Acquire);
+    if !current.is_null() {
+        return current;
+    }
+
+    let new = alloc();
+    match ptr.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => new,
+        Err(actual) => {
+            test_println!("-> lost race to allocate; deallocating");
+            dealloc(new);
+            actual
+        }
+    }
+}
+
+/// Allocates the slots for the page at `page_index`, returning a pointer to
+/// the first slot.
+fn alloc_page<U, C: cfg::Config>(page_index: usize) -> *mut Slot<Option<U>, C> {
+    // The slots' free list links are unused.
+    let slots = (0..C::page_size(page_index))
+        .map(|_| Slot::<Option<U>, C>::new(0, None))
+        .collect::<Box<[_]>>();
+    Box::into_raw(slots) as *mut Slot<Option<U>, C>
+}
+
+/// Deallocates a page allocated by `alloc_page`.
+///
+/// ## Safety
+///
+/// The page must have been allocated by `alloc_page` for the same index, and
+/// must not be accessed again.
+unsafe fn dealloc_page<U, C: cfg::Config>(slots: *mut Slot<Option<U>, C>, page_index: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        slots,
+        C::page_size(page_index),
+    )));
+}
+
+/// Returns the total size of the pages before the page at `page_index`.
+fn prev_sz<C: cfg::Config>(page_index: usize) -> usize {
+    (0..page_index).map(C::page_size).sum()
+}
+
+// === impl Entry ===
+
+impl<U, C: cfg::Config> Entry<'_, U, C> {
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<U, C: cfg::Config> std::ops::Deref for Entry<'_, U, C> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: the value is not dropped while the guard exists.
+            self.value.as_ref()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<U, C> serde::Serialize for Entry<'_, U, C>
+where
+    U: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<U, C: cfg::Config> Drop for Entry<'_, U, C> {
+    fn drop(&mut self) {
+        let should_clear = unsafe {
+            // Safety: the `Entry` borrows the column, so the slot will not be
+            // dropped while this `Guard` exists.
+            self.inner.release()
+        };
+        if should_clear {
+            self.slot.clear_vacant();
+        }
+    }
+}
+
+impl<U, C> fmt::Debug for Entry<'_, U, C>
+where
+    U: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<U, C> PartialEq<U> for Entry<'_, U, C>
+where
+    U: PartialEq<U>,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &U) -> bool {
+        **self == *other
+    }
+}
+
+unsafe impl<U: Sync, C: cfg::Config> Send for Entry<'_, U, C> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Slab;
+    use std::sync::Arc;
+
+    #[test]
+    fn remove_drops_value() {
+        let slab = Slab::new();
+        let column = slab.column();
+        let value = Arc::new(());
+
+        let key = slab.insert(()).unwrap();
+        column.insert(key, value.clone()).unwrap();
+
+        // Removing the entry from the slab doesn't drop its value...
+        assert!(slab.remove(key));
+        assert_eq!(Arc::strong_count(&value), 2);
+
+        // ...until it is removed from the column.
+        let guard = column.get(key).unwrap();
+        assert!(column.remove(key));
+        assert!(!column.contains(key));
+        assert_eq!(Arc::strong_count(&value), 2);
+        drop(guard);
+        assert_eq!(Arc::strong_count(&value), 1);
+        assert!(!column.remove(key));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    cfg::{self, CfgPrivate},
+    Pack, Slab,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// A handle to a value which was removed from a [`Slab`] by
+/// [`Slab::take_deferred`], and which is delivered once every guard
+/// referencing it has been dropped.
+///
+/// The value is moved into the handle by whichever thread drops the last
+/// guard, so the thread that removed it never has to wait. If the handle is
+/// dropped before the value is delivered, the value is dropped when it is
+/// delivered, rather than leaked.
+///
+/// The value may be received by blocking on [`wait`], or by polling
+/// [`try_take`]. If the `async` feature is enabled, `TakeHandle` is also a
+/// [`Future`].
+///
+/// [`wait`]: TakeHandle::wait
+/// [`try_take`]: TakeHandle::try_take
+/// [`Future`]: std::future::Future
+pub struct TakeHandle<T> {
+    delivery: Arc<Delivery<Option<T>>>,
+}
+
+/// The takes in a shard which are waiting for the last guard referencing
+/// their value to be dropped.
+pub(crate) struct DeferredTakes<T> {
+    /// The number of pending takes.
+    ///
+    /// This is checked every time a removed value's last guard is dropped, so
+    /// that the lock is only acquired if a take may be waiting for it.
+    pending: AtomicUsize,
+    /// The delivery for each pending take, by the key of the value being
+    /// taken.
+    takes: Mutex<HashMap<usize, Arc<Delivery<T>>>>,
+}
+
+/// A value being passed from the thread that drops its last guard to a
+/// [`TakeHandle`].
+pub(crate) struct Delivery<T> {
+    state: Mutex<Delivered<T>>,
+    delivered: Condvar,
+}
+
+struct Delivered<T> {
+    /// The delivered value, if it has been delivered and not yet received.
+    ///
+    /// This is `None` once delivered if the value was taken by someone else.
+    value: Option<T>,
+    /// Whether anything has been delivered.
+    done: bool,
+    #[cfg(feature = "async")]
+    waker: Option<Waker>,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Removes the value associated with the given key from the slab, and
+    /// returns a [`TakeHandle`] that it will be delivered to.
+    ///
+    /// Unlike [`take`], this never blocks. If the value isn't being accessed,
+    /// it is delivered to the handle immediately. Otherwise, it is marked for
+    /// removal, like [`remove`], and delivered by whichever thread drops the
+    /// last guard referencing it. Either way, no new guards can be acquired
+    /// through `key` once this returns.
+    ///
+    /// If the slab does not contain a value for the key, `None` is returned
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let slab = Arc::new(Slab::new());
+    /// let key = slab.insert(String::from("hello world")).unwrap();
+    ///
+    /// let guard = slab.clone().get_owned(key).unwrap();
+    /// let mut handle = slab.take_deferred(key).unwrap();
+    /// assert!(!slab.contains(key));
+    /// assert_eq!(handle.try_take(), None);
+    ///
+    /// // The value is delivered when the other thread drops its guard.
+    /// thread::spawn(move || {
+    ///     assert_eq!(guard.as_str(), "hello world");
+    /// });
+    /// assert_eq!(handle.wait().unwrap(), "hello world");
+    /// ```
+    ///
+    /// [`take`]: Slab::take
+    /// [`remove`]: Slab::remove
+    pub fn take_deferred(&self, key: usize) -> Option<TakeHandle<T>> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("take_deferred {:?}", tid);
+        let shard = self.shards.get(tid.as_usize())?;
+        let delivery = shard.take_deferred(key)?;
+        Some(TakeHandle { delivery })
+    }
+}
+
+// === impl TakeHandle ===
+
+impl<T> TakeHandle<T> {
+    /// Returns `true` if the value has been delivered.
+    pub fn is_ready(&self) -> bool {
+        self.delivery.lock().done
+    }
+
+    /// Receives the value, if it has been delivered.
+    ///
+    /// Once the value has been received, this always returns `None`.
+    pub fn try_take(&mut self) -> Option<T> {
+        self.delivery.lock().value.take().and_then(|value| value)
+    }
+
+    /// Blocks the current thread until the value is delivered, and returns
+    /// it.
+    ///
+    /// This returns `None` if the value was taken by a concurrent call to
+    /// [`Slab::take`], or was already received by [`try_take`].
+    ///
+    /// [`try_take`]: TakeHandle::try_take
+    pub fn wait(self) -> Option<T> {
+        let mut state = self.delivery.lock();
+        while !state.done {
+            state = self
+                .delivery
+                .delivered
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        state.value.take().and_then(|value| value)
+    }
+}
+
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+impl<T> Future for TakeHandle<T> {
+    /// The removed value, or `None` if it was taken by a concurrent call to
+    /// [`Slab::take`].
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.delivery.lock();
+        if state.done {
+            return Poll::Ready(state.value.take().and_then(|value| value));
+        }
+        match state.waker {
+            Some(ref waker) if waker.will_wake(cx.waker()) => {}
+            _ => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> fmt::Debug for TakeHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TakeHandle")
+            .field("done", &self.delivery.lock().done)
+            .finish()
+    }
+}
+
+// === impl DeferredTakes ===
+
+impl<T> DeferredTakes<T> {
+    /// Registers a take of the value at `key`, returning the delivery it will
+    /// be passed to, or `None` if the value is already being taken.
+    ///
+    /// This must be called before the value is marked for removal, so that
+    /// the thread which drops its last guard finds the take.
+    pub(crate) fn register(&self, key: usize) -> Option<Arc<Delivery<T>>> {
+        let mut takes = self.lock();
+        if takes.contains_key(&key) {
+            return None;
+        }
+        let delivery = Arc::new(Delivery::new());
+        takes.insert(key, delivery.clone());
+        self.pending.store(takes.len(), Ordering::SeqCst);
+        Some(delivery)
+    }
+
+    /// Removes the take of the value at `key`, if one is registered and
+    /// hasn't been claimed already.
+    pub(crate) fn claim(&self, key: usize) -> Option<Arc<Delivery<T>>> {
+        if self.pending.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let mut takes = self.lock();
+        let delivery = takes.remove(&key)?;
+        self.pending.store(takes.len(), Ordering::SeqCst);
+        test_println!("-> claimed deferred take of {:#x}", key);
+        Some(delivery)
+    }
+
+    /// No user code runs while this lock is held, so a panic can't leave the
+    /// pending takes inconsistent.
+    fn lock(&self) -> MutexGuard<'_, HashMap<usize, Arc<Delivery<T>>>> {
+        self.takes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Default for DeferredTakes<T> {
+    fn default() -> Self {
+        Self {
+            pending: AtomicUsize::new(0),
+            takes: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for DeferredTakes<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeferredTakes")
+            .field("pending", &self.pending.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+// === impl Delivery ===
+
+impl<T> Delivery<T> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(Delivered {
+                value: None,
+                done: false,
+                #[cfg(feature = "async")]
+                waker: None,
+            }),
+            delivered: Condvar::new(),
+        }
+    }
+
+    /// Delivers `value`, waking the handle if it is waiting.
+    pub(crate) fn deliver(&self, value: Option<T>) {
+        let mut state = self.lock();
+        state.value = value;
+        state.done = true;
+        #[cfg(feature = "async")]
+        let waker = state.waker.take();
+        drop(state);
+        self.delivered.notify_all();
+        #[cfg(feature = "async")]
+        {
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    /// A delivered value is never dropped while this lock is held, so a
+    /// panic can't leave the state inconsistent.
+    fn lock(&self) -> MutexGuard<'_, Delivered<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Slab;
+    use std::sync::Arc;
+
+    #[test]
+    fn delivered_by_last_guard() {
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(1).unwrap();
+        let unreferenced = slab.insert(2).unwrap();
+
+        let guards = (0..2).map(|_| slab.get(key).unwrap()).collect::<Vec<_>>();
+        let mut handle = slab.take_deferred(key).unwrap();
+        assert!(slab.take_deferred(key).is_none());
+        assert!(slab.get(key).is_none());
+
+        let mut guards = guards.into_iter();
+        drop(guards.next());
+        assert!(!handle.is_ready());
+        drop(guards.next());
+        assert!(handle.is_ready());
+        assert_eq!(handle.try_take(), Some(1));
+        assert_eq!(handle.try_take(), None);
+
+        // An unreferenced value is delivered immediately.
+        let handle = slab.take_deferred(unreferenced).unwrap();
+        assert!(handle.is_ready());
+        assert_eq!(handle.wait(), Some(2));
+        assert!(!slab.contains(unreferenced));
+
+        // The slot is reused once the value is delivered.
+        let reused = slab.insert(3).unwrap();
+        assert_eq!(slab.get(reused).unwrap(), 3);
+    }
+}
@@ -0,0 +1,578 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+    Entry, Slab,
+};
+use std::{fmt, ptr};
+
+/// A slab which identifies its values by small, dense `u32` handles, rather
+/// than by packed keys.
+///
+/// A slab's keys pack a thread ID, a generation, and an address into a
+/// `usize`, so they are sparse and may use every bit of the word. This makes
+/// them a poor fit for indexing arrays which are mirrored elsewhere, such as
+/// buffers on a GPU, or for compact serialization formats. A `DenseSlab`
+/// hands out `u32` handles instead, always reusing the lowest free handle, so
+/// the handles in use stay close to `0..len`. Each handle is translated to
+/// the key of its value in an inner [`Slab`] through a table which is
+/// maintained alongside it.
+///
+/// Looking up a handle is lock-free, like accessing a [`Slab`], and so are
+/// inserting and removing values: the free handles are tracked in a bitmap
+/// alongside the table, which is searched from the lowest handle up. While
+/// other threads are inserting and removing values concurrently, a handle a
+/// little higher than the lowest free one may be issued.
+///
+/// Unlike keys, handles don't have a generation: once a value is removed,
+/// its handle may be issued again for another value, just like an index
+/// into a `Vec`. The packed [`key`] for a handle does carry a generation, so
+/// it may be stored instead where a stale handle must not refer to a new
+/// value.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::DenseSlab;
+/// let slab = DenseSlab::new();
+/// let a = slab.insert("a").unwrap();
+/// let b = slab.insert("b").unwrap();
+/// assert_eq!((a, b), (0, 1));
+/// assert_eq!(slab.get(b).unwrap(), "b");
+///
+/// // The lowest free handle is reused first.
+/// assert!(slab.remove(a));
+/// assert_eq!(slab.insert("c").unwrap(), 0);
+/// assert_eq!(slab.handle_bound(), 2);
+/// ```
+///
+/// [`key`]: DenseSlab::key
+pub struct DenseSlab<T, C: cfg::Config = DefaultConfig> {
+    slab: Slab<T, C>,
+    /// The key for each handle, or `EMPTY`, followed by the page's free
+    /// bitmap. Page `n` holds the keys for `FIRST_PAGE_SIZE << n` handles,
+    /// and is allocated the first time one of those handles is issued.
+    pages: Box<[AtomicPtr<AtomicUsize>]>,
+    /// Bit `n` is set if page `n` may have free handles.
+    free_pages: AtomicUsize,
+    /// One past the highest handle ever issued.
+    next: AtomicUsize,
+}
+
+/// A page of the handle table.
+///
+/// Each page records which of its handles are free in two levels of
+/// bitmaps: a bit in `free` is set for each free handle, and a bit in
+/// `summary` is set for each word of `free` which may have bits set. A bit
+/// is set at every level, lowest first, when a handle is released. A thread
+/// which clears a summary bit (or a bit in the slab's `free_pages`) because
+/// it found nothing below it must then check again, and set the bit again if
+/// a handle was released in the meantime, so that no free handle is ever
+/// hidden.
+struct Page<'a> {
+    entries: &'a [AtomicUsize],
+    free: &'a [AtomicUsize],
+    summary: &'a [AtomicUsize],
+}
+
+/// The key stored for a handle with no value.
+///
+/// No slab key has every address bit set, since that address is past the
+/// end of the last page, so this is never a valid key.
+const EMPTY: usize = !0;
+
+/// The number of handles in the first page of the table.
+const FIRST_PAGE_SIZE: usize = 32;
+
+/// The number of pages needed to hold a key for every `u32` handle.
+const PAGES: usize = 28;
+
+/// The number of bits in each word of a free bitmap.
+const WORD_BITS: usize = 0usize.count_zeros() as usize;
+
+impl<T> DenseSlab<T> {
+    /// Returns a new `DenseSlab` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `DenseSlab` with the provided configuration parameters.
+    pub fn new_with_config<C: cfg::Config>() -> DenseSlab<T, C> {
+        DenseSlab {
+            slab: Slab::new_with_config(),
+            pages: (0..PAGES)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            free_pages: AtomicUsize::new(0),
+            next: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T, C: cfg::Config> DenseSlab<T, C> {
+    /// Inserts a value into the slab, returning its handle.
+    ///
+    /// The returned handle is the lowest handle which isn't currently in use.
+    ///
+    /// If this function returns `None`, then either the shard for the current
+    /// thread is full and no items can be added until some are removed, or
+    /// every `u32` handle is in use.
+    pub fn insert(&self, value: T) -> Option<u32> {
+        // Insert the value first, so that a handle is only issued once there
+        // is a value for it.
+        let key = self.slab.insert(value)?;
+        let handle = match self.alloc() {
+            Some(handle) => handle,
+            None => {
+                self.slab.remove(key);
+                return None;
+            }
+        };
+        // The handle hasn't been returned to anyone yet, so nothing can be
+        // reading its entry concurrently with this store.
+        self.entry_or_alloc(handle).store(key, Ordering::Release);
+        test_println!("dense: insert {} -> {:#x}", handle, key);
+        Some(handle)
+    }
+
+    /// Returns a reference to the value for `handle`, or `None` if there is
+    /// no value for it.
+    pub fn get(&self, handle: u32) -> Option<Entry<'_, T, C>> {
+        self.slab.get(self.key(handle)?)
+    }
+
+    /// Returns the packed key of the value for `handle` in the underlying
+    /// slab, or `None` if there is no value for it.
+    ///
+    /// Unlike the handle, the key is never reused for another value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::DenseSlab;
+    /// let slab = DenseSlab::new();
+    /// let handle = slab.insert(1).unwrap();
+    /// let key = slab.key(handle).unwrap();
+    ///
+    /// slab.remove(handle);
+    /// assert_eq!(slab.insert(2), Some(handle));
+    /// assert_ne!(slab.key(handle), Some(key));
+    /// ```
+    pub fn key(&self, handle: u32) -> Option<usize> {
+        match self.entry(handle)?.load(Ordering::Acquire) {
+            EMPTY => None,
+            key => Some(key),
+        }
+    }
+
+    /// Returns `true` if the slab contains a value for `handle`.
+    pub fn contains(&self, handle: u32) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Removes the value for `handle`, returning `true` if a value was
+    /// removed.
+    ///
+    /// As with [`Slab::remove`], the value is dropped once every guard
+    /// referencing it has been dropped. The handle may be reused immediately.
+    pub fn remove(&self, handle: u32) -> bool {
+        match self.key(handle) {
+            Some(key) if self.slab.remove(key) => {
+                self.release(handle);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes the value for `handle` and returns it, or `None` if there is
+    /// no value for it.
+    ///
+    /// As with [`Slab::take`], this waits for any guards referencing the
+    /// value to be dropped.
+    pub fn take(&self, handle: u32) -> Option<T> {
+        let value = self.slab.take(self.key(handle)?)?;
+        self.release(handle);
+        Some(value)
+    }
+
+    /// Returns one past the highest handle that has been issued.
+    ///
+    /// Every handle in use is less than this, so it is the length an array
+    /// indexed by this slab's handles must have.
+    pub fn handle_bound(&self) -> u32 {
+        self.next.load(Ordering::Acquire) as u32
+    }
+
+    /// Returns the lowest handle which isn't in use, or `None` if every
+    /// handle is in use.
+    fn alloc(&self) -> Option<u32> {
+        // The bitmaps are only ever updated with `SeqCst` operations, so
+        // that a thread which clears a bit and then checks below it again is
+        // ordered with respect to a thread which sets the bits below it.
+        let mut pages = self.free_pages.load(Ordering::SeqCst);
+        while pages != 0 {
+            let page = pages.trailing_zeros() as usize;
+            pages &= pages - 1;
+            if let Some(handle) = self.alloc_in(page) {
+                return Some(handle);
+            }
+            self.free_pages.fetch_and(!(1 << page), Ordering::SeqCst);
+            if self.page(page).map_or(false, |page| page.may_have_free()) {
+                test_println!("-> page {} was released to; setting again", page);
+                self.free_pages.fetch_or(1 << page, Ordering::SeqCst);
+            }
+        }
+
+        // The all-ones handle is never issued, so that `next` can't overflow.
+        let mut next = self.next.load(Ordering::Relaxed);
+        loop {
+            if next >= !0u32 as usize {
+                return None;
+            }
+            match self.next.compare_exchange_weak(
+                next,
+                next + 1,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(next as u32),
+                Err(actual) => next = actual,
+            }
+        }
+    }
+
+    /// Claims the lowest free handle in the page at `page_idx`, if there is
+    /// one.
+    fn alloc_in(&self, page_idx: usize) -> Option<u32> {
+        let page = self.page(page_idx)?;
+        for (summary_idx, summary) in page.summary.iter().enumerate() {
+            let mut words = summary.load(Ordering::SeqCst);
+            while words != 0 {
+                let word_bit = words.trailing_zeros() as usize;
+                words &= words - 1;
+                let word_idx = summary_idx * WORD_BITS + word_bit;
+                let word = &page.free[word_idx];
+                let mut bits = word.load(Ordering::SeqCst);
+                while bits != 0 {
+                    let bit = bits.trailing_zeros() as usize;
+                    match word.compare_exchange_weak(
+                        bits,
+                        bits & !(1 << bit),
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                    ) {
+                        Ok(_) => {
+                            let offset = word_idx * WORD_BITS + bit;
+                            return Some(handle(page_idx, offset));
+                        }
+                        Err(actual) => bits = actual,
+                    }
+                }
+                summary.fetch_and(!(1 << word_bit), Ordering::SeqCst);
+                if word.load(Ordering::SeqCst) != 0 {
+                    // A handle in this word was released after we found it
+                    // empty, so don't hide it. The page's bit may have been
+                    // cleared in the meantime too.
+                    summary.fetch_or(1 << word_bit, Ordering::SeqCst);
+                    self.free_pages.fetch_or(1 << page_idx, Ordering::SeqCst);
+                }
+            }
+        }
+        None
+    }
+
+    /// Clears the entry for a handle whose value was removed, and makes the
+    /// handle available to be issued again.
+    fn release(&self, handle: u32) {
+        let (page_idx, offset) = indices(handle);
+        let page = match self.page(page_idx) {
+            Some(page) => page,
+            None => return,
+        };
+        page.entries[offset].store(EMPTY, Ordering::Release);
+        test_println!("dense: release {}", handle);
+        let word_idx = offset / WORD_BITS;
+        page.free[word_idx].fetch_or(1 << (offset % WORD_BITS), Ordering::SeqCst);
+        page.summary[word_idx / WORD_BITS].fetch_or(1 << (word_idx % WORD_BITS), Ordering::SeqCst);
+        self.free_pages.fetch_or(1 << page_idx, Ordering::SeqCst);
+    }
+
+    /// Returns the page at `page`, or `None` if it hasn't been allocated.
+    fn page(&self, page: usize) -> Option<Page<'_>> {
+        let ptr = self.pages[page].load(Ordering::Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+        let words = unsafe {
+            // Safety: pages are only deallocated when the slab is dropped,
+            // and were allocated with this length.
+            std::slice::from_raw_parts(ptr as *const AtomicUsize, page_len(page))
+        };
+        Some(Page::new(words, page))
+    }
+
+    /// Returns the entry for `handle`, or `None` if its page hasn't been
+    /// allocated.
+    fn entry(&self, handle: u32) -> Option<&AtomicUsize> {
+        let (page, offset) = indices(handle);
+        let entries = self.pages[page].load(Ordering::Acquire);
+        if entries.is_null() {
+            return None;
+        }
+        Some(unsafe {
+            // Safety: pages are only deallocated when the slab is dropped,
+            // and `offset` is within the page's entries.
+            &*entries.add(offset)
+        })
+    }
+
+    /// Returns the entry for `handle`, allocating its page if necessary.
+    fn entry_or_alloc(&self, handle: u32) -> &AtomicUsize {
+        let (page, offset) = indices(handle);
+        let ptr = &self.pages[page];
+        let mut entries = ptr.load(Ordering::Acquire);
+        if entries.is_null() {
+            let new = alloc_page(page);
+            entries = match ptr.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(actual) => {
+                    test_println!("-> lost race to allocate; deallocating");
+                    unsafe {
+                        // Safety: the page was never shared.
+                        dealloc_page(new, page);
+                    }
+                    actual
+                }
+            };
+        }
+        unsafe {
+            // Safety: pages are only deallocated when the slab is dropped,
+            // and `offset` is within the page.
+            &*entries.add(offset)
+        }
+    }
+}
+
+impl<T> Default for DenseSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C: cfg::Config> Drop for DenseSlab<T, C> {
+    fn drop(&mut self) {
+        for (page, entries) in self.pages.iter().enumerate() {
+            let entries = entries.load(Ordering::Acquire);
+            if !entries.is_null() {
+                unsafe {
+                    // Safety: we have exclusive access to the slab.
+                    dealloc_page(entries, page);
+                }
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for DenseSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DenseSlab")
+            .field("shards", &self.slab.shards)
+            .field("handle_bound", &self.handle_bound())
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl Page ===
+
+impl<'a> Page<'a> {
+    /// Splits the words of the page at `page` into its entries and bitmaps.
+    fn new(words: &'a [AtomicUsize], page: usize) -> Self {
+        let size = FIRST_PAGE_SIZE << page;
+        let (entries, rest) = words.split_at(size);
+        let (free, summary) = rest.split_at(bitmap_len(size));
+        Self {
+            entries,
+            free,
+            summary,
+        }
+    }
+
+    /// Returns `true` if any word of the page's free bitmap may have bits
+    /// set.
+    fn may_have_free(&self) -> bool {
+        self.summary
+            .iter()
+            .any(|summary| summary.load(Ordering::SeqCst) != 0)
+    }
+}
+
+/// Returns the page containing the entry for `handle`, and the entry's
+/// offset within that page.
+fn indices(handle: u32) -> (usize, usize) {
+    let index = handle as usize + FIRST_PAGE_SIZE;
+    let page = (0usize.leading_zeros() - index.leading_zeros()) as usize
+        - FIRST_PAGE_SIZE.trailing_zeros() as usize
+        - 1;
+    (page, index - (FIRST_PAGE_SIZE << page))
+}
+
+/// Returns the handle at `offset` in the page at `page`.
+fn handle(page: usize, offset: usize) -> u32 {
+    ((FIRST_PAGE_SIZE << page) - FIRST_PAGE_SIZE + offset) as u32
+}
+
+/// Returns the number of words needed for a bitmap with `bits` bits.
+fn bitmap_len(bits: usize) -> usize {
+    (bits + WORD_BITS - 1) / WORD_BITS
+}
+
+/// Returns the number of words in the page at `page`: its entries, followed
+/// by its free bitmap and the summary of that bitmap.
+fn page_len(page: usize) -> usize {
+    let size = FIRST_PAGE_SIZE << page;
+    let free = bitmap_len(size);
+    size + free + bitmap_len(free)
+}
+
+/// Allocates the page at `page`, returning a pointer to the first entry.
+///
+/// Every entry is `EMPTY`, and no handle is marked free.
+fn alloc_page(page: usize) -> *mut AtomicUsize {
+    let size = FIRST_PAGE_SIZE << page;
+    let words = (0..page_len(page))
+        .map(|i| AtomicUsize::new(if i < size { EMPTY } else { 0 }))
+        .collect::<Box<[_]>>();
+    Box::into_raw(words) as *mut AtomicUsize
+}
+
+/// Deallocates a page allocated by `alloc_page`.
+///
+/// ## Safety
+///
+/// The page must have been allocated by `alloc_page` for the same index, and
+/// must not be accessed again.
+unsafe fn dealloc_page(entries: *mut AtomicUsize, page: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        entries,
+        page_len(page),
+    )));
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handle_indices() {
+        assert_eq!(indices(0), (0, 0));
+        assert_eq!(indices(31), (0, 31));
+        assert_eq!(indices(32), (1, 0));
+        assert_eq!(indices(95), (1, 63));
+        assert_eq!(indices(96), (2, 0));
+        assert_eq!(indices(!0 - 1).0, PAGES - 1);
+
+        for &h in &[0, 31, 32, 95, 96, 1000, !0 - 1] {
+            let (page, offset) = indices(h);
+            assert_eq!(handle(page, offset), h);
+        }
+    }
+
+    #[test]
+    fn reuses_lowest_handle() {
+        let slab = DenseSlab::new();
+        let handles = (0..100)
+            .map(|i| slab.insert(i).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(handles, (0..100).collect::<Vec<_>>());
+
+        for &handle in &[70, 5, 40] {
+            assert!(slab.remove(handle));
+            assert!(!slab.contains(handle));
+        }
+        assert_eq!(slab.take(41), Some(41));
+        assert!(!slab.remove(5));
+        assert_eq!(slab.key(5), None);
+
+        assert_eq!(slab.insert(100), Some(5));
+        assert_eq!(slab.insert(101), Some(40));
+        assert_eq!(slab.insert(102), Some(41));
+        assert_eq!(slab.insert(103), Some(70));
+        assert_eq!(slab.insert(104), Some(100));
+        assert_eq!(slab.handle_bound(), 101);
+        assert_eq!(slab.get(40).unwrap(), 101);
+        assert!(slab.get(200).is_none());
+    }
+
+    #[test]
+    fn failed_insert_issues_no_handle() {
+        struct TinyConfig;
+
+        impl cfg::Config for TinyConfig {
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 1;
+        }
+
+        let slab = DenseSlab::new_with_config::<TinyConfig>();
+        assert_eq!(slab.insert(0), Some(0));
+        assert_eq!(slab.insert(1), Some(1));
+        assert_eq!(slab.insert(2), None);
+        assert_eq!(slab.handle_bound(), 2);
+    }
+
+    #[test]
+    fn concurrent_inserts_and_removes() {
+        use std::{collections::HashSet, sync::Arc, thread};
+
+        const THREADS: usize = 4;
+        const ITERS: usize = 500;
+
+        let slab = Arc::new(DenseSlab::new());
+        let threads = (0..THREADS)
+            .map(|t| {
+                let slab = slab.clone();
+                thread::spawn(move || {
+                    let mut held = Vec::new();
+                    for i in 0..ITERS {
+                        let handle = slab.insert((t, i)).unwrap();
+                        assert_eq!(*slab.get(handle).unwrap(), (t, i));
+                        held.push(handle);
+                        if i % 3 != 0 {
+                            let handle = held.swap_remove(i % held.len());
+                            assert!(slab.remove(handle));
+                        }
+                    }
+                    held.into_iter()
+                        .map(|handle| (handle, *slab.get(handle).unwrap()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut seen = HashSet::new();
+        for thread in threads {
+            for (handle, (t, _)) in thread.join().unwrap() {
+                assert!(seen.insert(handle), "handle {} issued twice", handle);
+                assert!(t < THREADS);
+            }
+        }
+        // Every handle in use is below the bound, and the bound never grows
+        // past the number of values that were live at once.
+        assert!(seen.iter().all(|&handle| handle < slab.handle_bound()));
+        assert!(slab.handle_bound() as usize <= THREADS * ITERS);
+
+        // Every handle below the bound is either in use or free again.
+        let held = seen.len() as u32;
+        let bound = slab.handle_bound();
+        for i in 0..bound - held {
+            let handle = slab.insert((THREADS, i as usize)).unwrap();
+            assert!(handle < bound);
+        }
+        assert_eq!(slab.handle_bound(), bound);
+    }
+}
@@ -0,0 +1,461 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    reserved::{self, Field},
+    Entry, Pack, Slab,
+};
+use std::{
+    fmt,
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+/// A slab storing values of different types behind a shared, unsized type,
+/// such as a trait object.
+///
+/// A `Slab<Box<dyn Trait>>` stores a pointer in each slot, and each value in
+/// its own heap allocation. A `DynSlab<dyn Trait>` instead stores values
+/// inline, in one of several slabs of fixed-size slots ("size classes"). A
+/// value is stored in the smallest size class it fits in; values larger than
+/// 128 bytes, or with an alignment greater than 16 bytes, are boxed.
+///
+/// Since stable Rust does not provide a way to be generic over unsizing
+/// coercions, values are inserted along with a closure that converts a
+/// reference to the value into a reference to the unsized type. Usually, this
+/// is just `|value| value`, or `|value| value as &dyn Trait` where the type
+/// can't be inferred.
+///
+/// Values may be dropped by whichever thread removes them, so they must be
+/// `Send` and `Sync` regardless of the unsized type.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::DynSlab;
+/// use std::fmt::Display;
+///
+/// let slab: DynSlab<dyn Display + Send + Sync> = DynSlab::new();
+/// let number = slab.insert(42u8, |value| value).unwrap();
+/// let string = slab.insert(String::from("hello"), |value| value).unwrap();
+///
+/// assert_eq!(slab.get(number).unwrap().to_string(), "42");
+/// assert_eq!(slab.get(string).unwrap().to_string(), "hello");
+///
+/// assert!(slab.remove(string));
+/// assert!(slab.get(string).is_none());
+/// ```
+pub struct DynSlab<U: ?Sized, C: cfg::Config = DefaultConfig> {
+    class16: Slab<Stored<U, [u8; 16]>, Classed<C>>,
+    class32: Slab<Stored<U, [u8; 32]>, Classed<C>>,
+    class64: Slab<Stored<U, [u8; 64]>, Classed<C>>,
+    class128: Slab<Stored<U, [u8; 128]>, Classed<C>>,
+}
+
+/// A guard that allows access to a value in a [`DynSlab`].
+///
+/// While the guard exists, it indicates to the slab that the item the guard
+/// references is currently being accessed. If the item is removed from the
+/// slab while a guard exists, the removal will be deferred until all guards
+/// are dropped.
+pub struct DynEntry<'a, U: ?Sized, C: cfg::Config = DefaultConfig> {
+    _inner: Inner<'a, U, C>,
+    value: ptr::NonNull<U>,
+    key: usize,
+}
+
+/// The guard for a value in one of the size classes.
+enum Inner<'a, U: ?Sized, C: cfg::Config> {
+    Class16(Entry<'a, Stored<U, [u8; 16]>, Classed<C>>),
+    Class32(Entry<'a, Stored<U, [u8; 32]>, Classed<C>>),
+    Class64(Entry<'a, Stored<U, [u8; 64]>, Classed<C>>),
+    Class128(Entry<'a, Stored<U, [u8; 128]>, Classed<C>>),
+}
+
+/// A value stored inline in a slot of a size class.
+struct Stored<U: ?Sized, B> {
+    /// Points to the value in `data`.
+    ///
+    /// Since a value is not moved while it is present in the slab, this is
+    /// set once the value has been written to its slot, and is only valid
+    /// while the `Stored` is in that slot.
+    value: Option<ptr::NonNull<U>>,
+    /// Drops the value in `data`.
+    ///
+    /// Unlike `value`, this is valid wherever the `Stored` has been moved, so
+    /// the value may be dropped after it is taken out of its slot.
+    drop: unsafe fn(*mut u8),
+    data: Inline<B>,
+}
+
+#[repr(C, align(16))]
+struct Inline<B>(MaybeUninit<B>);
+
+/// The configuration of each size class's slab.
+///
+/// The size class of a value is stored in two bits of its key, which are
+/// reserved from the size class's slab.
+type Classed<C> = reserved::Reserve<C, Class>;
+
+/// The size class of a value.
+struct Class;
+
+impl Field for Class {
+    const BITS: usize = 2;
+}
+
+const CLASS_16: usize = 0;
+const CLASS_32: usize = 1;
+const CLASS_64: usize = 2;
+const CLASS_128: usize = 3;
+
+impl<U: ?Sized> DynSlab<U> {
+    /// Returns a new `DynSlab` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `DynSlab` with the provided configuration parameters.
+    pub fn new_with_config<C: cfg::Config>() -> DynSlab<U, C> {
+        C::validate();
+        DynSlab {
+            class16: Slab::new_with_config(),
+            class32: Slab::new_with_config(),
+            class64: Slab::new_with_config(),
+            class128: Slab::new_with_config(),
+        }
+    }
+}
+
+impl<U: ?Sized, C: cfg::Config> DynSlab<U, C> {
+    /// Inserts a value into the slab, returning the integer index at which
+    /// that value was inserted.
+    ///
+    /// Once the value has been moved into the slab, `as_dyn` is called to
+    /// convert a reference to it into a reference to `U`.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no items can be added until some are removed, or the maximum
+    /// number of shards has been reached.
+    ///
+    /// `None` is also returned if the current thread is exiting, and its
+    /// thread-local storage has already been destroyed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::DynSlab;
+    /// use std::any::Any;
+    ///
+    /// let slab: DynSlab<dyn Any + Send + Sync> = DynSlab::new();
+    /// let key = slab.insert(1u32, |value| value).unwrap();
+    ///
+    /// let entry = slab.get(key).unwrap();
+    /// assert_eq!(entry.downcast_ref::<u32>(), Some(&1));
+    /// ```
+    pub fn insert<V>(&self, value: V, as_dyn: impl FnOnce(&V) -> &U) -> Option<usize>
+    where
+        V: Send + Sync + 'static,
+    {
+        if fits::<V, [u8; 16]>() {
+            insert_in(&self.class16, CLASS_16, value, as_dyn)
+        } else if fits::<V, [u8; 32]>() {
+            insert_in(&self.class32, CLASS_32, value, as_dyn)
+        } else if fits::<V, [u8; 64]>() {
+            insert_in(&self.class64, CLASS_64, value, as_dyn)
+        } else if fits::<V, [u8; 128]>() {
+            insert_in(&self.class128, CLASS_128, value, as_dyn)
+        } else {
+            test_println!("DynSlab::insert: boxing {}", std::any::type_name::<V>());
+            insert_in(&self.class16, CLASS_16, Box::new(value), |value| {
+                as_dyn(value)
+            })
+        }
+    }
+
+    /// Return a reference to the value associated with the given key.
+    ///
+    /// If the slab does not contain a value for the given key, `None` is
+    /// returned instead.
+    pub fn get(&self, key: usize) -> Option<DynEntry<'_, U, C>> {
+        let inner = match reserved::get::<C, Class>(key) {
+            CLASS_16 => Inner::Class16(self.class16.get(key)?),
+            CLASS_32 => Inner::Class32(self.class32.get(key)?),
+            CLASS_64 => Inner::Class64(self.class64.get(key)?),
+            _ => Inner::Class128(self.class128.get(key)?),
+        };
+        let value = inner.value();
+        Some(DynEntry {
+            _inner: inner,
+            value,
+            key,
+        })
+    }
+
+    /// Remove the value associated with the given key from the slab, returning
+    /// `true` if a value was removed.
+    ///
+    /// If the value is currently being accessed, it will be dropped when the
+    /// last guard to it is dropped. See [`Slab::remove`] for details.
+    pub fn remove(&self, key: usize) -> bool {
+        match reserved::get::<C, Class>(key) {
+            CLASS_16 => self.class16.remove(key),
+            CLASS_32 => self.class32.remove(key),
+            CLASS_64 => self.class64.remove(key),
+            _ => self.class128.remove(key),
+        }
+    }
+
+    /// Returns `true` if the slab contains a value for the given key.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+impl<U: ?Sized> Default for DynSlab<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<U: ?Sized, C: cfg::Config> fmt::Debug for DynSlab<U, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynSlab")
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// Values themselves are always `Send` and `Sync`, but the slab hands out
+// references to them as `U`.
+unsafe impl<U: ?Sized + Send, C: cfg::Config> Send for DynSlab<U, C> {}
+unsafe impl<U: ?Sized + Sync, C: cfg::Config> Sync for DynSlab<U, C> {}
+
+/// Returns `true` if values of type `V` may be stored inline in a size class
+/// of `B`.
+fn fits<V, B>() -> bool {
+    mem::size_of::<V>() <= mem::size_of::<B>()
+        && mem::align_of::<V>() <= mem::align_of::<Inline<B>>()
+}
+
+fn insert_in<U, V, B, C>(
+    slab: &Slab<Stored<U, B>, Classed<C>>,
+    class: usize,
+    value: V,
+    as_dyn: impl FnOnce(&V) -> &U,
+) -> Option<usize>
+where
+    U: ?Sized,
+    C: cfg::Config,
+{
+    debug_assert!(fits::<V, B>());
+    let (tid, shard) = slab.shards.current()?;
+    test_println!("DynSlab::insert {:?}; class={}", tid, class);
+    let mut value = Some((value, as_dyn));
+    shard.init_with(|idx, slot| {
+        let mut init = slot.init()?;
+        let key = shard.tag(tid.pack(init.generation().pack(idx)));
+        let (value, as_dyn) = value.take()?;
+        unsafe {
+            // Safety: the `InitGuard` is released before this closure
+            // returns, and nothing else may access the slot until then.
+            let stored = init.value_mut().get_or_insert(Stored {
+                value: None,
+                drop: drop_value::<V>,
+                data: Inline(MaybeUninit::uninit()),
+            });
+            let data = stored.data.0.as_mut_ptr() as *mut V;
+            data.write(value);
+            stored.value = Some(ptr::NonNull::from(as_dyn(&*data)));
+            let _released = init.release();
+            debug_assert!(
+                !_released,
+                "removing a value before it was inserted should be a no-op"
+            );
+        }
+        Some(reserved::set::<C, Class>(key, class))
+    })
+}
+
+unsafe fn drop_value<V>(data: *mut u8) {
+    ptr::drop_in_place(data as *mut V)
+}
+
+// === impl Stored ===
+
+impl<U: ?Sized, B> Drop for Stored<U, B> {
+    fn drop(&mut self) {
+        if self.value.is_some() {
+            unsafe {
+                // Safety: `value` is only set once a value has been written
+                // to `data`, which is never read or written again until the
+                // `Stored` is dropped.
+                (self.drop)(self.data.0.as_mut_ptr() as *mut u8)
+            }
+        }
+    }
+}
+
+// === impl Inner ===
+
+impl<U: ?Sized, C: cfg::Config> Inner<'_, U, C> {
+    fn value(&self) -> ptr::NonNull<U> {
+        let value = match self {
+            Inner::Class16(entry) => (**entry).value,
+            Inner::Class32(entry) => (**entry).value,
+            Inner::Class64(entry) => (**entry).value,
+            Inner::Class128(entry) => (**entry).value,
+        };
+        value.expect("a value is always in place once it is inserted")
+    }
+}
+
+// === impl DynEntry ===
+
+impl<U: ?Sized, C: cfg::Config> DynEntry<'_, U, C> {
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<U: ?Sized, C: cfg::Config> std::ops::Deref for DynEntry<'_, U, C> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: the value is not moved or dropped while the guard
+            // exists.
+            self.value.as_ref()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<U, C> serde::Serialize for DynEntry<'_, U, C>
+where
+    U: ?Sized + serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<U, C> fmt::Debug for DynEntry<'_, U, C>
+where
+    U: ?Sized + fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+unsafe impl<U: ?Sized + Sync, C: cfg::Config> Send for DynEntry<'_, U, C> {}
+unsafe impl<U: ?Sized + Sync, C: cfg::Config> Sync for DynEntry<'_, U, C> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    trait Tracked: Send + Sync {
+        fn id(&self) -> usize;
+    }
+
+    struct Value<B> {
+        id: usize,
+        drops: Arc<AtomicUsize>,
+        _padding: B,
+    }
+
+    impl<B: Send + Sync> Tracked for Value<B> {
+        fn id(&self) -> usize {
+            self.id
+        }
+    }
+
+    impl<B> Drop for Value<B> {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn insert<B: Send + Sync + 'static>(
+        slab: &DynSlab<dyn Tracked>,
+        id: usize,
+        drops: &Arc<AtomicUsize>,
+        padding: B,
+    ) -> usize {
+        let value = Value {
+            id,
+            drops: drops.clone(),
+            _padding: padding,
+        };
+        slab.insert(value, |value| value).unwrap()
+    }
+
+    #[test]
+    fn size_classes() {
+        let slab: DynSlab<dyn Tracked> = DynSlab::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let keys = [
+            insert(&slab, 0, &drops, ()),
+            insert(&slab, 1, &drops, [0u8; 16]),
+            insert(&slab, 2, &drops, [0u8; 48]),
+            insert(&slab, 3, &drops, [0u8; 100]),
+            insert(&slab, 4, &drops, [0u8; 1024]),
+        ];
+
+        let classes = keys
+            .iter()
+            .map(|&key| reserved::get::<DefaultConfig, Class>(key));
+        assert_eq!(classes.collect::<Vec<_>>(), [0, 1, 2, 3, 0]);
+        for (id, &key) in keys.iter().enumerate() {
+            assert_eq!(slab.get(key).unwrap().id(), id);
+        }
+
+        for &key in &keys {
+            assert!(slab.remove(key));
+            assert!(!slab.contains(key));
+        }
+        assert_eq!(drops.load(Ordering::SeqCst), keys.len());
+    }
+
+    #[test]
+    fn remove_while_referenced() {
+        let slab: DynSlab<dyn Tracked> = DynSlab::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let key = insert(&slab, 1, &drops, [0u8; 32]);
+
+        let entry = slab.get(key).unwrap();
+        assert!(slab.remove(key));
+        assert!(slab.get(key).is_none());
+        assert_eq!(entry.id(), 1);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+
+        drop(entry);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+        // The slot is reused, without dropping the new value.
+        let key2 = insert(&slab, 2, &drops, [0u8; 32]);
+        assert_ne!(key, key2);
+        assert_eq!(slab.get(key2).unwrap().id(), 2);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn drops_values_with_slab() {
+        let slab: DynSlab<dyn Tracked> = DynSlab::new();
+        let drops = Arc::new(AtomicUsize::new(0));
+        insert(&slab, 0, &drops, ());
+        insert(&slab, 1, &drops, [0u8; 100]);
+        insert(&slab, 2, &drops, [0u8; 1024]);
+
+        drop(slab);
+        assert_eq!(drops.load(Ordering::SeqCst), 3);
+    }
+}
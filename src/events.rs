@@ -0,0 +1,190 @@
+use crate::Slab;
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread::{self, ThreadId},
+};
+
+/// The number of events retained by each shard's log.
+const LOG_LEN: usize = 64;
+
+/// Orders events recorded by different shards.
+static NEXT_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// A lifecycle transition recorded by a slab's event log.
+///
+/// See [`Slab::lifecycle_events`] for details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LifecycleEvent {
+    seq: usize,
+    kind: EventKind,
+    key: usize,
+    thread: ThreadId,
+}
+
+/// The kind of a [`LifecycleEvent`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    /// A value was inserted.
+    Insert,
+    /// A guard referencing a value was acquired.
+    Get,
+    /// A guard referencing a value was dropped.
+    Release,
+    /// A value was marked for removal.
+    Mark,
+    /// A value was cleared from its slot, making the slot available for
+    /// reuse.
+    Clear,
+}
+
+/// A shard's ring buffer of recent lifecycle events.
+#[derive(Debug, Default)]
+pub(crate) struct EventLog(Mutex<VecDeque<LifecycleEvent>>);
+
+// === impl LifecycleEvent ===
+
+impl LifecycleEvent {
+    /// Returns what happened.
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Returns the key of the value the event happened to.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Returns the ID of the thread that performed the operation.
+    pub fn thread(&self) -> ThreadId {
+        self.thread
+    }
+}
+
+impl fmt::Display for LifecycleEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {:?} {:#x} on {:?}",
+            self.seq, self.kind, self.key, self.thread
+        )
+    }
+}
+
+// === impl EventLog ===
+
+impl EventLog {
+    pub(crate) fn record(&self, kind: EventKind, key: usize) {
+        let event = LifecycleEvent {
+            seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed),
+            kind,
+            key,
+            thread: thread::current().id(),
+        };
+        // Don't panic while recording an event that happened during a panic.
+        let mut log = match self.0.lock() {
+            Ok(log) => log,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if log.len() == LOG_LEN {
+            log.pop_front();
+        }
+        log.push_back(event);
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<LifecycleEvent> {
+        let log = match self.0.lock() {
+            Ok(log) => log,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        log.iter().cloned().collect()
+    }
+}
+
+/// Prints the events in `logs` to stderr, oldest first.
+pub(crate) fn dump<'a>(logs: impl Iterator<Item = &'a EventLog>) {
+    let mut events = logs.flat_map(EventLog::snapshot).collect::<Vec<_>>();
+    if events.is_empty() {
+        return;
+    }
+    events.sort_by_key(|event| event.seq);
+    eprintln!("recent sharded-slab lifecycle events (oldest first):");
+    for event in events {
+        eprintln!("  {}", event);
+    }
+}
+
+impl<T, C: crate::cfg::Config> Slab<T, C> {
+    /// Returns the most recent lifecycle events recorded by the slab, oldest
+    /// first.
+    ///
+    /// Each shard retains its last 64 events: insertions, guards being
+    /// acquired and dropped, values being marked for removal, and values
+    /// being cleared from their slots. Each event records the thread that
+    /// performed it. This is intended for post-mortem debugging of
+    /// lifecycle bugs, such as a guard that is never dropped or a key that
+    /// is used after its value was removed.
+    ///
+    /// If a slab is dropped while its thread is panicking (such as when an
+    /// assertion fails in a test that owns the slab), its recent events are
+    /// also printed to stderr.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{EventKind, Slab};
+    ///
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    /// let guard = slab.get(key).unwrap();
+    /// slab.remove(key);
+    /// drop(guard);
+    ///
+    /// let events = slab
+    ///     .lifecycle_events()
+    ///     .iter()
+    ///     .map(|event| event.kind())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     events,
+    ///     &[
+    ///         EventKind::Insert,
+    ///         EventKind::Get,
+    ///         EventKind::Mark,
+    ///         EventKind::Release,
+    ///         EventKind::Clear,
+    ///     ]
+    /// );
+    /// ```
+    pub fn lifecycle_events(&self) -> Vec<LifecycleEvent> {
+        let mut events = self
+            .shards
+            .iter()
+            .flat_map(|shard| shard.events.snapshot())
+            .collect::<Vec<_>>();
+        events.sort_by_key(|event| event.seq);
+        events
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_buffer() {
+        let log = EventLog::default();
+        for key in 0..LOG_LEN + 2 {
+            log.record(EventKind::Insert, key);
+        }
+        let events = log.snapshot();
+        assert_eq!(events.len(), LOG_LEN);
+        assert_eq!(events[0].key(), 2);
+        assert_eq!(events[LOG_LEN - 1].key(), LOG_LEN + 1);
+        assert_eq!(events[0].thread(), thread::current().id());
+    }
+}
@@ -0,0 +1,655 @@
+//! A fixed-capacity variant of [`Slab`].
+//!
+//! See the [`FixedSlab` type's documentation][FixedSlab] for details.
+//!
+//! [`Slab`]: crate::Slab
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    page,
+    sync::atomic,
+    tid::Tid,
+    Pack,
+};
+use std::{cell, fmt, marker::PhantomData, mem::MaybeUninit, ops::Deref, ptr, slice};
+
+/// A sharded slab with a fixed capacity.
+///
+/// Unlike a [`Slab`], which allocates each thread's shard the first time that
+/// thread inserts a value, and grows each shard by allocating additional pages
+/// as it fills up, a `FixedSlab` allocates all of its storage up front: every
+/// shard consists of exactly one page, with the capacity passed to
+/// [`FixedSlab::new`]. Once the slab has been constructed, inserting and
+/// removing values never allocates. Since there is only one page per shard,
+/// locating a slot from a key is also slightly simpler.
+///
+/// Because a shard is allocated for every thread that _could_ access the slab,
+/// a `FixedSlab` will allocate [`Config::MAX_THREADS`] times the per-shard
+/// capacity when it is constructed. Applications using a `FixedSlab` will
+/// usually want to use a [custom configuration][config] which limits the
+/// number of threads.
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::{Config, FixedSlab};
+///
+/// struct FourThreads;
+/// impl Config for FourThreads {
+///     const MAX_THREADS: usize = 4;
+/// }
+///
+/// // Each of the four shards can hold two values.
+/// let slab = FixedSlab::new_with_config::<FourThreads>(2);
+/// assert_eq!(slab.capacity(), 2);
+///
+/// let hello = slab.insert("hello").unwrap();
+/// let world = slab.insert("world").unwrap();
+///
+/// // This thread's shard is full.
+/// assert!(slab.insert("!").is_none());
+///
+/// assert_eq!(slab.take(hello), Some("hello"));
+/// let key = slab.insert("goodbye").unwrap();
+/// assert_eq!(slab.get(key).unwrap(), "goodbye");
+/// assert_eq!(slab.get(world).unwrap(), "world");
+/// ```
+///
+/// A `FixedSlab` may also be constructed with [`FixedSlab::from_static`], in
+/// which case its shards and slots are stored in memory provided by the
+/// caller, and it never allocates at all.
+///
+/// [`Slab`]: crate::Slab
+/// [`Config::MAX_THREADS`]: crate::Config::MAX_THREADS
+/// [config]: crate#configuration
+pub struct FixedSlab<T, C: cfg::Config = DefaultConfig> {
+    shards: Shards<T, C>,
+    capacity: usize,
+    _cfg: PhantomData<C>,
+}
+
+/// A handle that allows access to an occupied entry in a [`FixedSlab`].
+///
+/// While the guard exists, it indicates to the slab that the item the guard
+/// references is currently being accessed. If the item is removed from the slab
+/// while a guard exists, the removal will be deferred until all guards are
+/// dropped.
+pub struct Entry<'a, T, C: cfg::Config = DefaultConfig> {
+    inner: page::slot::Guard<Option<T>, C>,
+    value: ptr::NonNull<T>,
+    shard: &'a Shard<T, C>,
+    key: usize,
+}
+
+/// Storage for one of a [`FixedSlab`]'s shards, for use with
+/// [`FixedSlab::from_static`].
+///
+/// This is uninitialized until it is passed to [`FixedSlab::from_static`], so
+/// arrays of it may be constructed in a `static` using
+/// [`ShardStorage::EMPTY`].
+#[repr(transparent)]
+pub struct ShardStorage<T, C: cfg::Config = DefaultConfig>(MaybeUninit<Shard<T, C>>);
+
+/// Storage for one of the slots in a [`FixedSlab`], for use with
+/// [`FixedSlab::from_static`].
+///
+/// This is uninitialized until the slot is first used, so arrays of it may be
+/// constructed in a `static` using [`SlotStorage::EMPTY`].
+#[repr(transparent)]
+pub struct SlotStorage<T, C: cfg::Config = DefaultConfig>(page::SlotCell<Option<T>, C>);
+
+struct Shard<T, C: cfg::Config> {
+    tid: usize,
+    local: page::Local,
+    page: page::Shared<Option<T>, C>,
+}
+
+enum Shards<T, C: cfg::Config> {
+    Boxed(Box<[Shard<T, C>]>),
+    Static(ptr::NonNull<[Shard<T, C>]>),
+}
+
+impl<T> FixedSlab<T> {
+    /// Returns a new `FixedSlab` with the default configuration parameters,
+    /// where each thread's shard can hold up to `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero, or is too large to be represented by the
+    /// configuration's page address bits.
+    pub fn new(capacity: usize) -> Self {
+        Self::new_with_config(capacity)
+    }
+
+    /// Returns a new `FixedSlab` with the provided configuration parameters,
+    /// where each thread's shard can hold up to `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero, or is too large to be represented by the
+    /// configuration's page address bits, or if the configuration sets
+    /// [`Config::SINGLE_THREADED`].
+    ///
+    /// [`Config::SINGLE_THREADED`]: crate::Config::SINGLE_THREADED
+    pub fn new_with_config<C: cfg::Config>(capacity: usize) -> FixedSlab<T, C> {
+        validate::<C>(capacity);
+        let shards = (0..C::MAX_SHARDS)
+            .map(|tid| {
+                let page = page::Shared::new(capacity, 0);
+                page.allocate();
+                Shard {
+                    tid,
+                    local: page::Local::new(),
+                    page,
+                }
+            })
+            .collect();
+        FixedSlab {
+            shards: Shards::Boxed(shards),
+            capacity,
+            _cfg: PhantomData,
+        }
+    }
+}
+
+impl<T, C: cfg::Config> FixedSlab<T, C> {
+    /// Returns a new `FixedSlab` whose shards and slots are stored in the
+    /// provided memory, rather than being allocated.
+    ///
+    /// The first `shards.len()` threads each get a shard, whose capacity is
+    /// `slots.len() / shards.len()`. Inserting from any other thread returns
+    /// `None`. Both slices are usually arrays in a `static`, constructed with
+    /// [`ShardStorage::EMPTY`] and [`SlotStorage::EMPTY`].
+    ///
+    /// A `FixedSlab` constructed this way never uses the global allocator.
+    /// Note, however, that this crate still relies on `std` to assign thread
+    /// IDs, which may allocate the first time a thread accesses a slab, or
+    /// when it exits.
+    ///
+    /// # Panics
+    ///
+    /// - If `shards` is empty, or has more shards than the configuration's
+    ///   [`Config::MAX_THREADS`].
+    /// - If `slots.len()` is not a multiple of `shards.len()`, or under the
+    ///   same conditions as [`FixedSlab::new_with_config`] for the resulting
+    ///   capacity.
+    /// - If the configuration enables tracking of when slots are used, or
+    ///   which slots are occupied, since those require allocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{
+    ///     fixed::{ShardStorage, SlotStorage},
+    ///     Config, FixedSlab,
+    /// };
+    /// use std::ptr;
+    ///
+    /// struct TwoThreads;
+    /// impl Config for TwoThreads {
+    ///     const MAX_THREADS: usize = 2;
+    /// }
+    ///
+    /// static mut SHARDS: [ShardStorage<u32, TwoThreads>; 2] = [ShardStorage::EMPTY; 2];
+    /// static mut SLOTS: [SlotStorage<u32, TwoThreads>; 8] = [SlotStorage::EMPTY; 8];
+    ///
+    /// let slab = unsafe {
+    ///     // Safety: the storage is only ever used by this slab.
+    ///     FixedSlab::from_static(&mut *ptr::addr_of_mut!(SHARDS), &mut *ptr::addr_of_mut!(SLOTS))
+    /// };
+    /// assert_eq!(slab.capacity(), 4);
+    ///
+    /// let key = slab.insert(42).unwrap();
+    /// assert_eq!(slab.get(key).unwrap(), 42);
+    /// assert_eq!(slab.take(key), Some(42));
+    /// ```
+    ///
+    /// [`Config::MAX_THREADS`]: crate::Config::MAX_THREADS
+    pub fn from_static(
+        shards: &'static mut [ShardStorage<T, C>],
+        slots: &'static mut [SlotStorage<T, C>],
+    ) -> Self {
+        assert!(
+            !shards.is_empty() && shards.len() <= C::MAX_THREADS,
+            "a `FixedSlab` must have between 1 and {} shards, but {} were provided",
+            C::MAX_THREADS,
+            shards.len(),
+        );
+        assert!(
+            slots.len() % shards.len() == 0,
+            "{} slots can't be divided evenly between {} shards",
+            slots.len(),
+            shards.len(),
+        );
+        assert!(
+            !(C::TRACK_IDLE || C::TRACK_ACCESS || C::TRACK_INSERTED || C::TRACK_OCCUPANCY),
+            "a static `FixedSlab` can't track slot usage with config {:#?}",
+            C::debug(),
+        );
+        let capacity = slots.len() / shards.len();
+        validate::<C>(capacity);
+
+        let slots = unsafe {
+            // Safety: `SlotStorage` is a transparent wrapper around a slot's
+            // cell.
+            slice::from_raw_parts_mut(
+                slots.as_mut_ptr() as *mut page::SlotCell<Option<T>, C>,
+                slots.len(),
+            )
+        };
+        for (tid, (shard, slots)) in shards
+            .iter_mut()
+            .zip(slots.chunks_mut(capacity))
+            .enumerate()
+        {
+            let page = page::Shared::new(capacity, 0);
+            page.allocate_static(slots);
+            shard.0 = MaybeUninit::new(Shard {
+                tid,
+                local: page::Local::new(),
+                page,
+            });
+        }
+        let shards = unsafe {
+            // Safety: every shard was just initialized, and `ShardStorage` is
+            // a transparent wrapper around a shard.
+            slice::from_raw_parts_mut(shards.as_mut_ptr() as *mut Shard<T, C>, shards.len())
+        };
+        FixedSlab {
+            shards: Shards::Static(ptr::NonNull::from(shards)),
+            capacity,
+            _cfg: PhantomData,
+        }
+    }
+}
+
+impl<T, C: cfg::Config> FixedSlab<T, C> {
+    /// Returns the number of values each thread's shard can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Inserts a value into the slab, returning the integer index at which that
+    /// value was inserted. This index can then be used to access the entry.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no items can be added until some are removed.
+    ///
+    /// `None` is also returned if the current thread is exiting, and its
+    /// thread-local storage has already been destroyed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::FixedSlab;
+    /// let slab = FixedSlab::new(32);
+    ///
+    /// let key = slab.insert("hello world").unwrap();
+    /// assert_eq!(slab.get(key).unwrap(), "hello world");
+    /// ```
+    pub fn insert(&self, value: T) -> Option<usize> {
+        let tid = Tid::<C>::current();
+        test_println!("FixedSlab::insert {:?}", tid);
+        if tid.is_poisoned() {
+            return None;
+        }
+        let shard = self.shards.get(tid.as_usize())?;
+        let mut value = Some(value);
+        shard
+            .page
+            .init_with(&shard.local, |idx, slot| {
+                let gen = slot.insert(&mut value)?;
+                Some(gen.pack(idx))
+            })
+            .map(|idx| tid.pack(idx))
+    }
+
+    /// Return a reference to the value associated with the given key.
+    ///
+    /// If the slab does not contain a value for the given key, or if the
+    /// maximum number of concurrent references to the slot has been reached,
+    /// `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::FixedSlab;
+    /// let slab = FixedSlab::new(32);
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// assert_eq!(slab.get(key).unwrap(), "hello world");
+    /// assert!(slab.get(12345).is_none());
+    /// ```
+    pub fn get(&self, key: usize) -> Option<Entry<'_, T, C>> {
+        let shard = self.shard(key)?;
+        shard.page.with_slot(C::unpack_addr(key), |slot| {
+            let inner = slot.get(C::unpack_gen(key))?;
+            let value = ptr::NonNull::from(slot.value().as_ref().unwrap());
+            Some(Entry {
+                inner,
+                value,
+                shard,
+                key,
+            })
+        })
+    }
+
+    /// Returns `true` if the slab contains a value for the given key.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value at the given index in the slab, returning `true` if a
+    /// value was removed.
+    ///
+    /// Like [`Slab::remove`], this does not block the current thread until the
+    /// value can be removed. Instead, if another thread is currently accessing
+    /// that value, this marks it to be removed by that thread when it finishes
+    /// accessing the value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::FixedSlab;
+    /// let slab = FixedSlab::new(32);
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// assert!(slab.remove(key));
+    /// assert!(!slab.contains(key));
+    /// ```
+    ///
+    /// [`Slab::remove`]: crate::Slab::remove
+    pub fn remove(&self, key: usize) -> bool {
+        test_println!("FixedSlab::remove {:#x}", key);
+        let shard = match self.shard(key) {
+            Some(shard) => shard,
+            None => return false,
+        };
+        let (addr, gen) = (C::unpack_addr(key), C::unpack_gen(key));
+        if shard.is_local() {
+            shard.page.remove(addr, gen, &shard.local)
+        } else {
+            shard.page.remove(addr, gen, shard.page.free_list())
+        }
+    }
+
+    /// Removes the value associated with the given key from the slab, returning
+    /// it.
+    ///
+    /// If the slab does not contain a value for that key, `None` is returned
+    /// instead.
+    ///
+    /// Like [`Slab::take`], if the value is currently being accessed by other
+    /// threads, this blocks the current thread until it can be removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::FixedSlab;
+    /// let slab = FixedSlab::new(32);
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// assert_eq!(slab.take(key), Some("hello world"));
+    /// assert!(!slab.contains(key));
+    /// ```
+    ///
+    /// [`Slab::take`]: crate::Slab::take
+    pub fn take(&self, key: usize) -> Option<T> {
+        test_println!("FixedSlab::take {:#x}", key);
+        let shard = self.shard(key)?;
+        let (addr, gen) = (C::unpack_addr(key), C::unpack_gen(key));
+        if shard.is_local() {
+            shard.page.take(addr, gen, &shard.local)
+        } else {
+            shard.page.take(addr, gen, shard.page.free_list())
+        }
+    }
+
+    #[inline]
+    fn shard(&self, key: usize) -> Option<&Shard<T, C>> {
+        self.shards.get(C::unpack_tid(key).as_usize())
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for FixedSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedSlab")
+            .field("capacity", &self.capacity)
+            .field("shards", &self.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+unsafe impl<T: Send, C: cfg::Config> Send for FixedSlab<T, C> {}
+unsafe impl<T: Sync, C: cfg::Config> Sync for FixedSlab<T, C> {}
+
+/// Panics if a `FixedSlab` can't have the given capacity with the config `C`.
+fn validate<C: cfg::Config>(capacity: usize) {
+    C::validate();
+    assert!(
+        !C::SINGLE_THREADED,
+        "a `FixedSlab` can't use a single-threaded config"
+    );
+    assert!(capacity > 0, "a `FixedSlab`'s capacity must not be zero");
+    assert!(
+        capacity <= page::Addr::<C>::BITS,
+        "a `FixedSlab`'s capacity must be at most {} with config {:#?}",
+        page::Addr::<C>::BITS,
+        C::debug(),
+    );
+}
+
+// === impl ShardStorage ===
+
+impl<T, C: cfg::Config> ShardStorage<T, C> {
+    /// Uninitialized storage for a shard.
+    pub const EMPTY: Self = ShardStorage(MaybeUninit::uninit());
+}
+
+impl<T, C: cfg::Config> fmt::Debug for ShardStorage<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardStorage").finish()
+    }
+}
+
+// === impl SlotStorage ===
+
+impl<T, C: cfg::Config> SlotStorage<T, C> {
+    /// Uninitialized storage for a slot.
+    #[allow(clippy::declare_interior_mutable_const)] // only used to initialize arrays
+    pub const EMPTY: Self = SlotStorage(cell::UnsafeCell::new(MaybeUninit::uninit()));
+}
+
+impl<T, C: cfg::Config> fmt::Debug for SlotStorage<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlotStorage").finish()
+    }
+}
+
+// === impl Shards ===
+
+impl<T, C: cfg::Config> Deref for Shards<T, C> {
+    type Target = [Shard<T, C>];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Shards::Boxed(shards) => shards,
+            Shards::Static(shards) => unsafe {
+                // Safety: the shards were initialized by `from_static`, which
+                // was given exclusive access to them.
+                shards.as_ref()
+            },
+        }
+    }
+}
+
+impl<T, C: cfg::Config> Drop for Shards<T, C> {
+    fn drop(&mut self) {
+        if let Shards::Static(shards) = self {
+            unsafe {
+                // Safety: the shards were initialized by `from_static`, and
+                // are never accessed again once the slab is dropped. This
+                // drops any values still in their slots.
+                ptr::drop_in_place(shards.as_ptr());
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Shards<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// === impl Shard ===
+
+impl<T, C: cfg::Config> Shard<T, C> {
+    /// Returns `true` if the current thread owns this shard.
+    ///
+    /// A poisoned thread ID never matches a shard, so slots freed while the
+    /// current thread's thread-local storage is being destroyed are pushed to
+    /// the remote free list.
+    #[inline]
+    fn is_local(&self) -> bool {
+        Tid::<C>::current().as_usize() == self.tid
+    }
+
+    fn clear_after_release(&self, key: usize) {
+        atomic::fence(atomic::Ordering::Acquire);
+        let (addr, gen) = (C::unpack_addr(key), C::unpack_gen(key));
+        test_println!("-> FixedSlab clear_after_release; shard={};", self.tid);
+        if self.is_local() {
+            self.page.clear(addr, gen, &self.local);
+        } else {
+            self.page.clear(addr, gen, self.page.free_list());
+        }
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Shard<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shard")
+            .field("tid", &self.tid)
+            .field("local", &self.local)
+            .field("page", &self.page)
+            .finish()
+    }
+}
+
+// === impl Entry ===
+
+impl<T, C: cfg::Config> Entry<'_, T, C> {
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    #[inline(always)]
+    fn value(&self) -> &T {
+        unsafe {
+            // Safety: this is always going to be valid, as it's projected from
+            // the safe reference to `self.value` --- this is just to avoid
+            // having to `expect` an option in the hot path when dereferencing.
+            self.value.as_ref()
+        }
+    }
+}
+
+impl<T, C: cfg::Config> std::ops::Deref for Entry<'_, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for Entry<'_, T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C: cfg::Config> Drop for Entry<'_, T, C> {
+    fn drop(&mut self) {
+        let should_remove = unsafe {
+            // Safety: calling `slot::Guard::release` is unsafe, since the
+            // `Guard` value contains a pointer to the slot that may outlive the
+            // slab containing that slot. Here, the `Entry` guard owns a
+            // borrowed reference to the shard containing that slot, which
+            // ensures that the slot will not be dropped while this `Guard`
+            // exists.
+            self.inner.release()
+        };
+        if should_remove {
+            self.shard.clear_after_release(self.key)
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for Entry<'_, T, C>
+where
+    T: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.value(), f)
+    }
+}
+
+impl<T, C> PartialEq<T> for Entry<'_, T, C>
+where
+    T: PartialEq<T>,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        *self.value() == *other
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::{FixedSlab, ShardStorage, SlotStorage};
+    use crate::Config;
+    use std::sync::Arc;
+
+    struct TestConfig;
+    impl Config for TestConfig {
+        const MAX_THREADS: usize = 256;
+    }
+
+    #[test]
+    fn from_static_drops_values() {
+        let shards = Box::leak(Box::new(
+            [ShardStorage::<Arc<()>, TestConfig>::EMPTY; TestConfig::MAX_THREADS],
+        ));
+        let slots = Box::leak(Box::new([SlotStorage::EMPTY; TestConfig::MAX_THREADS * 2]));
+        let slab = FixedSlab::from_static(shards, slots);
+        assert_eq!(slab.capacity(), 2);
+
+        let value = Arc::new(());
+        let removed = slab.insert(value.clone()).unwrap();
+        slab.insert(value.clone()).unwrap();
+        assert!(slab.insert(value.clone()).is_none());
+        assert!(slab.remove(removed));
+        slab.insert(value.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&value), 3);
+
+        drop(slab);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn from_static_uneven_slots() {
+        let shards = Box::leak(Box::new([ShardStorage::<(), TestConfig>::EMPTY; 2]));
+        let slots = Box::leak(Box::new([SlotStorage::EMPTY; 3]));
+        FixedSlab::from_static(shards, slots);
+    }
+}
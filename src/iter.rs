@@ -1,13 +1,18 @@
-use std::{iter::FusedIterator, slice};
+use std::{
+    fmt,
+    iter::FusedIterator,
+    ops::{Bound, Range, RangeBounds},
+    vec,
+};
 
-use crate::{cfg, page, shard};
+use crate::{cfg, page, shard, Entry, Slab};
 
 /// An exclusive fused iterator over the items in a [`Slab`](crate::Slab).
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 #[derive(Debug)]
 pub struct UniqueIter<'a, T, C: cfg::Config> {
     pub(super) shards: shard::IterMut<'a, Option<T>, C>,
-    pub(super) pages: slice::Iter<'a, page::Shared<Option<T>, C>>,
+    pub(super) pages: shard::Pages<'a, Option<T>, C>,
     pub(super) slots: Option<page::Iter<'a, T, C>>,
 }
 
@@ -43,3 +48,106 @@ impl<'a, T, C: cfg::Config> Iterator for UniqueIter<'a, T, C> {
 }
 
 impl<T, C: cfg::Config> FusedIterator for UniqueIter<'_, T, C> {}
+
+/// An exclusive iterator over the keys and values in a
+/// [`Slab`](crate::Slab), in ascending order of key.
+///
+/// This is returned by [`Slab::sorted_iter`](crate::Slab::sorted_iter).
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+#[derive(Debug)]
+pub struct SortedIter<'a, T> {
+    pub(super) entries: vec::IntoIter<(usize, &'a T)>,
+}
+
+impl<'a, T> Iterator for SortedIter<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for SortedIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for SortedIter<'_, T> {}
+
+impl<T> FusedIterator for SortedIter<'_, T> {}
+
+/// An iterator over the values in a range of a [`Slab`]'s shards and pages.
+///
+/// This is returned by [`Slab::scan_range`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct ScanRange<'a, T, C: cfg::Config> {
+    pub(super) slab: &'a Slab<T, C>,
+    pub(super) shards: Range<usize>,
+    pub(super) pages: Range<usize>,
+    pub(super) shard: Option<&'a shard::Shard<Option<T>, C>>,
+    pub(super) page: usize,
+    pub(super) keys: vec::IntoIter<usize>,
+}
+
+impl<'a, T, C: cfg::Config> Iterator for ScanRange<'a, T, C> {
+    type Item = Entry<'a, T, C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(shard) = self.shard {
+                for key in self.keys.by_ref() {
+                    if let Some(entry) = Entry::scanned(shard, key) {
+                        return Some(entry);
+                    }
+                }
+
+                if self.page < self.pages.end {
+                    let page = self.page;
+                    self.page += 1;
+                    test_println!("ScanRange: page {}", page);
+                    if let Some(keys) = shard.page_keys(page) {
+                        self.keys = keys.into_iter();
+                    }
+                    continue;
+                }
+            }
+
+            let idx = self.shards.next()?;
+            test_println!("ScanRange: shard {}", idx);
+            self.shard = self.slab.shards.get(idx);
+            self.page = self.pages.start;
+        }
+    }
+}
+
+impl<T, C: cfg::Config> FusedIterator for ScanRange<'_, T, C> {}
+
+impl<T, C: cfg::Config> fmt::Debug for ScanRange<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanRange")
+            .field("shards", &self.shards)
+            .field("pages", &self.pages)
+            .field("page", &self.page)
+            .finish()
+    }
+}
+
+/// Clamps `range` to the indices below `len`.
+pub(super) fn clamp(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.saturating_add(1),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    std::cmp::min(start, len)..std::cmp::min(end, len)
+}
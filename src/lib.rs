@@ -167,6 +167,23 @@
 //! [aba]: https://en.wikipedia.org/wiki/ABA_problem
 //! [`Slab::insert`]: struct.Slab.html#method.insert
 //!
+//! # Stable Addresses
+//!
+//! The storage for each page in a slab is allocated once, and is never
+//! reallocated or moved while the page is in use. Therefore, a value in a
+//! [`Slab`] stays at the same address from when it is inserted until it is
+//! removed, and since removal is deferred while a value is being accessed, a
+//! value referenced by a guard stays at the same address at least until the
+//! guard is dropped. The only exception is [`Slab::compact`], which moves
+//! values but requires exclusive access to the slab.
+//!
+//! This is a guarantee that `unsafe` code may rely on. [`Entry::as_ptr`],
+//! [`OwnedEntry::as_ptr`], and [`Slab::get_raw`] return raw pointers to values,
+//! which may be stored in intrusive data structures or passed over FFI, and
+//! dereferenced for as long as the value is known to remain in the slab.
+//!
+//! [`Slab::compact`]: Slab::compact
+//!
 //! # Performance
 //!
 //! These graphs were produced by [benchmarks] of the sharded slab implementation,
@@ -204,37 +221,123 @@
 #[macro_use]
 mod macros;
 
+pub mod column;
+pub mod fixed;
 pub mod implementation;
 pub mod pool;
+pub mod pool_set;
+pub mod remap;
+#[cfg(all(feature = "testing", not(loom)))]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
 
 pub(crate) mod cfg;
 pub(crate) mod sync;
 
+mod arc_slab;
+mod bounded;
+mod buffer_pool;
+mod checkout;
 mod clear;
+mod deferred;
+mod dense;
+mod dyn_slab;
+#[cfg(feature = "event-log")]
+mod events;
 mod iter;
+mod lifecycle;
+mod locked;
 mod page;
+mod partition;
+mod read_only;
+mod reserved;
+mod sealed;
 mod shard;
+mod shard_view;
+mod snapshot;
+mod stats;
+mod stream;
 mod tid;
+#[cfg(feature = "debug-tools")]
+mod visualize;
+mod wait;
+mod waker_slab;
+mod watch;
 
 pub use self::{
-    cfg::{Config, DefaultConfig},
+    arc_slab::{ArcEntry, ArcSlab},
+    bounded::{Bounded, Ceiling, Shrink},
+    buffer_pool::{AlignedBuf, BufferPool},
+    cfg::{Config, ConfigError, ConfigInfo, DefaultConfig, GenerationWrap},
+    checkout::Checkout,
     clear::Clear,
-    iter::UniqueIter,
+    deferred::TakeHandle,
+    dense::DenseSlab,
+    dyn_slab::{DynEntry, DynSlab},
+    iter::{ScanRange, SortedIter, UniqueIter},
+    lifecycle::Lifecycle,
+    locked::{EntryMut, LockedSlab},
+    partition::{Partition, PartitionEntry, PartitionedSlab},
+    read_only::{ReadGuardless, ReadOnlySlab},
+    sealed::KeySealer,
+    shard_view::ShardViewMut,
+    stats::{
+        InsertFailures, PageStats, PoolMetrics, RemainingCapacity, ShardPages, ShardStats,
+        SlabStats,
+    },
+    stream::DrainStream,
+    tid::{register_thread, unregister_thread, with_shard_hint, ShardHinted, ShardId},
+    waker_slab::WakerSlab,
+    watch::WatchInserts,
 };
+#[cfg(feature = "event-log")]
+#[cfg_attr(docsrs, doc(cfg(feature = "event-log")))]
+pub use events::{EventKind, LifecycleEvent};
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub use locked::{AsyncLockedSlab, Lock};
+#[cfg(feature = "derive")]
+#[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
+pub use sharded_slab_derive::Clear;
+#[cfg(feature = "debug-tools")]
+#[cfg_attr(docsrs, doc(cfg(feature = "debug-tools")))]
+pub use visualize::DiagramFormat;
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub use wait::{RemoveAsync, TakeAsync};
+
+#[doc(inline)]
+pub use column::Column;
+#[doc(inline)]
+pub use fixed::FixedSlab;
 #[doc(inline)]
 pub use pool::Pool;
+#[doc(inline)]
+pub use pool_set::PoolSet;
+#[doc(inline)]
+pub use remap::KeyRemap;
 
 pub(crate) use tid::Tid;
 
 use cfg::CfgPrivate;
+use page::timestamps;
 use shard::Shard;
-use std::{fmt, marker::PhantomData, ptr, sync::Arc};
+use std::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::RangeBounds,
+    ptr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// A sharded slab.
 ///
 /// See the [crate-level documentation](crate) for details on using this type.
 pub struct Slab<T, C: cfg::Config = DefaultConfig> {
     shards: shard::Array<Option<T>, C>,
+    watchers: watch::Watchers,
     _cfg: PhantomData<C>,
 }
 
@@ -244,6 +347,26 @@ pub struct Slab<T, C: cfg::Config = DefaultConfig> {
 /// references is currently being accessed. If the item is removed from the slab
 /// while a guard exists, the removal will be deferred until all guards are
 /// dropped.
+///
+/// An `Entry` may be passed to generic code which accepts an [`AsRef`] or
+/// [`Borrow`] of the value, and displays and compares like the value:
+///
+/// ```
+/// fn total(values: &[impl AsRef<u64>]) -> u64 {
+///     values.iter().map(|value| *value.as_ref()).sum()
+/// }
+///
+/// let slab = sharded_slab::Slab::new();
+/// let a = slab.insert(1).unwrap();
+/// let b = slab.insert(2).unwrap();
+///
+/// let entries = [slab.get(a).unwrap(), slab.get(b).unwrap()];
+/// assert_eq!(total(&entries), 3);
+/// assert_eq!(entries[1].to_string(), "2");
+/// assert!(entries[0] != entries[1]);
+/// ```
+///
+/// [`Borrow`]: std::borrow::Borrow
 pub struct Entry<'a, T, C: cfg::Config = DefaultConfig> {
     inner: page::slot::Guard<Option<T>, C>,
     value: ptr::NonNull<T>,
@@ -251,6 +374,39 @@ pub struct Entry<'a, T, C: cfg::Config = DefaultConfig> {
     key: usize,
 }
 
+/// A weak reference to an entry in a [`Slab`].
+///
+/// A `WeakEntry` refers to one particular value stored in the slab: if that
+/// value is removed, the weak entry will not upgrade, even if a new value is
+/// later inserted into the same slot. Unlike an [`Entry`], holding a
+/// `WeakEntry` does not keep the value from being removed.
+///
+/// Weak entries are returned by [`Slab::downgrade`] and [`Entry::downgrade`].
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// let slab = Slab::new();
+/// let key = slab.insert("hello world").unwrap();
+///
+/// let weak = slab.downgrade(key);
+/// assert_eq!(weak.upgrade().unwrap(), "hello world");
+///
+/// // Removing the value invalidates the weak entry...
+/// assert!(slab.remove(key));
+/// assert!(weak.upgrade().is_none());
+///
+/// // ...even if the slot it referred to has been reused.
+/// let key2 = slab.insert("goodbye world").unwrap();
+/// assert!(weak.upgrade().is_none());
+/// # drop(key2);
+/// ```
+pub struct WeakEntry<'a, T, C: cfg::Config = DefaultConfig> {
+    shard: Option<&'a Shard<Option<T>, C>>,
+    key: usize,
+}
+
 /// A handle to a vacant entry in a [`Slab`].
 ///
 /// `VacantEntry` allows constructing values with the key that they will be
@@ -273,11 +429,19 @@ pub struct Entry<'a, T, C: cfg::Config = DefaultConfig> {
 /// assert_eq!(hello, slab.get(hello).unwrap().0);
 /// assert_eq!("hello", slab.get(hello).unwrap().1);
 /// ```
-#[derive(Debug)]
 pub struct VacantEntry<'a, T, C: cfg::Config = DefaultConfig> {
     inner: page::slot::InitGuard<Option<T>, C>,
     key: usize,
-    _lt: PhantomData<&'a ()>,
+    shard: &'a Shard<Option<T>, C>,
+    watchers: &'a watch::Watchers,
+}
+
+/// Returns a reserved slot to the slab if it is dropped before a value is
+/// inserted.
+///
+/// This ensures that a slot whose value's constructor panicked can be reused.
+struct AbandonOnPanic<'a, T, C: cfg::Config> {
+    entry: Option<VacantEntry<'a, T, C>>,
 }
 
 /// An owned reference to an occupied entry in a [`Slab`].
@@ -361,6 +525,22 @@ pub struct VacantEntry<'a, T, C: cfg::Config = DefaultConfig> {
 /// }).join().unwrap();
 /// ```
 ///
+/// `OwnedEntry`s compare and hash like the values they reference, and borrow
+/// as them, so they may be stored in sets and maps keyed by the value:
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// use std::{collections::HashSet, sync::Arc};
+///
+/// let slab = Arc::new(Slab::new());
+/// let key = slab.insert(String::from("hello world")).unwrap();
+///
+/// let mut entries = HashSet::new();
+/// entries.insert(slab.clone().get_owned(key).unwrap());
+/// assert!(!entries.insert(slab.clone().get_owned(key).unwrap()));
+/// assert!(entries.contains(&String::from("hello world")));
+/// ```
+///
 /// [`get`]: Slab::get
 /// [`Arc`]: std::sync::Arc
 pub struct OwnedEntry<T, C = DefaultConfig>
@@ -373,20 +553,124 @@ where
     key: usize,
 }
 
+/// The reason [`Slab::get_detailed`] couldn't access the value for a key.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LookupError {
+    /// The key doesn't refer to a slot that the slab has allocated.
+    ///
+    /// Either the key was never issued by this slab, or the storage for its
+    /// slot has since been released, such as by [`Slab::compact`].
+    OutOfRange,
+    /// The key's slot exists at the key's generation, but holds no value.
+    ///
+    /// This only happens with keys that were not returned by an insert, such
+    /// as those of values that were taken out of a slab while it was being
+    /// iterated over.
+    Vacant,
+    /// The value for the key was removed, and the slot may since have been
+    /// reused for another value.
+    Stale,
+    /// The value for the key has been removed, but its slot hasn't been
+    /// freed yet, usually because the value was referenced when it was
+    /// removed.
+    Clearing,
+    /// The value is present, but already has the maximum number of
+    /// concurrent references.
+    TooManyReferences,
+    /// The value is present, but is exclusively locked, such as by a
+    /// [`Checkout`] or an [`EntryMut`], so no shared references to it may be
+    /// acquired until the lock is released.
+    Locked,
+}
+
+/// The reason [`Slab::try_insert`] couldn't insert a value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InsertError {
+    /// The inserting thread's shard already holds as many values as its
+    /// quota allows. See [`Slab::with_shard_quota`].
+    QuotaExceeded,
+    /// Every slot in the inserting thread's shard is occupied.
+    Full,
+    /// The inserting thread has no shard, either because its index is
+    /// greater than [`Config::MAX_THREADS`], or because it is exiting, and
+    /// its thread-local storage has already been destroyed.
+    ///
+    /// [`Config::MAX_THREADS`]: crate::Config::MAX_THREADS
+    NoShard,
+    /// The slab has been closed. See [`Slab::close`].
+    Closed,
+}
+
 impl<T> Slab<T> {
-    /// Returns a new slab with the default configuration parameters.
-    pub fn new() -> Self {
-        Self::new_with_config()
+    const_fn! {
+        /// Returns a new slab with the default configuration parameters.
+        ///
+        /// The slab's storage isn't allocated until it is first used, so on
+        /// Rust 1.63 and later, this is a `const fn`, and may be used to
+        /// initialize a `static`:
+        ///
+        /// ```
+        /// use sharded_slab::Slab;
+        ///
+        /// static NAMES: Slab<&'static str> = Slab::new();
+        ///
+        /// let key = NAMES.insert("hello world").unwrap();
+        /// assert_eq!(NAMES.get(key).unwrap(), "hello world");
+        /// ```
+        pub fn new() -> Self {
+            Slab {
+                shards: shard::Array::new(),
+                watchers: watch::Watchers::new(),
+                _cfg: PhantomData,
+            }
+        }
     }
 
     /// Returns a new slab with the provided configuration parameters.
+    ///
+    /// # Panics
+    ///
+    /// If the configuration is invalid. See [`Slab::try_new_with_config`] for
+    /// a version of this function which returns an error instead.
     pub fn new_with_config<C: cfg::Config>() -> Slab<T, C> {
         C::validate();
         Slab {
             shards: shard::Array::new(),
+            watchers: watch::Watchers::new(),
             _cfg: PhantomData,
         }
     }
+
+    /// Returns a new slab with the provided configuration parameters, or an
+    /// error describing why the configuration is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    ///
+    /// struct TinyConfig;
+    ///
+    /// // With so few bits for the page index and thread ID, nearly every bit
+    /// // is used for the generation, which may leave too few for each slot's
+    /// // reference count.
+    /// impl Config for TinyConfig {
+    ///     const INITIAL_PAGE_SIZE: usize = 1;
+    ///     const MAX_THREADS: usize = 1;
+    ///     const MAX_PAGES: usize = 1;
+    /// }
+    ///
+    /// match Slab::<u64>::try_new_with_config::<TinyConfig>() {
+    ///     Ok(slab) => { /* ... */ }
+    ///     Err(error) => eprintln!("invalid config: {}", error),
+    /// }
+    /// ```
+    pub fn try_new_with_config<C: cfg::Config>() -> Result<Slab<T, C>, ConfigError> {
+        C::check()?;
+        Ok(Self::new_with_config())
+    }
 }
 
 impl<T, C: cfg::Config> Slab<T, C> {
@@ -403,6 +687,150 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// [res]: crate::Config#RESERVED_BITS
     pub const USED_BITS: usize = C::USED_BITS;
 
+    /// Attaches an observer to the slab, which is notified as values are
+    /// inserted and removed. See [`Lifecycle`] for details.
+    ///
+    /// This replaces any observer previously attached to the slab.
+    pub fn with_lifecycle(mut self, lifecycle: impl Lifecycle) -> Self {
+        self.shards.set_lifecycle(Arc::new(lifecycle));
+        self
+    }
+
+    /// Sends values whose removal was deferred to `reaper`, rather than
+    /// dropping them.
+    ///
+    /// A value which is referenced when it is [removed] is normally dropped
+    /// by whichever thread drops the last guard referencing it. If dropping
+    /// the value is expensive, that may stall a thread which was only reading
+    /// it. With a reaper, the value is moved out of the slab and passed to
+    /// `reaper` instead, which may send it to another thread to be dropped.
+    ///
+    /// `reaper` is still called on the thread which drops the last guard, so
+    /// it should be cheap. Values which aren't referenced when they are
+    /// removed are dropped by the thread removing them, as usual. Since the
+    /// slab can't tell when the reaper drops a value, a future returned by
+    /// `remove_async` resolves once the value has been moved out of the slab
+    /// for the reaper, rather than once it has been dropped.
+    ///
+    /// This replaces any reaper previously set for the slab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::sync::mpsc;
+    ///
+    /// let (tx, rx) = mpsc::sync_channel(64);
+    /// let slab = Slab::new().with_reaper(move |value: String| {
+    ///     let _ = tx.send(value);
+    /// });
+    ///
+    /// let key = slab.insert(String::from("hello world")).unwrap();
+    /// let guard = slab.get(key).unwrap();
+    /// assert!(slab.remove(key));
+    ///
+    /// // Dropping the last guard sends the value to the reaper.
+    /// drop(guard);
+    /// assert_eq!(rx.try_recv().unwrap(), "hello world");
+    /// ```
+    ///
+    /// [removed]: Slab::remove
+    pub fn with_reaper(mut self, reaper: impl Fn(T) + Send + Sync + 'static) -> Self
+    where
+        T: 'static,
+    {
+        self.shards.set_reaper(Arc::new(move |value: Option<T>| {
+            if let Some(value) = value {
+                reaper(value);
+            }
+        }));
+        self
+    }
+
+    /// Limits the number of values each shard of the slab may hold at once
+    /// to `quota`.
+    ///
+    /// Each thread inserts into its own shard, so this bounds how much of the
+    /// slab any one thread can fill, even if that is less than the shard's
+    /// capacity. Once a shard holds `quota` values, inserts into it fail with
+    /// [`InsertError::QuotaExceeded`] until some of them are removed.
+    ///
+    /// This replaces any quota previously set for the slab. The quota may be
+    /// changed later with [`set_shard_quota`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{InsertError, Slab};
+    ///
+    /// let slab = Slab::new().with_shard_quota(2);
+    /// let key = slab.insert("a").unwrap();
+    /// slab.insert("b").unwrap();
+    /// assert_eq!(slab.try_insert("c"), Err(InsertError::QuotaExceeded));
+    ///
+    /// slab.remove(key);
+    /// assert!(slab.try_insert("c").is_ok());
+    /// ```
+    ///
+    /// [`set_shard_quota`]: Slab::set_shard_quota
+    pub fn with_shard_quota(self, quota: usize) -> Self {
+        self.shards.set_quota(quota);
+        self
+    }
+
+    /// Changes the number of values each shard of the slab may hold at once,
+    /// while the slab is in use.
+    ///
+    /// If `quota` is `None`, shards may hold as many values as they have
+    /// slots for. Otherwise, this behaves like [`with_shard_quota`], which
+    /// allows an emergency limit to be applied, or lifted, without
+    /// rebuilding the slab.
+    ///
+    /// The new quota is enforced conservatively:
+    ///
+    /// - Lowering the quota doesn't remove any values. A shard which already
+    ///   holds more values than the new quota allows rejects inserts until
+    ///   enough of them are removed.
+    /// - The quota is read without synchronizing with other threads, so an
+    ///   insert which is running concurrently with `set_shard_quota` may be
+    ///   checked against either the previous quota or the new one. However,
+    ///   no shard ever holds more values than the higher of the two allows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{InsertError, Slab};
+    ///
+    /// let slab = Slab::new();
+    /// slab.insert("a").unwrap();
+    /// slab.insert("b").unwrap();
+    ///
+    /// slab.set_shard_quota(Some(1));
+    /// assert_eq!(slab.shard_quota(), Some(1));
+    /// assert_eq!(slab.try_insert("c"), Err(InsertError::QuotaExceeded));
+    ///
+    /// slab.set_shard_quota(None);
+    /// assert!(slab.try_insert("c").is_ok());
+    /// ```
+    ///
+    /// [`with_shard_quota`]: Slab::with_shard_quota
+    pub fn set_shard_quota(&self, quota: Option<usize>) {
+        self.shards.set_quota(quota.unwrap_or(!0));
+    }
+
+    /// Returns the number of values each shard of the slab may hold at once,
+    /// or `None` if the shards have no quota.
+    ///
+    /// See [`set_shard_quota`].
+    ///
+    /// [`set_shard_quota`]: Slab::set_shard_quota
+    pub fn shard_quota(&self) -> Option<usize> {
+        match self.shards.quota() {
+            quota if quota == !0 => None,
+            quota => Some(quota),
+        }
+    }
+
     /// Inserts a value into the slab, returning the integer index at which that
     /// value was inserted. This index can then be used to access the entry.
     ///
@@ -410,6 +838,10 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// is full and no items can be added until some are removed, or the maximum
     /// number of shards has been reached.
     ///
+    /// `None` is also returned if the current thread is exiting, and its
+    /// thread-local storage has already been destroyed. This may happen when a
+    /// value is inserted by the destructor of another thread-local.
+    ///
     /// # Examples
     /// ```rust
     /// # use sharded_slab::Slab;
@@ -419,15 +851,95 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// assert_eq!(slab.get(key).unwrap(), "hello world");
     /// ```
     pub fn insert(&self, value: T) -> Option<usize> {
-        let (tid, shard) = self.shards.current();
+        self.try_insert(value).ok()
+    }
+
+    /// Inserts a value into the slab, like [`insert`], but returns the reason
+    /// the value couldn't be inserted if it fails.
+    ///
+    /// This distinguishes a shard which is at its [quota] from one which is
+    /// actually full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, InsertError, Slab};
+    ///
+    /// struct TinyConfig;
+    ///
+    /// impl Config for TinyConfig {
+    ///     const INITIAL_PAGE_SIZE: usize = 2;
+    ///     const MAX_PAGES: usize = 1;
+    /// }
+    ///
+    /// let slab = Slab::new_with_config::<TinyConfig>();
+    /// assert!(slab.try_insert(1).is_ok());
+    /// assert!(slab.try_insert(2).is_ok());
+    /// assert_eq!(slab.try_insert(3), Err(InsertError::Full));
+    /// ```
+    ///
+    /// [`insert`]: Slab::insert
+    /// [quota]: Slab::with_shard_quota
+    pub fn try_insert(&self, value: T) -> Result<usize, InsertError> {
+        let (tid, shard) = match self.shards.current() {
+            Some(current) => current,
+            None if self.shards.is_closed() => return Err(InsertError::Closed),
+            None => return Err(InsertError::NoShard),
+        };
         test_println!("insert {:?}", tid);
         let mut value = Some(value);
-        shard
-            .init_with(|idx, slot| {
+        let key = shard
+            .try_init_with(|idx, slot| {
                 let gen = slot.insert(&mut value)?;
                 Some(gen.pack(idx))
             })
-            .map(|idx| tid.pack(idx))
+            .map(|idx| shard.tag(tid.pack(idx)))?;
+        shard.on_insert(key);
+        self.watchers.notify(shard.tid, key);
+        Ok(key)
+    }
+
+    /// Inserts the value returned by `f`, which is passed the key at which it
+    /// will be inserted, returning that key.
+    ///
+    /// This allows values which must contain their own key, such as graph
+    /// nodes that store their own ID, or callbacks that capture their key, to
+    /// be constructed in one step. It is a shorthand for reserving a slot
+    /// with [`vacant_entry`] and inserting into it.
+    ///
+    /// As with [`insert`](Slab::insert), this returns `None` if the slab is
+    /// full, or the current thread's thread-local storage has already been
+    /// destroyed. In that case, `f` is not called.
+    ///
+    /// If `f` panics, the slot reserved for its value is returned to the slab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// struct Node {
+    ///     id: usize,
+    ///     name: &'static str,
+    /// }
+    ///
+    /// let slab = Slab::new();
+    /// let key = slab.insert_with(|id| Node { id, name: "root" }).unwrap();
+    ///
+    /// let node = slab.get(key).unwrap();
+    /// assert_eq!(node.id, key);
+    /// assert_eq!(node.name, "root");
+    /// ```
+    ///
+    /// [`vacant_entry`]: Slab::vacant_entry
+    pub fn insert_with(&self, f: impl FnOnce(usize) -> T) -> Option<usize> {
+        let mut reserved = AbandonOnPanic {
+            entry: Some(self.vacant_entry()?),
+        };
+        let key = reserved.entry.as_ref()?.key();
+        let value = f(key);
+        // `f` didn't panic, so insert its value as usual.
+        reserved.entry.take()?.insert(value);
+        Some(key)
     }
 
     /// Return a handle to a vacant entry allowing for further manipulation.
@@ -436,6 +948,10 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// slab index. The returned [`VacantEntry`] reserves a slot in the slab and
     /// is able to return the index of the entry.
     ///
+    /// As with [`insert`](Slab::insert), this returns `None` if the slab is
+    /// full, or the current thread's thread-local storage has already been
+    /// destroyed.
+    ///
     /// # Examples
     ///
     /// ```
@@ -454,19 +970,58 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// assert_eq!("hello", slab.get(hello).unwrap().1);
     /// ```
     pub fn vacant_entry(&self) -> Option<VacantEntry<'_, T, C>> {
-        let (tid, shard) = self.shards.current();
+        let (tid, shard) = self.shards.current()?;
         test_println!("vacant_entry {:?}", tid);
         shard.init_with(|idx, slot| {
             let inner = slot.init()?;
-            let key = inner.generation().pack(tid.pack(idx));
+            let key = shard.tag(inner.generation().pack(tid.pack(idx)));
             Some(VacantEntry {
                 inner,
                 key,
-                _lt: PhantomData,
+                shard,
+                watchers: &self.watchers,
             })
         })
     }
 
+    /// Closes the slab, so that no more values can be inserted into it.
+    ///
+    /// Once this returns, every insert fails, and [`try_insert`] returns
+    /// [`InsertError::Closed`]. Values already in the slab may still be
+    /// accessed and removed as usual, which allows a shutdown to stop
+    /// accepting new work while finishing what it has.
+    ///
+    /// An insert which is running concurrently with `close` may or may not
+    /// succeed. A slab can't be reopened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{InsertError, Slab};
+    ///
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello").unwrap();
+    ///
+    /// slab.close();
+    /// assert!(slab.is_closed());
+    /// assert_eq!(slab.try_insert("world"), Err(InsertError::Closed));
+    ///
+    /// assert_eq!(slab.get(key).unwrap(), "hello");
+    /// assert!(slab.remove(key));
+    /// ```
+    ///
+    /// [`try_insert`]: Slab::try_insert
+    pub fn close(&self) {
+        self.shards.close();
+    }
+
+    /// Returns `true` if the slab has been [closed].
+    ///
+    /// [closed]: Slab::close
+    pub fn is_closed(&self) -> bool {
+        self.shards.is_closed()
+    }
+
     /// Remove the value at the given index in the slab, returning `true` if a
     /// value was removed.
     ///
@@ -518,7 +1073,7 @@ impl<T, C: cfg::Config> Slab<T, C> {
 
         test_println!("rm_deferred {:?}", tid);
         let shard = self.shards.get(tid.as_usize());
-        if tid.is_current() {
+        if tid.is_local() {
             shard.map(|shard| shard.remove_local(idx)).unwrap_or(false)
         } else {
             shard.map(|shard| shard.remove_remote(idx)).unwrap_or(false)
@@ -584,13 +1139,68 @@ impl<T, C: cfg::Config> Slab<T, C> {
 
         test_println!("rm {:?}", tid);
         let shard = self.shards.get(tid.as_usize())?;
-        if tid.is_current() {
+        if tid.is_local() {
             shard.take_local(idx)
         } else {
             shard.take_remote(idx)
         }
     }
 
+    /// Exchanges the values associated with two keys, returning `true` if
+    /// they were swapped.
+    ///
+    /// Both keys remain valid: afterwards, `a` refers to the value that was
+    /// associated with `b`, and vice versa. This allows entities to be
+    /// rearranged without invalidating keys held elsewhere, as removing and
+    /// reinserting the values would.
+    ///
+    /// Swapping requires exclusive access to both values, so it does not
+    /// wait for other threads to finish accessing them: if either value is
+    /// currently referenced, or the slab doesn't contain a value for either
+    /// key, nothing is swapped and `false` is returned. The values are
+    /// always locked in the same order, so concurrent swaps involving the
+    /// same keys can't prevent each other from succeeding when retried.
+    /// Swapping a key with itself succeeds if the slab contains a value for
+    /// it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let a = slab.insert("a").unwrap();
+    /// let b = slab.insert("b").unwrap();
+    ///
+    /// assert!(slab.swap(a, b));
+    /// assert_eq!(slab.get(a).unwrap(), "b");
+    /// assert_eq!(slab.get(b).unwrap(), "a");
+    ///
+    /// // A value that is being accessed can't be swapped.
+    /// let guard = slab.get(a).unwrap();
+    /// assert!(!slab.swap(a, b));
+    /// drop(guard);
+    ///
+    /// slab.remove(b);
+    /// assert!(!slab.swap(a, b));
+    /// ```
+    pub fn swap(&self, a: usize, b: usize) -> bool {
+        test_println!("swap {:#x} <-> {:#x}", a, b);
+        if a == b {
+            return self.contains(a);
+        }
+        let (first, second) = if a < b { (a, b) } else { (b, a) };
+        let mut first = match self.lock(first) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let mut second = match self.lock(second) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        mem::swap(&mut *first, &mut *second);
+        true
+    }
+
     /// Return a reference to the value associated with the given key.
     ///
     /// If the slab does not contain a value for the given key, or if the
@@ -611,89 +1221,234 @@ impl<T, C: cfg::Config> Slab<T, C> {
 
         test_println!("get {:?}; current={:?}", tid, Tid::<C>::current());
         let shard = self.shards.get(tid.as_usize())?;
-        shard.with_slot(key, |slot| {
-            let inner = slot.get(C::unpack_gen(key))?;
-            let value = ptr::NonNull::from(slot.value().as_ref().unwrap());
-            Some(Entry {
-                inner,
-                value,
-                shard,
-                key,
-            })
-        })
+        Entry::new(shard, key)
     }
 
-    /// Return an owned reference to the value at the given index.
+    /// Return a reference to the value associated with the given key, or the
+    /// reason it can't be accessed.
     ///
-    /// If the slab does not contain a value for the given key, `None` is
-    /// returned instead.
+    /// This is like [`get`], but distinguishes keys that never referred to a
+    /// value from keys whose value was removed, so that callers can tell a
+    /// stale key (which may just need to be looked up again) from one that
+    /// indicates a bug.
     ///
-    /// Unlike [`get`], which borrows the slab, this method _clones_ the [`Arc`]
-    /// around the slab. This means that the returned [`OwnedEntry`] can be held
-    /// for an arbitrary lifetime. However,  this method requires that the slab
-    /// itself be wrapped in an `Arc`.
+    /// The reason is only a snapshot: since other threads may be modifying
+    /// the slab concurrently, the slot may have changed by the time this
+    /// returns.
     ///
     /// # Examples
     ///
-    /// ```
-    /// # use sharded_slab::Slab;
-    /// use std::sync::Arc;
+    /// ```rust
+    /// use sharded_slab::{LookupError, Slab};
     ///
-    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
+    /// let slab = Slab::new();
     /// let key = slab.insert("hello world").unwrap();
+    /// assert_eq!(slab.get_detailed(key).unwrap(), "hello world");
     ///
-    /// // Look up the created key, returning an `OwnedEntry`.
-    /// let value = slab.clone().get_owned(key).unwrap();
-    ///
-    /// // Now, the original `Arc` clone of the slab may be dropped, but the
-    /// // returned `OwnedEntry` can still access the value.
-    /// assert_eq!(value, "hello world");
+    /// slab.remove(key);
+    /// assert_eq!(slab.get_detailed(key).unwrap_err(), LookupError::Stale);
     /// ```
     ///
-    /// Unlike [`Entry`], an `OwnedEntry` may be stored in a struct which must live
-    /// for the `'static` lifetime:
+    /// [`get`]: Slab::get
+    pub fn get_detailed(&self, key: usize) -> Result<Entry<'_, T, C>, LookupError> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("get_detailed {:?}; current={:?}", tid, Tid::<C>::current());
+        let shard = self
+            .shards
+            .get(tid.as_usize())
+            .ok_or(LookupError::OutOfRange)?;
+        Entry::new_detailed(shard, key)
+    }
+
+    /// Return a reference to the value associated with the given key, waiting
+    /// up to `timeout` for it to become accessible.
     ///
-    /// ```
-    /// # use sharded_slab::Slab;
-    /// use sharded_slab::OwnedEntry;
-    /// use std::sync::Arc;
+    /// This is like [`get`], but if the value already has the maximum number
+    /// of concurrent references (or, in a [`LockedSlab`], is locked for
+    /// writing), it backs off and tries again until the value can be
+    /// referenced or the timeout elapses, rather than returning `None`
+    /// immediately. This smooths over brief bursts of contention without the
+    /// caller having to write its own retry loop.
     ///
-    /// pub struct MyStruct {
-    ///     entry: OwnedEntry<&'static str>,
-    ///     // ... other fields ...
-    /// }
+    /// If the slab doesn't contain a value for the given key, or the value has
+    /// been removed and is being cleared, it can never become accessible, so
+    /// `None` is returned without waiting.
     ///
-    /// // Suppose this is some arbitrary function which requires a value that
-    /// // lives for the 'static lifetime...
-    /// fn function_requiring_static<T: 'static>(t: &T) {
-    ///     // ... do something extremely important and interesting ...
-    /// }
+    /// Since this waits by spinning and yielding to the OS scheduler, it
+    /// should only be used with short timeouts, and never in async code.
     ///
-    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
-    /// let key = slab.insert("hello world").unwrap();
+    /// # Examples
     ///
-    /// // Look up the created key, returning an `OwnedEntry`.
-    /// let entry = slab.clone().get_owned(key).unwrap();
-    /// let my_struct = MyStruct {
-    ///     entry,
-    ///     // ...
-    /// };
+    /// ```rust
+    /// use std::time::Duration;
     ///
-    /// // We can use `my_struct` anywhere where it is required to have the
-    /// // `'static` lifetime:
-    /// function_requiring_static(&my_struct);
-    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
     ///
-    /// [`OwnedEntry`]s may be sent between threads:
+    /// let timeout = Duration::from_millis(10);
+    /// assert_eq!(slab.get_timeout(key, timeout).unwrap(), "hello world");
     ///
+    /// slab.remove(key);
+    /// assert!(slab.get_timeout(key, timeout).is_none());
     /// ```
-    /// # use sharded_slab::Slab;
-    /// use std::{thread, sync::Arc};
     ///
-    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
-    /// let key = slab.insert("hello world").unwrap();
-    ///
-    /// // Look up the created key, returning an `OwnedEntry`.
+    /// [`get`]: Slab::get
+    pub fn get_timeout(&self, key: usize, timeout: Duration) -> Option<Entry<'_, T, C>> {
+        // A timeout too long to represent is as good as no timeout at all.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut backoff = 0;
+        loop {
+            match self.get_detailed(key) {
+                Ok(entry) => return Some(entry),
+                Err(LookupError::TooManyReferences) | Err(LookupError::Locked) => {}
+                Err(_) => return None,
+            }
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                test_println!("get_timeout {:#x}: timed out", key);
+                return None;
+            }
+            page::slot::exponential_backoff(&mut backoff);
+        }
+    }
+
+    /// Returns a raw pointer to the value associated with the given key,
+    /// without holding a guard to it.
+    ///
+    /// If the slab does not contain a value for the given key, `None` is
+    /// returned instead.
+    ///
+    /// Since no guard is held, the value may be removed as soon as this
+    /// returns. The pointer may only be dereferenced while the value remains
+    /// in the slab, which the caller must ensure by other means; for example,
+    /// by only removing values from a single thread, or by holding a guard
+    /// for the value elsewhere. See [Stable Addresses](crate#stable-addresses)
+    /// for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert(1234u64).unwrap();
+    ///
+    /// let ptr = slab.get_raw(key).unwrap();
+    /// // Safety: the value is not removed until after the pointer is used.
+    /// assert_eq!(unsafe { *ptr }, 1234);
+    ///
+    /// slab.remove(key);
+    /// assert!(slab.get_raw(key).is_none());
+    /// ```
+    pub fn get_raw(&self, key: usize) -> Option<*const T> {
+        self.get(key).map(|entry| entry.as_ptr())
+    }
+
+    /// Returns a [`WeakEntry`] referring to the value associated with the
+    /// given key.
+    ///
+    /// The weak entry can later be [upgraded] to an [`Entry`], provided the
+    /// value it refers to has not been removed in the meantime. Unlike an
+    /// `Entry`, a `WeakEntry` does not keep the value from being removed.
+    ///
+    /// If the slab does not currently contain a value for the key, the
+    /// returned `WeakEntry` will never upgrade.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// let weak = slab.downgrade(key);
+    /// assert_eq!(weak.key(), key);
+    /// assert_eq!(weak.upgrade().unwrap(), "hello world");
+    ///
+    /// slab.take(key).unwrap();
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    ///
+    /// [upgraded]: WeakEntry::upgrade
+    pub fn downgrade(&self, key: usize) -> WeakEntry<'_, T, C> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("downgrade {:?}; current={:?}", tid, Tid::<C>::current());
+        WeakEntry {
+            shard: self.shards.get(tid.as_usize()),
+            key,
+        }
+    }
+
+    /// Return an owned reference to the value at the given index.
+    ///
+    /// If the slab does not contain a value for the given key, `None` is
+    /// returned instead.
+    ///
+    /// Unlike [`get`], which borrows the slab, this method _clones_ the [`Arc`]
+    /// around the slab. This means that the returned [`OwnedEntry`] can be held
+    /// for an arbitrary lifetime. However,  this method requires that the slab
+    /// itself be wrapped in an `Arc`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::sync::Arc;
+    ///
+    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// // Look up the created key, returning an `OwnedEntry`.
+    /// let value = slab.clone().get_owned(key).unwrap();
+    ///
+    /// // Now, the original `Arc` clone of the slab may be dropped, but the
+    /// // returned `OwnedEntry` can still access the value.
+    /// assert_eq!(value, "hello world");
+    /// ```
+    ///
+    /// Unlike [`Entry`], an `OwnedEntry` may be stored in a struct which must live
+    /// for the `'static` lifetime:
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use sharded_slab::OwnedEntry;
+    /// use std::sync::Arc;
+    ///
+    /// pub struct MyStruct {
+    ///     entry: OwnedEntry<&'static str>,
+    ///     // ... other fields ...
+    /// }
+    ///
+    /// // Suppose this is some arbitrary function which requires a value that
+    /// // lives for the 'static lifetime...
+    /// fn function_requiring_static<T: 'static>(t: &T) {
+    ///     // ... do something extremely important and interesting ...
+    /// }
+    ///
+    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// // Look up the created key, returning an `OwnedEntry`.
+    /// let entry = slab.clone().get_owned(key).unwrap();
+    /// let my_struct = MyStruct {
+    ///     entry,
+    ///     // ...
+    /// };
+    ///
+    /// // We can use `my_struct` anywhere where it is required to have the
+    /// // `'static` lifetime:
+    /// function_requiring_static(&my_struct);
+    /// ```
+    ///
+    /// [`OwnedEntry`]s may be sent between threads:
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{thread, sync::Arc};
+    ///
+    /// let slab: Arc<Slab<&'static str>> = Arc::new(Slab::new());
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// // Look up the created key, returning an `OwnedEntry`.
     /// let value = slab.clone().get_owned(key).unwrap();
     ///
     /// thread::spawn(move || {
@@ -712,6 +1467,7 @@ impl<T, C: cfg::Config> Slab<T, C> {
         shard.with_slot(key, |slot| {
             let inner = slot.get(C::unpack_gen(key))?;
             let value = ptr::NonNull::from(slot.value().as_ref().unwrap());
+            shard.record_access(key);
             Some(OwnedEntry {
                 inner,
                 value,
@@ -721,21 +1477,850 @@ impl<T, C: cfg::Config> Slab<T, C> {
         })
     }
 
-    /// Returns `true` if the slab contains a value for the given key.
+    /// Returns a clone of the value associated with the given key.
+    ///
+    /// The value is cloned while a guard referencing it is held, and the
+    /// guard is dropped before this returns. This is useful when only a copy
+    /// of the value is needed, such as when returning it from a function,
+    /// since the returned value doesn't borrow the slab, and holding it
+    /// doesn't delay the clearing of the slot if the value is removed.
+    ///
+    /// If the slab does not contain a value for the given key, or if the
+    /// maximum number of concurrent references to the slot has been reached,
+    /// `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Slab;
+    /// fn lookup(slab: &Slab<String>, key: usize) -> Option<String> {
+    ///     slab.get_cloned(key)
+    /// }
+    ///
+    /// let slab = Slab::new();
+    /// let key = slab.insert(String::from("hello world")).unwrap();
+    ///
+    /// let value = lookup(&slab, key).unwrap();
+    /// assert!(slab.remove(key));
+    /// assert_eq!(value, "hello world");
+    /// assert!(slab.get_cloned(key).is_none());
+    /// ```
+    pub fn get_cloned(&self, key: usize) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.with(key, T::clone)
+    }
+
+    /// Calls `f` with a reference to the value associated with the given
+    /// key, returning its result.
+    ///
+    /// This is like [`get`], but the value is only borrowed for the duration
+    /// of the call, so the guard type doesn't have to be named, and can't be
+    /// held for longer than intended.
+    ///
+    /// If the slab does not contain a value for the given key, or if the
+    /// maximum number of concurrent references to the slot has been reached,
+    /// `f` is not called, and `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// let slab = sharded_slab::Slab::new();
+    /// let key = slab.insert(String::from("hello world")).unwrap();
+    ///
+    /// assert_eq!(slab.with(key, |value| value.len()), Some(11));
+    /// assert_eq!(slab.with(12345, |value| value.len()), None);
+    /// ```
+    ///
+    /// [`get`]: Slab::get
+    pub fn with<R>(&self, key: usize, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let entry = self.get(key)?;
+        Some(f(&entry))
+    }
+
+    /// Returns the keys of the values which were inserted into the slab more
+    /// than `max_age` ago.
+    ///
+    /// This requires [`Config::TRACK_INSERTED`] to be enabled; otherwise, it
+    /// always returns an empty `Vec`. This may be called concurrently with
+    /// other operations, so it may not reflect values which are being
+    /// inserted or removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    /// use std::{thread, time::Duration};
+    ///
+    /// struct TrackInserted;
+    ///
+    /// impl Config for TrackInserted {
+    ///     const TRACK_INSERTED: bool = true;
+    /// }
+    ///
+    /// let slab = Slab::new_with_config::<TrackInserted>();
+    /// let old = slab.insert("old").unwrap();
+    /// thread::sleep(Duration::from_millis(50));
+    /// slab.insert("new").unwrap();
+    ///
+    /// assert_eq!(slab.keys_older_than(Duration::from_millis(20)), vec![old]);
+    /// ```
+    ///
+    /// [`Config::TRACK_INSERTED`]: crate::Config::TRACK_INSERTED
+    pub fn keys_older_than(&self, max_age: Duration) -> Vec<usize> {
+        if !C::TRACK_INSERTED {
+            return Vec::new();
+        }
+        let now = timestamps::now();
+        let max_age = timestamps::to_ticks(max_age);
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                // A slot's insertion time is cleared when its value is
+                // removed, so only occupied slots have one.
+                shard
+                    .keys()
+                    .filter(move |&key| timestamps::is_older(shard.inserted_at(key), now, max_age))
+            })
+            .collect()
+    }
+
+    /// Returns the keys of the values in the slab which have not been
+    /// accessed for more than `older_than`.
+    ///
+    /// This requires [`Config::TRACK_ACCESS`] to be enabled; otherwise, it
+    /// always returns an empty `Vec`. This is useful for sweeping idle values
+    /// out of the slab, such as connections which have timed out.
+    ///
+    /// Inserting a value counts as an access, as does each lookup of its key
+    /// which finds it: [`get`], [`get_detailed`], [`get_timeout`],
+    /// [`get_owned`], [`get_raw`], [`get_cloned`], [`with`], [`contains`] and
+    /// [`WeakEntry::upgrade`]. So does [`find`] returning it.
+    ///
+    /// Visiting a value while scanning the slab does not count: neither
+    /// [`to_vec`], [`scan_range`], [`snapshot`], nor [`find`] passing it to the
+    /// predicate without returning it, record an access, and nor does this
+    /// method. So a sweep may inspect values before deciding which to remove
+    /// without keeping them all alive.
+    ///
+    /// This may be called concurrently with other operations, so it may not
+    /// reflect values which are being accessed, inserted or removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    /// use std::{thread, time::Duration};
+    ///
+    /// struct TrackAccess;
+    ///
+    /// impl Config for TrackAccess {
+    ///     const TRACK_ACCESS: bool = true;
+    /// }
+    ///
+    /// let slab = Slab::new_with_config::<TrackAccess>();
+    /// let idle = slab.insert("idle").unwrap();
+    /// let busy = slab.insert("busy").unwrap();
+    /// let removed = slab.insert("removed").unwrap();
+    /// slab.remove(removed);
+    /// thread::sleep(Duration::from_millis(50));
+    ///
+    /// drop(slab.get(busy));
+    /// assert_eq!(slab.idle_keys(Duration::from_millis(20)), vec![idle]);
+    /// ```
+    ///
+    /// [`Config::TRACK_ACCESS`]: crate::Config::TRACK_ACCESS
+    /// [`get`]: Slab::get
+    /// [`get_detailed`]: Slab::get_detailed
+    /// [`get_timeout`]: Slab::get_timeout
+    /// [`get_owned`]: Slab::get_owned
+    /// [`get_raw`]: Slab::get_raw
+    /// [`get_cloned`]: Slab::get_cloned
+    /// [`with`]: Slab::with
+    /// [`contains`]: Slab::contains
+    /// [`find`]: Slab::find
+    /// [`to_vec`]: Slab::to_vec
+    /// [`scan_range`]: Slab::scan_range
+    /// [`snapshot`]: Slab::snapshot
+    pub fn idle_keys(&self, older_than: Duration) -> Vec<usize> {
+        if !C::TRACK_ACCESS {
+            return Vec::new();
+        }
+        let now = timestamps::now();
+        let max_idle = timestamps::to_ticks(older_than);
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                // A slot's last use is recorded when its value is removed,
+                // too, so vacant slots must be skipped.
+                shard.keys().filter(move |&key| {
+                    timestamps::is_older(shard.last_used_at(key), now, max_idle)
+                        && shard.holds_value(key)
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the key and a clone of the value of each item in the slab.
+    ///
+    /// Each value is cloned while a guard referencing it is held, and only
+    /// one guard is held at a time, so this never blocks, and delays the
+    /// clearing of at most one removed value. The returned `Vec` is sized for
+    /// the number of items in the slab when this is called.
+    ///
+    /// Unlike [`unique_iter`], this may be called concurrently with other
+    /// operations, so it may not reflect values which are being inserted or
+    /// removed. The items are in no particular order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let a = slab.insert(String::from("a")).unwrap();
+    /// let b = slab.insert(String::from("b")).unwrap();
+    /// slab.remove(slab.insert(String::from("c")).unwrap());
+    ///
+    /// let mut items = slab.to_vec();
+    /// items.sort();
+    /// assert_eq!(items, vec![(a, String::from("a")), (b, String::from("b"))]);
+    /// ```
+    ///
+    /// [`unique_iter`]: Slab::unique_iter
+    pub fn to_vec(&self) -> Vec<(usize, T)>
+    where
+        T: Clone,
+    {
+        let len = self
+            .shards
+            .iter()
+            .flat_map(|shard| shard.pages().map(|page| page.live()))
+            .sum();
+        let mut items = Vec::with_capacity(len);
+        for shard in self.shards.iter() {
+            for key in shard.keys() {
+                if let Some(entry) = Entry::scanned(shard, key) {
+                    items.push((key, T::clone(&entry)));
+                }
+            }
+        }
+        items
+    }
+
+    /// Returns the key of the first item in the slab for which `predicate`
+    /// returns `true`, along with a guard referencing it.
+    ///
+    /// Pages which don't contain any values are skipped, and the scan stops
+    /// at the first match. Only one guard is held at a time while the slab is
+    /// scanned, so this never blocks.
+    ///
+    /// Unlike [`unique_iter`], this may be called concurrently with other
+    /// operations, so it may not find values which are being inserted. Since
+    /// the items are in no particular order, if more than one item matches,
+    /// any of them may be returned.
+    ///
+    /// Only the returned item counts as having been accessed, as if it had
+    /// been looked up with [`get`]; the items which were passed to
+    /// `predicate` but didn't match don't.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// slab.insert("hello").unwrap();
+    /// let key = slab.insert("world").unwrap();
+    ///
+    /// let (found, entry) = slab.find(|&value| value == "world").unwrap();
+    /// assert_eq!(found, key);
+    /// assert_eq!(*entry, "world");
+    ///
+    /// assert!(slab.find(|&value| value == "goodbye").is_none());
+    /// ```
+    ///
+    /// [`unique_iter`]: Slab::unique_iter
+    /// [`get`]: Slab::get
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<(usize, Entry<'_, T, C>)> {
+        self.shards.iter().find_map(|shard| {
+            shard.live_keys().find_map(|key| {
+                let entry = Entry::scanned(shard, key)?;
+                if predicate(&entry) {
+                    shard.record_access(key);
+                    Some((key, entry))
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    /// Returns an iterator over the values in a range of the slab's shards
+    /// and pages.
+    ///
+    /// `shards` is a range of shard indices, as returned by
+    /// [`ShardId::as_usize`], and `pages` is a range of page indices within
+    /// each of those shards, as in [`page_stats`]. Only the slots in those
+    /// pages are visited, so a region of a large slab may be scanned without
+    /// visiting every key. Ranges that extend past the slab's last shard or
+    /// page are clamped to it.
+    ///
+    /// Like [`find`], only one guard is held by the iterator at a time, and
+    /// this may be called concurrently with other operations, so it may not
+    /// reflect values which are being inserted or removed. The values visited
+    /// don't count as having been accessed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let keys = (0..100).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    /// let shard = slab.shard_id(keys[0]).as_usize();
+    ///
+    /// // The first page holds 32 values, and the second holds the next 64.
+    /// let values = slab
+    ///     .scan_range(shard..=shard, 2..)
+    ///     .map(|entry| *entry)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(values, vec![96, 97, 98, 99]);
+    ///
+    /// assert_eq!(slab.scan_range(.., ..1).count(), 32);
+    /// assert_eq!(slab.scan_range(shard + 1.., ..).count(), 0);
+    /// ```
+    ///
+    /// [`page_stats`]: Slab::page_stats
+    /// [`find`]: Slab::find
+    pub fn scan_range(
+        &self,
+        shards: impl RangeBounds<usize>,
+        pages: impl RangeBounds<usize>,
+    ) -> ScanRange<'_, T, C> {
+        let pages = iter::clamp(pages, C::MAX_PAGES);
+        ScanRange {
+            slab: self,
+            shards: iter::clamp(shards, C::MAX_SHARDS),
+            page: pages.end,
+            pages,
+            shard: None,
+            keys: Vec::new().into_iter(),
+        }
+    }
+
+    /// Returns `true` if the slab contains a value for the given key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    ///
+    /// let key = slab.insert("hello world").unwrap();
+    /// assert!(slab.contains(key));
+    ///
+    /// slab.take(key).unwrap();
+    /// assert!(!slab.contains(key));
+    /// ```
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of outstanding references to the value associated
+    /// with the given key, or `None` if the key is stale or was never valid.
+    ///
+    /// This counts every [`Entry`] and [`OwnedEntry`] currently referencing
+    /// the value. It also reports values which have been [removed] but not
+    /// yet cleared because they are still referenced, which makes it useful
+    /// for tracking down guards that are held longer than expected.
+    ///
+    /// The returned count is advisory only: since other threads may acquire
+    /// or drop references concurrently, it may already be out of date by the
+    /// time this returns, and it should not be used for synchronization.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    /// assert_eq!(slab.ref_count(key), Some(0));
+    ///
+    /// let guard = slab.get(key).unwrap();
+    /// assert_eq!(slab.ref_count(key), Some(1));
+    ///
+    /// // The value can't be cleared while it's still referenced.
+    /// assert!(slab.remove(key));
+    /// assert_eq!(slab.ref_count(key), Some(1));
+    ///
+    /// drop(guard);
+    /// assert_eq!(slab.ref_count(key), None);
+    /// ```
+    ///
+    /// [removed]: Slab::remove
+    pub fn ref_count(&self, key: usize) -> Option<usize> {
+        let tid = C::unpack_tid(key);
+        let shard = self.shards.get(tid.as_usize())?;
+        shard.ref_count(key)
+    }
+
+    /// Returns the effective values of the slab's [`Config`] parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    ///
+    /// struct CustomConfig;
+    ///
+    /// impl Config for CustomConfig {
+    ///     const INITIAL_PAGE_SIZE: usize = 30;
+    ///     const MAX_PAGES: usize = 4;
+    ///     const MAX_THREADS: usize = 10;
+    /// }
+    ///
+    /// let slab = Slab::<u64, _>::new_with_config::<CustomConfig>();
+    /// let config = slab.config();
+    /// assert_eq!(config.initial_page_size(), 32);
+    /// assert_eq!(config.max_shards(), 16);
+    /// assert_eq!(config.shard_capacity(), 32 + 64 + 128 + 256);
+    /// ```
+    pub fn config(&self) -> ConfigInfo {
+        ConfigInfo::new::<C>()
+    }
+
+    /// Returns the ID of the shard that the given key belongs to.
+    ///
+    /// This doesn't check whether the key refers to a value in the slab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// // Values are inserted into the current thread's shard.
+    /// assert_eq!(Some(slab.shard_id(key)), slab.current_shard_id());
+    /// ```
+    pub fn shard_id(&self, key: usize) -> ShardId {
+        C::unpack_tid(key).into()
+    }
+
+    /// Returns `true` if removing the entry with the given key on the current
+    /// thread would free its slot to the current thread's own shard.
+    ///
+    /// A slot freed by the thread that owns its shard is pushed to a free
+    /// list that only that thread accesses. A slot freed by any other thread
+    /// must be pushed to a free list shared with other threads, which requires
+    /// an atomic compare-and-swap, and may contend with other threads freeing
+    /// slots in the same page. Applications that can choose which thread
+    /// handles each entry, such as a work-stealing scheduler, can use this to
+    /// hand removals off to the thread that owns the entry's shard.
+    ///
+    /// This does not check whether the slab contains a value for the key. If
+    /// the slab's [`Config`] sets [`BALANCED_INSERTS`] or [`DETERMINISTIC`],
+    /// slots are never freed to the local free list, so this always returns
+    /// `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let slab = Arc::new(Slab::new());
+    /// let key = slab.insert("hello").unwrap();
+    /// assert!(slab.is_local(key));
+    ///
+    /// let slab2 = slab.clone();
+    /// thread::spawn(move || {
+    ///     assert!(!slab2.is_local(key));
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// ```
+    ///
+    /// [`BALANCED_INSERTS`]: Config::BALANCED_INSERTS
+    /// [`DETERMINISTIC`]: Config::DETERMINISTIC
+    pub fn is_local(&self, key: usize) -> bool {
+        C::unpack_tid(key).is_local()
+    }
+
+    /// Returns the ID of the shard that values inserted by the current thread
+    /// are stored in.
+    ///
+    /// This returns `None` if the current thread's ID can't be accessed
+    /// because its thread-local storage is being destroyed, in which case the
+    /// current thread can't insert into the slab either. If the slab's
+    /// [`Config`] sets [`DETERMINISTIC`] or [`SINGLE_THREADED`], this always
+    /// returns shard 0, and if it sets [`SHARD_HINTS`] and a shard hint is
+    /// set, this returns the hinted shard.
+    ///
+    /// [`DETERMINISTIC`]: Config::DETERMINISTIC
+    /// [`SINGLE_THREADED`]: Config::SINGLE_THREADED
+    /// [`SHARD_HINTS`]: Config::SHARD_HINTS
+    pub fn current_shard_id(&self) -> Option<ShardId> {
+        if C::SINGLE_THREADED {
+            return Some(Tid::<C>::from_usize(0).into());
+        }
+        let tid = Tid::<C>::current();
+        if tid.is_poisoned() {
+            return None;
+        }
+        if C::DETERMINISTIC {
+            return Some(Tid::<C>::from_usize(0).into());
+        }
+        if C::SHARD_HINTS {
+            if let Some(hinted) = Tid::<C>::hinted() {
+                return Some(hinted.into());
+            }
+        }
+        Some(tid.into())
+    }
+
+    /// Returns how many more values the current thread can insert into the
+    /// slab before its shard is full.
+    ///
+    /// This allows admission control to shed load before inserts start to
+    /// fail, and to tell whether accepting more values will require new pages
+    /// to be allocated. Slots freed concurrently by other threads may not be
+    /// counted. If the slab has a [shard quota], the remaining capacity is
+    /// limited by it.
+    ///
+    /// This returns `None` if the current thread's ID can't be accessed
+    /// because its thread-local storage is being destroyed, or if the slab's
+    /// [`Config`] sets [`BALANCED_INSERTS`], in which case values aren't
+    /// inserted into the current thread's shard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    ///
+    /// struct CustomConfig;
+    ///
+    /// impl Config for CustomConfig {
+    ///     const INITIAL_PAGE_SIZE: usize = 4;
+    ///     const MAX_PAGES: usize = 2;
+    /// }
+    ///
+    /// let slab = Slab::new_with_config::<CustomConfig>();
+    /// let remaining = slab.remaining_on_current_shard().unwrap();
+    /// assert_eq!(remaining.without_allocating(), 0);
+    /// assert_eq!(remaining.with_allocating(), 4 + 8);
+    ///
+    /// slab.insert("hello").unwrap();
+    /// let remaining = slab.remaining_on_current_shard().unwrap();
+    /// assert_eq!(remaining.without_allocating(), 3);
+    /// assert_eq!(remaining.with_allocating(), 3 + 8);
+    /// ```
+    ///
+    /// [`BALANCED_INSERTS`]: Config::BALANCED_INSERTS
+    /// [shard quota]: Slab::with_shard_quota
+    pub fn remaining_on_current_shard(&self) -> Option<RemainingCapacity> {
+        if C::BALANCED_INSERTS {
+            return None;
+        }
+        let idx = self.current_shard_id()?.as_usize();
+        if idx >= C::MAX_SHARDS {
+            return Some(RemainingCapacity {
+                vacant: 0,
+                unallocated: 0,
+            });
+        }
+        match self.shards.get(idx) {
+            Some(shard) => Some(shard.remaining()),
+            None => Some(
+                RemainingCapacity {
+                    vacant: 0,
+                    unallocated: self.config().shard_capacity(),
+                }
+                .clamp(self.shards.quota()),
+            ),
+        }
+    }
+
+    /// Assigns the given shard to the current thread, returning `true` if it
+    /// was assigned.
+    ///
+    /// By default, threads are assigned shards in the order in which they
+    /// first access any slab. Binding each worker in a thread pool to a
+    /// particular shard makes the assignment deterministic, so that, for
+    /// example, a pool's workers always use shards `0..N`.
+    ///
+    /// Shards are assigned to threads process-wide: the current thread will
+    /// use the same shard in every slab and pool. If the current thread was
+    /// previously assigned a different shard, it stops using that shard, as
+    /// though it had exited.
+    ///
+    /// This returns `false`, and the current thread's shard is not changed,
+    /// if the shard is in use by another thread, or is greater than the
+    /// maximum number of shards in this slab's configuration. Shards are
+    /// assigned to threads as they access slabs, so threads should be bound
+    /// to shards before any other threads access a slab.
+    pub fn bind_current_thread_to(&self, shard: ShardId) -> bool {
+        shard.as_usize() < C::MAX_SHARDS && Tid::<C>::bind_current(shard.as_usize())
+    }
+
+    /// Assigns the current thread a shard whose thread has exited, returning
+    /// its ID.
+    ///
+    /// Only a shard's own thread may insert into it, or reuse the slots freed
+    /// by that thread. When a thread exits, its shard is orphaned: its vacant
+    /// slots, and any freed by other threads since, can't be reused until
+    /// some thread that is spawned later happens to be assigned the same
+    /// shard. Adopting the shard allows the current thread to reuse those
+    /// slots immediately.
+    ///
+    /// Of this slab's shards that are not in use by any thread, the one with
+    /// the lowest ID is adopted. As with [`bind_current_thread_to`], shards
+    /// are assigned process-wide, and if the current thread was previously
+    /// assigned a different shard, it stops using that shard, which may then
+    /// itself be adopted.
+    ///
+    /// This returns `None`, and the current thread's shard is not changed,
+    /// if none of this slab's shards are orphaned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let slab = Arc::new(Slab::new());
+    /// let slab2 = slab.clone();
+    /// let key = thread::spawn(move || slab2.insert("hello").unwrap())
+    ///     .join()
+    ///     .unwrap();
+    ///
+    /// // The thread which inserted the value has exited, so its shard is
+    /// // orphaned.
+    /// thread::spawn(move || {
+    ///     let shard = slab
+    ///         .adopt_orphaned_shard()
+    ///         .expect("the inserting thread has exited");
+    ///     assert_eq!(slab.current_shard_id(), Some(shard));
+    ///     assert_eq!(slab.shard_id(key), shard);
+    ///     assert!(slab.remove(key));
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// ```
+    ///
+    /// [`bind_current_thread_to`]: Slab::bind_current_thread_to
+    pub fn adopt_orphaned_shard(&self) -> Option<ShardId> {
+        Tid::<C>::adopt_current(self.shards.iter().map(|shard| shard.tid)).map(ShardId::from)
+    }
+
+    /// Returns the number of shards that have been allocated in this slab.
+    ///
+    /// A shard is allocated the first time a thread inserts into the slab, and
+    /// is not deallocated until the slab is dropped. Since threads that exit
+    /// release their shards for reuse by threads spawned later, this should
+    /// remain close to the largest number of threads that have used the slab
+    /// at once. If it instead grows with the total number of threads spawned,
+    /// thread IDs are not being reused, which will eventually cause inserts to
+    /// fail once the configured [`MAX_THREADS`] is exceeded. Such failures
+    /// are counted by [`InsertFailures::max_shards`].
+    ///
+    /// Shards may be allocated concurrently, in which case they may or may not
+    /// be counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// assert_eq!(slab.active_shards(), 0);
+    ///
+    /// let key = slab.insert("hello").unwrap();
+    /// assert_eq!(slab.active_shards(), 1);
+    /// assert_eq!(
+    ///     slab.active_shard_ids().collect::<Vec<_>>(),
+    ///     vec![slab.shard_id(key)],
+    /// );
+    /// ```
+    ///
+    /// [`MAX_THREADS`]: Config::MAX_THREADS
+    pub fn active_shards(&self) -> usize {
+        self.shards.iter().count()
+    }
+
+    /// Returns an iterator over the IDs of the shards that have been allocated
+    /// in this slab, in ascending order.
+    ///
+    /// See [`active_shards`] for details.
+    ///
+    /// [`active_shards`]: Slab::active_shards
+    pub fn active_shard_ids(&self) -> impl Iterator<Item = ShardId> + '_ {
+        self.shards
+            .iter()
+            .map(|shard| Tid::<C>::from_usize(shard.tid).into())
+    }
+
+    /// Moves the values in the slab into the lowest vacant slots of their
+    /// shards, and releases the memory for any pages left empty.
+    ///
+    /// A slab's pages are never deallocated as values are removed, so a slab
+    /// that once held many values otherwise retains its peak memory use for
+    /// as long as any of its highest-addressed slots are occupied. Since a
+    /// value's key encodes its location in the slab, moving a value changes
+    /// its key: `remap` is called with the old and new keys of each value
+    /// that is moved, so that any stored keys may be updated.
+    ///
+    /// Values are only moved within the shard they were inserted into.
+    ///
+    /// To collect the moved keys into a table instead, use
+    /// [`compact_remap`](Slab::compact_remap).
+    ///
+    /// Keys that were not updated must not be used once the slab is
+    /// compacted. Since the storage for a page is released, slots that are
+    /// later reallocated will reuse generations, and stale keys may refer to
+    /// unrelated values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// let mut slab = sharded_slab::Slab::new();
+    /// let mut names = HashMap::new();
+    /// for i in 0..100 {
+    ///     let key = slab.insert(i).unwrap();
+    ///     names.insert(format!("value {}", i), key);
+    /// }
+    ///
+    /// // Remove all but the last value...
+    /// for i in 0..99 {
+    ///     slab.remove(names.remove(&format!("value {}", i)).unwrap());
+    /// }
+    ///
+    /// // ...and move it to the front of the slab.
+    /// slab.compact(|old_key, new_key| {
+    ///     for key in names.values_mut() {
+    ///         if *key == old_key {
+    ///             *key = new_key;
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// let key = names["value 99"];
+    /// assert_eq!(slab.get(key).unwrap(), 99);
+    /// ```
+    pub fn compact(&mut self, mut remap: impl FnMut(usize, usize)) {
+        for shard in self.shards.iter_mut() {
+            unsafe {
+                // Safety: we have exclusive access to the slab.
+                shard.compact(&mut remap);
+            }
+        }
+    }
+
+    /// Moves every value into a new slab with a different [`Config`],
+    /// returning the new slab.
+    ///
+    /// This allows a slab's configuration parameters, such as its
+    /// [`INITIAL_PAGE_SIZE`] or [`RESERVED_BITS`], to be changed without
+    /// draining and reinserting its values by hand. Since a value's key
+    /// encodes its location in the slab, every value's key changes: `remap`
+    /// is called with the old and new keys of each value, so that any stored
+    /// keys may be updated. The pairs may also be collected into a
+    /// [`KeyRemap`].
+    ///
+    /// Each value stays in the shard with the same index, if the new slab
+    /// has that many shards and room for it there, and is otherwise moved
+    /// to the next shard with room for it. Values are inserted as though
+    /// they were new, so timestamps tracked by the new configuration start
+    /// from when they were moved. Any lifecycle, reaper, or quota set on the
+    /// slab is not carried over, and must be set on the new slab again.
+    ///
+    /// If the new configuration can't hold every value in the slab, no
+    /// values are moved, and the slab is returned unchanged as an error
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// If the new configuration is invalid.
     ///
     /// # Examples
     ///
     /// ```
-    /// let slab = sharded_slab::Slab::new();
+    /// use sharded_slab::{Config, KeyRemap, Slab};
     ///
-    /// let key = slab.insert("hello world").unwrap();
-    /// assert!(slab.contains(key));
+    /// struct Reserved;
+    /// impl Config for Reserved {
+    ///     const RESERVED_BITS: usize = 8;
+    /// }
     ///
-    /// slab.take(key).unwrap();
-    /// assert!(!slab.contains(key));
+    /// let slab = Slab::new();
+    /// let mut keys = (0..4).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    ///
+    /// let mut remap = KeyRemap::new();
+    /// let slab = slab
+    ///     .migrate::<Reserved>(|old, new| remap.extend(Some((old, new))))
+    ///     .unwrap();
+    /// assert_eq!(remap.translate_all(&mut keys), 4);
+    /// for (i, &key) in keys.iter().enumerate() {
+    ///     assert_eq!(slab.get(key).unwrap(), i);
+    /// }
     /// ```
-    pub fn contains(&self, key: usize) -> bool {
-        self.get(key).is_some()
+    ///
+    /// [`INITIAL_PAGE_SIZE`]: Config::INITIAL_PAGE_SIZE
+    /// [`RESERVED_BITS`]: Config::RESERVED_BITS
+    #[allow(clippy::result_large_err)] // the slab is returned as is
+    pub fn migrate<D: cfg::Config>(
+        self,
+        mut remap: impl FnMut(usize, usize),
+    ) -> Result<Slab<T, D>, Self> {
+        let migrated = Slab::new_with_config::<D>();
+        let shards = if D::DETERMINISTIC || D::SINGLE_THREADED {
+            1
+        } else {
+            D::MAX_SHARDS
+        };
+        let capacity = migrated.config().shard_capacity().saturating_mul(shards);
+        let len = self
+            .shards
+            .iter()
+            .flat_map(|shard| shard.pages().map(|page| page.live()))
+            .sum::<usize>();
+        if len > capacity {
+            test_println!("migrate: {} values exceed capacity {}", len, capacity);
+            return Err(self);
+        }
+
+        let keys = self
+            .shards
+            .iter()
+            .flat_map(|shard| shard.live_keys())
+            .filter(|&key| self.contains(key))
+            .collect::<Vec<_>>();
+        for old in keys {
+            // Nothing else can hold a guard, since we own the slab, so this
+            // never blocks.
+            let mut value = match self.take(old) {
+                Some(value) => Some(value),
+                None => continue,
+            };
+            let first = C::unpack_tid(old).as_usize();
+            let new = (0..shards)
+                .map(|i| (first + i) % shards)
+                .find_map(|idx| {
+                    // Any thread may allocate and insert into the new slab's
+                    // shards, since nothing else can access it until we
+                    // return it.
+                    let shard = migrated.shards.get_or_alloc(idx);
+                    let key = unsafe {
+                        // Safety: we have exclusive access to the new slab.
+                        shard.init_with_exclusive(|addr, slot| {
+                            let gen = slot.insert(&mut value)?;
+                            Some(gen.pack(addr))
+                        })
+                    }?;
+                    let key = shard.tag(Tid::<D>::from_usize(idx).pack(key));
+                    shard.on_insert(key);
+                    Some(key)
+                })
+                .expect("capacity was checked before migrating");
+            test_println!("migrate: {:#x} -> {:#x}", old, new);
+            remap(old, new);
+        }
+        Ok(migrated)
     }
 
     /// Returns an iterator over all the items in the slab.
@@ -743,6 +2328,12 @@ impl<T, C: cfg::Config> Slab<T, C> {
     /// Because this iterator exclusively borrows the slab (i.e. it holds an
     /// `&mut Slab<T>`), elements will not be added or removed while the
     /// iteration is in progress.
+    ///
+    /// By default, this visits every slot that has ever held a value. If the
+    /// slab's [`Config`] sets [`TRACK_OCCUPANCY`], only the slots that
+    /// currently hold values are visited.
+    ///
+    /// [`TRACK_OCCUPANCY`]: Config::TRACK_OCCUPANCY
     pub fn unique_iter(&mut self) -> iter::UniqueIter<'_, T, C> {
         let mut shards = self.shards.iter_mut();
 
@@ -752,7 +2343,7 @@ impl<T, C: cfg::Config> Slab<T, C> {
                 let slots = pages.next().and_then(page::Shared::iter);
                 (pages, slots)
             }
-            None => ([].iter(), None),
+            None => (shard::Pages::empty(), None),
         };
 
         iter::UniqueIter {
@@ -761,6 +2352,55 @@ impl<T, C: cfg::Config> Slab<T, C> {
             slots,
         }
     }
+
+    /// Returns an iterator over the key and value of each item in the slab,
+    /// in ascending order of key.
+    ///
+    /// The order in which [`unique_iter`] visits items depends on which
+    /// threads inserted them and on the history of each shard's free lists,
+    /// so two slabs holding the same keys and values may be visited in
+    /// different orders. This iterator always visits them in the same order,
+    /// which makes it suitable for dumping a slab's contents to compare with
+    /// another dump, such as in replay and debugging tools.
+    ///
+    /// To sort the items, this collects them into a `Vec` before returning,
+    /// so it is slower than [`unique_iter`], and allocates. Like
+    /// `unique_iter`, it exclusively borrows the slab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let slab = Arc::new(Slab::new());
+    /// let mut keys = (0..4).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    /// let slab2 = slab.clone();
+    /// keys.push(thread::spawn(move || slab2.insert(4).unwrap()).join().unwrap());
+    /// slab.remove(keys.remove(1));
+    ///
+    /// let mut slab = Arc::try_unwrap(slab).unwrap();
+    /// let sorted = slab.sorted_iter().map(|(key, _)| key).collect::<Vec<_>>();
+    /// keys.sort();
+    /// assert_eq!(sorted, keys);
+    /// ```
+    ///
+    /// [`unique_iter`]: Slab::unique_iter
+    pub fn sorted_iter(&mut self) -> iter::SortedIter<'_, T> {
+        let mut entries = self
+            .shards
+            .iter_mut()
+            .flat_map(|shard| {
+                shard.keys().filter_map(move |key| {
+                    shard.with_slot(key, |slot| slot.value().as_ref().map(|value| (key, value)))
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        iter::SortedIter {
+            entries: entries.into_iter(),
+        }
+    }
 }
 
 impl<T> Default for Slab<T> {
@@ -783,12 +2423,185 @@ unsafe impl<T: Sync, C: cfg::Config> Sync for Slab<T, C> {}
 
 // === impl Entry ===
 
+impl<'a, T, C: cfg::Config> Entry<'a, T, C> {
+    fn new(shard: &'a Shard<Option<T>, C>, key: usize) -> Option<Self> {
+        let entry = Self::scanned(shard, key)?;
+        shard.record_access(key);
+        Some(entry)
+    }
+
+    /// Like `new`, but doesn't record an access to the value.
+    ///
+    /// This is used when visiting values while scanning the slab, rather
+    /// than when looking up a key on behalf of the user, so that scanning
+    /// doesn't make every value look recently used to `Slab::idle_keys`.
+    fn scanned(shard: &'a Shard<Option<T>, C>, key: usize) -> Option<Self> {
+        shard.with_slot(key, |slot| {
+            let inner = slot.get(C::unpack_gen(key))?;
+            Self::from_guard(shard, key, slot, inner)
+        })
+    }
+
+    fn new_detailed(shard: &'a Shard<Option<T>, C>, key: usize) -> Result<Self, LookupError> {
+        let gen = C::unpack_gen(key);
+        let entry = shard
+            .with_slot(key, |slot| loop {
+                if let Some(inner) = slot.get(gen) {
+                    return Some(
+                        Self::from_guard(shard, key, slot, inner).ok_or(LookupError::Vacant),
+                    );
+                }
+                if let Some(error) = slot.lookup_error(gen) {
+                    return Some(Err(error));
+                }
+                // The slot became accessible after `get` failed; try again.
+            })
+            .unwrap_or(Err(LookupError::OutOfRange))?;
+        shard.record_access(key);
+        Ok(entry)
+    }
+
+    fn from_guard(
+        shard: &'a Shard<Option<T>, C>,
+        key: usize,
+        slot: &'a page::Slot<Option<T>, C>,
+        inner: page::slot::Guard<Option<T>, C>,
+    ) -> Option<Self> {
+        let value = match slot.value().as_ref() {
+            Some(value) => ptr::NonNull::from(value),
+            None => {
+                // The value at the key's generation was taken, and the
+                // slot hasn't been reused yet. This is only reachable with
+                // keys that were never returned by the slab, such as the
+                // keys visited while iterating over a shared slab.
+                let should_remove = unsafe {
+                    // Safety: the guard is not used again after it is
+                    // released, and the slot is borrowed from the shard.
+                    inner.release()
+                };
+                if should_remove {
+                    shard.clear_after_release(key);
+                }
+                return None;
+            }
+        };
+        Some(Entry {
+            inner,
+            value,
+            shard,
+            key,
+        })
+    }
+
+    /// Returns a [`WeakEntry`] referring to the same value as this guard.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let slab = sharded_slab::Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// let weak = slab.get(key).unwrap().downgrade();
+    /// assert_eq!(weak.upgrade().unwrap(), "hello world");
+    /// ```
+    pub fn downgrade(&self) -> WeakEntry<'a, T, C> {
+        WeakEntry {
+            shard: Some(self.shard),
+            key: self.key,
+        }
+    }
+}
+
 impl<T, C: cfg::Config> Entry<'_, T, C> {
     /// Returns the key used to access the guard.
     pub fn key(&self) -> usize {
         self.key
     }
 
+    /// Returns a raw pointer to the value referenced by this guard.
+    ///
+    /// The pointer is valid for as long as the value remains in the slab,
+    /// which is at least as long as this guard exists. See
+    /// [Stable Addresses](crate#stable-addresses) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert(String::from("hello world")).unwrap();
+    ///
+    /// let ptr = slab.get(key).unwrap().as_ptr();
+    /// // Values are not moved while they are in the slab.
+    /// assert_eq!(slab.get(key).unwrap().as_ptr(), ptr);
+    /// assert_eq!(unsafe { &*ptr }, "hello world");
+    /// ```
+    pub fn as_ptr(&self) -> *const T {
+        self.value.as_ptr()
+    }
+
+    /// Returns when the value referenced by this guard was inserted into the
+    /// slab.
+    ///
+    /// This requires [`Config::TRACK_INSERTED`] to be enabled; otherwise, it
+    /// always returns `None`. Times are recorded with millisecond precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Config, Slab};
+    /// use std::time::Instant;
+    ///
+    /// struct TrackInserted;
+    ///
+    /// impl Config for TrackInserted {
+    ///     const TRACK_INSERTED: bool = true;
+    /// }
+    ///
+    /// let slab = Slab::new_with_config::<TrackInserted>();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// let inserted_at = slab.get(key).unwrap().inserted_at().unwrap();
+    /// assert!(inserted_at <= Instant::now());
+    ///
+    /// // Without `TRACK_INSERTED`, insertion times aren't recorded.
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    /// assert_eq!(slab.get(key).unwrap().inserted_at(), None);
+    /// ```
+    ///
+    /// [`Config::TRACK_INSERTED`]: crate::Config::TRACK_INSERTED
+    pub fn inserted_at(&self) -> Option<Instant> {
+        timestamps::to_instant(self.shard.inserted_at(self.key))
+    }
+
+    /// Returns the number of outstanding references to the value referenced
+    /// by this guard, including this one.
+    ///
+    /// Like [`Slab::ref_count`], this is advisory only, since other threads
+    /// may acquire or drop references concurrently.
+    pub fn ref_count(&self) -> usize {
+        unsafe {
+            // Safety: the guard is still held, so the slot is still valid.
+            self.inner.ref_count()
+        }
+    }
+
+    /// Returns when the value referenced by this guard was last accessed.
+    ///
+    /// This requires [`Config::TRACK_ACCESS`] to be enabled; otherwise, it
+    /// always returns `None`. Acquiring this guard counts as an access, so
+    /// this is the time at which the guard was acquired, unless the value has
+    /// been accessed again since.
+    ///
+    /// [`Config::TRACK_ACCESS`]: crate::Config::TRACK_ACCESS
+    pub fn last_accessed(&self) -> Option<Instant> {
+        if !C::TRACK_ACCESS {
+            return None;
+        }
+        timestamps::to_instant(self.shard.last_used_at(self.key))
+    }
+
     #[inline(always)]
     fn value(&self) -> &T {
         unsafe {
@@ -819,6 +2632,7 @@ impl<T, C: cfg::Config> Drop for Entry<'_, T, C> {
             // exists.
             self.inner.release()
         };
+        self.shard.on_release(self.key);
         if should_remove {
             self.shard.clear_after_release(self.key)
         }
@@ -845,6 +2659,56 @@ where
     }
 }
 
+impl<T, C> PartialEq for Entry<'_, T, C>
+where
+    T: PartialEq,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T, C> fmt::Display for Entry<'_, T, C>
+where
+    T: fmt::Display,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T, C> std::borrow::Borrow<T> for Entry<'_, T, C>
+where
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for Entry<'_, T, C>
+where
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for Entry<'_, T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
 unsafe impl<T, C> Send for Entry<'_, T, C>
 where
     T: Sync,
@@ -885,7 +2749,7 @@ impl<T, C: cfg::Config> VacantEntry<'_, T, C> {
             // borrowed from, so it cannot outlive the entry's slot.
             self.inner.value_mut()
         };
-        debug_assert!(
+        strict_assert!(
             value.is_none(),
             "tried to insert to a slot that already had a value!"
         );
@@ -896,10 +2760,12 @@ impl<T, C: cfg::Config> VacantEntry<'_, T, C> {
             // slot.
             self.inner.release()
         };
-        debug_assert!(
+        strict_assert!(
             !_released,
             "removing a value before it was inserted should be a no-op"
-        )
+        );
+        self.shard.on_insert(self.key);
+        self.watchers.notify(self.shard.tid, self.key);
     }
 
     /// Return the integer index at which this entry will be inserted.
@@ -927,6 +2793,77 @@ impl<T, C: cfg::Config> VacantEntry<'_, T, C> {
         self.key
     }
 }
+
+impl<T, C: cfg::Config> fmt::Debug for VacantEntry<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+// === impl AbandonOnPanic ===
+
+impl<T, C: cfg::Config> Drop for AbandonOnPanic<'_, T, C> {
+    fn drop(&mut self) {
+        let entry = match self.entry.as_mut() {
+            Some(entry) => entry,
+            None => return,
+        };
+        test_println!(
+            "insert_with: constructor panicked; abandoning {:#x}",
+            entry.key
+        );
+        unsafe {
+            // Safety: the `VacantEntry` only lives as long as the `Slab` it
+            // was borrowed from, so it cannot outlive the entry's slot.
+            entry.inner.abandon();
+        }
+        entry.shard.abandon(entry.key);
+    }
+}
+
+// === impl WeakEntry ===
+
+impl<'a, T, C: cfg::Config> WeakEntry<'a, T, C> {
+    /// Attempts to upgrade this weak entry to an [`Entry`].
+    ///
+    /// This returns `None` if the value this entry refers to has been removed
+    /// from the slab (even if its slot has since been reused by another
+    /// value), or if the maximum number of concurrent references to the slot
+    /// has been reached.
+    pub fn upgrade(&self) -> Option<Entry<'a, T, C>> {
+        test_println!("upgrade {:#x}", self.key);
+        Entry::new(self.shard?, self.key)
+    }
+
+    /// Returns the key of the value this weak entry refers to.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<T, C: cfg::Config> Clone for WeakEntry<'_, T, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, C: cfg::Config> Copy for WeakEntry<'_, T, C> {}
+
+impl<T, C: cfg::Config> fmt::Debug for WeakEntry<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakEntry")
+            .field("key", &format_args!("{:#x}", self.key))
+            .finish()
+    }
+}
+
+// A `WeakEntry` is effectively a `&Slab` and a key, so it may be shared and
+// sent between threads whenever a `&Slab` may be.
+unsafe impl<T: Sync, C: cfg::Config> Send for WeakEntry<'_, T, C> {}
+unsafe impl<T: Sync, C: cfg::Config> Sync for WeakEntry<'_, T, C> {}
+
 // === impl OwnedEntry ===
 
 impl<T, C> OwnedEntry<T, C>
@@ -938,6 +2875,46 @@ where
         self.key
     }
 
+    /// Returns a raw pointer to the value referenced by this guard.
+    ///
+    /// The pointer is valid for as long as the value remains in the slab,
+    /// which is at least as long as this guard exists. See
+    /// [Stable Addresses](crate#stable-addresses) for details.
+    pub fn as_ptr(&self) -> *const T {
+        self.value.as_ptr()
+    }
+
+    /// Returns when the value referenced by this guard was inserted into the
+    /// slab.
+    ///
+    /// See [`Entry::inserted_at`] for details.
+    pub fn inserted_at(&self) -> Option<Instant> {
+        let shard = self.slab.shards.get(C::unpack_tid(self.key).as_usize())?;
+        timestamps::to_instant(shard.inserted_at(self.key))
+    }
+
+    /// Returns the number of outstanding references to the value referenced
+    /// by this guard, including this one.
+    ///
+    /// See [`Entry::ref_count`] for details.
+    pub fn ref_count(&self) -> usize {
+        unsafe {
+            // Safety: the guard is still held, so the slot is still valid.
+            self.inner.ref_count()
+        }
+    }
+
+    /// Returns when the value referenced by this guard was last accessed.
+    ///
+    /// See [`Entry::last_accessed`] for details.
+    pub fn last_accessed(&self) -> Option<Instant> {
+        if !C::TRACK_ACCESS {
+            return None;
+        }
+        let shard = self.slab.shards.get(C::unpack_tid(self.key).as_usize())?;
+        timestamps::to_instant(shard.last_used_at(self.key))
+    }
+
     #[inline(always)]
     fn value(&self) -> &T {
         unsafe {
@@ -974,10 +2951,14 @@ where
             // exists.
             self.inner.release()
         };
+        let shard_idx = Tid::<C>::from_packed(self.key);
+        let shard = self.slab.shards.get(shard_idx.as_usize());
+        if let Some(shard) = shard {
+            shard.on_release(self.key);
+        }
         if should_clear {
-            let shard_idx = Tid::<C>::from_packed(self.key);
             test_println!("-> shard={:?}", shard_idx);
-            if let Some(shard) = self.slab.shards.get(shard_idx.as_usize()) {
+            if let Some(shard) = shard {
                 shard.clear_after_release(self.key)
             } else {
                 test_println!("-> shard={:?} does not exist! THIS IS A BUG", shard_idx);
@@ -1007,6 +2988,85 @@ where
     }
 }
 
+/// Compares the referenced values, so that the guard may be stored in place
+/// of the value in collections.
+impl<T, C> PartialEq for OwnedEntry<T, C>
+where
+    T: PartialEq,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T, C> Eq for OwnedEntry<T, C>
+where
+    T: Eq,
+    C: cfg::Config,
+{
+}
+
+impl<T, C> PartialOrd for OwnedEntry<T, C>
+where
+    T: PartialOrd,
+    C: cfg::Config,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T, C> Ord for OwnedEntry<T, C>
+where
+    T: Ord,
+    C: cfg::Config,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T, C> std::hash::Hash for OwnedEntry<T, C>
+where
+    T: std::hash::Hash,
+    C: cfg::Config,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T, C> std::borrow::Borrow<T> for OwnedEntry<T, C>
+where
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for OwnedEntry<T, C>
+where
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for OwnedEntry<T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
 unsafe impl<T, C> Sync for OwnedEntry<T, C>
 where
     T: Sync,
@@ -1021,6 +3081,38 @@ where
 {
 }
 
+// === impl LookupError ===
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LookupError::OutOfRange => "key does not refer to an allocated slot",
+            LookupError::Vacant => "slot is vacant",
+            LookupError::Stale => "value for key was removed",
+            LookupError::Clearing => "value for key is being removed",
+            LookupError::TooManyReferences => "value has too many concurrent references",
+            LookupError::Locked => "value is locked for exclusive access",
+        })
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+// === impl InsertError ===
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InsertError::QuotaExceeded => "shard is at its quota",
+            InsertError::Full => "shard is full",
+            InsertError::NoShard => "current thread has no shard",
+            InsertError::Closed => "slab is closed",
+        })
+    }
+}
+
+impl std::error::Error for InsertError {}
+
 // === pack ===
 
 pub(crate) trait Pack<C: cfg::Config>: Sized {
@@ -1070,7 +3162,7 @@ pub(crate) trait Pack<C: cfg::Config>: Sized {
     #[inline(always)]
     fn pack(&self, to: usize) -> usize {
         let value = self.as_usize();
-        debug_assert!(value <= Self::BITS);
+        strict_assert!(value <= Self::BITS);
 
         (to & !Self::MASK) | (value << Self::SHIFT)
     }
@@ -1078,7 +3170,7 @@ pub(crate) trait Pack<C: cfg::Config>: Sized {
     #[inline(always)]
     fn from_packed(from: usize) -> Self {
         let value = (from & Self::MASK) >> Self::SHIFT;
-        debug_assert!(value <= Self::BITS);
+        strict_assert!(value <= Self::BITS);
         Self::from_usize(value)
     }
 }
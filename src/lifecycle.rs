@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+/// Observes the lifecycle of the entries in a [`Slab`].
+///
+/// An observer is attached to a slab with [`Slab::with_lifecycle`], and is
+/// notified as values are inserted and removed, and as the slab allocates
+/// storage. This can be used to account for the number of live entries, to
+/// enforce quotas, or to log the slab's activity.
+///
+/// Methods are called synchronously, on whichever thread performed the
+/// operation being observed. In particular, a value that is [removed] while it
+/// is being accessed is cleared by the thread that drops the last guard to it,
+/// which may be a different thread from the one that removed it. Observers
+/// should therefore be cheap, and must not access the slab they are observing.
+///
+/// All methods have default implementations which do nothing, so observers
+/// need only implement the methods for the events they are interested in.
+/// `Lifecycle` is also implemented for `Arc`s of observers, so that an
+/// observer's state may be shared with other code.
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::{Lifecycle, Slab};
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc,
+/// };
+///
+/// #[derive(Default)]
+/// struct Live(AtomicUsize);
+///
+/// impl Lifecycle for Live {
+///     fn on_insert(&self, _: usize) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn on_clear(&self, _: usize) {
+///         self.0.fetch_sub(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let live = Arc::new(Live::default());
+/// let slab = Slab::new().with_lifecycle(live.clone());
+///
+/// let key = slab.insert("hello").unwrap();
+/// assert_eq!(live.0.load(Ordering::Relaxed), 1);
+///
+/// // While the value is being accessed, removing it only marks it for
+/// // removal...
+/// let entry = slab.get(key).unwrap();
+/// assert!(slab.remove(key));
+/// assert_eq!(live.0.load(Ordering::Relaxed), 1);
+///
+/// // ...and it is cleared once the last guard is dropped.
+/// drop(entry);
+/// assert_eq!(live.0.load(Ordering::Relaxed), 0);
+/// ```
+///
+/// [`Slab`]: crate::Slab
+/// [`Slab::with_lifecycle`]: crate::Slab::with_lifecycle
+/// [removed]: crate::Slab::remove
+pub trait Lifecycle: Send + Sync + 'static {
+    /// Called after a value is inserted at `key`.
+    fn on_insert(&self, key: usize) {
+        let _ = key;
+    }
+
+    /// Called after the value at `key` is marked for removal by
+    /// [`Slab::remove`].
+    ///
+    /// If the value is not being accessed, this is immediately followed by a
+    /// call to [`on_clear`]. Otherwise, `on_clear` is called once the last
+    /// guard to the value is dropped.
+    ///
+    /// [`Slab::remove`]: crate::Slab::remove
+    /// [`on_clear`]: Lifecycle::on_clear
+    fn on_mark(&self, key: usize) {
+        let _ = key;
+    }
+
+    /// Called after the value at `key` has been removed from its slot.
+    ///
+    /// The value has been dropped, unless it was removed by [`Slab::take`],
+    /// in which case it is returned to the caller. The slot may be reused as
+    /// soon as this is called.
+    ///
+    /// This is not called for values which are still in the slab when it is
+    /// dropped.
+    ///
+    /// [`Slab::take`]: crate::Slab::take
+    fn on_clear(&self, key: usize) {
+        let _ = key;
+    }
+
+    /// Called after storage for a page of `size` slots is allocated.
+    ///
+    /// `shard` is the index of the thread whose shard the page belongs to, and
+    /// `page` is the index of the page in that shard.
+    fn on_page_alloc(&self, shard: usize, page: usize, size: usize) {
+        let _ = (shard, page, size);
+    }
+}
+
+impl<L: Lifecycle + ?Sized> Lifecycle for Arc<L> {
+    fn on_insert(&self, key: usize) {
+        (**self).on_insert(key)
+    }
+
+    fn on_mark(&self, key: usize) {
+        (**self).on_mark(key)
+    }
+
+    fn on_clear(&self, key: usize) {
+        (**self).on_clear(key)
+    }
+
+    fn on_page_alloc(&self, shard: usize, page: usize, size: usize) {
+        (**self).on_page_alloc(shard, page, size)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Slab;
+    use std::{sync::Mutex, thread};
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Insert(usize),
+        Mark(usize),
+        Clear(usize),
+        PageAlloc(usize, usize),
+    }
+
+    #[derive(Default)]
+    struct Events(Mutex<Vec<Event>>);
+
+    impl Events {
+        fn take(&self) -> Vec<Event> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl Lifecycle for Events {
+        fn on_insert(&self, key: usize) {
+            self.0.lock().unwrap().push(Event::Insert(key));
+        }
+
+        fn on_mark(&self, key: usize) {
+            self.0.lock().unwrap().push(Event::Mark(key));
+        }
+
+        fn on_clear(&self, key: usize) {
+            self.0.lock().unwrap().push(Event::Clear(key));
+        }
+
+        fn on_page_alloc(&self, _: usize, page: usize, size: usize) {
+            self.0.lock().unwrap().push(Event::PageAlloc(page, size));
+        }
+    }
+
+    struct TinyConfig;
+    impl crate::Config for TinyConfig {
+        const INITIAL_PAGE_SIZE: usize = 2;
+    }
+
+    #[test]
+    fn insert_remove_take() {
+        let events = Arc::new(Events::default());
+        let slab = Slab::new_with_config::<TinyConfig>().with_lifecycle(events.clone());
+
+        let key1 = slab.insert(1).unwrap();
+        let key2 = slab.vacant_entry().map(|entry| {
+            let key = entry.key();
+            entry.insert(2);
+            key
+        });
+        let key2 = key2.unwrap();
+        let key3 = slab.insert(3).unwrap();
+        assert_eq!(
+            events.take(),
+            vec![
+                Event::PageAlloc(0, 2),
+                Event::Insert(key1),
+                Event::Insert(key2),
+                Event::PageAlloc(1, 4),
+                Event::Insert(key3),
+            ]
+        );
+
+        assert!(slab.remove(key1));
+        assert!(!slab.remove(key1));
+        assert_eq!(slab.take(key2), Some(2));
+        assert_eq!(slab.take(key2), None);
+        assert_eq!(
+            events.take(),
+            vec![Event::Mark(key1), Event::Clear(key1), Event::Clear(key2)]
+        );
+
+        // Values left in the slab are not reported when it is dropped.
+        drop(slab);
+        assert_eq!(events.take(), vec![]);
+    }
+
+    #[test]
+    fn deferred_clear_on_other_thread() {
+        let events = Arc::new(Events::default());
+        let slab = Arc::new(Slab::new().with_lifecycle(events.clone()));
+        let key = slab.insert(1).unwrap();
+
+        let entry = slab.clone().get_owned(key).unwrap();
+        assert!(slab.remove(key));
+        assert_eq!(
+            events.take(),
+            vec![
+                Event::PageAlloc(0, 32),
+                Event::Insert(key),
+                Event::Mark(key)
+            ]
+        );
+
+        thread::spawn(move || drop(entry)).join().unwrap();
+        assert_eq!(events.take(), vec![Event::Clear(key)]);
+    }
+
+    #[test]
+    fn take_deferred_marks() {
+        let events = Arc::new(Events::default());
+        let slab = Arc::new(Slab::new().with_lifecycle(events.clone()));
+        let key = slab.insert(1).unwrap();
+        let unreferenced = slab.insert(2).unwrap();
+        events.take();
+
+        let entry = slab.clone().get_owned(key).unwrap();
+        let handle = slab.take_deferred(key).unwrap();
+        assert_eq!(events.take(), vec![Event::Mark(key)]);
+        thread::spawn(move || drop(entry)).join().unwrap();
+        assert_eq!(handle.wait(), Some(1));
+        assert_eq!(events.take(), vec![Event::Clear(key)]);
+
+        assert_eq!(slab.take_deferred(unreferenced).unwrap().wait(), Some(2));
+        assert_eq!(
+            events.take(),
+            vec![Event::Mark(unreferenced), Event::Clear(unreferenced)]
+        );
+    }
+
+    #[test]
+    fn attached_after_insert() {
+        let events = Arc::new(Events::default());
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+
+        let slab = slab.with_lifecycle(events.clone());
+        assert!(slab.remove(key));
+        assert_eq!(events.take(), vec![Event::Mark(key), Event::Clear(key)]);
+    }
+}
@@ -0,0 +1,635 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    page, Entry, Pack, Shard, Slab, Tid,
+};
+use std::{fmt, ptr, time::Duration};
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// A slab whose entries may be locked for reading or writing.
+///
+/// A `LockedSlab` behaves like a [`Slab`], except that in addition to shared
+/// access through [`get`], a value may also be mutably accessed in place
+/// through [`get_mut`]. Each entry behaves like a [`RwLock`]: any number of
+/// [`Entry`] guards may exist for a value at the same time, _or_ a single
+/// [`EntryMut`] guard.
+///
+/// Rather than wrapping each value in its own lock, the write lock is stored
+/// in the same word as the slot's lifecycle state and reference count, so this
+/// requires no additional space per value.
+///
+/// Like the rest of this crate, acquiring a guard never blocks: if a value is
+/// currently locked for writing, [`get`] returns `None`, and if it is
+/// currently referenced at all, [`get_mut`] returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::LockedSlab;
+/// let slab = LockedSlab::new();
+/// let key = slab.insert(String::from("hello")).unwrap();
+///
+/// slab.get_mut(key).unwrap().push_str(" world");
+/// assert_eq!(*slab.get(key).unwrap(), "hello world");
+///
+/// // While a value is being read, it may not be written...
+/// let read = slab.get(key).unwrap();
+/// assert!(slab.get_mut(key).is_none());
+/// drop(read);
+///
+/// // ...and while it is being written, it may not be read.
+/// let write = slab.get_mut(key).unwrap();
+/// assert!(slab.get(key).is_none());
+/// drop(write);
+/// ```
+///
+/// [`get`]: LockedSlab::get
+/// [`get_mut`]: LockedSlab::get_mut
+/// [`RwLock`]: std::sync::RwLock
+pub struct LockedSlab<T, C: cfg::Config = DefaultConfig> {
+    slab: Slab<T, C>,
+}
+
+/// A guard that allows exclusive, mutable access to an object in a
+/// [`LockedSlab`].
+///
+/// While the guard exists, no other guards to the same value may be acquired.
+/// If the value is removed from the slab while the guard exists, it will not
+/// be dropped until the guard is dropped.
+pub struct EntryMut<'a, T, C: cfg::Config = DefaultConfig> {
+    inner: page::slot::WriteGuard<Option<T>, C>,
+    value: ptr::NonNull<T>,
+    shard: &'a Shard<Option<T>, C>,
+    key: usize,
+}
+
+/// A slab whose entries are each protected by an asynchronous mutex.
+///
+/// This is a [`LockedSlab`] which only permits exclusive access to its
+/// values, through [`lock`]. Rather than returning `None` if a value is
+/// already locked, as [`LockedSlab::get_mut`] does, `lock` returns a future
+/// which waits for the value to be unlocked, without blocking the current
+/// thread.
+///
+/// This replaces storing a `Mutex<T>` from an async runtime in each entry:
+/// the lock is stored in the same word as the slot's lifecycle state, so it
+/// needs no additional allocation, and a task waiting to lock a value is
+/// woken when the guard currently holding the lock is dropped.
+///
+/// The lock is not fair: when a guard is dropped, every task waiting for the
+/// value is woken, and whichever task is polled first acquires the lock.
+///
+/// # Examples
+///
+/// ```rust
+/// # use sharded_slab::AsyncLockedSlab;
+/// async fn append(slab: &AsyncLockedSlab<String>, key: usize, s: &str) {
+///     if let Some(mut entry) = slab.lock(key).await {
+///         entry.push_str(s);
+///     }
+/// }
+/// ```
+///
+/// [`lock`]: AsyncLockedSlab::lock
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+pub struct AsyncLockedSlab<T, C: cfg::Config = DefaultConfig> {
+    slab: LockedSlab<T, C>,
+}
+
+/// A future which locks a value in an [`AsyncLockedSlab`].
+///
+/// This is returned by [`AsyncLockedSlab::lock`].
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[must_use = "futures do nothing unless polled"]
+pub struct Lock<'a, T, C: cfg::Config = DefaultConfig> {
+    slab: &'a AsyncLockedSlab<T, C>,
+    key: usize,
+    /// The waker registered for the last time this was polled, if any.
+    waker: Option<Waker>,
+}
+
+impl<T> LockedSlab<T> {
+    /// Returns a new `LockedSlab` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `LockedSlab` with the provided configuration parameters.
+    pub fn new_with_config<C: cfg::Config>() -> LockedSlab<T, C> {
+        LockedSlab {
+            slab: Slab::new_with_config(),
+        }
+    }
+}
+
+impl<T, C: cfg::Config> LockedSlab<T, C> {
+    /// Inserts a value into the slab, returning the integer index at which
+    /// that value was inserted.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no items can be added until some are removed, or the maximum
+    /// number of shards has been reached.
+    ///
+    /// See [`Slab::insert`] for details.
+    pub fn insert(&self, value: T) -> Option<usize> {
+        self.slab.insert(value)
+    }
+
+    /// Returns a guard for shared access to the value associated with the
+    /// given key.
+    ///
+    /// If the slab does not contain a value for the given key, or the value is
+    /// currently locked by an [`EntryMut`], `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::LockedSlab;
+    /// let slab = LockedSlab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// assert_eq!(slab.get(key).unwrap(), "hello world");
+    /// assert!(slab.get(12345).is_none());
+    /// ```
+    pub fn get(&self, key: usize) -> Option<Entry<'_, T, C>> {
+        self.slab.get(key)
+    }
+
+    /// Returns a guard for shared access to the value associated with the
+    /// given key, waiting up to `timeout` for it to be unlocked if it is
+    /// currently locked by an [`EntryMut`].
+    ///
+    /// See [`Slab::get_timeout`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::LockedSlab;
+    /// use std::{thread, time::Duration};
+    ///
+    /// let slab = LockedSlab::new();
+    /// let key = slab.insert(1).unwrap();
+    ///
+    /// let mut write = slab.get_mut(key).unwrap();
+    /// thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let read = slab.get_timeout(key, Duration::from_secs(10)).unwrap();
+    ///         assert_eq!(read, 2);
+    ///     });
+    ///
+    ///     thread::sleep(Duration::from_millis(10));
+    ///     *write += 1;
+    ///     drop(write);
+    /// });
+    /// ```
+    pub fn get_timeout(&self, key: usize, timeout: Duration) -> Option<Entry<'_, T, C>> {
+        self.slab.get_timeout(key, timeout)
+    }
+
+    /// Returns a guard for exclusive, mutable access to the value associated
+    /// with the given key.
+    ///
+    /// If the slab does not contain a value for the given key, or any other
+    /// guards to that value currently exist, `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::LockedSlab;
+    /// let slab = LockedSlab::new();
+    /// let key = slab.insert(1).unwrap();
+    ///
+    /// *slab.get_mut(key).unwrap() += 1;
+    /// assert_eq!(slab.get(key).unwrap(), 2);
+    /// assert!(slab.get_mut(12345).is_none());
+    /// ```
+    pub fn get_mut(&self, key: usize) -> Option<EntryMut<'_, T, C>> {
+        self.slab.lock(key)
+    }
+
+    /// Remove the value associated with the given key from the slab, returning
+    /// `true` if a value was removed.
+    ///
+    /// If the value is currently locked for reading or writing, it will be
+    /// removed when the last guard to it is dropped. See [`Slab::remove`] for
+    /// details.
+    pub fn remove(&self, idx: usize) -> bool {
+        self.slab.remove(idx)
+    }
+
+    /// Removes the value associated with the given key from the slab,
+    /// returning it.
+    ///
+    /// If the value is currently locked for reading or writing, this blocks
+    /// the current thread until all guards to it are dropped. See
+    /// [`Slab::take`] for details.
+    pub fn take(&self, idx: usize) -> Option<T> {
+        self.slab.take(idx)
+    }
+
+    /// Returns `true` if the slab contains a value for the given key.
+    pub fn contains(&self, key: usize) -> bool {
+        self.slab.contains(key)
+    }
+}
+
+impl<T> Default for LockedSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for LockedSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedSlab")
+            .field("shards", &self.slab.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// Like `RwLock<T>`, values may only be accessed from multiple threads if they
+// may be both shared with and mutated by (and thus sent to) other threads.
+unsafe impl<T: Send, C: cfg::Config> Send for LockedSlab<T, C> {}
+unsafe impl<T: Send + Sync, C: cfg::Config> Sync for LockedSlab<T, C> {}
+
+// === impl Slab ===
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Locks the value associated with the given key for writing, if it is
+    /// not currently referenced.
+    pub(crate) fn lock(&self, key: usize) -> Option<EntryMut<'_, T, C>> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("lock {:?}; current={:?}", tid, Tid::<C>::current());
+        let shard = self.shards.get(tid.as_usize())?;
+        shard.with_slot(key, |slot| {
+            let inner = slot.get_mut(C::unpack_gen(key))?;
+            let value = unsafe {
+                // Safety: the slot is locked for writing, so no other
+                // references to its value may exist until the guard is
+                // released.
+                (*inner.value_mut()).as_mut()
+            };
+            let value = match value {
+                Some(value) => ptr::NonNull::from(value),
+                None => {
+                    // The slot is at the key's generation, but holds no
+                    // value, because it has been reserved by a
+                    // `VacantEntry` which hasn't been filled yet, or the
+                    // key was never returned by the slab.
+                    let should_remove = unsafe {
+                        // Safety: the guard is not used again after it is
+                        // released, and the slot is borrowed from the shard.
+                        inner.release()
+                    };
+                    if should_remove {
+                        shard.clear_after_release(key);
+                    }
+                    return None;
+                }
+            };
+            Some(EntryMut {
+                inner,
+                value,
+                shard,
+                key,
+            })
+        })
+    }
+}
+
+// === impl AsyncLockedSlab ===
+
+#[cfg(feature = "async")]
+impl<T> AsyncLockedSlab<T> {
+    /// Returns a new `AsyncLockedSlab` with the default configuration
+    /// parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `AsyncLockedSlab` with the provided configuration
+    /// parameters.
+    pub fn new_with_config<C: cfg::Config>() -> AsyncLockedSlab<T, C> {
+        AsyncLockedSlab {
+            slab: LockedSlab::new_with_config(),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> AsyncLockedSlab<T, C> {
+    /// Inserts a value into the slab, returning the integer index at which
+    /// that value was inserted.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no items can be added until some are removed, or the maximum
+    /// number of shards has been reached.
+    ///
+    /// See [`Slab::insert`] for details.
+    pub fn insert(&self, value: T) -> Option<usize> {
+        self.slab.insert(value)
+    }
+
+    /// Returns a future which locks the value associated with the given key,
+    /// resolving to a guard for exclusive, mutable access to it.
+    ///
+    /// If the value is currently locked, the future waits for the guard
+    /// holding the lock to be dropped. If the slab does not contain a value
+    /// for the given key, or the value is removed while waiting, the future
+    /// resolves to `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::AsyncLockedSlab;
+    /// # fn noop_waker() -> std::task::Waker {
+    /// #     use std::task::{RawWaker, RawWakerVTable, Waker};
+    /// #     static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    /// #     unsafe fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+    /// #     unsafe fn noop(_: *const ()) {}
+    /// #     unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    /// # }
+    /// use std::{future::Future, pin::Pin, task::Context};
+    ///
+    /// let slab = AsyncLockedSlab::new();
+    /// let key = slab.insert(1).unwrap();
+    ///
+    /// let waker = noop_waker();
+    /// let mut cx = Context::from_waker(&waker);
+    ///
+    /// let guard = slab.try_lock(key).unwrap();
+    /// let mut lock = slab.lock(key);
+    /// assert!(Pin::new(&mut lock).poll(&mut cx).is_pending());
+    ///
+    /// drop(guard);
+    /// let mut guard = match Pin::new(&mut lock).poll(&mut cx) {
+    ///     std::task::Poll::Ready(guard) => guard.unwrap(),
+    ///     std::task::Poll::Pending => unreachable!(),
+    /// };
+    /// *guard += 1;
+    /// ```
+    pub fn lock(&self, key: usize) -> Lock<'_, T, C> {
+        Lock {
+            slab: self,
+            key,
+            waker: None,
+        }
+    }
+
+    /// Locks the value associated with the given key, if it is not already
+    /// locked.
+    ///
+    /// If the slab does not contain a value for the given key, or the value is
+    /// currently locked, `None` is returned instead.
+    pub fn try_lock(&self, key: usize) -> Option<EntryMut<'_, T, C>> {
+        self.slab.get_mut(key)
+    }
+
+    /// Remove the value associated with the given key from the slab, returning
+    /// `true` if a value was removed.
+    ///
+    /// If the value is currently locked, it will be removed when the guard is
+    /// dropped, and any tasks waiting to lock it will resolve to `None`.
+    pub fn remove(&self, idx: usize) -> bool {
+        self.slab.remove(idx)
+    }
+
+    /// Tries to lock the value for `key`, returning `Poll::Pending` if it is
+    /// currently locked.
+    fn poll_lock(&self, key: usize) -> Poll<Option<EntryMut<'_, T, C>>> {
+        loop {
+            if let Some(entry) = self.slab.get_mut(key) {
+                return Poll::Ready(Some(entry));
+            }
+            match self.slab.slab.get_detailed(key) {
+                // Locked by someone else.
+                Err(crate::LookupError::TooManyReferences) | Err(crate::LookupError::Locked) => {
+                    return Poll::Pending
+                }
+                Err(_) => return Poll::Ready(None),
+                // Unlocked since we tried to lock it; try again.
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T> Default for AsyncLockedSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for AsyncLockedSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncLockedSlab")
+            .field("shards", &self.slab.slab.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl Lock ===
+
+#[cfg(feature = "async")]
+impl<'a, T, C: cfg::Config> Future for Lock<'a, T, C> {
+    type Output = Option<EntryMut<'a, T, C>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let slab = self.slab;
+        if let Poll::Ready(entry) = slab.poll_lock(self.key) {
+            return Poll::Ready(entry);
+        }
+
+        let vacancies = slab.slab.slab.shards.vacancies();
+        let key = C::strip_reserved(self.key);
+        if let Some(ref waker) = self.waker {
+            if !waker.will_wake(cx.waker()) {
+                vacancies.unwatch_release(key, waker);
+            }
+        }
+        vacancies.watch_release(key, cx.waker());
+        self.waker = Some(cx.waker().clone());
+
+        // The lock may have been released since we last tried.
+        slab.poll_lock(self.key)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> Drop for Lock<'_, T, C> {
+    fn drop(&mut self) {
+        if let Some(ref waker) = self.waker {
+            let key = C::strip_reserved(self.key);
+            self.slab
+                .slab
+                .slab
+                .shards
+                .vacancies()
+                .unwatch_release(key, waker);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> fmt::Debug for Lock<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lock")
+            .field("key", &format_args!("{:#x}", self.key))
+            .finish()
+    }
+}
+
+// === impl EntryMut ===
+
+impl<T, C: cfg::Config> EntryMut<'_, T, C> {
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<T, C: cfg::Config> std::ops::Deref for EntryMut<'_, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: the slot is locked for writing for as long as this
+            // guard exists.
+            self.value.as_ref()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for EntryMut<'_, T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C: cfg::Config> std::ops::DerefMut for EntryMut<'_, T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            // Safety: the slot is locked for writing for as long as this
+            // guard exists.
+            self.value.as_mut()
+        }
+    }
+}
+
+impl<T, C: cfg::Config> Drop for EntryMut<'_, T, C> {
+    fn drop(&mut self) {
+        let should_remove = unsafe {
+            // Safety: calling `slot::WriteGuard::release` is unsafe, since the
+            // `WriteGuard` value contains a pointer to the slot that may
+            // outlive the slab containing that slot. Here, the `EntryMut`
+            // guard owns a borrowed reference to the shard containing that
+            // slot, which ensures that the slot will not be dropped while
+            // this `WriteGuard` exists.
+            self.inner.release()
+        };
+        self.shard.on_release(self.key);
+        if should_remove {
+            self.shard.clear_after_release(self.key)
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for EntryMut<'_, T, C>
+where
+    T: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, C> PartialEq<T> for EntryMut<'_, T, C>
+where
+    T: PartialEq<T>,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+// Like `RwLockWriteGuard`, the guard may only be shared between threads if the
+// value may be.
+unsafe impl<T: Sync, C: cfg::Config> Sync for EntryMut<'_, T, C> {}
+// Unlike `RwLockWriteGuard`, the lock isn't tied to the thread that acquired
+// it, so the guard may be released on another thread, as long as the value may
+// be both mutated on and dropped on (if it was removed) that thread.
+unsafe impl<T: Send + Sync, C: cfg::Config> Send for EntryMut<'_, T, C> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::{LockedSlab, Slab};
+
+    #[test]
+    fn swap_with_reserved_key() {
+        let slab = Slab::new();
+        let a = slab.insert(1).unwrap();
+        // Reserve a slot which has already held a value, so that it is at
+        // the key's generation.
+        let b = slab.insert(3).unwrap();
+        assert_eq!(slab.take(b), Some(3));
+        let entry = slab.vacant_entry().unwrap();
+        let reserved = entry.key();
+        assert_ne!(reserved, b);
+
+        assert!(!slab.swap(a, reserved));
+        assert!(!slab.swap(reserved, a));
+        assert_eq!(slab.get(a).unwrap(), 1);
+
+        // The reservation is unaffected.
+        entry.insert(2);
+        assert!(slab.swap(a, reserved));
+        assert_eq!(slab.get(a).unwrap(), 2);
+        assert_eq!(slab.get(reserved).unwrap(), 1);
+    }
+
+    #[test]
+    fn get_mut_reserved_key() {
+        let slab = LockedSlab::new();
+        let key = slab.insert(1).unwrap();
+        assert_eq!(slab.take(key), Some(1));
+        let entry = slab.slab.vacant_entry().unwrap();
+        let reserved = entry.key();
+
+        assert!(slab.get_mut(reserved).is_none());
+        assert!(!slab.contains(reserved));
+
+        entry.insert(2);
+        *slab.get_mut(reserved).unwrap() += 1;
+        assert_eq!(slab.get(reserved).unwrap(), 3);
+    }
+
+    #[test]
+    fn swap_with_removed_key() {
+        let slab = Slab::new();
+        let a = slab.insert(1).unwrap();
+        let b = slab.insert(2).unwrap();
+        assert!(slab.remove(b));
+
+        assert!(!slab.swap(a, b));
+        assert_eq!(slab.get(a).unwrap(), 1);
+    }
+}
@@ -43,6 +43,28 @@ macro_rules! panic_in_drop {
     }
 }
 
+/// Like `assert!`, but only checked if debug assertions or the `strict`
+/// feature are enabled.
+macro_rules! strict_assert {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "strict")) {
+            assert!($($arg)*);
+        }
+    };
+}
+
+/// Like `assert_eq!`, but only checked if debug assertions or the `strict`
+/// feature are enabled.
+macro_rules! strict_assert_eq {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "strict")) {
+            assert_eq!($($arg)*);
+        }
+    };
+}
+
+/// Like `strict_assert_eq!`, but doesn't panic if the thread is already
+/// panicking.
 macro_rules! debug_assert_eq_in_drop {
     ($this:expr, $that:expr) => {
         debug_assert_eq_in_drop!(@inner $this, $that, "")
@@ -51,7 +73,7 @@ macro_rules! debug_assert_eq_in_drop {
         debug_assert_eq_in_drop!(@inner $this, $that, format_args!(": {}", format_args!($($arg)+)))
     };
     (@inner $this:expr, $that:expr, $msg:expr) => {
-        if cfg!(debug_assertions) {
+        if cfg!(any(debug_assertions, feature = "strict")) {
             if $this != $that {
                 panic_in_drop!(
                     "assertion failed ({} == {})\n  left: `{:?}`,\n right: `{:?}`{}",
@@ -65,3 +87,18 @@ macro_rules! debug_assert_eq_in_drop {
         }
     }
 }
+
+/// Defines a function which is a `const fn` on compilers which allow it to
+/// be, since it uses language features which are newer than the MSRV.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(all(slab_const_fn, not(loom)))]
+        #[allow(clippy::incompatible_msrv)] // only built on Rust 1.63+; see build.rs
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(not(all(slab_const_fn, not(loom))))]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
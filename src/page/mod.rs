@@ -1,12 +1,24 @@
 use crate::cfg::{self, CfgPrivate};
 use crate::clear::Clear;
-use crate::sync::UnsafeCell;
+use crate::sync::{
+    atomic::{AtomicUsize, Ordering},
+    UnsafeCell,
+};
 use crate::Pack;
 
+mod occupancy;
 pub(crate) mod slot;
 mod stack;
+pub(crate) mod timestamps;
 pub(crate) use self::slot::Slot;
-use std::{fmt, marker::PhantomData};
+use self::{occupancy::Occupancy, timestamps::Timestamps};
+use std::{
+    cell, fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr, slice,
+};
 
 /// A page address encodes the location of a slot within a shard (the page
 /// number and offset within that page) as a single linear value.
@@ -43,6 +55,22 @@ pub(crate) trait FreeList<C> {
     fn push<T>(&self, new_head: usize, slot: &Slot<T, C>)
     where
         C: cfg::Config;
+
+    /// Frees a slot whose generation has just wrapped around to zero.
+    ///
+    /// Unless the free list is tracking wraps for a page, this just pushes
+    /// the slot.
+    fn push_wrapped<T>(&self, new_head: usize, slot: &Slot<T, C>)
+    where
+        C: cfg::Config,
+    {
+        self.push(new_head, slot)
+    }
+
+    /// Returns the owning thread's free list, if this is (or wraps) it.
+    fn as_local(&self) -> Option<&Local> {
+        None
+    }
 }
 
 impl<C: cfg::Config> Pack<C> for Addr<C> {
@@ -55,7 +83,7 @@ impl<C: cfg::Config> Pack<C> for Addr<C> {
     }
 
     fn from_usize(addr: usize) -> Self {
-        debug_assert!(addr <= Self::BITS);
+        strict_assert!(addr <= Self::BITS);
         Self {
             addr,
             _cfg: PhantomData,
@@ -63,10 +91,32 @@ impl<C: cfg::Config> Pack<C> for Addr<C> {
     }
 }
 
-pub(crate) type Iter<'a, T, C> = std::iter::FilterMap<
-    std::slice::Iter<'a, Slot<Option<T>, C>>,
-    fn(&'a Slot<Option<T>, C>) -> Option<&'a T>,
->;
+/// An iterator over the values in a page.
+#[derive(Debug)]
+pub(crate) struct Iter<'a, T, C: cfg::Config> {
+    slots: &'a [Slot<Option<T>, C>],
+    offsets: Offsets<'a>,
+}
+
+/// The offsets of the initialized slots in a page which may be occupied.
+///
+/// If `C::TRACK_OCCUPANCY` is set, only the slots whose occupancy bits are
+/// set are visited; otherwise, every initialized slot is.
+#[derive(Debug)]
+enum Offsets<'a> {
+    All(std::ops::Range<usize>),
+    Occupied(occupancy::Iter<'a>),
+}
+
+/// A free list which clears the occupancy bit of each slot pushed to it.
+///
+/// If `C::PREFER_LOW_ADDRESSES` is set, slots pushed to the local free list
+/// are inserted in order of their offsets.
+struct Vacate<'a, T, C, F> {
+    page: &'a Shared<T, C>,
+    free_list: &'a F,
+    occupancy: Option<&'a Occupancy>,
+}
 
 pub(crate) struct Local {
     /// Index of the first slot on the local free list
@@ -85,10 +135,89 @@ pub(crate) struct Shared<T, C> {
     // then there are no slots left in that page.
     size: usize,
     prev_sz: usize,
+    /// The number of slots at the start of the page which have been
+    /// initialized.
+    ///
+    /// Slots are initialized lazily, the first time the local free list
+    /// reaches them, so that allocating a page doesn't require writing every
+    /// slot in it. Slots at or past this index have never been used; they
+    /// are not yet linked into a free list, and may not be accessed.
+    ///
+    /// This is only ever written by the page's owning thread.
+    initialized: AtomicUsize,
     slab: UnsafeCell<Option<Slots<T, C>>>,
+    /// When each slot was last checked out or cleared, if `C::TRACK_IDLE` is
+    /// set, or accessed, if `C::TRACK_ACCESS` is set.
+    ///
+    /// This is allocated along with the slots.
+    last_used: UnsafeCell<Option<Timestamps>>,
+    /// When the value in each occupied slot was inserted, if
+    /// `C::TRACK_INSERTED` is set.
+    ///
+    /// This is allocated along with the slots.
+    inserted: UnsafeCell<Option<Timestamps>>,
+    /// Which slots are occupied, if `C::TRACK_OCCUPANCY` is set.
+    ///
+    /// This is allocated along with the slots.
+    occupancy: UnsafeCell<Option<Occupancy>>,
+    /// The number of times a slot's generation has wrapped around, unless
+    /// `C::GENERATION_WRAP` is `Ignore`.
+    ///
+    /// This is only a statistic, so it is always a `std` atomic.
+    wraps: std::sync::atomic::AtomicUsize,
+    /// The number of slots in this page which hold values.
+    ///
+    /// Like `wraps`, this is only a statistic.
+    live: std::sync::atomic::AtomicUsize,
+}
+
+/// A snapshot of a page's slots and free lists, for `Slab::visualize`.
+#[cfg(feature = "debug-tools")]
+#[derive(Debug)]
+pub(crate) struct Layout {
+    pub(crate) prev_sz: usize,
+    pub(crate) size: usize,
+    pub(crate) allocated: bool,
+    /// The state of each initialized slot. Slots past the end have never been
+    /// used.
+    pub(crate) states: Vec<SlotState>,
+    pub(crate) local: FreeChain,
+    pub(crate) remote: FreeChain,
 }
 
-type Slots<T, C> = Box<[Slot<T, C>]>;
+#[cfg(feature = "debug-tools")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum SlotState {
+    Occupied,
+    Vacant,
+    Quarantined,
+}
+
+/// The offsets of the initialized slots on a free list, in order.
+#[cfg(feature = "debug-tools")]
+#[derive(Debug)]
+pub(crate) struct FreeChain {
+    pub(crate) head: usize,
+    pub(crate) offsets: Vec<usize>,
+    /// The link after the last initialized slot on the list. If this is
+    /// less than the page's size, the list continues with the slots that
+    /// have never been used.
+    pub(crate) end: usize,
+    /// Set if the list links back to a slot already on it.
+    pub(crate) cycle: bool,
+}
+
+/// Storage for a page's slots, only a prefix of which is initialized.
+///
+/// This is usually allocated by the page, but a `FixedSlab` may instead be
+/// given `'static` storage, which the page has exclusive access to.
+enum Slots<T, C> {
+    Boxed(Box<[SlotCell<T, C>]>),
+    Static(ptr::NonNull<[SlotCell<T, C>]>),
+}
+
+/// A possibly uninitialized slot.
+pub(crate) type SlotCell<T, C> = cell::UnsafeCell<MaybeUninit<Slot<T, C>>>;
 
 impl Local {
     pub(crate) fn new() -> Self {
@@ -115,6 +244,10 @@ impl<C: cfg::Config> FreeList<C> for Local {
         slot.set_next(self.head());
         self.set_head(new_head);
     }
+
+    fn as_local(&self) -> Option<&Local> {
+        Some(self)
+    }
 }
 
 impl<T, C> Shared<T, C>
@@ -128,10 +261,62 @@ where
             prev_sz,
             size,
             remote: stack::TransferStack::new(),
+            initialized: AtomicUsize::new(0),
             slab: UnsafeCell::new(None),
+            last_used: UnsafeCell::new(None),
+            inserted: UnsafeCell::new(None),
+            occupancy: UnsafeCell::new(None),
+            wraps: std::sync::atomic::AtomicUsize::new(0),
+            live: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of times a slot's generation has wrapped around.
+    #[inline]
+    pub(crate) fn generation_wraps(&self) -> usize {
+        self.wraps.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the total number of slots in this page.
+    #[inline]
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of slots in this page which hold values.
+    ///
+    /// Unlike `occupied`, this may be called concurrently with inserts and
+    /// removals, although it may not reflect those which are in progress.
+    #[inline]
+    pub(crate) fn live(&self) -> usize {
+        self.live.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of slots for which storage is allocated.
+    ///
+    /// Unlike `is_unallocated`, this may be called concurrently with an
+    /// insert that allocates the page, in which case it may return 0.
+    pub(crate) fn allocated_slots(&self) -> usize {
+        if self.slots().is_some() {
+            self.size
+        } else {
+            0
         }
     }
 
+    /// Returns the number of slots in this page whose values have been
+    /// removed, but can't be released until the guards referencing them are
+    /// dropped.
+    ///
+    /// This checks every initialized slot, but may be called concurrently.
+    pub(crate) fn deferred_releases(&self) -> usize {
+        self.slots()
+            .unwrap_or(&[])
+            .iter()
+            .filter(|slot| slot.is_release_deferred())
+            .count()
+    }
+
     /// Return the head of the freelist
     ///
     /// If there is space on the local list, it returns the head of the local list. Otherwise, it
@@ -145,12 +330,28 @@ where
 
         test_println!("-> local head {:?}", head);
 
+        if C::PREFER_LOW_ADDRESSES {
+            // Slots freed by other threads must be merged into the local free
+            // list immediately, rather than once it runs out, so that the
+            // lowest free slot is always at its head.
+            if let Some(remote) = self.remote.pop_all() {
+                self.merge_sorted(local, remote);
+            }
+            return if local.head() < self.size {
+                Some(local.head())
+            } else {
+                test_println!("-> NULL! {:?}", local.head());
+                None
+            };
+        }
+
         // are there any items on the local free list? (fast path)
         let head = if head < self.size {
             head
         } else {
             // slow path: if the local free list is empty, pop all the items on
-            // the remote free list.
+            // the remote free list. This doesn't traverse the remote list; it
+            // just becomes the new local free list.
             let head = self.remote.pop_all();
 
             test_println!("-> remote head {:?}", head);
@@ -170,10 +371,180 @@ where
     /// Returns `true` if storage is currently allocated for this page, `false`
     /// otherwise.
     #[inline]
-    fn is_unallocated(&self) -> bool {
+    pub(crate) fn is_unallocated(&self) -> bool {
         self.slab.with(|s| unsafe { (*s).is_none() })
     }
 
+    /// Returns the initialized slots in this page, or `None` if no slots have
+    /// been initialized (or the page has not been allocated).
+    #[inline]
+    fn slots(&self) -> Option<&[Slot<T, C>]> {
+        // This synchronizes with the `Release` store in `init_slot`, ensuring
+        // that every slot below the loaded index has been written.
+        let initialized = self.initialized.load(Ordering::Acquire);
+        if initialized == 0 {
+            // If no slots have been initialized, the page may be being
+            // allocated concurrently, so we can't access `self.slab`.
+            return None;
+        }
+        self.slab.with(|slab| {
+            let slab = unsafe { &*slab }.as_ref()?;
+            debug_assert!(initialized <= slab.len());
+            Some(unsafe {
+                // Safety: the first `initialized` slots have been initialized,
+                // and a slot is never deinitialized while the page exists.
+                // `UnsafeCell<MaybeUninit<Slot>>` has the same layout as `Slot`.
+                slice::from_raw_parts(slab.as_ptr() as *const Slot<T, C>, initialized)
+            })
+        })
+    }
+
+    /// Returns when each slot in this page was last used, if idle tracking is
+    /// enabled and the page has been allocated.
+    #[inline]
+    fn last_used(&self) -> Option<&Timestamps> {
+        if !(C::TRACK_IDLE || C::TRACK_ACCESS) {
+            return None;
+        }
+        // The timestamps are allocated before any slot is initialized, so if
+        // `slots` observes an allocated page, the timestamps are visible too.
+        self.slots()?;
+        self.last_used
+            .with(|last_used| unsafe { (*last_used).as_ref() })
+    }
+
+    #[inline]
+    fn touch(&self, offset: usize) {
+        if let Some(last_used) = self.last_used() {
+            last_used.touch(offset);
+        }
+    }
+
+    /// Returns when the value in each slot in this page was inserted, if
+    /// insertion tracking is enabled and the page has been allocated.
+    #[inline]
+    fn inserted(&self) -> Option<&Timestamps> {
+        if !C::TRACK_INSERTED {
+            return None;
+        }
+        // Like `last_used`, this is allocated before any slot is initialized.
+        self.slots()?;
+        self.inserted
+            .with(|inserted| unsafe { (*inserted).as_ref() })
+    }
+
+    /// Records that the value in the slot at `addr` was accessed, if access
+    /// tracking is enabled.
+    #[inline]
+    pub(crate) fn record_access(&self, addr: Addr<C>) {
+        if C::TRACK_ACCESS {
+            self.touch(addr.offset() - self.prev_sz);
+        }
+    }
+
+    /// Returns when the value in the slot at `addr` was inserted, or `NEVER`
+    /// if insertion tracking is disabled.
+    pub(crate) fn inserted_at(&self, addr: Addr<C>) -> timestamps::Tick {
+        let offset = addr.offset() - self.prev_sz;
+        self.inserted()
+            .filter(|_| offset < self.size)
+            .map_or(timestamps::NEVER, |inserted| inserted.get(offset))
+    }
+
+    /// Returns when the slot at `addr` was last used, or `NEVER` if neither
+    /// idle nor access tracking is enabled.
+    pub(crate) fn last_used_at(&self, addr: Addr<C>) -> timestamps::Tick {
+        let offset = addr.offset() - self.prev_sz;
+        self.last_used()
+            .filter(|_| offset < self.size)
+            .map_or(timestamps::NEVER, |last_used| last_used.get(offset))
+    }
+
+    /// Returns which slots in this page are occupied, if occupancy tracking
+    /// is enabled and the page has been allocated.
+    #[inline]
+    fn occupancy(&self) -> Option<&Occupancy> {
+        if !C::TRACK_OCCUPANCY {
+            return None;
+        }
+        // Like the timestamps, the bitmap is allocated before any slot is
+        // initialized.
+        self.slots()?;
+        self.occupancy
+            .with(|occupancy| unsafe { (*occupancy).as_ref() })
+    }
+
+    #[inline]
+    fn occupy(&self, offset: usize) {
+        self.live.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if cfg!(any(debug_assertions, feature = "strict")) {
+            if let Some(slot) = self.slots().and_then(|slots| slots.get(offset)) {
+                slot.claim();
+            }
+        }
+        if let Some(occupancy) = self.occupancy() {
+            occupancy.set(offset);
+        }
+        if let Some(inserted) = self.inserted() {
+            inserted.touch(offset);
+        }
+    }
+
+    /// Wraps `free_list` so that slots pushed to it are marked as vacant.
+    #[inline]
+    fn vacate<'a, F: FreeList<C>>(&'a self, free_list: &'a F) -> Vacate<'a, T, C, F> {
+        Vacate {
+            page: self,
+            free_list,
+            occupancy: self.occupancy(),
+        }
+    }
+
+    /// Pushes the slot at `offset` to the local free list, after any free
+    /// slots with lower offsets.
+    ///
+    /// This must only be called by the page's owning thread.
+    fn push_sorted(&self, local: &Local, offset: usize) {
+        let slots = self
+            .slots()
+            .expect("page must have been allocated to free a slot!");
+        let mut prev = None;
+        let mut next = local.head();
+        // The list ends with the first never-used slot, if there is one,
+        // which is past every initialized slot, including this one.
+        while next < offset {
+            prev = Some(&slots[next]);
+            next = slots[next].next();
+        }
+        slots[offset].set_next(next);
+        match prev {
+            Some(prev) => prev.set_next(offset),
+            None => local.set_head(offset),
+        }
+    }
+
+    /// Moves each slot on the remote free list starting at `head` to the
+    /// local free list, keeping it sorted.
+    fn merge_sorted(&self, local: &Local, mut head: usize) {
+        let slots = self
+            .slots()
+            .expect("page must have been allocated to free a slot!");
+        while head != Self::NULL {
+            let next = slots[head].next();
+            self.push_sorted(local, head);
+            head = next;
+        }
+    }
+
+    /// Returns the offsets of the slots in this page which may be occupied,
+    /// in ascending order.
+    fn offsets(&self) -> Offsets<'_> {
+        match self.occupancy() {
+            Some(occupancy) => Offsets::Occupied(occupancy.iter()),
+            None => Offsets::All(0..self.slots().map_or(0, <[_]>::len)),
+        }
+    }
+
     #[inline]
     pub(crate) fn with_slot<'a, U>(
         &'a self,
@@ -184,10 +555,38 @@ where
 
         test_println!("-> offset {:?}", poff);
 
-        self.slab.with(|slab| {
-            let slot = unsafe { &*slab }.as_ref()?.get(poff)?;
-            f(slot)
-        })
+        let slot = self.slots()?.get(poff)?;
+        slot.prefetch();
+        f(slot)
+    }
+
+    /// Drops any initialized slots and releases the page's storage.
+    ///
+    /// This must only be called with exclusive access to the page.
+    fn deallocate(&self) {
+        let initialized = self.initialized.load(Ordering::Acquire);
+        test_println!(
+            "-> dealloc page ({}); initialized={}",
+            self.size,
+            initialized
+        );
+        let slab = self.slab.with_mut(|slab| unsafe { (*slab).take() });
+        if let Some(mut slab) = slab {
+            for slot in &mut slab[..initialized] {
+                unsafe {
+                    // Safety: the first `initialized` slots were initialized,
+                    // and we have exclusive access to them.
+                    ptr::drop_in_place(slot.get() as *mut Slot<T, C>);
+                }
+            }
+        }
+        self.initialized.store(0, Ordering::Release);
+        self.last_used
+            .with_mut(|last_used| unsafe { *last_used = None });
+        self.inserted
+            .with_mut(|inserted| unsafe { *inserted = None });
+        self.occupancy
+            .with_mut(|occupancy| unsafe { *occupancy = None });
     }
 
     #[inline(always)]
@@ -213,11 +612,27 @@ where
 
         test_println!("-> take: offset {:?}", offset);
 
-        self.slab.with(|slab| {
-            let slab = unsafe { &*slab }.as_ref()?;
-            let slot = slab.get(offset)?;
-            slot.remove_value(gen, offset, free_list)
-        })
+        let slot = self.slots()?.get(offset)?;
+        slot.remove_value(gen, offset, &self.vacate(free_list))
+    }
+
+    pub(crate) fn poll_take<F>(
+        &self,
+        addr: Addr<C>,
+        gen: slot::Generation<C>,
+        free_list: &F,
+    ) -> std::task::Poll<Option<T>>
+    where
+        F: FreeList<C>,
+    {
+        let offset = addr.offset() - self.prev_sz;
+
+        test_println!("-> poll_take: offset {:?}", offset);
+
+        match self.slots().and_then(|slots| slots.get(offset)) {
+            Some(slot) => slot.poll_remove_value(gen, offset, &self.vacate(free_list)),
+            None => std::task::Poll::Ready(None),
+        }
     }
 
     pub(crate) fn remove<F: FreeList<C>>(
@@ -230,40 +645,308 @@ where
 
         test_println!("-> offset {:?}", offset);
 
-        self.slab.with(|slab| {
-            let slab = unsafe { &*slab }.as_ref();
-            if let Some(slot) = slab.and_then(|slab| slab.get(offset)) {
-                slot.try_remove_value(gen, offset, free_list)
-            } else {
-                false
+        if let Some(slot) = self.slots().and_then(|slots| slots.get(offset)) {
+            slot.try_remove_value(gen, offset, &self.vacate(free_list))
+        } else {
+            false
+        }
+    }
+
+    /// Pushes the slot at `addr`, which was initialized but never given a
+    /// value, to `free_list`.
+    pub(crate) fn abandon<F: FreeList<C>>(&self, addr: Addr<C>, free_list: &F) {
+        let offset = addr.offset() - self.prev_sz;
+
+        test_println!("-> abandon: offset {:?}", offset);
+
+        if let Some(slot) = self.slots().and_then(|slots| slots.get(offset)) {
+            self.vacate(free_list).push(offset, slot);
+        }
+    }
+
+    /// Marks the value at `gen` for removal, without removing it.
+    ///
+    /// Returns whether the value is unreferenced, and so may be removed now,
+    /// or `None` if there is no value at `gen`.
+    pub(crate) fn mark_release(&self, addr: Addr<C>, gen: slot::Generation<C>) -> Option<bool> {
+        let offset = addr.offset() - self.prev_sz;
+
+        test_println!("-> mark_release: offset {:?}", offset);
+
+        self.slots()?.get(offset)?.mark_release(gen)
+    }
+
+    /// Returns the packed address and current generation of each initialized
+    /// slot in this page.
+    ///
+    /// Since the slots may be modified concurrently, a slot's current
+    /// generation does not necessarily refer to a value.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        let prev_sz = self.prev_sz;
+        let slots = self.slots().unwrap_or(&[]);
+        self.offsets()
+            .filter_map(move |offset| slots.get(offset).map(|slot| (offset, slot)))
+            .map(move |(offset, slot)| slot.generation().pack(offset + prev_sz))
+    }
+
+    /// Stores `value` in the slot at `addr`, at the given generation,
+    /// returning `false` if that slot already contains a value.
+    ///
+    /// The page's free lists are not updated, so `reset_free_lists` must be
+    /// called before any more values are inserted.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard containing this
+    /// page.
+    pub(crate) unsafe fn restore(&self, addr: Addr<C>, gen: slot::Generation<C>, value: T) -> bool {
+        let offset = addr.offset() - self.prev_sz;
+        test_println!("-> restore: offset {:?}; gen {:?}", offset, gen);
+
+        if self.is_unallocated() {
+            self.allocate();
+        }
+        let mut initialized = self.initialized.load(Ordering::Relaxed);
+        while initialized <= offset {
+            self.init_slot(initialized, None);
+            initialized += 1;
+        }
+
+        let slot = &self
+            .slots()
+            .expect("page must be allocated to restore into")[offset];
+        if !slot.is_empty() {
+            return false;
+        }
+        slot.restore(gen, value);
+        self.touch(offset);
+        self.occupy(offset);
+        true
+    }
+
+    /// Returns the address of each occupied slot in this page, in ascending
+    /// order.
+    pub(crate) fn occupied(&self) -> impl Iterator<Item = Addr<C>> + '_ {
+        let prev_sz = self.prev_sz;
+        let slots = self.slots().unwrap_or(&[]);
+        self.offsets()
+            .filter(move |&offset| slots.get(offset).map_or(false, |slot| !slot.is_empty()))
+            .map(move |offset| Addr::from_usize(offset + prev_sz))
+    }
+
+    /// Returns the address of each vacant slot in this page, in ascending
+    /// order, including slots which have never been used, but not slots which
+    /// have been quarantined.
+    ///
+    /// If the page is not allocated, it has no vacant slots.
+    pub(crate) fn vacant(&self) -> impl Iterator<Item = Addr<C>> + '_ {
+        let size = if self.is_unallocated() { 0 } else { self.size };
+        (0..size)
+            .filter(move |&offset| {
+                self.slots()
+                    .and_then(|slots| slots.get(offset))
+                    .map_or(true, |slot| slot.is_empty() && !slot.is_quarantined())
+            })
+            .map(move |offset| Addr::from_usize(offset + self.prev_sz))
+    }
+
+    /// Returns the state of each of this page's slots, and the order of its
+    /// free lists, for `Slab::visualize`.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard containing this
+    /// page.
+    #[cfg(feature = "debug-tools")]
+    pub(crate) unsafe fn layout(&self, local: &Local) -> Layout {
+        let slots = self.slots().unwrap_or(&[]);
+        let states = slots
+            .iter()
+            .map(|slot| {
+                if !slot.is_empty() {
+                    SlotState::Occupied
+                } else if slot.is_quarantined() {
+                    SlotState::Quarantined
+                } else {
+                    SlotState::Vacant
+                }
+            })
+            .collect();
+        let chain = |mut head: usize| {
+            let mut chain = FreeChain {
+                head,
+                offsets: Vec::new(),
+                end: head,
+                cycle: false,
+            };
+            let mut seen = vec![false; slots.len()];
+            while let Some(slot) = slots.get(head) {
+                if seen[head] {
+                    // The list is corrupted; don't follow it forever.
+                    chain.cycle = true;
+                    break;
+                }
+                seen[head] = true;
+                chain.offsets.push(head);
+                head = slot.next();
             }
-        })
+            chain.end = head;
+            chain
+        };
+        Layout {
+            prev_sz: self.prev_sz,
+            size: self.size,
+            allocated: !self.is_unallocated(),
+            states,
+            local: chain(local.head()),
+            remote: chain(self.remote.head()),
+        }
+    }
+
+    /// Moves the value at `from` in this page into the vacant slot at `to` in
+    /// `dst`, returning the generations of the old and new slots.
+    ///
+    /// Neither page's free lists are updated, so `reset_free_lists` must be
+    /// called on both pages before any more values are inserted.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard containing both
+    /// pages.
+    pub(crate) unsafe fn relocate(
+        &self,
+        from: Addr<C>,
+        dst: &Self,
+        to: Addr<C>,
+    ) -> (slot::Generation<C>, slot::Generation<C>) {
+        let from = from.offset() - self.prev_sz;
+        let to = to.offset() - dst.prev_sz;
+        test_println!("-> relocate: offset {:?} -> {:?}", from, to);
+
+        // Vacant slots are filled in order, so if the destination slot has
+        // never been used, it is the next one to initialize.
+        debug_assert!(to <= dst.initialized.load(Ordering::Relaxed));
+        if to == dst.initialized.load(Ordering::Relaxed) {
+            dst.init_slot(to, None);
+        }
+
+        let src = &self
+            .slots()
+            .expect("page must be allocated to relocate from")[from];
+        let dst_slot = &dst.slots().expect("page must be allocated to relocate to")[to];
+        // The value keeps the time it was originally inserted.
+        let inserted_at = self.inserted().map(|inserted| inserted.get(from));
+        let gens = src.relocate(from, &self.vacate(&Local::new()), dst_slot);
+        dst.touch(to);
+        dst.occupy(to);
+        if let (Some(inserted), Some(tick)) = (dst.inserted(), inserted_at) {
+            inserted.set(to, tick);
+        }
+        gens
+    }
+
+    /// Returns the lengths of this page's local and remote free lists. Slots
+    /// which have never been used are counted as part of the local list.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard containing this
+    /// page.
+    pub(crate) unsafe fn free_list_lens(&self, local: &Local) -> (usize, usize) {
+        if self.is_unallocated() {
+            return (0, 0);
+        }
+        let slots = self.slots().unwrap_or(&[]);
+        let len = |mut head: usize| {
+            let mut len = 0;
+            while let Some(slot) = slots.get(head) {
+                len += 1;
+                head = slot.next();
+            }
+            // If the list reaches the first uninitialized slot, the rest of
+            // the page follows it.
+            if head < self.size {
+                len += self.size - head;
+            }
+            len
+        };
+        (len(local.head()), len(self.remote.head()))
     }
 
-    // Need this function separately, as we need to pass a function pointer to `filter_map` and
-    // `Slot::value` just returns a `&T`, specifically a `&Option<T>` for this impl.
-    fn make_ref(slot: &'a Slot<Option<T>, C>) -> Option<&'a T> {
-        slot.value().as_ref()
+    /// Rebuilds this page's free lists from scratch, so that the lowest vacant
+    /// slots are used first. If the page no longer contains any values, its
+    /// storage is released.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard containing this
+    /// page.
+    pub(crate) unsafe fn reset_free_lists(&self, local: &Local) {
+        // Anything on the remote free list is vacant, and will be relinked
+        // below.
+        let _ = self.remote.pop_all();
+
+        let slots = match self.slots() {
+            Some(slots) if !slots.iter().all(Slot::is_empty) => slots,
+            _ => {
+                self.deallocate();
+                local.set_head(0);
+                return;
+            }
+        };
+
+        let mut head = if slots.len() < self.size {
+            slots.len()
+        } else {
+            Self::NULL
+        };
+        for (offset, slot) in slots.iter().enumerate().rev() {
+            if slot.is_empty() && !slot.is_quarantined() {
+                slot.set_next(head);
+                head = offset;
+            }
+        }
+        test_println!("-> reset free lists; head={:?}", head);
+        local.set_head(head);
     }
 
     pub(crate) fn iter(&self) -> Option<Iter<'a, T, C>> {
-        let slab = self.slab.with(|slab| unsafe { (*slab).as_ref() });
-        slab.map(|slab| {
-            slab.iter()
-                .filter_map(Shared::make_ref as fn(&'a Slot<Option<T>, C>) -> Option<&'a T>)
+        let page = unsafe {
+            // Safety: the `UniqueIter` that this is called by holds a mutable
+            // borrow of the slab, so the page will live for `'a`.
+            &*(self as *const Self)
+        };
+        Some(Iter {
+            slots: page.slots()?,
+            offsets: page.offsets(),
         })
     }
 }
 
 impl<T, C> Shared<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     pub(crate) fn init_with<U>(
         &self,
         local: &Local,
         init: impl FnOnce(usize, &Slot<T, C>) -> Option<U>,
+    ) -> Option<U>
+    where
+        T: Default,
+    {
+        self.init_with_factory(local, T::default, init)
+    }
+
+    /// Like `init_with`, but if a slot that has never been used is
+    /// initialized, its value is created by `new_value`, rather than
+    /// `T::default`.
+    pub(crate) fn init_with_factory<U>(
+        &self,
+        local: &Local,
+        new_value: impl FnOnce() -> T,
+        init: impl FnOnce(usize, &Slot<T, C>) -> Option<U>,
     ) -> Option<U> {
         let head = self.pop(local)?;
 
@@ -272,39 +955,119 @@ where
             self.allocate();
         }
 
+        // is the head of the free list a slot that has never been used?
+        if head == self.initialized.load(Ordering::Relaxed) {
+            self.init_slot(head, new_value());
+        }
+
         let index = head + self.prev_sz;
 
-        let result = self.slab.with(|slab| {
-            let slab = unsafe { &*(slab) }
-                .as_ref()
-                .expect("page must have been allocated to insert!");
-            let slot = &slab[head];
-            let result = init(index, slot)?;
-            local.set_head(slot.next());
-            Some(result)
-        })?;
+        let slot = &self
+            .slots()
+            .expect("page must have been allocated to insert!")[head];
+        let result = init(index, slot)?;
+        local.set_head(slot.next());
+        self.touch(head);
+        self.occupy(head);
 
         test_println!("-> init_with: insert at offset: {}", index);
         Some(result)
     }
 
     /// Allocates storage for the page's slots.
+    ///
+    /// The slots themselves are not initialized until they are first used
+    /// (see `init_slot`), so this does not touch the allocated memory.
     #[cold]
-    fn allocate(&self) {
+    pub(crate) fn allocate(&self) {
         test_println!("-> alloc new page ({})", self.size);
         debug_assert!(self.is_unallocated());
+        #[cfg(feature = "lifecycle-u32")]
+        assert!(
+            self.size < !0u32 as usize,
+            "page size {} is too large for `u32` free list links",
+            self.size,
+        );
+
+        if C::TRACK_IDLE || C::TRACK_ACCESS {
+            self.last_used.with_mut(|last_used| unsafe {
+                // Safety: this is only written to when the page is allocated,
+                // by the owning thread, and other threads don't read it until
+                // a slot has been initialized.
+                *last_used = Some(Timestamps::new(self.size));
+            });
+        }
+        if C::TRACK_INSERTED {
+            self.inserted.with_mut(|inserted| unsafe {
+                // Safety: as above.
+                *inserted = Some(Timestamps::new(self.size));
+            });
+        }
+        if C::TRACK_OCCUPANCY {
+            self.occupancy.with_mut(|occupancy| unsafe {
+                // Safety: as above.
+                *occupancy = Some(Occupancy::new(self.size));
+            });
+        }
 
         let mut slab = Vec::with_capacity(self.size);
-        slab.extend((1..self.size).map(Slot::new));
-        slab.push(Slot::new(Self::NULL));
+        unsafe {
+            // Safety: the elements are `MaybeUninit`, so they do not need to
+            // be initialized, and the capacity was just reserved.
+            slab.set_len(self.size);
+        }
         self.slab.with_mut(|s| {
             // safety: this mut access is safe — it only occurs to initially allocate the page,
             // which only happens on this thread; if the page has not yet been allocated, other
             // threads will not try to access it yet.
             unsafe {
-                *s = Some(slab.into_boxed_slice());
+                *s = Some(Slots::Boxed(slab.into_boxed_slice()));
+            }
+        });
+    }
+
+    /// Uses `slots` as the storage for the page's slots, rather than
+    /// allocating it.
+    ///
+    /// Nothing is allocated, so this must not be called if the configuration
+    /// tracks when slots are used, or which slots are occupied.
+    pub(crate) fn allocate_static(&self, slots: &'static mut [SlotCell<T, C>]) {
+        test_println!("-> use static page ({})", self.size);
+        debug_assert!(self.is_unallocated());
+        debug_assert_eq!(slots.len(), self.size);
+        debug_assert!(
+            !(C::TRACK_IDLE || C::TRACK_ACCESS || C::TRACK_INSERTED || C::TRACK_OCCUPANCY)
+        );
+        self.slab.with_mut(|s| unsafe {
+            // Safety: as in `allocate`, the page has not been allocated yet,
+            // so no other thread can access it.
+            *s = Some(Slots::Static(ptr::NonNull::from(slots)));
+        });
+    }
+
+    /// Initializes the never-used slot at index `idx` with `value`, linking it
+    /// to the next never-used slot in the page (if there is one).
+    #[cold]
+    fn init_slot(&self, idx: usize, value: T) {
+        test_println!("-> init slot {} ({})", idx, self.size);
+        debug_assert_eq!(idx, self.initialized.load(Ordering::Relaxed));
+
+        let next = if idx + 1 < self.size {
+            idx + 1
+        } else {
+            Self::NULL
+        };
+        self.slab.with(|slab| {
+            let slab = unsafe { &*slab }
+                .as_ref()
+                .expect("page must have been allocated to initialize a slot!");
+            unsafe {
+                // Safety: slots past the initialized index are only ever
+                // accessed by the page's owning thread, which is this one.
+                ptr::write(slab[idx].get(), MaybeUninit::new(Slot::new(next, value)));
             }
         });
+        self.initialized.store(idx + 1, Ordering::Release);
     }
 
     pub(crate) fn mark_clear<F: FreeList<C>>(
@@ -317,14 +1080,12 @@ where
 
         test_println!("-> offset {:?}", offset);
 
-        self.slab.with(|slab| {
-            let slab = unsafe { &*slab }.as_ref();
-            if let Some(slot) = slab.and_then(|slab| slab.get(offset)) {
-                slot.try_clear_storage(gen, offset, free_list)
-            } else {
-                false
-            }
-        })
+        if let Some(slot) = self.slots().and_then(|slots| slots.get(offset)) {
+            self.touch(offset);
+            slot.try_clear_storage(gen, offset, &self.vacate(free_list))
+        } else {
+            false
+        }
     }
 
     pub(crate) fn clear<F: FreeList<C>>(
@@ -337,14 +1098,201 @@ where
 
         test_println!("-> offset {:?}", offset);
 
-        self.slab.with(|slab| {
-            let slab = unsafe { &*slab }.as_ref();
-            if let Some(slot) = slab.and_then(|slab| slab.get(offset)) {
-                slot.clear_storage(gen, offset, free_list)
-            } else {
-                false
+        if let Some(slot) = self.slots().and_then(|slots| slots.get(offset)) {
+            self.touch(offset);
+            slot.clear_storage(gen, offset, &self.vacate(free_list))
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn take_storage<F: FreeList<C>>(
+        &self,
+        addr: Addr<C>,
+        gen: slot::Generation<C>,
+        free_list: &F,
+        new_value: impl FnOnce() -> T,
+    ) -> Option<T> {
+        let offset = addr.offset() - self.prev_sz;
+
+        test_println!("-> take_storage: offset {:?}", offset);
+
+        let slot = self.slots()?.get(offset)?;
+        slot.take_storage(gen, offset, &self.vacate(free_list), new_value)
+    }
+
+    /// Replaces the values of released slots which were last used more than
+    /// `max_idle` ticks before `now` with values created by `new_value`,
+    /// returning the number of slots evicted.
+    pub(crate) fn evict_idle(
+        &self,
+        now: timestamps::Tick,
+        max_idle: timestamps::Tick,
+        new_value: impl Fn() -> T,
+    ) -> usize {
+        let (slots, last_used) = match (self.slots(), self.last_used()) {
+            (Some(slots), Some(last_used)) => (slots, last_used),
+            _ => return 0,
+        };
+
+        let mut evicted = 0;
+        for (offset, slot) in slots.iter().enumerate() {
+            if timestamps::is_older(last_used.get(offset), now, max_idle) && slot.evict(&new_value)
+            {
+                // Don't evict the same slot again until it's used again.
+                last_used.clear(offset);
+                evicted += 1;
             }
-        })
+        }
+        evicted
+    }
+}
+
+impl<T, C> Drop for Shared<T, C> {
+    fn drop(&mut self) {
+        let initialized = self.initialized.load(Ordering::Acquire);
+        self.slab.with_mut(|slab| {
+            if let Some(slab) = unsafe { &mut *slab } {
+                for slot in &mut slab[..initialized] {
+                    unsafe {
+                        // Safety: the first `initialized` slots were
+                        // initialized, and we have exclusive access to them.
+                        ptr::drop_in_place(slot.get() as *mut Slot<T, C>);
+                    }
+                }
+            }
+        });
+    }
+}
+
+// === impl Slots ===
+
+impl<T, C> Deref for Slots<T, C> {
+    type Target = [SlotCell<T, C>];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Slots::Boxed(slots) => slots,
+            Slots::Static(slots) => unsafe {
+                // Safety: the storage was a `&'static mut`, which was given
+                // to this page.
+                slots.as_ref()
+            },
+        }
+    }
+}
+
+impl<T, C> DerefMut for Slots<T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Slots::Boxed(slots) => slots,
+            Slots::Static(slots) => unsafe {
+                // Safety: as above.
+                slots.as_mut()
+            },
+        }
+    }
+}
+
+// === impl Iter ===
+
+impl<'a, T, C: cfg::Config> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slots = self.slots;
+        self.offsets
+            .by_ref()
+            .filter_map(|offset| slots.get(offset)?.value().as_ref())
+            .next()
+    }
+}
+
+// === impl Offsets ===
+
+impl Iterator for Offsets<'_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            Offsets::All(range) => range.next(),
+            Offsets::Occupied(bits) => bits.next(),
+        }
+    }
+}
+
+// === impl Vacate ===
+
+impl<T, C: cfg::Config, F: FreeList<C>> Vacate<'_, T, C, F> {
+    /// Returns `true` if the slot at `offset` may be freed, panicking if it
+    /// is already on a free list (and we aren't already panicking).
+    ///
+    /// Pushing a slot that is already free would link it into a free list
+    /// twice, so that two inserts are later handed the same slot.
+    #[inline]
+    fn check_not_free<U>(&self, offset: usize, slot: &Slot<U, C>) -> bool {
+        if slot.is_claimed() {
+            return true;
+        }
+        panic_in_drop!(
+            "slot {} was freed while already on a free list; this is a bug!",
+            self.page.prev_sz + offset
+        );
+        false
+    }
+}
+
+impl<T, C: cfg::Config, F: FreeList<C>> FreeList<C> for Vacate<'_, T, C, F> {
+    fn push<U>(&self, new_head: usize, slot: &Slot<U, C>) {
+        if !self.check_not_free(new_head, slot) {
+            return;
+        }
+        self.page
+            .live
+            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+        if let Some(occupancy) = self.occupancy {
+            occupancy.clear(new_head);
+        }
+        if let Some(inserted) = self.page.inserted() {
+            inserted.clear(new_head);
+        }
+        match self.free_list.as_local() {
+            Some(local) if C::PREFER_LOW_ADDRESSES => self.page.push_sorted(local, new_head),
+            _ => self.free_list.push(new_head, slot),
+        }
+    }
+
+    fn push_wrapped<U>(&self, new_head: usize, slot: &Slot<U, C>) {
+        self.page
+            .wraps
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if C::GENERATION_WRAP == cfg::GenerationWrap::Quarantine {
+            if !self.check_not_free(new_head, slot) {
+                return;
+            }
+            // Leave the slot off the free list, so that it is never reused.
+            self.page
+                .live
+                .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            if let Some(occupancy) = self.occupancy {
+                occupancy.clear(new_head);
+            }
+            if let Some(inserted) = self.page.inserted() {
+                inserted.clear(new_head);
+            }
+            return;
+        }
+        self.push(new_head, slot);
+        debug_assert!(
+            C::GENERATION_WRAP != cfg::GenerationWrap::DebugAssert,
+            "generation of slot {} wrapped around",
+            self.page.prev_sz + new_head
+        );
+    }
+
+    fn as_local(&self) -> Option<&Local> {
+        self.free_list.as_local()
     }
 }
 
@@ -420,6 +1368,210 @@ mod test {
     use crate::Pack;
     use proptest::prelude::*;
 
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    fn slots_initialized_lazily() {
+        let page = Shared::<Option<usize>, cfg::DefaultConfig>::new(1 << 16, 0);
+        let local = Local::new();
+        assert!(page.slots().is_none());
+
+        for i in 0..3 {
+            let mut value = Some(i);
+            page.init_with(&local, |_, slot| slot.insert(&mut value))
+                .expect("insert");
+        }
+
+        assert_eq!(page.slots().expect("page allocated").len(), 3);
+    }
+
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    fn remote_frees_become_local_free_list() {
+        const N: usize = 1024;
+        let page = Shared::<Option<usize>, cfg::DefaultConfig>::new(N, 0);
+        let local = Local::new();
+
+        let gens: Vec<_> = (0..N)
+            .map(|i| {
+                let mut value = Some(i);
+                page.init_with(&local, |_, slot| slot.insert(&mut value))
+                    .expect("insert")
+            })
+            .collect();
+        assert!(
+            page.init_with(&local, |_, _| Some(())).is_none(),
+            "page full"
+        );
+
+        // Free every slot "remotely", as though from another thread.
+        for (i, gen) in gens.into_iter().enumerate() {
+            assert!(page.remove(Addr::from_usize(i), gen, page.free_list()));
+        }
+
+        // The first insert takes the whole remote list, the slot freed last
+        // being its head...
+        let mut value = Some(0);
+        let idx = page
+            .init_with(&local, |idx, slot| slot.insert(&mut value).map(|_| idx))
+            .expect("insert");
+        assert_eq!(idx, N - 1);
+        assert_eq!(page.remote.pop_all(), None);
+
+        // ...and the rest are then popped one at a time from the local list.
+        assert_eq!(local.head(), N - 2);
+    }
+
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    #[cfg(any(debug_assertions, feature = "strict"))]
+    #[should_panic(expected = "already on a free list")]
+    fn double_free_panics() {
+        let page = Shared::<Option<usize>, cfg::DefaultConfig>::new(32, 0);
+        let local = Local::new();
+        let mut value = Some(1);
+        page.init_with(&local, |_, slot| slot.insert(&mut value))
+            .expect("insert");
+        let slot = &page.slots().unwrap()[0];
+
+        // Free the slot, and then push it again, as though its generation
+        // check had been bypassed.
+        let vacate = page.vacate(&local);
+        vacate.push(0, slot);
+        vacate.push(0, slot);
+    }
+
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    fn reset_free_lists_releases_empty_page() {
+        let page = Shared::<Option<usize>, cfg::DefaultConfig>::new(32, 0);
+        let local = Local::new();
+
+        let gens: Vec<_> = (0..3)
+            .map(|i| {
+                let mut value = Some(i);
+                page.init_with(&local, |_, slot| slot.insert(&mut value))
+                    .expect("insert")
+            })
+            .collect();
+
+        // While a value remains, only the free lists are rebuilt, lowest
+        // vacant slot first.
+        assert!(page.remove(Addr::from_usize(0), gens[0], page.free_list()));
+        assert!(page.remove(Addr::from_usize(2), gens[2], page.free_list()));
+        unsafe { page.reset_free_lists(&local) };
+        assert_eq!(local.head(), 0);
+        assert_eq!(
+            page.occupied().collect::<Vec<_>>(),
+            vec![Addr::from_usize(1)]
+        );
+
+        // Once the page is empty, its storage is released...
+        assert!(page.remove(Addr::from_usize(1), gens[1], page.free_list()));
+        unsafe { page.reset_free_lists(&local) };
+        assert!(page.is_unallocated());
+        assert!(page.slots().is_none());
+
+        // ...and reallocated on the next insert.
+        let mut value = Some(3);
+        let idx = page
+            .init_with(&local, |idx, slot| slot.insert(&mut value).map(|_| idx))
+            .expect("insert");
+        assert_eq!(idx, 0);
+    }
+
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    fn occupancy_skips_vacant_slots() {
+        struct TrackOccupancy;
+        impl cfg::Config for TrackOccupancy {
+            const TRACK_OCCUPANCY: bool = true;
+        }
+
+        let page = Shared::<Option<usize>, TrackOccupancy>::new(256, 0);
+        let local = Local::new();
+        let gens: Vec<_> = (0..200)
+            .map(|i| {
+                let mut value = Some(i);
+                page.init_with(&local, |_, slot| slot.insert(&mut value))
+                    .expect("insert")
+            })
+            .collect();
+
+        // Free most slots, some locally and some "remotely".
+        for (i, &gen) in gens.iter().enumerate() {
+            if [3, 64, 199].contains(&i) {
+                continue;
+            }
+            let removed = if i % 2 == 0 {
+                page.remove(Addr::from_usize(i), gen, &local)
+            } else {
+                page.remove(Addr::from_usize(i), gen, page.free_list())
+            };
+            assert!(removed);
+        }
+        assert_eq!(page.offsets().collect::<Vec<_>>(), vec![3, 64, 199]);
+        assert_eq!(
+            page.iter().expect("page allocated").collect::<Vec<_>>(),
+            vec![&3, &64, &199]
+        );
+
+        // Reused slots become occupied again.
+        let mut value = Some(1000);
+        let idx = page
+            .init_with(&local, |idx, slot| slot.insert(&mut value).map(|_| idx))
+            .expect("insert");
+        assert!(page.offsets().any(|offset| offset == idx));
+        assert_eq!(page.offsets().count(), 4);
+    }
+
+    #[test]
+    #[cfg_attr(loom, ignore)]
+    fn prefer_low_addresses_reuses_lowest_slot() {
+        struct PreferLow;
+        impl cfg::Config for PreferLow {
+            const PREFER_LOW_ADDRESSES: bool = true;
+        }
+
+        let page = Shared::<Option<usize>, PreferLow>::new(32, 0);
+        let local = Local::new();
+        let gens: Vec<_> = (0..10)
+            .map(|i| {
+                let mut value = Some(i);
+                page.init_with(&local, |_, slot| slot.insert(&mut value))
+                    .expect("insert")
+            })
+            .collect();
+
+        // Free slots in no particular order, some locally and some
+        // "remotely".
+        for &i in &[7, 2, 9, 5, 4] {
+            let removed = if i % 2 == 0 {
+                page.remove(Addr::from_usize(i), gens[i], &local)
+            } else {
+                page.remove(Addr::from_usize(i), gens[i], page.free_list())
+            };
+            assert!(removed);
+        }
+
+        // The freed slots are reused lowest first, followed by the slots that
+        // have never been used.
+        let reused: Vec<_> = (0..7)
+            .map(|i| {
+                let mut value = Some(100 + i);
+                page.init_with(&local, |idx, slot| slot.insert(&mut value).map(|_| idx))
+                    .expect("insert")
+            })
+            .collect();
+        assert_eq!(reused, vec![2, 4, 5, 7, 9, 10, 11]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "lifecycle-u32", not(loom)))]
+    fn compact_slot_metadata() {
+        // The lifecycle word and the free list link should be 4 bytes each.
+        assert_eq!(std::mem::size_of::<Slot<(), cfg::DefaultConfig>>(), 8);
+    }
+
     proptest! {
         #[test]
         fn addr_roundtrips(pidx in 0usize..Addr::<cfg::DefaultConfig>::BITS) {
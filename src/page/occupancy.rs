@@ -0,0 +1,70 @@
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use std::{mem, slice};
+
+const BITS: usize = mem::size_of::<usize>() * 8;
+
+/// A bitmap of the occupied slots in a page.
+///
+/// Like `Timestamps`, this is stored alongside a page's slots, rather than in
+/// them, so that configurations which don't track occupancy don't pay for it.
+///
+/// A slot's bit is set once a value has been inserted into it, and cleared
+/// just before the slot is pushed to a free list. Since a slot can only be
+/// reused after it has been popped from a free list, the bit is always cleared
+/// before it is set again, and these operations can be `Relaxed`. Readers
+/// must still check each slot's lifecycle before accessing its value.
+pub(crate) struct Occupancy(Box<[AtomicUsize]>);
+
+/// An iterator over the indices of the set bits in an `Occupancy` bitmap.
+#[derive(Debug)]
+pub(crate) struct Iter<'a> {
+    words: slice::Iter<'a, AtomicUsize>,
+    /// The index of the first bit in the next word.
+    next_base: usize,
+    /// The bits in the current word which have not yet been returned.
+    word: usize,
+}
+
+impl Occupancy {
+    pub(crate) fn new(len: usize) -> Self {
+        let words = (len + BITS - 1) / BITS;
+        Self((0..words).map(|_| AtomicUsize::new(0)).collect())
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, idx: usize) {
+        self.0[idx / BITS].fetch_or(1 << (idx % BITS), Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn clear(&self, idx: usize) {
+        self.0[idx / BITS].fetch_and(!(1 << (idx % BITS)), Ordering::Relaxed);
+    }
+
+    /// Returns the index of each set bit, in ascending order.
+    ///
+    /// Each word is read once, so bits which are set or cleared concurrently
+    /// may or may not be observed.
+    pub(crate) fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: self.0.iter(),
+            next_base: 0,
+            word: 0,
+        }
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.word == 0 {
+            self.word = self.words.next()?.load(Ordering::Relaxed);
+            self.next_base += BITS;
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        // Clear the lowest set bit.
+        self.word &= self.word - 1;
+        Some(self.next_base - BITS + bit)
+    }
+}
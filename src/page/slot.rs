@@ -1,17 +1,42 @@
 use super::FreeList;
-use crate::sync::{
-    atomic::{AtomicUsize, Ordering},
-    hint, UnsafeCell,
-};
+use crate::sync::{atomic::Ordering, hint, UnsafeCell};
 use crate::{cfg, clear::Clear, Pack, Tid};
 use std::{fmt, marker::PhantomData, mem, ptr, thread};
 
+#[cfg(feature = "lifecycle-u32")]
+use crate::sync::atomic::AtomicU32 as AtomicWord;
+#[cfg(not(feature = "lifecycle-u32"))]
+use crate::sync::atomic::AtomicUsize as AtomicWord;
+
+/// The integer type used to store a slot's lifecycle and free list link.
+#[cfg(not(feature = "lifecycle-u32"))]
+type Word = usize;
+#[cfg(feature = "lifecycle-u32")]
+type Word = u32;
+
+/// MSRV: the `MAX` associated constants require Rust 1.43.
+const WORD_MAX: Word = !0;
+
+/// Stored as the free list link of a slot that is in use, if debug assertions
+/// or the `strict` feature are enabled, so that freeing a slot which is
+/// already on a free list can be detected before the list is corrupted.
+#[cfg(any(debug_assertions, feature = "strict"))]
+const CLAIMED: Word = WORD_MAX - 1;
+
+/// The cache line size assumed when deciding whether to prefetch an item.
+const CACHE_LINE: usize = 64;
+
+// The field order here is deliberate: `get` loads the lifecycle word and then
+// immediately reads the item, so keeping them adjacent means that for small
+// `T` both usually land on the same cache line. The free list link is only
+// touched when inserting or removing, so it goes last.
+#[repr(C)]
 pub(crate) struct Slot<T, C> {
-    lifecycle: AtomicUsize,
-    /// The offset of the next item on the free list.
-    next: UnsafeCell<usize>,
+    lifecycle: LifecycleWord,
     /// The data stored in the slot.
     item: UnsafeCell<T>,
+    /// The offset of the next item on the free list.
+    next: UnsafeCell<Word>,
     _cfg: PhantomData<fn(C)>,
 }
 
@@ -20,6 +45,16 @@ pub(crate) struct Guard<T, C: cfg::Config = cfg::DefaultConfig> {
     slot: ptr::NonNull<Slot<T, C>>,
 }
 
+/// A guard holding exclusive access to a slot's value.
+///
+/// While a `WriteGuard` exists, the slot's ref count is set to
+/// `RefCount::WRITE_LOCKED`, which prevents any other guards from being
+/// acquired.
+#[derive(Debug)]
+pub(crate) struct WriteGuard<T, C: cfg::Config = cfg::DefaultConfig> {
+    slot: ptr::NonNull<Slot<T, C>>,
+}
+
 #[derive(Debug)]
 pub(crate) struct InitGuard<T, C: cfg::Config = cfg::DefaultConfig> {
     slot: ptr::NonNull<Slot<T, C>>,
@@ -45,6 +80,15 @@ pub(crate) struct Lifecycle<C> {
 }
 struct LifecycleGen<C>(Generation<C>);
 
+/// The atomic word holding a slot's packed `Lifecycle`, `RefCount`, and
+/// `LifecycleGen`.
+///
+/// When the `lifecycle-u32` feature is enabled, this is stored as a `u32`
+/// regardless of the target's pointer width. Since all values packed into it
+/// are bounded by `cfg::LIFECYCLE_WIDTH`, converting to and from `usize` is
+/// lossless.
+struct LifecycleWord(AtomicWord);
+
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 #[repr(usize)]
 enum State {
@@ -55,14 +99,18 @@ enum State {
 
 impl<C: cfg::Config> Pack<C> for Generation<C> {
     /// Use all the remaining bits in the word for the generation counter, minus
-    /// any bits reserved by the user.
-    const LEN: usize = (cfg::WIDTH - C::RESERVED_BITS) - Self::SHIFT;
+    /// any bits reserved by the user or used for tags, up to the maximum that
+    /// fits in a slot's lifecycle word.
+    const LEN: usize = cfg::min(
+        (cfg::WIDTH - C::RESERVED_BITS - C::TAG_BITS) - Self::SHIFT,
+        cfg::MAX_GENERATION_BITS,
+    );
 
     type Prev = Tid<C>;
 
     #[inline(always)]
     fn from_usize(u: usize) -> Self {
-        debug_assert!(u <= Self::BITS);
+        strict_assert!(u <= Self::BITS);
         Self::new(u)
     }
 
@@ -87,8 +135,29 @@ where
     C: cfg::Config,
 {
     #[inline(always)]
+    #[allow(clippy::unnecessary_cast)] // `Word` may not be `usize`
     pub(super) fn next(&self) -> usize {
-        self.next.with(|next| unsafe { *next })
+        let next = self.next.with(|next| unsafe { *next });
+        if cfg!(feature = "lifecycle-u32") && next == WORD_MAX {
+            return super::Addr::<C>::NULL;
+        }
+        next as usize
+    }
+
+    /// Start pulling the rest of this slot's item into the cache, so that
+    /// reading it doesn't stall once the lifecycle CAS has completed.
+    ///
+    /// If the whole slot fits in a cache line, this does nothing: the
+    /// lifecycle CAS is about to load that line anyway, and issuing a
+    /// prefetch for it right before the load only costs cycles (the
+    /// `get_random` benchmark regresses by ~40% with an unconditional
+    /// prefetch).
+    #[inline(always)]
+    pub(super) fn prefetch(&self) {
+        if mem::size_of::<Self>() > CACHE_LINE {
+            let next_line = (self as *const Self as *const u8).wrapping_add(CACHE_LINE);
+            crate::sync::prefetch_read(next_line);
+        }
     }
 
     #[inline(always)]
@@ -96,13 +165,96 @@ where
         self.item.with(|item| unsafe { &*item })
     }
 
+    /// Returns a mutable pointer to the slot's value.
+    ///
+    /// The pointer may only be dereferenced by a caller with exclusive access
+    /// to the slot.
     #[inline(always)]
+    pub(crate) fn value_mut(&self) -> *mut T {
+        self.item.with_mut(|item| item)
+    }
+
+    /// Returns the slot's current generation.
+    #[inline]
+    pub(crate) fn generation(&self) -> Generation<C> {
+        LifecycleGen::<C>::from_packed(self.lifecycle.load(Ordering::Acquire)).0
+    }
+
+    #[inline(always)]
+    #[allow(clippy::unnecessary_cast)] // `Word` may not be `usize`
     pub(super) fn set_next(&self, next: usize) {
+        let next = if cfg!(feature = "lifecycle-u32") && next == super::Addr::<C>::NULL {
+            WORD_MAX
+        } else {
+            strict_assert!(next < WORD_MAX as usize, "free list link out of range");
+            next as Word
+        };
         self.next.with_mut(|n| unsafe {
             (*n) = next;
         })
     }
 
+    /// Records that this slot has been taken off its free list.
+    ///
+    /// This does nothing unless debug assertions or the `strict` feature are
+    /// enabled.
+    #[inline(always)]
+    pub(super) fn claim(&self) {
+        #[cfg(any(debug_assertions, feature = "strict"))]
+        self.next.with_mut(|n| unsafe {
+            (*n) = CLAIMED;
+        })
+    }
+
+    /// Returns `true` if this slot was claimed, and so may be pushed to a
+    /// free list.
+    ///
+    /// If neither debug assertions nor the `strict` feature are enabled, this
+    /// always returns `true`.
+    #[inline(always)]
+    pub(super) fn is_claimed(&self) -> bool {
+        #[cfg(any(debug_assertions, feature = "strict"))]
+        {
+            self.next.with(|n| unsafe { *n }) == CLAIMED
+        }
+        #[cfg(not(any(debug_assertions, feature = "strict")))]
+        {
+            true
+        }
+    }
+
+    /// Returns `true` if this slot's value has been marked for removal, but
+    /// is still referenced.
+    ///
+    /// A slot remains `MARKED` after it is released, but its ref count is
+    /// then 0, and no new references can be acquired until it's reused.
+    pub(super) fn is_release_deferred(&self) -> bool {
+        let lifecycle = self.lifecycle.load(Ordering::Acquire);
+        Lifecycle::<C>::from_packed(lifecycle).state == State::Marked
+            && RefCount::<C>::from_packed(lifecycle).value > 0
+    }
+
+    /// Returns the number of outstanding references to the slot, if its
+    /// current generation is `gen`.
+    ///
+    /// This includes slots which have been marked for removal but are still
+    /// referenced. A write-locked slot is counted as a single reference.
+    ///
+    /// A vacant slot is unreferenced, so this returns `Some(0)` for it if its
+    /// generation matches; the caller must check whether an unreferenced slot
+    /// holds a value.
+    pub(crate) fn ref_count(&self, gen: Generation<C>) -> Option<usize> {
+        let lifecycle = self.lifecycle.load(Ordering::Acquire);
+        if LifecycleGen::<C>::from_packed(lifecycle).0 != gen {
+            return None;
+        }
+        let refs = RefCount::<C>::from_packed(lifecycle).value;
+        if refs == RefCount::<C>::WRITE_LOCKED {
+            return Some(1);
+        }
+        Some(refs)
+    }
+
     #[inline(always)]
     pub(crate) fn get(&self, gen: Generation<C>) -> Option<Guard<T, C>> {
         let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
@@ -158,13 +310,98 @@ where
         }
     }
 
+    /// Returns the reason that `get` can't access this slot at the given
+    /// generation, or `None` if it could now.
+    pub(crate) fn lookup_error(&self, gen: Generation<C>) -> Option<crate::LookupError> {
+        use crate::LookupError;
+
+        let lifecycle = self.lifecycle.load(Ordering::Acquire);
+        let state = Lifecycle::<C>::from_packed(lifecycle).state;
+        let current_gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+        let refs = RefCount::<C>::from_packed(lifecycle);
+        test_println!(
+            "-> lookup_error {:?}; current_gen={:?}; state={:?}; refs={:?};",
+            gen,
+            current_gen,
+            state,
+            refs
+        );
+
+        if gen != current_gen {
+            return Some(LookupError::Stale);
+        }
+        match state {
+            State::Present if refs.value == RefCount::<C>::WRITE_LOCKED => {
+                Some(LookupError::Locked)
+            }
+            State::Present if refs.incr().is_none() => Some(LookupError::TooManyReferences),
+            State::Present => None,
+            // Once the value has been cleared, the generation is advanced, so
+            // a slot that is still at the key's generation is being cleared.
+            State::Marked | State::Removing => Some(LookupError::Clearing),
+        }
+    }
+
+    /// Acquires exclusive access to this slot's value, if it is present at the
+    /// given generation and is not currently referenced.
+    ///
+    /// This does not wait for existing guards to be released; if the slot is
+    /// referenced, it returns `None`.
+    #[inline]
+    pub(crate) fn get_mut(&self, gen: Generation<C>) -> Option<WriteGuard<T, C>> {
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        loop {
+            let state = Lifecycle::<C>::from_packed(lifecycle);
+            let current_gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+            let refs = RefCount::<C>::from_packed(lifecycle);
+
+            test_println!(
+                "-> get_mut {:?}; current_gen={:?}; lifecycle={:#x}; state={:?}; refs={:?};",
+                gen,
+                current_gen,
+                lifecycle,
+                state,
+                refs,
+            );
+
+            if gen != current_gen || state != Lifecycle::PRESENT {
+                test_println!("-> get_mut: no longer exists!");
+                return None;
+            }
+
+            if refs.value != 0 {
+                test_println!("-> get_mut: slot is referenced!");
+                return None;
+            }
+
+            let locked = RefCount::<C>::from_usize(RefCount::<C>::WRITE_LOCKED).pack(lifecycle);
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                locked,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    test_println!("-> get_mut: locked");
+                    return Some(WriteGuard {
+                        slot: ptr::NonNull::from(self),
+                    });
+                }
+                Err(actual) => {
+                    test_println!("-> get_mut: retrying; lifecycle={:#x};", actual);
+                    lifecycle = actual;
+                }
+            }
+        }
+    }
+
     /// Marks this slot to be released, returning `true` if the slot can be
     /// mutated *now* and `false` otherwise.
     ///
     /// This method checks if there are any references to this slot. If there _are_ valid
     /// references, it just marks them for modification and returns and the next thread calling
     /// either `clear_storage` or `remove_value` will try and modify the storage
-    fn mark_release(&self, gen: Generation<C>) -> Option<bool> {
+    pub(super) fn mark_release(&self, gen: Generation<C>) -> Option<bool> {
         let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
         let mut curr_gen;
 
@@ -281,7 +518,13 @@ where
                         let value = self
                             .item
                             .with_mut(|item| mutator(Some(unsafe { &mut *item })));
-                        free.push(offset, self);
+                        if C::GENERATION_WRAP != cfg::GenerationWrap::Ignore
+                            && next_gen.is_wrapped()
+                        {
+                            free.push_wrapped(offset, self);
+                        } else {
+                            free.push(offset, self);
+                        }
                         return value;
                     }
 
@@ -325,6 +568,24 @@ where
             return None;
         }
 
+        // If idle slots may be evicted by other threads, we must reserve the
+        // slot, so that it isn't evicted while we are initializing it. The
+        // reservation is released along with the `InitGuard`.
+        let lifecycle = if C::TRACK_IDLE {
+            let reserved = RefCount::<C>::from_usize(1).pack(lifecycle);
+            if self
+                .lifecycle
+                .compare_exchange(lifecycle, reserved, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                test_println!("-> initialize while being evicted! cancelling");
+                return None;
+            }
+            reserved
+        } else {
+            lifecycle
+        };
+
         Some(InitGuard {
             slot: ptr::NonNull::from(self),
             curr_lifecycle: lifecycle,
@@ -333,22 +594,95 @@ where
     }
 }
 
+// These methods are specific to `Pool`.
+impl<T, C> Slot<T, C>
+where
+    C: cfg::Config,
+{
+    /// Replaces the value in this slot with `new_value()`, if the slot is not
+    /// currently in use, returning `true` if it was evicted.
+    ///
+    /// This must only be called if `C::TRACK_IDLE` is set, as otherwise
+    /// `init` doesn't reserve slots it is initializing.
+    pub(super) fn evict(&self, new_value: impl FnOnce() -> T) -> bool {
+        debug_assert!(C::TRACK_IDLE, "evicted a slot without `TRACK_IDLE`");
+        let lifecycle = self.lifecycle.load(Ordering::Acquire);
+        let state = Lifecycle::<C>::from_packed(lifecycle).state;
+        let refs = RefCount::<C>::from_packed(lifecycle);
+        test_println!("-> evict; state={:?}; refs={:?};", state, refs);
+
+        // Only a slot which has been released (and is not referenced or being
+        // initialized) may be evicted. Depending on how it was released, such
+        // a slot may be either marked or removing.
+        if state == State::Present || refs.value != 0 {
+            return false;
+        }
+
+        // Reserve the slot, so that it can't be initialized while we're
+        // replacing its value.
+        let reserved = RefCount::<C>::from_usize(1).pack(lifecycle);
+        if self
+            .lifecycle
+            .compare_exchange(lifecycle, reserved, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            test_println!("-> evict; lifecycle changed, skipping");
+            return false;
+        }
+
+        self.item.with_mut(|item| unsafe {
+            // Safety: the slot is not referenced, and we have reserved it, so
+            // we have exclusive access to its value.
+            *item = new_value();
+        });
+
+        // Release the reservation. Other threads may have advanced the
+        // generation in the meantime (if the slot was still being cleared),
+        // so only decrement the ref count, leaving the rest as-is.
+        let mut lifecycle = reserved;
+        loop {
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                refs.decr().pack(lifecycle),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    test_println!("-> evicted!");
+                    return true;
+                }
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+}
+
 // Slot impl which _needs_ an `Option` for self.item, this is for `Slab` to use.
 impl<T, C> Slot<Option<T>, C>
 where
     C: cfg::Config,
 {
-    fn is_empty(&self) -> bool {
+    pub(super) fn is_empty(&self) -> bool {
         self.item.with(|item| unsafe { (*item).is_none() })
     }
 
+    /// Returns `true` if this vacant slot was quarantined when its
+    /// generation wrapped around, and so must not be reused.
+    ///
+    /// A vacant slot's generation is only zero if it wrapped: removing the
+    /// value it was initialized with advances it to one.
+    pub(super) fn is_quarantined(&self) -> bool {
+        C::GENERATION_WRAP == cfg::GenerationWrap::Quarantine && self.generation().is_wrapped()
+    }
+
     /// Insert a value into a slot
     ///
     /// We first initialize the state and then insert the pased in value into the slot.
     #[inline]
     pub(crate) fn insert(&self, value: &mut Option<T>) -> Option<Generation<C>> {
-        debug_assert!(self.is_empty(), "inserted into full slot");
-        debug_assert!(value.is_some(), "inserted twice");
+        strict_assert!(self.is_empty(), "inserted into full slot");
+        strict_assert!(value.is_some(), "inserted twice");
 
         let mut guard = self.init()?;
         let gen = guard.generation();
@@ -405,6 +739,51 @@ where
         true
     }
 
+    /// Moves this slot's value into the empty slot `dst`, returning the
+    /// generations at which the value was stored in this slot and is now
+    /// stored in `dst`.
+    ///
+    /// This must only be called with exclusive access to the slab, so that
+    /// neither slot is referenced.
+    pub(super) fn relocate<F: FreeList<C>>(
+        &self,
+        offset: usize,
+        free: &F,
+        dst: &Self,
+    ) -> (Generation<C>, Generation<C>) {
+        let gen = self.generation();
+        let mut value = self.remove_value(gen, offset, free);
+        debug_assert!(value.is_some(), "relocated an empty slot");
+        let new_gen = dst
+            .insert(&mut value)
+            .expect("slots must not be referenced while relocating");
+        test_println!("-> relocated {:?} -> {:?}", gen, new_gen);
+        (gen, new_gen)
+    }
+
+    /// Stores `value` in this empty slot, at the given generation.
+    ///
+    /// This must only be called with exclusive access to the slab, so that the
+    /// slot is not referenced.
+    pub(super) fn restore(&self, gen: Generation<C>, value: T) {
+        strict_assert!(self.is_empty(), "restored into full slot");
+        self.item.with_mut(|item| unsafe { *item = Some(value) });
+        let lifecycle = self.lifecycle.load(Ordering::Acquire);
+        let new_lifecycle = LifecycleGen(gen).pack(Lifecycle::<C>::PRESENT.pack(0));
+        test_println!(
+            "-> restore; lifecycle={:#x}; new_lifecycle={:#x};",
+            lifecycle,
+            new_lifecycle
+        );
+        let swapped = self.lifecycle.compare_exchange(
+            lifecycle,
+            new_lifecycle,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+        strict_assert!(swapped.is_ok(), "slot modified while restoring");
+    }
+
     #[inline]
     pub(super) fn remove_value<F: FreeList<C>>(
         &self,
@@ -414,21 +793,234 @@ where
     ) -> Option<T> {
         self.release_with(gen, offset, free, |item| item.and_then(Option::take))
     }
+
+    /// Removes and returns the value at `gen`, if it isn't referenced.
+    ///
+    /// Unlike `remove_value`, this doesn't wait for outstanding references to
+    /// be released: if the slot is referenced, it returns `Poll::Pending`
+    /// without modifying the slot, so that the caller may try again once a
+    /// reference is released. Since the generation is only advanced once the
+    /// slot is unreferenced, new references may be acquired in the meantime.
+    pub(super) fn poll_remove_value<F: FreeList<C>>(
+        &self,
+        gen: Generation<C>,
+        offset: usize,
+        free: &F,
+    ) -> std::task::Poll<Option<T>> {
+        use std::task::Poll;
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        let next_gen = gen.advance();
+        loop {
+            let current_gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+            let state = Lifecycle::<C>::from_packed(lifecycle).state;
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            test_println!(
+                "-> poll_remove_value; lifecycle={:#x}; gen={:?}; current_gen={:?}; state={:?}; refs={:?};",
+                lifecycle,
+                gen,
+                current_gen,
+                state,
+                refs,
+            );
+
+            // If the value was already removed, or is marked to be removed by
+            // whoever releases it last, there's nothing for us to take.
+            if gen != current_gen || state != State::Present {
+                return Poll::Ready(None);
+            }
+
+            if refs.value != 0 {
+                return Poll::Pending;
+            }
+
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                LifecycleGen(next_gen).pack(lifecycle),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    // Safety: the slot was unreferenced when we advanced its
+                    // generation, so no references to it can exist.
+                    let value = self.item.with_mut(|item| unsafe { (*item).take() });
+                    if C::GENERATION_WRAP != cfg::GenerationWrap::Ignore && next_gen.is_wrapped() {
+                        free.push_wrapped(offset, self);
+                    } else {
+                        free.push(offset, self);
+                    }
+                    return Poll::Ready(value);
+                }
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+}
+
+// These methods are specific to `Column`, whose slots are addressed directly by
+// key rather than allocated from a free list. Since there is no free list to
+// guarantee exclusive access to a vacant slot, a slot's value is only ever
+// written while the slot is write-locked. A vacant slot is `REMOVING` and
+// unreferenced, and may hold a value that has yet to be cleared.
+impl<T, C> Slot<Option<T>, C>
+where
+    C: cfg::Config,
+{
+    /// Stores `value` in this slot at the given generation, returning any
+    /// value left over from a previous generation.
+    ///
+    /// If the slot already holds a value at this generation, or the slot is
+    /// referenced, `value` is returned as an error.
+    pub(crate) fn insert_at(&self, gen: Generation<C>, value: T) -> Result<Option<T>, T> {
+        let locked = RefCount::<C>::from_usize(RefCount::<C>::WRITE_LOCKED)
+            .pack(LifecycleGen(gen).pack(Lifecycle::<C>::REMOVING.pack(0)));
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        loop {
+            let state = Lifecycle::<C>::from_packed(lifecycle).state;
+            let current_gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            test_println!(
+                "-> insert_at {:?}; current_gen={:?}; state={:?}; refs={:?};",
+                gen,
+                current_gen,
+                state,
+                refs
+            );
+
+            let vacant = match state {
+                State::Removing => true,
+                // A value from a previous generation is stale, and may be
+                // replaced.
+                State::Present => current_gen != gen,
+                State::Marked => false,
+            };
+            if !vacant || refs.value != 0 {
+                return Err(value);
+            }
+
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                locked,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(actual) => lifecycle = actual,
+            }
+        }
+
+        let prev = self.item.with_mut(|item| unsafe { (*item).replace(value) });
+        self.unlock(locked, Lifecycle::<C>::PRESENT);
+        Ok(prev)
+    }
+
+    /// Removes the value in this slot at the given generation, returning
+    /// `true` if a value was removed.
+    ///
+    /// If the slot is referenced, it is marked, and the value is removed when
+    /// the last reference is released (see `clear_vacant`).
+    pub(crate) fn remove_at(&self, gen: Generation<C>) -> bool {
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        loop {
+            let state = Lifecycle::<C>::from_packed(lifecycle).state;
+            let current_gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            test_println!(
+                "-> remove_at {:?}; current_gen={:?}; state={:?}; refs={:?};",
+                gen,
+                current_gen,
+                state,
+                refs
+            );
+
+            if gen != current_gen || state != State::Present {
+                return false;
+            }
+
+            let new_lifecycle = if refs.value == 0 {
+                RefCount::<C>::from_usize(RefCount::<C>::WRITE_LOCKED)
+                    .pack(Lifecycle::<C>::REMOVING.pack(lifecycle))
+            } else {
+                Lifecycle::<C>::MARKED.pack(lifecycle)
+            };
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                new_lifecycle,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) if refs.value == 0 => {
+                    let value = self.item.with_mut(|item| unsafe { (*item).take() });
+                    self.unlock(new_lifecycle, Lifecycle::<C>::REMOVING);
+                    drop(value);
+                    return true;
+                }
+                Ok(_) => return true,
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+
+    /// Clears the value left in this slot once its last reference was
+    /// released after it was marked for removal.
+    ///
+    /// If the slot was reused in the meantime, the leftover value was already
+    /// replaced, and this does nothing.
+    pub(crate) fn clear_vacant(&self) {
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        loop {
+            let state = Lifecycle::<C>::from_packed(lifecycle).state;
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            test_println!("-> clear_vacant; state={:?}; refs={:?};", state, refs);
+            if state != State::Removing || refs.value != 0 {
+                return;
+            }
+
+            let locked = RefCount::<C>::from_usize(RefCount::<C>::WRITE_LOCKED).pack(lifecycle);
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                locked,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let value = self.item.with_mut(|item| unsafe { (*item).take() });
+                    self.unlock(locked, Lifecycle::<C>::REMOVING);
+                    drop(value);
+                    return;
+                }
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+
+    fn unlock(&self, locked: usize, state: Lifecycle<C>) {
+        let unlocked = state.pack(RefCount::<C>::from_usize(0).pack(locked));
+        let swapped =
+            self.lifecycle
+                .compare_exchange(locked, unlocked, Ordering::AcqRel, Ordering::Acquire);
+        strict_assert!(
+            swapped.is_ok(),
+            "slot modified while write-locked; actual={:#x}",
+            swapped.unwrap_err()
+        );
+    }
 }
 
 // These impls are specific to `Pool`
 impl<T, C> Slot<T, C>
 where
-    T: Default + Clear,
+    T: Clear,
     C: cfg::Config,
 {
-    pub(in crate::page) fn new(next: usize) -> Self {
-        Self {
-            lifecycle: AtomicUsize::new(Lifecycle::<C>::REMOVING.as_usize()),
-            item: UnsafeCell::new(T::default()),
-            next: UnsafeCell::new(next),
+    pub(crate) fn new(next: usize, value: T) -> Self {
+        let slot = Self {
+            lifecycle: LifecycleWord::new(Lifecycle::<C>::REMOVING.as_usize()),
+            item: UnsafeCell::new(value),
+            next: UnsafeCell::new(0),
             _cfg: PhantomData,
-        }
+        };
+        slot.set_next(next);
+        slot
     }
 
     /// Try to clear this slot's storage
@@ -486,6 +1078,22 @@ where
             cleared
         })
     }
+
+    /// Take this slot's storage, replacing it with `new_value()`.
+    ///
+    /// Like `clear_storage`, this blocks until all references have been
+    /// dropped.
+    pub(super) fn take_storage<F: FreeList<C>>(
+        &self,
+        gen: Generation<C>,
+        offset: usize,
+        free: &F,
+        new_value: impl FnOnce() -> T,
+    ) -> Option<T> {
+        self.release_with(gen, offset, free, |item| {
+            item.map(|item| mem::replace(item, new_value()))
+        })
+    }
 }
 
 impl<T, C: cfg::Config> Slot<T, C> {
@@ -535,6 +1143,47 @@ impl<T, C: cfg::Config> Slot<T, C> {
     }
 }
 
+impl<T, C: cfg::Config> Slot<T, C> {
+    fn release_write(&self) -> bool {
+        let mut lifecycle = self.lifecycle.load(Ordering::Acquire);
+        loop {
+            let state = Lifecycle::<C>::from_packed(lifecycle).state;
+            let gen = LifecycleGen::<C>::from_packed(lifecycle).0;
+            strict_assert_eq!(
+                RefCount::<C>::from_packed(lifecycle).value,
+                RefCount::<C>::WRITE_LOCKED,
+                "released a write guard to a slot that isn't write-locked"
+            );
+
+            // Was the slot marked for removal while it was locked?
+            let dropping = state == State::Marked;
+            let new_lifecycle = if dropping {
+                LifecycleGen(gen).pack(State::Removing as usize)
+            } else {
+                RefCount::<C>::from_usize(0).pack(lifecycle)
+            };
+
+            test_println!(
+                "-> drop write guard: state={:?}; gen={:?}; lifecycle={:#x}; new_lifecycle={:#x}; dropping={:?}",
+                state,
+                gen,
+                lifecycle,
+                new_lifecycle,
+                dropping
+            );
+            match self.lifecycle.compare_exchange(
+                lifecycle,
+                new_lifecycle,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return dropping,
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+}
+
 impl<T, C: cfg::Config> fmt::Debug for Slot<T, C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let lifecycle = self.lifecycle.load(Ordering::Relaxed);
@@ -548,6 +1197,35 @@ impl<T, C: cfg::Config> fmt::Debug for Slot<T, C> {
     }
 }
 
+// === impl LifecycleWord ===
+
+#[allow(clippy::unnecessary_cast)] // `Word` may not be `usize`
+impl LifecycleWord {
+    fn new(lifecycle: usize) -> Self {
+        Self(AtomicWord::new(lifecycle as Word))
+    }
+
+    #[inline(always)]
+    fn load(&self, order: Ordering) -> usize {
+        self.0.load(order) as usize
+    }
+
+    #[inline(always)]
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        strict_assert!(new <= WORD_MAX as usize, "lifecycle out of range");
+        self.0
+            .compare_exchange(current as Word, new as Word, success, failure)
+            .map(|lifecycle| lifecycle as usize)
+            .map_err(|lifecycle| lifecycle as usize)
+    }
+}
+
 // === impl Generation ===
 
 impl<C> fmt::Debug for Generation<C> {
@@ -560,6 +1238,15 @@ impl<C: cfg::Config> Generation<C> {
     fn advance(self) -> Self {
         Self::from_usize((self.value + 1) % Self::BITS)
     }
+
+    /// Returns `true` if this generation was reached by wrapping around,
+    /// rather than by initializing the slot.
+    ///
+    /// This is only meaningful for a generation returned by `advance`.
+    #[inline]
+    fn is_wrapped(self) -> bool {
+        self.value == 0
+    }
 }
 
 impl<C: cfg::Config> PartialEq for Generation<C> {
@@ -605,6 +1292,58 @@ impl<T, C: cfg::Config> Guard<T, C> {
         self.slot().release()
     }
 
+    /// Acquires another guard referencing the same slot, returning `None` if
+    /// the maximum number of concurrent references has been reached.
+    ///
+    /// Unlike `Slot::get`, this succeeds even if the slot has been marked for
+    /// removal: since `self` is still referencing the slot, it cannot have been
+    /// released yet.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `Guard` does not outlive the slab that contains
+    /// the pointed slot. Failure to do so means this pointer may dangle.
+    #[inline]
+    pub(crate) unsafe fn try_clone(&self) -> Option<Self> {
+        let slot = self.slot();
+        let mut lifecycle = slot.lifecycle.load(Ordering::Relaxed);
+        loop {
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            strict_assert!(refs.value > 0, "cloned a guard to an unreferenced slot");
+            let new_refs = refs.incr()?;
+            match slot.lifecycle.compare_exchange(
+                lifecycle,
+                new_refs.pack(lifecycle),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    test_println!("-> Guard::try_clone; {:?}", new_refs);
+                    return Some(Guard { slot: self.slot });
+                }
+                Err(actual) => {
+                    test_println!("-> Guard::try_clone; retrying; lifecycle={:#x};", actual);
+                    lifecycle = actual;
+                }
+            }
+        }
+    }
+
+    /// Returns the number of outstanding references to the slot, including
+    /// this one.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `Guard` does not outlive the slab that contains
+    /// the pointed slot. Failure to do so means this pointer may dangle.
+    #[inline]
+    pub(crate) unsafe fn ref_count(&self) -> usize {
+        let lifecycle = self.slot().lifecycle.load(Ordering::Acquire);
+        RefCount::<C>::from_packed(lifecycle).value
+    }
+
     /// Returns a borrowed reference to the slot.
     ///
     /// ## Safety
@@ -630,6 +1369,36 @@ impl<T, C: cfg::Config> Guard<T, C> {
     }
 }
 
+// === impl WriteGuard ===
+
+impl<T, C: cfg::Config> WriteGuard<T, C> {
+    /// Releases the guard, returning `true` if the slot should be cleared.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `WriteGuard` does not outlive the slab that
+    /// contains the pointed slot. Failure to do so means this pointer may
+    /// dangle.
+    #[inline]
+    pub(crate) unsafe fn release(&self) -> bool {
+        self.slot.as_ref().release_write()
+    }
+
+    /// Returns a mutable pointer to the slot's value.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `WriteGuard` does not outlive the slab that
+    /// contains the pointed slot. Failure to do so means this pointer may
+    /// dangle.
+    #[inline]
+    pub(crate) unsafe fn value_mut(&self) -> *mut T {
+        self.slot.as_ref().item.with_mut(|item| item)
+    }
+}
+
 // === impl Lifecycle ===
 
 impl<C: cfg::Config> Lifecycle<C> {
@@ -685,11 +1454,11 @@ impl<C> fmt::Debug for Lifecycle<C> {
 // === impl RefCount ===
 
 impl<C: cfg::Config> Pack<C> for RefCount<C> {
-    const LEN: usize = cfg::WIDTH - (Lifecycle::<C>::LEN + Generation::<C>::LEN);
+    const LEN: usize = cfg::LIFECYCLE_WIDTH - (Lifecycle::<C>::LEN + Generation::<C>::LEN);
     type Prev = Lifecycle<C>;
 
     fn from_usize(value: usize) -> Self {
-        debug_assert!(value <= Self::BITS);
+        strict_assert!(value <= Self::BITS);
         Self {
             value,
             _cfg: PhantomData,
@@ -704,6 +1473,12 @@ impl<C: cfg::Config> Pack<C> for RefCount<C> {
 impl<C: cfg::Config> RefCount<C> {
     pub(crate) const MAX: usize = Self::BITS - 1;
 
+    /// A ref count indicating that the slot is locked for writing.
+    ///
+    /// Since immutable guards can never increment the ref count past `MAX`,
+    /// they cannot be acquired while a slot is write-locked.
+    const WRITE_LOCKED: usize = Self::BITS;
+
     #[inline]
     fn incr(self) -> Option<Self> {
         if self.value >= Self::MAX {
@@ -810,7 +1585,7 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
     /// contains the pointed slot. Failure to do so means this pointer may
     /// dangle.
     pub(crate) unsafe fn release(&mut self) -> bool {
-        self.release2(0)
+        self.release2(Lifecycle::<C>::PRESENT.pack(0))
     }
 
     /// Downgrades the guard to an immutable guard
@@ -822,15 +1597,78 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
     /// contains the pointed slot. Failure to do so means this pointer may
     /// dangle.
     pub(crate) unsafe fn downgrade(&mut self) -> Guard<T, C> {
-        let _ = self.release2(RefCount::<C>::from_usize(1).pack(0));
+        let _ = self.release2(Lifecycle::<C>::PRESENT.pack(RefCount::<C>::from_usize(1).pack(0)));
         Guard { slot: self.slot }
     }
 
-    unsafe fn release2(&mut self, new_refs: usize) -> bool {
+    /// Downgrades the guard to an immutable guard, and marks the slot for
+    /// removal, so that it is cleared as soon as the last guard referencing it
+    /// is released.
+    ///
+    /// Since the slot is marked, no new guards can be acquired through
+    /// `Slot::get`; they may only be created by cloning the returned guard.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `InitGuard` does not outlive the slab that
+    /// contains the pointed slot. Failure to do so means this pointer may
+    /// dangle.
+    pub(crate) unsafe fn downgrade_marked(&mut self) -> Guard<T, C> {
+        let _ = self.release2(Lifecycle::<C>::MARKED.pack(RefCount::<C>::from_usize(1).pack(0)));
+        Guard { slot: self.slot }
+    }
+
+    /// Releases the guard without storing a value in the slot, leaving the
+    /// slot at its current generation.
+    ///
+    /// The slot is not returned to a free list; the caller is responsible for
+    /// doing so.
+    ///
+    /// ## Safety
+    ///
+    /// This dereferences a raw pointer to the slot. The caller is responsible
+    /// for ensuring that the `InitGuard` does not outlive the slab that
+    /// contains the pointed slot. Failure to do so means this pointer may
+    /// dangle.
+    pub(crate) unsafe fn abandon(&mut self) {
+        test_println!(
+            "InitGuard::abandon; curr_lifecycle={:?}",
+            Lifecycle::<C>::from_packed(self.curr_lifecycle),
+        );
+        if self.released {
+            test_println!("-> already released!");
+            return;
+        }
+        self.released = true;
+        if !C::TRACK_IDLE {
+            // `Slot::init` didn't modify the lifecycle, so there's nothing to
+            // undo.
+            return;
+        }
+
+        // Drop the reservation taken by `Slot::init`.
+        let slot = self.slot.as_ref();
+        let mut lifecycle = slot.lifecycle.load(Ordering::Acquire);
+        loop {
+            let refs = RefCount::<C>::from_packed(lifecycle);
+            match slot.lifecycle.compare_exchange(
+                lifecycle,
+                refs.decr().pack(lifecycle),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return,
+                Err(actual) => lifecycle = actual,
+            }
+        }
+    }
+
+    unsafe fn release2(&mut self, new_state: usize) -> bool {
         test_println!(
-            "InitGuard::release; curr_lifecycle={:?}; downgrading={}",
+            "InitGuard::release; curr_lifecycle={:?}; new_state={:#x}",
             Lifecycle::<C>::from_packed(self.curr_lifecycle),
-            new_refs != 0,
+            new_state,
         );
         if self.released {
             test_println!("-> already released!");
@@ -839,8 +1677,7 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
         self.released = true;
         let mut curr_lifecycle = self.curr_lifecycle;
         let slot = self.slot.as_ref();
-        let new_lifecycle = LifecycleGen::<C>::from_packed(self.curr_lifecycle)
-            .pack(Lifecycle::<C>::PRESENT.pack(new_refs));
+        let new_lifecycle = LifecycleGen::<C>::from_packed(self.curr_lifecycle).pack(new_state);
 
         match slot.lifecycle.compare_exchange(
             curr_lifecycle,
@@ -874,8 +1711,8 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
                 refs,
             );
 
-            debug_assert!(state == State::Marked || thread::panicking(), "state was not MARKED; someone else has removed the slot while we have exclusive access!\nactual={:?}", state);
-            debug_assert!(refs.value == 0 || thread::panicking(), "ref count was not 0; someone else has referenced the slot while we have exclusive access!\nactual={:?}", refs);
+            strict_assert!(state == State::Marked || thread::panicking(), "state was not MARKED; someone else has removed the slot while we have exclusive access!\nactual={:?}", state);
+            strict_assert!(refs == RefCount::from_packed(self.curr_lifecycle) || thread::panicking(), "ref count changed; someone else has referenced the slot while we have exclusive access!\nactual={:?}", refs);
 
             let new_lifecycle = LifecycleGen(self.generation()).pack(State::Removing as usize);
 
@@ -890,7 +1727,7 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
                     return true;
                 }
                 Err(actual) => {
-                    debug_assert!(thread::panicking(), "we should not have to retry this CAS!");
+                    strict_assert!(thread::panicking(), "we should not have to retry this CAS!");
                     test_println!("-> InitGuard::release; retry, actual={:#x}", actual);
                     curr_lifecycle = actual;
                 }
@@ -902,7 +1739,7 @@ impl<T, C: cfg::Config> InitGuard<T, C> {
 // === helpers ===
 
 #[inline(always)]
-fn exponential_backoff(exp: &mut usize) {
+pub(crate) fn exponential_backoff(exp: &mut usize) {
     /// Maximum exponent we can back off to.
     const MAX_EXPONENT: usize = 8;
 
@@ -15,6 +15,14 @@ impl<C: cfg::Config> TransferStack<C> {
         }
     }
 
+    /// Takes the entire stack, returning the index of its head.
+    ///
+    /// This is a single atomic swap regardless of how many slots are on the
+    /// stack: the slots are already linked through their `next` fields, so
+    /// the returned head can become the owner's local free list as-is. The
+    /// owning thread then pops one slot per insert, just as it would from a
+    /// list it built itself, so even a very large burst of remote frees is
+    /// never walked all at once.
     pub(super) fn pop_all(&self) -> Option<usize> {
         let val = self.head.swap(super::Addr::<C>::NULL, Ordering::Acquire);
         test_println!("-> pop {:#x}", val);
@@ -25,6 +33,11 @@ impl<C: cfg::Config> TransferStack<C> {
         }
     }
 
+    /// Returns the index of the head of the stack, without taking it.
+    pub(super) fn head(&self) -> usize {
+        self.head.load(Ordering::Acquire)
+    }
+
     fn push(&self, new_head: usize, before: impl Fn(usize)) {
         // We loop to win the race to set the new head. The `next` variable
         // is the next slot on the stack which needs to be pointed to by the
@@ -0,0 +1,89 @@
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// A coarse timestamp, in milliseconds since a process-wide epoch.
+///
+/// Ticks are stored in a `usize` so that they can be updated atomically on
+/// every target, which means they wrap after about 49 days on 32-bit targets.
+/// Ages are therefore computed with wrapping arithmetic, and are only
+/// meaningful for durations shorter than that.
+///
+/// A tick of `NEVER` indicates that a slot has never been used.
+pub(crate) type Tick = usize;
+
+pub(crate) const NEVER: Tick = 0;
+
+/// Per-slot timestamps for a page.
+///
+/// These are stored alongside a page's slots, rather than in them, so that
+/// configurations which don't track timestamps don't pay for them.
+pub(crate) struct Timestamps(Box<[AtomicUsize]>);
+
+lazy_static::lazy_static! {
+    static ref EPOCH: Instant = Instant::now();
+}
+
+/// Returns the current tick.
+pub(crate) fn now() -> Tick {
+    let tick = to_ticks(EPOCH.elapsed()).wrapping_add(1);
+    // Don't let a wrapped clock claim that a slot was never used.
+    if tick == NEVER {
+        1
+    } else {
+        tick
+    }
+}
+
+/// Converts a duration to a number of ticks, saturating.
+pub(crate) fn to_ticks(duration: Duration) -> Tick {
+    // MSRV: the `MAX` associated constants require Rust 1.43.
+    const MAX: Tick = !0;
+    let millis = duration.as_millis();
+    if millis > MAX as u128 {
+        MAX
+    } else {
+        millis as Tick
+    }
+}
+
+/// Returns the instant at which `tick` was recorded, or `None` if it is
+/// `NEVER`.
+pub(crate) fn to_instant(tick: Tick) -> Option<Instant> {
+    if tick == NEVER {
+        return None;
+    }
+    Some(*EPOCH + Duration::from_millis((tick - 1) as u64))
+}
+
+/// Returns `true` if `tick` was recorded more than `max_age` ticks before
+/// `now`.
+pub(crate) fn is_older(tick: Tick, now: Tick, max_age: Tick) -> bool {
+    tick != NEVER && now.wrapping_sub(tick) > max_age
+}
+
+impl Timestamps {
+    pub(crate) fn new(len: usize) -> Self {
+        Self((0..len).map(|_| AtomicUsize::new(NEVER)).collect())
+    }
+
+    #[inline]
+    pub(crate) fn touch(&self, idx: usize) {
+        self.0[idx].store(now(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, idx: usize, tick: Tick) {
+        self.0[idx].store(tick, Ordering::Relaxed);
+    }
+
+    /// Marks the slot at `idx` as never used.
+    #[inline]
+    pub(crate) fn clear(&self, idx: usize) {
+        self.0[idx].store(NEVER, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, idx: usize) -> Tick {
+        self.0[idx].load(Ordering::Relaxed)
+    }
+}
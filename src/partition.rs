@@ -0,0 +1,342 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    reserved::{self, Field},
+    Entry, Slab,
+};
+use std::{fmt, ops::Deref};
+
+/// A slab whose values are divided into [`Partition`]s, which share its
+/// storage.
+///
+/// Creating a [`Slab`] for each of many tenants multiplies the cost of its
+/// shards by the number of tenants. A `PartitionedSlab` instead stores every
+/// tenant's values in the same shards, and hands out a [`Partition`] view for
+/// each tenant. A partition's keys encode its tag, so a key can only be used
+/// to access values through the partition that inserted it, and iterating
+/// over or clearing a partition only affects that partition's values.
+///
+/// There are [`PARTITIONS`] partitions, whose tags are stored in
+/// [`PARTITION_BITS`] bits of each key. Like the bits of a [`DynSlab`]'s size
+/// class, these are reserved from the slab, just below any bits reserved by
+/// the [`Config`].
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::PartitionedSlab;
+/// let slab = PartitionedSlab::new();
+/// let alice = slab.partition(1);
+/// let bob = slab.partition(2);
+///
+/// let key = alice.insert("hello").unwrap();
+/// bob.insert("world").unwrap();
+/// assert_eq!(alice.get(key).unwrap(), "hello");
+/// assert!(bob.get(key).is_none());
+///
+/// assert_eq!(bob.clear(), 1);
+/// assert_eq!(alice.keys(), vec![key]);
+/// ```
+///
+/// [`PARTITIONS`]: PartitionedSlab::PARTITIONS
+/// [`PARTITION_BITS`]: PartitionedSlab::PARTITION_BITS
+/// [`DynSlab`]: crate::DynSlab
+/// [`Config`]: crate::Config
+pub struct PartitionedSlab<T, C: cfg::Config = DefaultConfig> {
+    slab: Slab<Tagged<T>, Partitioned<C>>,
+}
+
+/// A view of the values in one partition of a [`PartitionedSlab`].
+///
+/// This is returned by [`PartitionedSlab::partition`].
+pub struct Partition<'a, T, C: cfg::Config = DefaultConfig> {
+    slab: &'a PartitionedSlab<T, C>,
+    tag: usize,
+}
+
+/// A guard that allows access to a value in a [`Partition`].
+///
+/// Like an [`Entry`], while the guard exists, the value will not be dropped
+/// if it is removed from the slab.
+pub struct PartitionEntry<'a, T, C: cfg::Config = DefaultConfig> {
+    inner: Entry<'a, Tagged<T>, Partitioned<C>>,
+    key: usize,
+}
+
+/// A value stored along with the tag of the partition it was inserted into.
+struct Tagged<T> {
+    partition: usize,
+    value: T,
+}
+
+/// The configuration of a partitioned slab's storage.
+///
+/// The partition of a value is stored in `PARTITION_BITS` bits of its key,
+/// which are reserved from the slab.
+type Partitioned<C> = reserved::Reserve<C, Tag>;
+
+/// The tag of the partition a value was inserted into.
+struct Tag;
+
+impl Field for Tag {
+    const BITS: usize = 8;
+}
+
+impl<T> PartitionedSlab<T> {
+    /// Returns a new `PartitionedSlab` with the default configuration
+    /// parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `PartitionedSlab` with the provided configuration
+    /// parameters.
+    ///
+    /// # Panics
+    ///
+    /// If the configuration is invalid, once `PARTITION_BITS` bits have
+    /// been reserved from it.
+    pub fn new_with_config<C: cfg::Config>() -> PartitionedSlab<T, C> {
+        C::validate();
+        PartitionedSlab {
+            slab: Slab::new_with_config(),
+        }
+    }
+}
+
+impl<T, C: cfg::Config> PartitionedSlab<T, C> {
+    /// The number of bits in each key which hold its partition's tag.
+    pub const PARTITION_BITS: usize = Tag::BITS;
+
+    /// The number of partitions. Each partition's tag is less than this.
+    pub const PARTITIONS: usize = 1 << Tag::BITS;
+
+    /// Returns a view of the values in the partition with the given tag.
+    ///
+    /// # Panics
+    ///
+    /// If `tag` is not less than [`PARTITIONS`].
+    ///
+    /// [`PARTITIONS`]: PartitionedSlab::PARTITIONS
+    pub fn partition(&self, tag: usize) -> Partition<'_, T, C> {
+        assert!(
+            tag < Self::PARTITIONS,
+            "partition tag {} must be less than {}",
+            tag,
+            Self::PARTITIONS
+        );
+        Partition { slab: self, tag }
+    }
+
+    /// Returns the tag of the partition that `key` was issued by.
+    ///
+    /// This doesn't check that the key refers to a value.
+    pub fn partition_of(&self, key: usize) -> usize {
+        reserved::get::<C, Tag>(key)
+    }
+}
+
+impl<T> Default for PartitionedSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C: cfg::Config> fmt::Debug for PartitionedSlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartitionedSlab")
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl Partition ===
+
+impl<'a, T, C: cfg::Config> Partition<'a, T, C> {
+    /// Returns this partition's tag.
+    pub fn tag(&self) -> usize {
+        self.tag
+    }
+
+    /// Inserts a value into this partition, returning its key.
+    ///
+    /// As with [`Slab::insert`], this returns `None` if the current thread's
+    /// shard is full, or its thread-local storage has already been
+    /// destroyed.
+    pub fn insert(&self, value: T) -> Option<usize> {
+        let key = self.slab.slab.insert(Tagged {
+            partition: self.tag,
+            value,
+        })?;
+        Some(reserved::set::<C, Tag>(key, self.tag))
+    }
+
+    /// Returns a reference to the value associated with the given key.
+    ///
+    /// If this partition does not contain a value for the key, `None` is
+    /// returned instead, even if another partition does.
+    pub fn get(&self, key: usize) -> Option<PartitionEntry<'a, T, C>> {
+        if reserved::get::<C, Tag>(key) != self.tag {
+            return None;
+        }
+        let inner = self.slab.slab.get(key)?;
+        if inner.partition != self.tag {
+            return None;
+        }
+        Some(PartitionEntry { inner, key })
+    }
+
+    /// Returns `true` if this partition contains a value for the given key.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes the value associated with the given key from this partition,
+    /// returning `true` if a value was removed.
+    ///
+    /// As with [`Slab::remove`], if the value is being accessed, it is
+    /// dropped when the last guard referencing it is dropped.
+    pub fn remove(&self, key: usize) -> bool {
+        // The key's generation ensures that only the value that was checked
+        // may be removed, even if its slot is concurrently reused.
+        self.contains(key) && self.slab.slab.remove(key)
+    }
+
+    /// Removes the value associated with the given key from this partition,
+    /// returning it.
+    ///
+    /// As with [`Slab::take`], if the value is being accessed, this blocks
+    /// until the last guard referencing it is dropped.
+    pub fn take(&self, key: usize) -> Option<T> {
+        if !self.contains(key) {
+            return None;
+        }
+        self.slab.slab.take(key).map(|tagged| tagged.value)
+    }
+
+    /// Returns the keys of the values in this partition.
+    ///
+    /// This may be called concurrently with other operations, so it may not
+    /// reflect values which are being inserted or removed.
+    pub fn keys(&self) -> Vec<usize> {
+        let mut keys = Vec::new();
+        for shard in self.slab.slab.shards.iter() {
+            for key in shard.keys() {
+                let in_partition = match Entry::new(shard, key) {
+                    Some(entry) => entry.partition == self.tag,
+                    None => false,
+                };
+                if in_partition {
+                    keys.push(reserved::set::<C, Tag>(key, self.tag));
+                }
+            }
+        }
+        keys
+    }
+
+    /// Removes every value in this partition, returning the number of values
+    /// removed.
+    ///
+    /// Values in other partitions are not affected. As with
+    /// [`Slab::remove`], values which are being accessed are dropped when the
+    /// last guard referencing them is dropped.
+    pub fn clear(&self) -> usize {
+        self.keys()
+            .into_iter()
+            .filter(|&key| self.slab.slab.remove(key))
+            .count()
+    }
+}
+
+impl<T, C: cfg::Config> fmt::Debug for Partition<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partition").field("tag", &self.tag).finish()
+    }
+}
+
+// === impl PartitionEntry ===
+
+impl<T, C: cfg::Config> PartitionEntry<'_, T, C> {
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<T, C: cfg::Config> Deref for PartitionEntry<'_, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        let tagged: &Tagged<T> = &self.inner;
+        &tagged.value
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for PartitionEntry<'_, T, C>
+where
+    T: serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C> fmt::Debug for PartitionEntry<'_, T, C>
+where
+    T: fmt::Debug,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, C> PartialEq<T> for PartitionEntry<'_, T, C>
+where
+    T: PartialEq<T>,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::{PartitionedSlab, Tag};
+    use crate::{reserved, DefaultConfig};
+
+    #[test]
+    fn partitions_are_isolated() {
+        let slab = PartitionedSlab::new();
+        let a = slab.partition(0);
+        let b = slab.partition(PartitionedSlab::<u32>::PARTITIONS - 1);
+
+        let key_a = a.insert(1).unwrap();
+        let key_b = b.insert(2).unwrap();
+        assert_eq!(slab.partition_of(key_a), 0);
+        assert_eq!(slab.partition_of(key_b), b.tag());
+
+        // A key from one partition can't be used through another, even if
+        // its tag is rewritten.
+        let forged = reserved::set::<DefaultConfig, Tag>(key_a, b.tag());
+        assert!(b.get(forged).is_none());
+        assert!(!b.remove(forged));
+        assert!(!b.remove(key_a));
+        assert_eq!(b.take(key_a), None);
+        assert_eq!(a.get(key_a).unwrap(), 1);
+
+        assert_eq!(a.take(key_a), Some(1));
+        assert!(a.keys().is_empty());
+        assert_eq!(b.keys(), vec![key_b]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn tag_out_of_range() {
+        let slab = PartitionedSlab::<()>::new();
+        slab.partition(PartitionedSlab::<()>::PARTITIONS);
+    }
+}
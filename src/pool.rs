@@ -8,12 +8,24 @@
 use crate::{
     cfg::{self, CfgPrivate, DefaultConfig},
     clear::Clear,
-    page, shard,
+    page::{self, timestamps},
+    shard,
     tid::Tid,
-    Pack, Shard,
+    Pack, PoolMetrics, Shard,
 };
 
-use std::{fmt, marker::PhantomData, sync::Arc};
+use std::{
+    fmt,
+    marker::PhantomData,
+    ptr,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+#[cfg(feature = "async")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 /// A lock-free concurrent object pool.
 ///
@@ -24,12 +36,15 @@ use std::{fmt, marker::PhantomData, sync::Arc};
 /// fragmentation and avoiding additional allocations.
 ///
 /// This type implements a lock-free concurrent pool, indexed by `usize`s. The items stored in this
-/// type need to implement [`Clear`] and `Default`.
+/// type need to implement [`Clear`]. They are created using `Default`, unless the pool is
+/// constructed [with a factory function][factory].
 ///
 /// The `Pool` type shares similar semantics to [`Slab`] when it comes to sharing across threads
-/// and storing mutable shared data. The biggest difference is there are no [`Slab::insert`] and
-/// [`Slab::take`] analouges for the `Pool` type. Instead new items are added to the pool by using
-/// the [`Pool::create`] method, and marked for clearing by the [`Pool::clear`] method.
+/// and storing mutable shared data. The biggest difference is there is no [`Slab::insert`]
+/// analouge for the `Pool` type. Instead new items are added to the pool by using
+/// the [`Pool::create`] method, and marked for clearing by the [`Pool::clear`] method. An
+/// item which should not be reused can be removed from the pool entirely by [`Pool::take`],
+/// similarly to [`Slab::take`].
 ///
 /// # Examples
 ///
@@ -77,13 +92,15 @@ use std::{fmt, marker::PhantomData, sync::Arc};
 /// [`Slab::take`]: crate::Slab::take
 /// [`Slab::insert`]: crate::Slab::insert
 /// [`Pool::create`]: Pool::create
+/// [`Pool::take`]: Pool::take
+/// [factory]: Pool::with_factory
 /// [`Pool::clear`]: Pool::clear
 /// [config-doc]: crate#configuration
 /// [`Clear`]: crate::Clear
 /// [`Slab`]: crate::Slab
 pub struct Pool<T, C = DefaultConfig>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     shards: shard::Array<T, C>,
@@ -97,7 +114,7 @@ where
 /// removal will be deferred until all guards are dropped.
 pub struct Ref<'a, T, C = DefaultConfig>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     inner: page::slot::Guard<T, C>,
@@ -114,7 +131,7 @@ where
 /// mutably.
 pub struct RefMut<'a, T, C = DefaultConfig>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     inner: page::slot::InitGuard<T, C>,
@@ -206,7 +223,7 @@ where
 /// [`Ref`]: crate::pool::Ref
 pub struct OwnedRef<T, C = DefaultConfig>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     inner: page::slot::Guard<T, C>,
@@ -316,7 +333,12 @@ where
 /// function_requiring_static(&my_struct);
 /// ```
 ///
-/// `OwnedRefMut`s may be sent between threads:
+/// `OwnedRefMut`s may be sent between threads, as long as `T` is both `Send`
+/// and `Sync`. This means that a value may be initialized across `.await`
+/// points in a task running on a multithreaded executor. The guard may be
+/// dropped on a different thread from the one that created it; if the value
+/// was [cleared] while the guard existed, the slot is then released to the
+/// shard's remote free list, just as if it had been cleared by that thread.
 ///
 /// ```
 /// # use sharded_slab::Pool;
@@ -368,9 +390,10 @@ where
 /// [`RefMut`]: crate::pool::RefMut
 /// [`OwnedRefMut`]: crate::pool::OwnedRefMut
 /// [downgraded]: crate::pool::OwnedRefMut::downgrade
+/// [cleared]: crate::Pool::clear
 pub struct OwnedRefMut<T, C = DefaultConfig>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     inner: page::slot::InitGuard<T, C>,
@@ -378,13 +401,76 @@ where
     key: usize,
 }
 
+/// A guard that allows access to part of an object in a pool.
+///
+/// This is returned by [`Ref::map`].
+pub struct MappedRef<'a, T, U: ?Sized, C = DefaultConfig>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    guard: Ref<'a, T, C>,
+    value: ptr::NonNull<U>,
+}
+
+/// A guard that allows exclusive mutable access to part of an object in a
+/// pool.
+///
+/// This is returned by [`RefMut::map`].
+pub struct MappedRefMut<'a, T, U: ?Sized, C = DefaultConfig>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    guard: RefMut<'a, T, C>,
+    value: ptr::NonNull<U>,
+    _value: PhantomData<&'a mut U>,
+}
+
+/// A future which creates a new object in a pool once there is room for it.
+///
+/// This is returned by [`Pool::create_async`].
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[must_use = "futures do nothing unless polled"]
+pub struct CreateAsync<'a, T, C = DefaultConfig>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    pool: &'a Pool<T, C>,
+}
+
+/// Clears a newly created object if it is dropped before being taken.
+///
+/// This ensures that an object whose initializer panicked is not left
+/// partially initialized in the pool, where nothing can reach it.
+struct ClearOnPanic<'a, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    guard: Option<RefMut<'a, T, C>>,
+}
+
 impl<T> Pool<T>
 where
     T: Clear + Default,
 {
-    /// Returns a new `Pool` with the default configuration parameters.
-    pub fn new() -> Self {
-        Self::new_with_config()
+    const_fn! {
+        /// Returns a new `Pool` with the default configuration parameters.
+        ///
+        /// As with [`Slab::new`], the pool's storage isn't allocated until it
+        /// is first used, so on Rust 1.63 and later, this is a `const fn`, and
+        /// may be used to initialize a `static`.
+        ///
+        /// [`Slab::new`]: crate::Slab::new
+        pub fn new() -> Self {
+            Pool {
+                shards: shard::Array::new(),
+                _cfg: PhantomData,
+            }
+        }
     }
 
     /// Returns a new `Pool` with the provided configuration parameters.
@@ -397,9 +483,69 @@ where
     }
 }
 
+impl<T> Pool<T>
+where
+    T: Clear,
+{
+    /// Returns a new `Pool` with the default configuration parameters, which
+    /// creates objects using the provided `factory` function.
+    ///
+    /// Each of the pool's slots holds an object even while it is vacant, so
+    /// that its storage may be reused. `factory` is called to create the
+    /// object for a slot the first time that slot is used, rather than
+    /// `T::default()`. This allows pooling objects which don't implement
+    /// `Default`, or which should be constructed with arguments, such as a
+    /// pre-sized buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{Clear, Pool};
+    ///
+    /// // A buffer type with no sensible default size.
+    /// struct Buffer {
+    ///     bytes: Vec<u8>,
+    /// }
+    ///
+    /// impl Buffer {
+    ///     fn new(size: usize) -> Self {
+    ///         Self { bytes: Vec::with_capacity(size) }
+    ///     }
+    /// }
+    ///
+    /// impl Clear for Buffer {
+    ///     fn clear(&mut self) {
+    ///         self.bytes.clear();
+    ///     }
+    /// }
+    ///
+    /// let pool = Pool::with_factory(|| Buffer::new(4096));
+    ///
+    /// let buf = pool.create().unwrap();
+    /// assert!(buf.bytes.capacity() >= 4096);
+    /// ```
+    pub fn with_factory(factory: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        Self::with_factory_and_config(factory)
+    }
+
+    /// Returns a new `Pool` with the provided configuration parameters, which
+    /// creates objects using the provided `factory` function.
+    ///
+    /// See [`Pool::with_factory`] for details.
+    pub fn with_factory_and_config<C: cfg::Config>(
+        factory: impl Fn() -> T + Send + Sync + 'static,
+    ) -> Pool<T, C> {
+        C::validate();
+        Pool {
+            shards: shard::Array::with_factory(shard::Factory::Custom(Arc::new(factory))),
+            _cfg: PhantomData,
+        }
+    }
+}
+
 impl<T, C> Pool<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     /// The number of bits in each index which are used by the pool.
@@ -423,6 +569,10 @@ where
     /// If this function returns `None`, then the shard for the current thread is full and no items
     /// can be added until some are removed, or the maximum number of shards has been reached.
     ///
+    /// `None` is also returned if the current thread is exiting, and its thread-local storage has
+    /// already been destroyed. This may happen when an object is created by the destructor of
+    /// another thread-local.
+    ///
     /// # Examples
     /// ```rust
     /// # use sharded_slab::Pool;
@@ -449,7 +599,7 @@ where
     ///
     /// [`RefMut`]: crate::pool::RefMut
     pub fn create(&self) -> Option<RefMut<'_, T, C>> {
-        let (tid, shard) = self.shards.current();
+        let (tid, shard) = self.shards.current()?;
         test_println!("pool: create {:?}", tid);
         let (key, inner) = shard.init_with(|idx, slot| {
             let guard = slot.init()?;
@@ -458,7 +608,7 @@ where
         })?;
         Some(RefMut {
             inner,
-            key: tid.pack(key),
+            key: shard.tag(tid.pack(key)),
             shard,
         })
     }
@@ -617,12 +767,12 @@ where
     /// [`OwnedRefMut`]: crate::pool::OwnedRefMut
     /// [downgraded]: crate::pool::OwnedRefMut::downgrade
     pub fn create_owned(self: Arc<Self>) -> Option<OwnedRefMut<T, C>> {
-        let (tid, shard) = self.shards.current();
+        let (tid, shard) = self.shards.current()?;
         test_println!("pool: create_owned {:?}", tid);
         let (inner, key) = shard.init_with(|idx, slot| {
             let inner = slot.init()?;
             let gen = inner.generation();
-            Some((inner, tid.pack(gen.pack(idx))))
+            Some((inner, shard.tag(tid.pack(gen.pack(idx)))))
         })?;
         Some(OwnedRefMut {
             inner,
@@ -631,11 +781,101 @@ where
         })
     }
 
+    /// Creates a new object in the pool, waiting for a slot to be cleared if
+    /// the pool is full.
+    ///
+    /// This returns a future which resolves to a [`RefMut`] guard, like
+    /// [`create`]. If [`create`] would return `None`, the future instead
+    /// waits until a value anywhere in the pool is cleared, and then tries
+    /// again. This applies backpressure to tasks creating objects in a pool
+    /// with a bounded capacity, rather than failing and leaving them to retry
+    /// in a loop.
+    ///
+    /// The future may be woken by a slot being cleared on another thread's
+    /// shard, in which case it will try again, and wait again if the current
+    /// thread's shard is still full. If the current thread can never have a
+    /// shard (because `MAX_THREADS` threads already have one), the future
+    /// will wait until one is freed up, which may be forever.
+    ///
+    /// If the pool is [closed], the future resolves to `None` rather than
+    /// waiting for room that will never become available.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Pool;
+    /// async fn handle(pool: &Pool<String>) {
+    ///     if let Some(mut item) = pool.create_async().await {
+    ///         item.push_str("hello world");
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`create`]: Pool::create
+    /// [closed]: Pool::close
+    #[cfg(feature = "async")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn create_async(&self) -> CreateAsync<'_, T, C> {
+        CreateAsync { pool: self }
+    }
+
+    /// Creates up to `n` new objects in the pool, returning a [`RefMut`]
+    /// guard for each.
+    ///
+    /// This is equivalent to calling [`create`] `n` times, but the objects are
+    /// all created in a single pass over the current thread's shard, which is
+    /// cheaper when many objects are needed at once.
+    ///
+    /// If the shard for the current thread fills up, fewer than `n` guards
+    /// are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    ///
+    /// let mut bufs = pool.create_many(8);
+    /// assert_eq!(bufs.len(), 8);
+    /// for (i, buf) in bufs.iter_mut().enumerate() {
+    ///     buf.push_str(&i.to_string());
+    /// }
+    ///
+    /// let keys = bufs.iter().map(|buf| buf.key()).collect::<Vec<_>>();
+    /// drop(bufs);
+    /// assert_eq!(pool.get(keys[3]).unwrap(), String::from("3"));
+    /// ```
+    ///
+    /// [`create`]: Pool::create
+    /// [`RefMut`]: crate::pool::RefMut
+    pub fn create_many(&self, n: usize) -> Vec<RefMut<'_, T, C>> {
+        let mut refs = Vec::with_capacity(n);
+        let (tid, shard) = match self.shards.current() {
+            Some(current) => current,
+            None => return refs,
+        };
+        test_println!("pool: create_many({}) {:?}", n, tid);
+        shard.init_many(
+            n,
+            |idx, slot| {
+                let inner = slot.init()?;
+                let key = shard.tag(tid.pack(inner.generation().pack(idx)));
+                Some(RefMut { inner, key, shard })
+            },
+            &mut refs,
+        );
+        refs
+    }
+
     /// Creates a new object in the pool with the provided initializer,
     /// returning a key that may be used to access the new object.
     ///
     /// If this function returns `None`, then the shard for the current thread is full and no items
     /// can be added until some are removed, or the maximum number of shards has been reached.
+    /// `None` is also returned if the current thread is exiting, and its thread-local storage has
+    /// already been destroyed.
+    ///
+    /// If `init` panics, the new object is cleared and returned to the pool.
     ///
     /// # Examples
     /// ```rust
@@ -653,9 +893,15 @@ where
     /// ```
     pub fn create_with(&self, init: impl FnOnce(&mut T)) -> Option<usize> {
         test_println!("pool: create_with");
-        let mut guard = self.create()?;
-        init(&mut guard);
-        Some(guard.key())
+        let mut created = ClearOnPanic {
+            guard: Some(self.create()?),
+        };
+        let guard = created.guard.as_mut()?;
+        init(guard);
+        let key = guard.key();
+        // `init` didn't panic, so release the object as usual.
+        drop(created.guard.take());
+        Some(key)
     }
 
     /// Return a borrowed reference to the value associated with the given key.
@@ -681,6 +927,34 @@ where
         Some(Ref { inner, shard, key })
     }
 
+    /// Calls `f` with a reference to the value associated with the given
+    /// key, returning its result.
+    ///
+    /// This is like [`get`], but the value is only borrowed for the duration
+    /// of the call, so the guard type doesn't have to be named, and can't be
+    /// held for longer than intended.
+    ///
+    /// If the pool does not contain a value for the given key, `f` is not
+    /// called, and `None` is returned instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    /// let key = pool.create_with(|item| item.push_str("hello world")).unwrap();
+    ///
+    /// assert_eq!(pool.with(key, |value| value.len()), Some(11));
+    /// assert!(pool.clear(key));
+    /// assert_eq!(pool.with(key, |value| value.len()), None);
+    /// ```
+    ///
+    /// [`get`]: Pool::get
+    pub fn with<R>(&self, key: usize, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let value = self.get(key)?;
+        Some(f(&value))
+    }
+
     /// Return an owned reference to the value associated with the given key.
     ///
     /// If the pool does not contain a value for the given key, `None` is
@@ -820,28 +1094,259 @@ where
     pub fn clear(&self, key: usize) -> bool {
         let tid = C::unpack_tid(key);
 
-        let shard = self.shards.get(tid.as_usize());
-        if tid.is_current() {
-            shard
-                .map(|shard| shard.mark_clear_local(key))
-                .unwrap_or(false)
+        let shard = match self.shards.get(tid.as_usize()) {
+            Some(shard) => shard,
+            None => return false,
+        };
+        let cleared = if tid.is_local() {
+            shard.mark_clear_local(key)
+        } else {
+            shard.mark_clear_remote(key)
+        };
+        if cleared {
+            shard.counters.cleared.fetch_add(1, Ordering::Relaxed);
+        }
+        cleared
+    }
+
+    /// Returns `true` if the value using the storage associated with the given
+    /// key has been cleared.
+    ///
+    /// If there are outstanding references to a value when [`clear`] is
+    /// called, the value is not cleared until the last reference is dropped.
+    /// This method can be used to determine whether such a clear is still
+    /// pending. If it returns `true`, no references to the value remain, and
+    /// it either has been cleared, or is currently being cleared by the thread
+    /// which dropped the last reference.
+    ///
+    /// If the key is still valid and [`clear`] has not been called, this
+    /// returns `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    /// let key = pool.create_with(|item| item.push_str("hello world")).unwrap();
+    /// assert!(!pool.is_cleared(key));
+    ///
+    /// let guard = pool.get(key).unwrap();
+    /// assert!(pool.clear(key));
+    ///
+    /// // The value can't be cleared until the guard is dropped...
+    /// assert!(!pool.is_cleared(key));
+    ///
+    /// drop(guard);
+    /// assert!(pool.is_cleared(key));
+    /// ```
+    ///
+    /// [`clear`]: Pool::clear
+    pub fn is_cleared(&self, key: usize) -> bool {
+        let tid = C::unpack_tid(key);
+        let gen = C::unpack_gen(key);
+        self.shards
+            .get(tid.as_usize())
+            .and_then(|shard| shard.with_slot(key, |slot| Some(slot.generation() != gen)))
+            .unwrap_or(true)
+    }
+
+    /// Removes the value using the storage associated with the given key from
+    /// the pool, returning it.
+    ///
+    /// Unlike [`clear`], which clears the value in place so that its storage
+    /// may be reused, this moves the value out of the pool, replacing it with
+    /// a newly created one. This is useful when a pooled object should not be
+    /// reused, such as when it has been left in an inconsistent state, or has
+    /// grown unusually large.
+    ///
+    /// If the pool does not contain a value for that key, `None` is returned
+    /// instead.
+    ///
+    /// If the value is currently being accessed by another thread, this method
+    /// will block the current thread until the value is no longer accessed.
+    /// As with [`Slab::take`], this spins until the outstanding references are
+    /// released, so it should only be called when those references are
+    /// expected to be dropped soon.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    ///
+    /// let key = pool.create_with(|item| item.push_str("hello world")).unwrap();
+    ///
+    /// assert_eq!(pool.take(key), Some(String::from("hello world")));
+    /// assert!(pool.get(key).is_none());
+    /// assert_eq!(pool.take(key), None);
+    ///
+    /// // The slot is reused, but the taken string's allocation is not.
+    /// let key = pool.create_with(|_| {}).unwrap();
+    /// assert_eq!(pool.get(key).unwrap().capacity(), 0);
+    /// ```
+    ///
+    /// [`clear`]: Pool::clear
+    /// [`Slab::take`]: crate::Slab::take
+    pub fn take(&self, key: usize) -> Option<T> {
+        let tid = C::unpack_tid(key);
+
+        test_println!("pool: take {:?}", tid);
+        let shard = self.shards.get(tid.as_usize())?;
+        let taken = if tid.is_local() {
+            shard.take_storage_local(key)
         } else {
-            shard
-                .map(|shard| shard.mark_clear_remote(key))
-                .unwrap_or(false)
+            shard.take_storage_remote(key)
+        }?;
+        shard.counters.taken.fetch_add(1, Ordering::Relaxed);
+        Some(taken)
+    }
+
+    /// Closes the pool, so that no more objects can be created in it.
+    ///
+    /// Once this returns, [`create`] and the other methods that create
+    /// objects fail, and any future returned by [`create_async`] which is
+    /// waiting for room in the pool is woken and resolves to `None`. Objects
+    /// already in the pool may still be accessed and cleared as usual. A pool
+    /// can't be reopened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    /// let key = pool.create_with(|s| s.push_str("hello")).unwrap();
+    ///
+    /// pool.close();
+    /// assert!(pool.create().is_none());
+    /// assert_eq!(pool.get(key).unwrap(), String::from("hello"));
+    /// ```
+    ///
+    /// [`create`]: Pool::create
+    /// [`create_async`]: Pool::create_async
+    pub fn close(&self) {
+        self.shards.close();
+    }
+
+    /// Returns `true` if the pool has been [closed].
+    ///
+    /// [closed]: Pool::close
+    pub fn is_closed(&self) -> bool {
+        self.shards.is_closed()
+    }
+
+    /// Returns counts of how the pool's objects have been used, totalled
+    /// across all of its shards.
+    ///
+    /// This can be used to determine whether a pool is paying for itself: if
+    /// most objects are newly constructed, rather than reusing the storage of
+    /// a previously cleared object, the pool isn't saving many allocations.
+    ///
+    /// The counts are read without synchronizing with other threads, so they
+    /// may not reflect operations which are in progress.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Pool;
+    /// let pool: Pool<String> = Pool::new();
+    ///
+    /// let key = pool.create_with(|s| s.push_str("hello")).unwrap();
+    /// pool.clear(key);
+    /// pool.create_with(|s| s.push_str("world")).unwrap();
+    ///
+    /// let metrics = pool.metrics();
+    /// assert_eq!(metrics.created(), 2);
+    /// assert_eq!(metrics.constructed(), 1);
+    /// assert_eq!(metrics.reused(), 1);
+    /// assert_eq!(metrics.cleared(), 1);
+    /// ```
+    pub fn metrics(&self) -> PoolMetrics {
+        let mut metrics = self
+            .shards
+            .iter()
+            .fold(PoolMetrics::default(), |mut metrics, shard| {
+                let counters = &shard.counters;
+                metrics.created += counters.created.load(Ordering::Relaxed);
+                metrics.constructed += counters.constructed.load(Ordering::Relaxed);
+                metrics.cleared += counters.cleared.load(Ordering::Relaxed);
+                metrics.taken += counters.taken.load(Ordering::Relaxed);
+                metrics.pages_skipped += counters.pages_skipped.load(Ordering::Relaxed);
+                metrics.generation_wraps += shard
+                    .pages()
+                    .map(|page| page.generation_wraps())
+                    .sum::<usize>();
+                metrics
+            });
+        metrics.insert_failures = self.shards.insert_failures();
+        metrics
+    }
+
+    /// Drops pooled objects which have not been used for longer than
+    /// `max_idle`, returning the number of objects dropped.
+    ///
+    /// An object is considered used when it is [created] (reusing its storage)
+    /// and when it is [cleared]. Objects which are currently in use are never
+    /// dropped. An idle object is replaced with a newly created one, so that
+    /// any storage it retained is released. This allows a pool that was sized
+    /// for peak load to shrink back down during quieter periods.
+    ///
+    /// This requires [`Config::TRACK_IDLE`] to be enabled. With the default
+    /// configuration, the pool doesn't record when objects were last used, so
+    /// this method does nothing and returns 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::{Config, Pool};
+    /// use std::{thread, time::Duration};
+    ///
+    /// struct TrackIdle;
+    /// impl Config for TrackIdle {
+    ///     const TRACK_IDLE: bool = true;
+    /// }
+    ///
+    /// let pool: Pool<String, TrackIdle> = Pool::new_with_config();
+    ///
+    /// let key = pool.create_with(|s| s.push_str("hello world")).unwrap();
+    /// pool.clear(key);
+    ///
+    /// // The cleared string is retained in the pool, for reuse...
+    /// assert_eq!(pool.evict_idle(Duration::from_secs(60)), 0);
+    ///
+    /// // ...until it hasn't been used for longer than `max_idle`.
+    /// thread::sleep(Duration::from_millis(20));
+    /// assert_eq!(pool.evict_idle(Duration::from_millis(10)), 1);
+    /// ```
+    ///
+    /// [created]: Pool::create
+    /// [cleared]: Pool::clear
+    /// [`Config::TRACK_IDLE`]: crate::Config::TRACK_IDLE
+    pub fn evict_idle(&self, max_idle: Duration) -> usize {
+        if !C::TRACK_IDLE {
+            return 0;
         }
+
+        self.evict_idle_at(timestamps::now(), timestamps::to_ticks(max_idle))
+    }
+
+    pub(crate) fn evict_idle_at(&self, now: timestamps::Tick, max_idle: timestamps::Tick) -> usize {
+        test_println!("Pool::evict_idle; now={}; max_idle={}", now, max_idle);
+        self.shards
+            .iter()
+            .map(|shard| shard.evict_idle(now, max_idle))
+            .sum()
     }
 }
 
 unsafe impl<T, C> Send for Pool<T, C>
 where
-    T: Send + Clear + Default,
+    T: Send + Clear,
     C: cfg::Config,
 {
 }
 unsafe impl<T, C> Sync for Pool<T, C>
 where
-    T: Sync + Clear + Default,
+    T: Sync + Clear,
     C: cfg::Config,
 {
 }
@@ -857,7 +1362,7 @@ where
 
 impl<T, C> fmt::Debug for Pool<T, C>
 where
-    T: fmt::Debug + Clear + Default,
+    T: fmt::Debug + Clear,
     C: cfg::Config,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -870,9 +1375,9 @@ where
 
 // === impl Ref ===
 
-impl<T, C> Ref<'_, T, C>
+impl<'a, T, C> Ref<'a, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     /// Returns the key used to access this guard
@@ -880,6 +1385,41 @@ where
         self.key
     }
 
+    /// Makes a guard for a component of the pooled object.
+    ///
+    /// The object remains referenced until the returned [`MappedRef`] is
+    /// dropped. This is an associated function, rather than a method, so that
+    /// it doesn't conflict with methods on the pooled type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::{Clear, Pool};
+    /// use sharded_slab::pool::Ref;
+    ///
+    /// #[derive(Default)]
+    /// struct Request {
+    ///     path: String,
+    ///     body: Vec<u8>,
+    /// }
+    /// # impl Clear for Request {
+    /// #     fn clear(&mut self) {
+    /// #         self.path.clear();
+    /// #         self.body.clear();
+    /// #     }
+    /// # }
+    ///
+    /// let pool: Pool<Request> = Pool::new();
+    /// let key = pool.create_with(|req| req.path.push_str("/hello")).unwrap();
+    ///
+    /// let path = Ref::map(pool.get(key).unwrap(), |req| req.path.as_str());
+    /// assert_eq!(&*path, "/hello");
+    /// ```
+    pub fn map<U: ?Sized>(this: Self, f: impl FnOnce(&T) -> &U) -> MappedRef<'a, T, U, C> {
+        let value = ptr::NonNull::from(f(this.value()));
+        MappedRef { guard: this, value }
+    }
+
     #[inline]
     fn value(&self) -> &T {
         unsafe {
@@ -895,7 +1435,7 @@ where
 
 impl<T, C> std::ops::Deref for Ref<'_, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     type Target = T;
@@ -907,7 +1447,7 @@ where
 
 impl<T, C> Drop for Ref<'_, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn drop(&mut self) {
@@ -929,7 +1469,7 @@ where
 
 impl<T, C> fmt::Debug for Ref<'_, T, C>
 where
-    T: fmt::Debug + Clear + Default,
+    T: fmt::Debug + Clear,
     C: cfg::Config,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -939,7 +1479,7 @@ where
 
 impl<T, C> PartialEq<T> for Ref<'_, T, C>
 where
-    T: PartialEq<T> + Clear + Default,
+    T: PartialEq<T> + Clear,
     C: cfg::Config,
 {
     fn eq(&self, other: &T) -> bool {
@@ -947,43 +1487,134 @@ where
     }
 }
 
-// === impl GuardMut ===
+impl<T, C> PartialEq for Ref<'_, T, C>
+where
+    T: PartialEq + Clear,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
 
-impl<'a, T, C: cfg::Config> RefMut<'a, T, C>
+impl<T, C> fmt::Display for Ref<'_, T, C>
 where
-    T: Clear + Default,
+    T: fmt::Display + Clear,
     C: cfg::Config,
 {
-    /// Returns the key used to access the guard.
-    pub fn key(&self) -> usize {
-        self.key
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
     }
+}
 
-    /// Downgrades the mutable guard to an immutable guard, allowing access to
-    /// the pooled value from other threads.
-    ///
-    /// ## Examples
-    ///
-    /// ```
-    /// # use sharded_slab::Pool;
-    /// # use std::{sync::Arc, thread};
-    /// let pool = Arc::new(Pool::<String>::new());
-    ///
-    /// let mut guard_mut = pool.clone().create_owned().unwrap();
-    /// let key = guard_mut.key();
-    /// guard_mut.push_str("Hello");
-    ///
-    /// // The pooled string is currently borrowed mutably, so other threads
-    /// // may not access it.
-    /// let pool2 = pool.clone();
-    /// thread::spawn(move || {
-    ///     assert!(pool2.get(key).is_none())
-    /// }).join().unwrap();
-    ///
-    /// // Downgrade the guard to an immutable reference.
-    /// let guard = guard_mut.downgrade();
-    ///
-    /// // Now, other threads may also access the pooled value.
+impl<T, C> std::borrow::Borrow<T> for Ref<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for Ref<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for Ref<'_, T, C>
+where
+    T: serde::Serialize + Clear,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+// === impl CreateAsync ===
+
+#[cfg(feature = "async")]
+impl<'a, T, C> std::future::Future for CreateAsync<'a, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    type Output = Option<RefMut<'a, T, C>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(guard) = self.pool.create() {
+            return Poll::Ready(Some(guard));
+        }
+        if self.pool.is_closed() {
+            return Poll::Ready(None);
+        }
+        self.pool.shards.vacancies().register(cx.waker());
+        // A slot may have been cleared, or the pool closed, since we last
+        // tried.
+        match self.pool.create() {
+            Some(guard) => Poll::Ready(Some(guard)),
+            None if self.pool.is_closed() => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C> fmt::Debug for CreateAsync<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreateAsync").finish()
+    }
+}
+
+// === impl GuardMut ===
+
+impl<'a, T, C: cfg::Config> RefMut<'a, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Downgrades the mutable guard to an immutable guard, allowing access to
+    /// the pooled value from other threads.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Pool;
+    /// # use std::{sync::Arc, thread};
+    /// let pool = Arc::new(Pool::<String>::new());
+    ///
+    /// let mut guard_mut = pool.clone().create_owned().unwrap();
+    /// let key = guard_mut.key();
+    /// guard_mut.push_str("Hello");
+    ///
+    /// // The pooled string is currently borrowed mutably, so other threads
+    /// // may not access it.
+    /// let pool2 = pool.clone();
+    /// thread::spawn(move || {
+    ///     assert!(pool2.get(key).is_none())
+    /// }).join().unwrap();
+    ///
+    /// // Downgrade the guard to an immutable reference.
+    /// let guard = guard_mut.downgrade();
+    ///
+    /// // Now, other threads may also access the pooled value.
     /// let pool2 = pool.clone();
     /// thread::spawn(move || {
     ///     let guard = pool2.get(key)
@@ -1003,6 +1634,54 @@ where
         }
     }
 
+    /// Makes a mutable guard for a component of the pooled object.
+    ///
+    /// The object remains exclusively borrowed until the returned
+    /// [`MappedRefMut`] is dropped. This is an associated function, rather
+    /// than a method, so that it doesn't conflict with methods on the pooled
+    /// type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::{Clear, Pool};
+    /// use sharded_slab::pool::RefMut;
+    ///
+    /// #[derive(Default)]
+    /// struct Request {
+    ///     path: String,
+    ///     body: Vec<u8>,
+    /// }
+    /// # impl Clear for Request {
+    /// #     fn clear(&mut self) {
+    /// #         self.path.clear();
+    /// #         self.body.clear();
+    /// #     }
+    /// # }
+    ///
+    /// let pool: Pool<Request> = Pool::new();
+    /// let request = pool.create().unwrap();
+    /// let key = request.key();
+    ///
+    /// // Only allow the body to be written to.
+    /// let mut body = RefMut::map(request, |req| &mut req.body);
+    /// body.extend_from_slice(b"hello world");
+    /// drop(body);
+    ///
+    /// assert_eq!(pool.get(key).unwrap().body, b"hello world");
+    /// ```
+    pub fn map<U: ?Sized>(
+        mut this: Self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRefMut<'a, T, U, C> {
+        let value = ptr::NonNull::from(f(&mut *this));
+        MappedRefMut {
+            guard: this,
+            value,
+            _value: PhantomData,
+        }
+    }
+
     #[inline]
     fn value(&self) -> &T {
         unsafe {
@@ -1016,7 +1695,7 @@ where
 
 impl<T, C: cfg::Config> std::ops::Deref for RefMut<'_, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     type Target = T;
@@ -1028,7 +1707,7 @@ where
 
 impl<T, C> std::ops::DerefMut for RefMut<'_, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
@@ -1042,7 +1721,7 @@ where
 
 impl<T, C> Drop for RefMut<'_, T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn drop(&mut self) {
@@ -1060,7 +1739,7 @@ where
 
 impl<T, C> fmt::Debug for RefMut<'_, T, C>
 where
-    T: fmt::Debug + Clear + Default,
+    T: fmt::Debug + Clear,
     C: cfg::Config,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1070,7 +1749,7 @@ where
 
 impl<T, C> PartialEq<T> for RefMut<'_, T, C>
 where
-    T: PartialEq<T> + Clear + Default,
+    T: PartialEq<T> + Clear,
     C: cfg::Config,
 {
     fn eq(&self, other: &T) -> bool {
@@ -1078,11 +1757,258 @@ where
     }
 }
 
+impl<T, C> PartialEq for RefMut<'_, T, C>
+where
+    T: PartialEq + Clear,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T, C> fmt::Display for RefMut<'_, T, C>
+where
+    T: fmt::Display + Clear,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T, C> std::borrow::Borrow<T> for RefMut<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for RefMut<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for RefMut<'_, T, C>
+where
+    T: serde::Serialize + Clear,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+// === impl ClearOnPanic ===
+
+impl<T, C> Drop for ClearOnPanic<'_, T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn drop(&mut self) {
+        let guard = match self.guard.as_mut() {
+            Some(guard) => guard,
+            None => return,
+        };
+        test_println!("pool: initializer panicked; clearing {:#x}", guard.key);
+        unsafe {
+            // Safety: we are holding a reference to the shard which keeps the
+            // pointed slot alive.
+            //
+            // Marking the slot before releasing it ensures that the object
+            // is never accessible while partially initialized. Once the write
+            // lock has been released, dropping the `RefMut` does nothing.
+            if guard.inner.downgrade_marked().release() {
+                guard.shard.clear_after_release(guard.key);
+            }
+        }
+    }
+}
+
+// === impl MappedRef ===
+
+impl<'a, T, U: ?Sized, C> MappedRef<'a, T, U, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    /// Returns the key used to access the pooled object.
+    pub fn key(&self) -> usize {
+        self.guard.key
+    }
+
+    /// Makes a guard for a component of the borrowed data.
+    ///
+    /// See [`Ref::map`] for details.
+    pub fn map<V: ?Sized>(this: Self, f: impl FnOnce(&U) -> &V) -> MappedRef<'a, T, V, C> {
+        let value = ptr::NonNull::from(f(&*this));
+        MappedRef {
+            guard: this.guard,
+            value,
+        }
+    }
+}
+
+impl<T, U: ?Sized, C> std::ops::Deref for MappedRef<'_, T, U, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: `value` points into the object referenced by `guard`,
+            // which will not be mutated or dropped while the guard exists.
+            self.value.as_ref()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, U, C> serde::Serialize for MappedRef<'_, T, U, C>
+where
+    T: Clear,
+    U: ?Sized + serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, U, C> fmt::Debug for MappedRef<'_, T, U, C>
+where
+    T: Clear,
+    U: fmt::Debug + ?Sized,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, U, C> PartialEq<U> for MappedRef<'_, T, U, C>
+where
+    T: Clear,
+    U: PartialEq<U> + ?Sized,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &U) -> bool {
+        **self == *other
+    }
+}
+
+// === impl MappedRefMut ===
+
+impl<'a, T, U: ?Sized, C> MappedRefMut<'a, T, U, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    /// Returns the key used to access the pooled object.
+    pub fn key(&self) -> usize {
+        self.guard.key
+    }
+
+    /// Makes a mutable guard for a component of the borrowed data.
+    ///
+    /// See [`RefMut::map`] for details.
+    pub fn map<V: ?Sized>(
+        mut this: Self,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> MappedRefMut<'a, T, V, C> {
+        let value = ptr::NonNull::from(f(&mut *this));
+        MappedRefMut {
+            guard: this.guard,
+            value,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T, U: ?Sized, C> std::ops::Deref for MappedRefMut<'_, T, U, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            // Safety: `value` points into the object exclusively borrowed by
+            // `guard`, which will not be dropped while the guard exists.
+            self.value.as_ref()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, U, C> serde::Serialize for MappedRefMut<'_, T, U, C>
+where
+    T: Clear,
+    U: ?Sized + serde::Serialize,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, U: ?Sized, C> std::ops::DerefMut for MappedRefMut<'_, T, U, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            // Safety: as above. Since `self` is borrowed mutably, no other
+            // references to the value exist.
+            self.value.as_mut()
+        }
+    }
+}
+
+impl<T, U, C> fmt::Debug for MappedRefMut<'_, T, U, C>
+where
+    T: Clear,
+    U: fmt::Debug + ?Sized,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, U, C> PartialEq<U> for MappedRefMut<'_, T, U, C>
+where
+    T: Clear,
+    U: PartialEq<U> + ?Sized,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &U) -> bool {
+        **self == *other
+    }
+}
+
 // === impl OwnedRef ===
 
 impl<T, C> OwnedRef<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     /// Returns the key used to access this guard
@@ -1105,7 +2031,7 @@ where
 
 impl<T, C> std::ops::Deref for OwnedRef<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     type Target = T;
@@ -1117,7 +2043,7 @@ where
 
 impl<T, C> Drop for OwnedRef<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn drop(&mut self) {
@@ -1145,7 +2071,7 @@ where
 
 impl<T, C> fmt::Debug for OwnedRef<T, C>
 where
-    T: fmt::Debug + Clear + Default,
+    T: fmt::Debug + Clear,
     C: cfg::Config,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1155,7 +2081,7 @@ where
 
 impl<T, C> PartialEq<T> for OwnedRef<T, C>
 where
-    T: PartialEq<T> + Clear + Default,
+    T: PartialEq<T> + Clear,
     C: cfg::Config,
 {
     fn eq(&self, other: &T) -> bool {
@@ -1163,16 +2089,97 @@ where
     }
 }
 
+/// Compares the referenced values, so that the guard may be stored in place
+/// of the value in collections.
+impl<T, C> PartialEq for OwnedRef<T, C>
+where
+    T: PartialEq + Clear,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T, C> Eq for OwnedRef<T, C>
+where
+    T: Eq + Clear,
+    C: cfg::Config,
+{
+}
+
+impl<T, C> PartialOrd for OwnedRef<T, C>
+where
+    T: PartialOrd + Clear,
+    C: cfg::Config,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T, C> Ord for OwnedRef<T, C>
+where
+    T: Ord + Clear,
+    C: cfg::Config,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T, C> std::hash::Hash for OwnedRef<T, C>
+where
+    T: std::hash::Hash + Clear,
+    C: cfg::Config,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T, C> std::borrow::Borrow<T> for OwnedRef<T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for OwnedRef<T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for OwnedRef<T, C>
+where
+    T: serde::Serialize + Clear,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
 unsafe impl<T, C> Sync for OwnedRef<T, C>
 where
-    T: Sync + Clear + Default,
+    T: Sync + Clear,
     C: cfg::Config,
 {
 }
 
 unsafe impl<T, C> Send for OwnedRef<T, C>
 where
-    T: Sync + Clear + Default,
+    T: Sync + Clear,
     C: cfg::Config,
 {
 }
@@ -1181,7 +2188,7 @@ where
 
 impl<T, C> OwnedRefMut<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     /// Returns the key used to access this guard
@@ -1254,7 +2261,7 @@ where
 
 impl<T, C> std::ops::Deref for OwnedRefMut<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     type Target = T;
@@ -1266,7 +2273,7 @@ where
 
 impl<T, C> std::ops::DerefMut for OwnedRefMut<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
@@ -1283,7 +2290,7 @@ where
 
 impl<T, C> Drop for OwnedRefMut<T, C>
 where
-    T: Clear + Default,
+    T: Clear,
     C: cfg::Config,
 {
     fn drop(&mut self) {
@@ -1309,7 +2316,7 @@ where
 
 impl<T, C> fmt::Debug for OwnedRefMut<T, C>
 where
-    T: fmt::Debug + Clear + Default,
+    T: fmt::Debug + Clear,
     C: cfg::Config,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1319,7 +2326,7 @@ where
 
 impl<T, C> PartialEq<T> for OwnedRefMut<T, C>
 where
-    T: PartialEq<T> + Clear + Default,
+    T: PartialEq<T> + Clear,
     C: cfg::Config,
 {
     fn eq(&self, other: &T) -> bool {
@@ -1327,16 +2334,97 @@ where
     }
 }
 
+/// Compares the referenced values, so that the guard may be stored in place
+/// of the value in collections.
+impl<T, C> PartialEq for OwnedRefMut<T, C>
+where
+    T: PartialEq + Clear,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T, C> Eq for OwnedRefMut<T, C>
+where
+    T: Eq + Clear,
+    C: cfg::Config,
+{
+}
+
+impl<T, C> PartialOrd for OwnedRefMut<T, C>
+where
+    T: PartialOrd + Clear,
+    C: cfg::Config,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T, C> Ord for OwnedRefMut<T, C>
+where
+    T: Ord + Clear,
+    C: cfg::Config,
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T, C> std::hash::Hash for OwnedRefMut<T, C>
+where
+    T: std::hash::Hash + Clear,
+    C: cfg::Config,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T, C> std::borrow::Borrow<T> for OwnedRefMut<T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T, C> AsRef<T> for OwnedRefMut<T, C>
+where
+    T: Clear,
+    C: cfg::Config,
+{
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for OwnedRefMut<T, C>
+where
+    T: serde::Serialize + Clear,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
 unsafe impl<T, C> Sync for OwnedRefMut<T, C>
 where
-    T: Sync + Clear + Default,
+    T: Sync + Clear,
     C: cfg::Config,
 {
 }
 
 unsafe impl<T, C> Send for OwnedRefMut<T, C>
 where
-    T: Sync + Clear + Default,
+    T: Send + Sync + Clear,
     C: cfg::Config,
 {
 }
@@ -0,0 +1,444 @@
+//! A family of object pools, one per size class.
+//!
+//! See the [`PoolSet` type's documentation][pool_set] for details.
+//!
+//! [pool_set]: crate::PoolSet
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    clear::Clear,
+    pool::{self, Pool},
+    reserved::{self, Field},
+};
+use std::fmt;
+
+/// A set of object [`Pool`]s, each holding objects of a different size class.
+///
+/// Pooled objects which own resizable storage, such as buffers, are most
+/// useful when the storage they retain is about the size that's needed. A
+/// `PoolSet` keeps objects of similar sizes together: it is created with a
+/// list of class sizes, and [`create`] returns an object from the smallest
+/// class that is at least as large as the requested size.
+///
+/// The size class of an object is encoded in its key, so a `PoolSet` is
+/// indexed by a single `usize` key, just like a `Pool`. The `PoolSet` doesn't
+/// interpret the class sizes itself; a new object is created with
+/// `T::default()`, and it's up to the caller to grow it to the class's size.
+/// Since [`Clear`] implementations retain storage, objects keep the capacity
+/// they grew to when they are reused.
+///
+/// A `PoolSet` may have at most [`MAX_CLASSES`] size classes. The class of an
+/// object is stored in three bits of its key, which are reserved from each
+/// pool; this means that a `PoolSet` can hold fewer objects per shard than a
+/// single `Pool` with the same configuration.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::PoolSet;
+/// let buffers: PoolSet<Vec<u8>> = PoolSet::new(vec![4096, 16384, 65536]);
+///
+/// let mut buf = buffers.create(5000).unwrap();
+/// assert_eq!(buffers.class_size(buf.key()), Some(16384));
+/// buf.reserve(16384);
+/// buf.extend_from_slice(b"hello world");
+/// let key = buf.key();
+/// drop(buf);
+///
+/// assert_eq!(buffers.get(key).unwrap(), b"hello world".to_vec());
+///
+/// // No class is large enough for this request.
+/// assert!(buffers.create(100_000).is_none());
+/// ```
+///
+/// [`create`]: PoolSet::create
+/// [`MAX_CLASSES`]: PoolSet::MAX_CLASSES
+pub struct PoolSet<T, C = DefaultConfig>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    /// The size classes, in increasing order of size.
+    classes: Box<[Class<T, C>]>,
+}
+
+/// A guard that allows access to an object in a [`PoolSet`].
+///
+/// This is the `PoolSet` equivalent of [`pool::Ref`].
+pub struct Ref<'a, T, C = DefaultConfig>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    inner: pool::Ref<'a, T, Classed<C>>,
+    key: usize,
+}
+
+/// A guard that allows exclusive mutable access to an object in a
+/// [`PoolSet`].
+///
+/// This is the `PoolSet` equivalent of [`pool::RefMut`].
+pub struct RefMut<'a, T, C = DefaultConfig>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    inner: pool::RefMut<'a, T, Classed<C>>,
+    key: usize,
+}
+
+/// A size class and its pool.
+struct Class<T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    size: usize,
+    pool: Pool<T, Classed<C>>,
+}
+
+/// The configuration of each size class's pool.
+///
+/// The size class of an object is stored in three bits of its key, which are
+/// reserved from the size class's pool.
+type Classed<C> = reserved::Reserve<C, SizeClass>;
+
+/// The size class of an object.
+struct SizeClass;
+
+impl Field for SizeClass {
+    const BITS: usize = 3;
+}
+
+impl<T> PoolSet<T>
+where
+    T: Clear + Default,
+{
+    /// The maximum number of size classes in a `PoolSet`.
+    pub const MAX_CLASSES: usize = 1 << SizeClass::BITS;
+
+    /// Returns a new `PoolSet` with the given class sizes and the default
+    /// configuration parameters.
+    ///
+    /// The sizes may be given in any order; duplicate sizes are ignored.
+    ///
+    /// # Panics
+    ///
+    /// If more than [`MAX_CLASSES`] distinct sizes are given.
+    ///
+    /// [`MAX_CLASSES`]: PoolSet::MAX_CLASSES
+    pub fn new(sizes: impl IntoIterator<Item = usize>) -> Self {
+        Self::new_with_config(sizes)
+    }
+
+    /// Returns a new `PoolSet` with the given class sizes and the provided
+    /// configuration parameters.
+    ///
+    /// # Panics
+    ///
+    /// If more than [`MAX_CLASSES`] distinct sizes are given.
+    ///
+    /// [`MAX_CLASSES`]: PoolSet::MAX_CLASSES
+    pub fn new_with_config<C: cfg::Config>(
+        sizes: impl IntoIterator<Item = usize>,
+    ) -> PoolSet<T, C> {
+        C::validate();
+        let mut sizes = sizes.into_iter().collect::<Vec<_>>();
+        sizes.sort_unstable();
+        sizes.dedup();
+        assert!(
+            sizes.len() <= Self::MAX_CLASSES,
+            "a `PoolSet` may have at most {} size classes, but {} were given",
+            Self::MAX_CLASSES,
+            sizes.len()
+        );
+        PoolSet {
+            classes: sizes
+                .into_iter()
+                .map(|size| Class {
+                    size,
+                    pool: Pool::new_with_config(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<T, C> PoolSet<T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    /// Creates a new object in the pool for the smallest size class that is
+    /// at least `size_hint`, returning a [`RefMut`] guard that may be used to
+    /// mutate the new object.
+    ///
+    /// If this function returns `None`, then no size class is at least
+    /// `size_hint`, or the shard for the current thread is full in that
+    /// class's pool.
+    pub fn create(&self, size_hint: usize) -> Option<RefMut<'_, T, C>> {
+        let class = self.class_for(size_hint)?;
+        test_println!("pool_set: create; size_hint={}; class={}", size_hint, class);
+        let inner = self.classes[class].pool.create()?;
+        let key = reserved::set::<C, SizeClass>(inner.key(), class);
+        Some(RefMut { inner, key })
+    }
+
+    /// Creates a new object in the pool for the smallest size class that is
+    /// at least `size_hint`, initializes it with the provided function, and
+    /// returns its key.
+    ///
+    /// If this function returns `None`, then no size class is at least
+    /// `size_hint`, or the shard for the current thread is full in that
+    /// class's pool.
+    ///
+    /// If `init` panics, the new object is cleared and returned to the pool.
+    pub fn create_with(&self, size_hint: usize, init: impl FnOnce(&mut T)) -> Option<usize> {
+        let class = self.class_for(size_hint)?;
+        let key = self.classes[class].pool.create_with(init)?;
+        Some(reserved::set::<C, SizeClass>(key, class))
+    }
+
+    /// Returns the index of the smallest size class that is at least
+    /// `size_hint`.
+    fn class_for(&self, size_hint: usize) -> Option<usize> {
+        self.classes
+            .iter()
+            .position(|class| class.size >= size_hint)
+    }
+
+    /// Return a borrowed reference to the object associated with the given
+    /// key.
+    ///
+    /// If the set does not contain an object for the given key, `None` is
+    /// returned instead.
+    pub fn get(&self, key: usize) -> Option<Ref<'_, T, C>> {
+        let inner = self.pool(key)?.get(key)?;
+        Some(Ref { inner, key })
+    }
+
+    /// Remove the object using the given key from the set, returning `true`
+    /// if it was removed.
+    ///
+    /// As with [`Pool::clear`], the object is cleared and retained for reuse
+    /// in the same size class.
+    pub fn clear(&self, key: usize) -> bool {
+        self.pool(key).map(|pool| pool.clear(key)).unwrap_or(false)
+    }
+
+    /// Returns the size of the class that the given key belongs to, or `None`
+    /// if the key doesn't belong to any of this set's classes.
+    ///
+    /// This doesn't check whether the key refers to an object that currently
+    /// exists.
+    pub fn class_size(&self, key: usize) -> Option<usize> {
+        self.classes
+            .get(reserved::get::<C, SizeClass>(key))
+            .map(|class| class.size)
+    }
+
+    fn pool(&self, key: usize) -> Option<&Pool<T, Classed<C>>> {
+        self.classes
+            .get(reserved::get::<C, SizeClass>(key))
+            .map(|class| &class.pool)
+    }
+}
+
+impl<T, C> fmt::Debug for PoolSet<T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolSet")
+            .field(
+                "classes",
+                &self
+                    .classes
+                    .iter()
+                    .map(|class| class.size)
+                    .collect::<Vec<_>>(),
+            )
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl Ref ===
+
+impl<T, C> Ref<'_, T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    /// Returns the key used to access this guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+}
+
+impl<T, C> std::ops::Deref for Ref<'_, T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for Ref<'_, T, C>
+where
+    T: serde::Serialize + Clear + Default,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C> fmt::Debug for Ref<'_, T, C>
+where
+    T: fmt::Debug + Clear + Default,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl<T, C> PartialEq<T> for Ref<'_, T, C>
+where
+    T: PartialEq<T> + Clear + Default,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        *self.inner == *other
+    }
+}
+
+// === impl RefMut ===
+
+impl<'a, T, C> RefMut<'a, T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    /// Returns the key used to access the guard.
+    pub fn key(&self) -> usize {
+        self.key
+    }
+
+    /// Downgrades the mutable guard to an immutable guard, allowing access to
+    /// the pooled object from other threads.
+    pub fn downgrade(self) -> Ref<'a, T, C> {
+        Ref {
+            inner: self.inner.downgrade(),
+            key: self.key,
+        }
+    }
+}
+
+impl<T, C> std::ops::Deref for RefMut<'_, T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+impl<T, C> serde::Serialize for RefMut<'_, T, C>
+where
+    T: serde::Serialize + Clear + Default,
+    C: cfg::Config,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T, C> std::ops::DerefMut for RefMut<'_, T, C>
+where
+    T: Clear + Default,
+    C: cfg::Config,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T, C> fmt::Debug for RefMut<'_, T, C>
+where
+    T: fmt::Debug + Clear + Default,
+    C: cfg::Config,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.inner, f)
+    }
+}
+
+impl<T, C> PartialEq<T> for RefMut<'_, T, C>
+where
+    T: PartialEq<T> + Clear + Default,
+    C: cfg::Config,
+{
+    fn eq(&self, other: &T) -> bool {
+        *self.inner == *other
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_to_smallest_fitting_class() {
+        let set: PoolSet<Vec<u8>> = PoolSet::new(vec![64, 16, 256, 16]);
+        let keys = [0, 16, 17, 64, 200, 256]
+            .iter()
+            .map(|&hint| set.create_with(hint, |buf| buf.push(hint as u8)).unwrap())
+            .collect::<Vec<_>>();
+        let sizes = keys
+            .iter()
+            .map(|&key| set.class_size(key).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![16, 16, 64, 64, 256, 256]);
+        assert!(set.create(257).is_none());
+
+        // Keys in different classes refer to different objects, even if they
+        // are at the same index in their pools.
+        assert_eq!(set.get(keys[0]).unwrap(), vec![0]);
+        assert_eq!(set.get(keys[2]).unwrap(), vec![17]);
+        assert_eq!(set.get(keys[4]).unwrap(), vec![200]);
+
+        assert!(set.clear(keys[2]));
+        assert!(set.get(keys[2]).is_none());
+        assert!(set.get(keys[3]).is_some());
+    }
+
+    #[test]
+    fn reuses_storage_within_class() {
+        let set: PoolSet<Vec<u8>> = PoolSet::new(vec![16, 1024]);
+        let key = set.create_with(1024, |buf| buf.reserve(1024)).unwrap();
+        assert!(set.clear(key));
+
+        let buf = set.create(1000).unwrap();
+        assert!(buf.is_empty());
+        assert!(buf.capacity() >= 1024);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_classes() {
+        let _ = PoolSet::<String>::new(0..9);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    Pack, Slab,
+};
+use std::fmt;
+
+/// A [`Slab`] which has been frozen, so that its values may be read without
+/// guards.
+///
+/// This is returned by [`Slab::into_read_only`]. Since a `ReadOnlySlab` can
+/// never be modified, reading a value doesn't need to increment and
+/// decrement the slot's reference count, or to retry if the slot is
+/// concurrently being removed: a lookup is just a bounds check, a comparison
+/// of the key's generation with the slot's, and a load. This makes it well
+/// suited to lookup tables which are built once and then only read, possibly
+/// from many threads.
+///
+/// References returned by [`get`] borrow the `ReadOnlySlab`, rather than
+/// being guards. To modify the slab again, it may be thawed with
+/// [`into_slab`].
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// use std::{sync::Arc, thread};
+///
+/// let slab = Slab::new();
+/// let hello = slab.insert("hello").unwrap();
+/// let world = slab.insert("world").unwrap();
+///
+/// let table = Arc::new(slab.into_read_only());
+/// let table2 = table.clone();
+/// thread::spawn(move || {
+///     assert_eq!(table2.get(world), Some(&"world"));
+/// })
+/// .join()
+/// .unwrap();
+/// assert_eq!(table.get(hello), Some(&"hello"));
+/// ```
+///
+/// [`get`]: ReadOnlySlab::get
+/// [`into_slab`]: ReadOnlySlab::into_slab
+pub struct ReadOnlySlab<T, C: cfg::Config = DefaultConfig> {
+    slab: Slab<T, C>,
+}
+
+/// A view of a [`Slab`] that reads values without guards, while the slab is
+/// mutably borrowed.
+///
+/// This is returned by [`Slab::read_guardless`]. Like a [`ReadOnlySlab`],
+/// it reads values without touching their reference counts, but it only
+/// borrows the slab, so that the slab may be modified again once the view is
+/// dropped.
+pub struct ReadGuardless<'a, T, C: cfg::Config = DefaultConfig> {
+    slab: &'a Slab<T, C>,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Freezes the slab, returning a [`ReadOnlySlab`] whose values may be read
+    /// without guards.
+    ///
+    /// This doesn't move or copy any values.
+    pub fn into_read_only(self) -> ReadOnlySlab<T, C> {
+        ReadOnlySlab { slab: self }
+    }
+
+    /// Returns a [`ReadGuardless`] view of the slab, whose values may be read
+    /// without guards for as long as the slab is borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut slab = sharded_slab::Slab::new();
+    /// let key = slab.insert(String::from("hello")).unwrap();
+    ///
+    /// let view = slab.read_guardless();
+    /// let value: &String = view.get(key).unwrap();
+    /// assert_eq!(value, "hello");
+    ///
+    /// // Once the view is dropped, the slab may be modified again.
+    /// assert!(slab.remove(key));
+    /// assert!(slab.read_guardless().get(key).is_none());
+    /// ```
+    pub fn read_guardless(&mut self) -> ReadGuardless<'_, T, C> {
+        ReadGuardless { slab: self }
+    }
+
+    /// Returns a reference to the value for `key`, without acquiring a guard.
+    ///
+    /// ## Safety
+    ///
+    /// The slab must not be modified while the returned reference is live.
+    /// This is ensured if the caller has (or had) exclusive access to the
+    /// slab, and only allows shared access until the reference is dropped.
+    unsafe fn get_unguarded(&self, key: usize) -> Option<&T> {
+        let shard = self.shards.get(C::unpack_tid(key).as_usize())?;
+        shard.with_slot(key, |slot| {
+            // Since no guards can exist, every value that was removed has
+            // been cleared, so a slot of the key's generation holds a value
+            // if and only if the key refers to it.
+            if slot.generation() != C::unpack_gen(key) {
+                return None;
+            }
+            slot.value().as_ref()
+        })
+    }
+}
+
+// === impl ReadOnlySlab ===
+
+impl<T, C: cfg::Config> ReadOnlySlab<T, C> {
+    /// Returns a reference to the value for `key`, or `None` if there is no
+    /// value for it.
+    pub fn get(&self, key: usize) -> Option<&T> {
+        unsafe {
+            // Safety: the slab can't be modified through a `ReadOnlySlab`,
+            // and no guards can outlive the `Slab` it was made from.
+            self.slab.get_unguarded(key)
+        }
+    }
+
+    /// Returns `true` if the slab contains a value for `key`.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Thaws the slab, returning the [`Slab`] it was made from.
+    ///
+    /// Every key that referred to a value in the `ReadOnlySlab` still refers
+    /// to the same value in the returned slab.
+    pub fn into_slab(self) -> Slab<T, C> {
+        self.slab
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for ReadOnlySlab<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadOnlySlab")
+            .field("shards", &self.slab.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl ReadGuardless ===
+
+impl<'a, T, C: cfg::Config> ReadGuardless<'a, T, C> {
+    /// Returns a reference to the value for `key`, or `None` if there is no
+    /// value for it.
+    ///
+    /// The reference may outlive the view, but not the borrow of the slab.
+    pub fn get(&self, key: usize) -> Option<&'a T> {
+        unsafe {
+            // Safety: the slab is mutably borrowed for `'a`, so it can't be
+            // modified until every reference returned by the view is dropped.
+            self.slab.get_unguarded(key)
+        }
+    }
+
+    /// Returns `true` if the slab contains a value for `key`.
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for ReadGuardless<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadGuardless")
+            .field("shards", &self.slab.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Slab;
+
+    #[test]
+    fn stale_keys() {
+        let slab = Slab::new();
+        let removed = slab.insert(1).unwrap();
+        assert!(slab.remove(removed));
+        let reused = slab.insert(2).unwrap();
+        let taken = slab.insert(3).unwrap();
+        assert_eq!(slab.take(taken), Some(3));
+
+        let slab = slab.into_read_only();
+        assert_eq!(slab.get(reused), Some(&2));
+        assert_eq!(slab.get(removed), None);
+        assert_eq!(slab.get(taken), None);
+        // A vacant slot's current generation was never issued as a key.
+        assert_eq!(slab.get(reused + 1), None);
+
+        let mut slab = slab.into_slab();
+        assert_eq!(slab.read_guardless().get(reused), Some(&2));
+        assert!(slab.remove(reused));
+        assert!(!slab.read_guardless().contains(reused));
+    }
+}
@@ -0,0 +1,186 @@
+//! Tables of keys that changed when values were moved within a slab.
+//!
+//! See [`KeyRemap`] for details.
+use crate::{cfg, Slab};
+use std::{iter::FromIterator, slice};
+
+/// A table mapping the old keys of values that were moved within a slab to
+/// their new keys.
+///
+/// This is returned by [`Slab::compact_remap`], so that systems which store
+/// keys outside the slab, such as indices or scene graphs, can be brought up
+/// to date after values are moved. A `KeyRemap` may also be built from
+/// `(old, new)` pairs, such as those passed to the callback of
+/// [`Slab::compact`], using [`FromIterator`] or [`Extend`].
+///
+/// The table only contains the keys of values that were moved. A key which
+/// isn't in the table either refers to a value that stayed where it was, or
+/// didn't refer to a value at all.
+///
+/// # Examples
+///
+/// ```
+/// let mut slab = sharded_slab::Slab::new();
+/// let mut keys = (0..8).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+/// for key in keys.drain(..6) {
+///     slab.remove(key);
+/// }
+///
+/// let remap = slab.compact_remap();
+/// assert_eq!(remap.len(), 2);
+/// assert_eq!(remap.translate_all(&mut keys), 2);
+/// assert_eq!(slab.get(keys[0]).unwrap(), 6);
+/// assert_eq!(slab.get(keys[1]).unwrap(), 7);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyRemap {
+    /// `(old, new)` pairs, sorted by old key.
+    entries: Vec<(usize, usize)>,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Compacts the slab, returning a [`KeyRemap`] from the old key of each
+    /// value that was moved to its new key.
+    ///
+    /// This is equivalent to [`compact`], but collects the moved keys into a
+    /// table, rather than passing each one to a callback. This is convenient
+    /// when the keys to update are stored elsewhere, or must be updated
+    /// later.
+    ///
+    /// [`compact`]: Slab::compact
+    pub fn compact_remap(&mut self) -> KeyRemap {
+        let mut entries = Vec::new();
+        self.compact(|old, new| entries.push((old, new)));
+        entries.into_iter().collect()
+    }
+}
+
+// === impl KeyRemap ===
+
+impl KeyRemap {
+    /// Returns an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the new key of the value whose old key was `key`, or `None`
+    /// if it wasn't moved.
+    pub fn get(&self, key: usize) -> Option<usize> {
+        self.entries
+            .binary_search_by_key(&key, |&(old, _)| old)
+            .ok()
+            .map(|idx| self.entries[idx].1)
+    }
+
+    /// Returns the current key of the value whose key was `key` before it was
+    /// moved.
+    ///
+    /// If `key` isn't in the table, it is returned unchanged.
+    pub fn translate(&self, key: usize) -> usize {
+        self.get(key).unwrap_or(key)
+    }
+
+    /// Replaces every key in `keys` which is in the table with its new key,
+    /// returning the number of keys that were replaced.
+    ///
+    /// This accepts anything that yields mutable references to keys, such as
+    /// a `&mut [usize]`, a `&mut Vec<usize>`, or the values of a map.
+    pub fn translate_all<'a>(&self, keys: impl IntoIterator<Item = &'a mut usize>) -> usize {
+        let mut replaced = 0;
+        for key in keys {
+            if let Some(new) = self.get(*key) {
+                *key = new;
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Returns the number of keys in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if no keys were moved.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the old and new key of each moved value, in
+    /// ascending order of old key.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+}
+
+impl FromIterator<(usize, usize)> for KeyRemap {
+    fn from_iter<I: IntoIterator<Item = (usize, usize)>>(iter: I) -> Self {
+        let mut remap = Self::new();
+        remap.extend(iter);
+        remap
+    }
+}
+
+impl Extend<(usize, usize)> for KeyRemap {
+    /// Adds `(old, new)` pairs to the table.
+    ///
+    /// If an old key is already in the table, its new key is replaced.
+    fn extend<I: IntoIterator<Item = (usize, usize)>>(&mut self, iter: I) {
+        self.entries.extend(iter);
+        // The sort is stable, so the pairs for each old key stay in the order
+        // they were added, and the last one wins.
+        self.entries.sort_by_key(|&(old, _)| old);
+        let mut entries: Vec<(usize, usize)> = Vec::with_capacity(self.entries.len());
+        for (old, new) in self.entries.drain(..) {
+            match entries.last_mut() {
+                Some(last) if last.0 == old => last.1 = new,
+                _ => entries.push((old, new)),
+            }
+        }
+        self.entries = entries;
+    }
+}
+
+impl<'a> IntoIterator for &'a KeyRemap {
+    type Item = (usize, usize);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries in a [`KeyRemap`].
+///
+/// This is returned by [`KeyRemap::iter`].
+#[derive(Clone, Debug)]
+pub struct Iter<'a>(slice::Iter<'a, (usize, usize)>);
+
+impl Iterator for Iter<'_> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().copied()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_pair_wins() {
+        let mut remap = vec![(4, 1), (2, 0)].into_iter().collect::<KeyRemap>();
+        remap.extend(vec![(4, 3), (6, 5)]);
+        assert_eq!(remap.iter().collect::<Vec<_>>(), &[(2, 0), (4, 3), (6, 5)]);
+        assert_eq!(remap.get(4), Some(3));
+        assert_eq!(remap.get(3), None);
+        assert_eq!(remap.translate(3), 3);
+    }
+}
@@ -0,0 +1,89 @@
+//! Fields stored in bits reserved from the keys of a wrapped slab.
+//!
+//! Types such as `DynSlab`, `PoolSet` and `PartitionedSlab` store extra
+//! information (a size class, or a partition tag) in each key they hand out.
+//! The bits for that field are reserved from the slabs they wrap, just below
+//! any bits reserved by the user's configuration.
+use crate::cfg;
+use std::marker::PhantomData;
+
+/// A field stored in bits reserved from each key.
+pub(crate) trait Field {
+    /// The number of bits the field occupies.
+    const BITS: usize;
+}
+
+/// A configuration which is identical to `C`, except that it reserves
+/// `F::BITS` more bits from each key to store the field `F`.
+pub(crate) struct Reserve<C, F>(PhantomData<fn(C, F)>);
+
+/// Returns the value of the field `F` in `key`.
+pub(crate) fn get<C: cfg::Config, F: Field>(key: usize) -> usize {
+    (key >> shift::<C, F>()) & ((1 << F::BITS) - 1)
+}
+
+/// Returns `key` with the field `F` set to `value`.
+///
+/// The field's bits in `key` must be unset.
+pub(crate) fn set<C: cfg::Config, F: Field>(key: usize, value: usize) -> usize {
+    debug_assert_eq!(get::<C, F>(key), 0);
+    key | (value << shift::<C, F>())
+}
+
+/// Returns the position of the field `F`'s lowest bit.
+pub(crate) fn shift<C: cfg::Config, F: Field>() -> usize {
+    cfg::WIDTH - C::RESERVED_BITS - F::BITS
+}
+
+impl<C: cfg::Config, F: Field> cfg::Config for Reserve<C, F> {
+    const MAX_THREADS: usize = C::MAX_THREADS;
+    const MAX_PAGES: usize = C::MAX_PAGES;
+    const INITIAL_PAGE_SIZE: usize = C::INITIAL_PAGE_SIZE;
+    const RESERVED_BITS: usize = C::RESERVED_BITS + F::BITS;
+    const TRACK_IDLE: bool = C::TRACK_IDLE;
+    const TRACK_INSERTED: bool = C::TRACK_INSERTED;
+    const TRACK_ACCESS: bool = C::TRACK_ACCESS;
+    const TRACK_OCCUPANCY: bool = C::TRACK_OCCUPANCY;
+    const PREFER_LOW_ADDRESSES: bool = C::PREFER_LOW_ADDRESSES;
+    const AUTO_SIZE_SHARDS: bool = C::AUTO_SIZE_SHARDS;
+    const BALANCED_INSERTS: bool = C::BALANCED_INSERTS;
+    const GENERATION_WRAP: cfg::GenerationWrap = C::GENERATION_WRAP;
+    const TAG_BITS: usize = C::TAG_BITS;
+    const DETERMINISTIC: bool = C::DETERMINISTIC;
+    const SHARD_HINTS: bool = C::SHARD_HINTS;
+    const SINGLE_THREADED: bool = C::SINGLE_THREADED;
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::{Field, Reserve};
+    use crate::{cfg, Config};
+
+    struct Custom;
+
+    impl Config for Custom {
+        const RESERVED_BITS: usize = 4;
+        const TRACK_OCCUPANCY: bool = true;
+        const SINGLE_THREADED: bool = true;
+        const MAX_THREADS: usize = 1;
+    }
+
+    struct Byte;
+
+    impl Field for Byte {
+        const BITS: usize = 8;
+    }
+
+    #[test]
+    fn forwards_config() {
+        type Reserved = Reserve<Custom, Byte>;
+        assert_eq!(<Reserved as Config>::RESERVED_BITS, 12);
+        assert!(<Reserved as Config>::TRACK_OCCUPANCY);
+        assert!(<Reserved as Config>::SINGLE_THREADED);
+
+        let key = super::set::<Custom, Byte>(0x1234, 0xab);
+        assert_eq!(key >> (cfg::WIDTH - 12), 0xab);
+        assert_eq!(super::get::<Custom, Byte>(key), 0xab);
+        assert_eq!(key & 0xffff, 0x1234);
+    }
+}
@@ -0,0 +1,158 @@
+// `SipHasher` is deprecated, but it is the only keyed hasher in `std`.
+#[allow(deprecated)]
+use std::hash::SipHasher;
+use std::{
+    collections::hash_map::RandomState,
+    convert::TryFrom,
+    fmt,
+    hash::{BuildHasher, Hasher},
+};
+
+/// Authenticates slab keys which are handed out to untrusted clients.
+///
+/// A slab's keys are predictable: a client holding one key can guess the
+/// keys of neighbouring slots, or of earlier generations of the same slot.
+/// When keys are embedded in tokens sent to clients, a `KeySealer` may be
+/// used to [`seal`] each key with a secret before it is sent, and to
+/// [`unseal`] it when it is received again.
+///
+/// A sealed key is a `u128` consisting of a 64-bit SipHash-2-4 MAC of the
+/// key, and the key itself, scrambled with another MAC derived from the
+/// first. Without the secret, a client can't tell which slot a sealed key
+/// refers to, and can't forge a sealed key for another slot: [`unseal`]
+/// rejects any sealed key that this sealer didn't create.
+///
+/// Sealing a key is deterministic, so the same key always seals to the same
+/// value. Since a key's generation changes every time its slot is reused,
+/// this doesn't reveal whether two values occupied the same slot.
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::{KeySealer, Slab};
+///
+/// let slab = Slab::new();
+/// let sealer = KeySealer::new();
+///
+/// let key = slab.insert("hello world").unwrap();
+/// let token = sealer.seal(key);
+///
+/// // ... the token is sent to a client, and later sent back ...
+///
+/// let key = sealer.unseal(token).unwrap();
+/// assert_eq!(slab.get(key).unwrap(), "hello world");
+///
+/// // A token that was tampered with is rejected.
+/// assert_eq!(sealer.unseal(token ^ 1), None);
+/// ```
+///
+/// [`seal`]: KeySealer::seal
+/// [`unseal`]: KeySealer::unseal
+#[derive(Clone)]
+pub struct KeySealer {
+    k0: u64,
+    k1: u64,
+}
+
+/// Distinguishes the two MACs computed with a sealer's secret.
+const TAG: u8 = 0;
+const MASK: u8 = 1;
+
+impl KeySealer {
+    /// Returns a new `KeySealer` with a randomly generated secret.
+    ///
+    /// Keys sealed by this sealer can only be unsealed by it, or by its
+    /// clones. To unseal keys in another process, or after a restart, use
+    /// [`KeySealer::from_secret`] instead.
+    pub fn new() -> Self {
+        // Each `RandomState` is seeded with random keys, which are used to
+        // generate the secret.
+        let state = RandomState::new();
+        let mut hasher = state.build_hasher();
+        hasher.write_u8(TAG);
+        let k0 = hasher.finish();
+        hasher.write_u8(MASK);
+        let k1 = hasher.finish();
+        Self { k0, k1 }
+    }
+
+    /// Returns a new `KeySealer` with the given secret.
+    ///
+    /// Sealers with the same secret seal keys identically. The secret should
+    /// be generated randomly, and kept private.
+    pub fn from_secret(secret: [u8; 16]) -> Self {
+        let mut k0 = [0; 8];
+        let mut k1 = [0; 8];
+        k0.copy_from_slice(&secret[..8]);
+        k1.copy_from_slice(&secret[8..]);
+        Self {
+            k0: u64::from_le_bytes(k0),
+            k1: u64::from_le_bytes(k1),
+        }
+    }
+
+    /// Seals `key`, returning a value that may be handed out to clients.
+    pub fn seal(&self, key: usize) -> u128 {
+        let key = key as u64;
+        let tag = self.mac(TAG, key);
+        let scrambled = key ^ self.mac(MASK, tag);
+        (u128::from(tag) << 64) | u128::from(scrambled)
+    }
+
+    /// Returns the key that was sealed to produce `sealed`, or `None` if it
+    /// wasn't produced by a sealer with this secret.
+    pub fn unseal(&self, sealed: u128) -> Option<usize> {
+        let tag = (sealed >> 64) as u64;
+        let key = sealed as u64 ^ self.mac(MASK, tag);
+        if self.mac(TAG, key) != tag {
+            return None;
+        }
+        usize::try_from(key).ok()
+    }
+
+    #[allow(deprecated)]
+    fn mac(&self, domain: u8, value: u64) -> u64 {
+        let mut hasher = SipHasher::new_with_keys(self.k0, self.k1);
+        hasher.write_u8(domain);
+        hasher.write_u64(value);
+        hasher.finish()
+    }
+}
+
+impl Default for KeySealer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for KeySealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Don't reveal the secret.
+        f.debug_struct("KeySealer").finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::KeySealer;
+
+    #[test]
+    fn rejects_forged_keys() {
+        let sealer = KeySealer::from_secret(*b"0123456789abcdef");
+        let same = KeySealer::from_secret(*b"0123456789abcdef");
+        let other = KeySealer::new();
+
+        for key in [0, 1, 2, 0x1_0000, !0].iter().copied() {
+            let sealed = sealer.seal(key);
+            assert_eq!(sealer.unseal(sealed), Some(key));
+            assert_eq!(same.unseal(sealed), Some(key));
+            assert_eq!(other.unseal(sealed), None);
+            // Flipping any bit invalidates the sealed key.
+            for bit in 0..128 {
+                assert_eq!(sealer.unseal(sealed ^ (1 << bit)), None);
+            }
+        }
+        // The key itself is scrambled.
+        assert_ne!(sealer.seal(1) as u64, 1);
+    }
+}
@@ -1,19 +1,25 @@
 use crate::{
     cfg::{self, CfgPrivate},
     clear::Clear,
+    deferred::{DeferredTakes, Delivery},
     page,
+    stats::{
+        Counters, InsertFailures, PageStats, RemainingCapacity, ShardPages, ShardStats,
+        ShardlessFailures,
+    },
     sync::{
         alloc,
         atomic::{
-            AtomicPtr, AtomicUsize,
+            AtomicBool, AtomicPtr, AtomicUsize,
             Ordering::{self, *},
         },
     },
     tid::Tid,
-    Pack,
+    wait::Vacancies,
+    InsertError, Lifecycle, Pack,
 };
 
-use std::{fmt, ptr, slice};
+use std::{cell::Cell, cmp, fmt, ptr, slice, sync::Arc};
 
 // ┌─────────────┐      ┌────────┐
 // │ page 1      │      │        │
@@ -43,23 +49,177 @@ pub(crate) struct Shard<T, C: cfg::Config> {
     /// stored separately from the shared state for the page that can be
     /// accessed concurrently, to minimize false sharing.
     local: Box<[page::Local]>,
-    /// The shared state for each page in this shard.
+    /// The shared state for the first page in this shard.
+    ///
+    /// The first page is always the first to be filled, so most lookups in a
+    /// lightly loaded shard will land on it. Storing its metadata inline in
+    /// the shard, rather than in the `shared` array, saves a pointer
+    /// dereference on that path.
+    first: page::Shared<T, C>,
+    /// The shared state for every page after the first in this shard.
     ///
     /// This consists of the page's metadata (size, previous size), remote free
     /// list, and a pointer to the actual array backing that page.
     shared: Box<[page::Shared<T, C>]>,
+    /// Observes the entries in this shard, if the slab has an observer.
+    lifecycle: Option<Arc<dyn Lifecycle>>,
+    /// Receives the values whose removal was deferred until their last guard
+    /// was dropped, if the slab has a reaper.
+    reaper: Option<Reaper<T>>,
+    /// Creates the value stored in each slot when it is first used.
+    factory: Factory<T>,
+    /// Counts operations on this shard's slots.
+    pub(crate) counters: Counters,
+    /// The maximum number of values this shard may hold at once, or `!0` if
+    /// it may hold as many as it has slots for.
+    ///
+    /// This is shared by every shard in the array, so that it may be changed
+    /// while the slab is in use. Like the counters, it doesn't synchronize
+    /// access to anything, so it is always a `std` atomic.
+    quota: Arc<std::sync::atomic::AtomicUsize>,
+    /// The tag added to each key issued by this shard, if `C::TAG_BITS` is
+    /// nonzero, already shifted into place.
+    tag: usize,
+    /// Held while inserting into this shard, if `C::SHARED_INSERTS` is set.
+    ///
+    /// In that case, any thread may insert into the shard, so its local free
+    /// lists must be locked. No thread frees slots to the local free lists,
+    /// so they are only accessed while inserting.
+    inserting: AtomicBool,
+    /// The tasks waiting for a slot in this shard's array to be freed.
+    vacancies: Arc<Vacancies>,
+    /// The takes waiting for the last guard referencing their value in this
+    /// shard to be dropped.
+    deferred: DeferredTakes<T>,
+    /// Recent lifecycle events on this shard's slots.
+    #[cfg(feature = "event-log")]
+    pub(crate) events: crate::events::EventLog,
+}
+
+/// An iterator over the shared state of each page in a shard.
+#[derive(Debug)]
+pub(crate) struct Pages<'a, T, C> {
+    first: Option<&'a page::Shared<T, C>>,
+    rest: slice::Iter<'a, page::Shared<T, C>>,
 }
 
 pub(crate) struct Array<T, C: cfg::Config> {
-    shards: Box<[Ptr<T, C>]>,
+    /// The shards with the lowest indices, or null if the table hasn't been
+    /// allocated yet.
+    ///
+    /// This points to the table's first segment, so that the shards in it may
+    /// be accessed without going through the table.
+    ///
+    /// This, `len`, `table` and `id` are always `std` atomics, even when
+    /// testing with loom: they only publish the table, which is allocated
+    /// once and never modified, so they don't need to be modelled.
+    shards: std::sync::atomic::AtomicPtr<Ptr<T, C>>,
+    /// The number of shards that `shards` points to.
+    len: std::sync::atomic::AtomicUsize,
+    /// The parts of the array which must be allocated, or null if they
+    /// haven't been allocated yet.
+    ///
+    /// These are allocated the first time a shard is allocated, rather than
+    /// when the array is constructed, so that a slab or pool may be
+    /// constructed in a `const` context, such as the initializer of a
+    /// `static`.
+    table: std::sync::atomic::AtomicPtr<Table<T, C>>,
     max: AtomicUsize,
+    /// The number of values inserted, if `C::BALANCED_INSERTS` is set, which
+    /// determines the shard that the next value is inserted into.
+    next_balanced: AtomicUsize,
+    /// The table's ID, or zero if it hasn't been allocated yet.
+    id: std::sync::atomic::AtomicUsize,
+    /// The observer given to each shard when it is allocated.
+    lifecycle: Option<Arc<dyn Lifecycle>>,
+    /// The reaper given to each shard when it is allocated.
+    reaper: Option<Reaper<T>>,
+    /// The factory given to each shard when it is allocated.
+    factory: Factory<T>,
+    /// The tag given to each shard when it is allocated, if it was adopted
+    /// from a key, rather than determined by the array's ID.
+    tag: Option<usize>,
+    /// Set once the array is closed, after which no values may be inserted
+    /// into any of its shards.
+    closed: AtomicBool,
+    /// Counts the inserts which failed because the inserting thread had no
+    /// shard.
+    pub(crate) failures: ShardlessFailures,
+}
+
+/// The parts of a shard array which are allocated along with its first shard.
+struct Table<T, C: cfg::Config> {
+    /// The shards with the lowest indices.
+    ///
+    /// This has room for `FIRST_SEGMENT_LEN` shards or, if
+    /// `C::AUTO_SIZE_SHARDS` is set, as many shards as the available
+    /// parallelism.
+    shards: Box<[Ptr<T, C>]>,
+    /// The shards with indices past the end of `shards`.
+    ///
+    /// Like the pages in a shard, each segment is twice the size of the
+    /// previous one, with the first the same size as `shards`. A segment is
+    /// allocated the first time a thread with an index in it accesses the
+    /// array, so a high `C::MAX_THREADS` costs only one pointer per segment
+    /// until that many threads are actually used.
+    segments: Box<[AtomicPtr<Segment<T, C>>]>,
+    /// Uniquely identifies this shard array, so that threads may cache a
+    /// pointer to their shard without risking confusing it with a shard in
+    /// another array (or in a since-deallocated array at the same address).
+    ///
+    /// IDs start at one, so that an array whose table hasn't been allocated
+    /// never matches a cached shard.
+    id: usize,
+    /// The quota shared by each shard.
+    quota: Arc<std::sync::atomic::AtomicUsize>,
+    /// The tasks waiting for a slot in any shard to be freed.
+    vacancies: Arc<Vacancies>,
+}
+
+/// Receives a value removed from a shard, in place of dropping it.
+pub(crate) type Reaper<T> = Arc<dyn Fn(T) + Send + Sync>;
+
+/// Creates the initial value of each slot in a shard.
+///
+/// A `Pool`'s slots hold values even while they are vacant. Usually, these are
+/// created by `T::default`, but a `Pool` may also be constructed with a
+/// factory function.
+pub(crate) enum Factory<T> {
+    /// Create values using `T::default`.
+    ///
+    /// This is a function pointer, rather than a trait object, so that it
+    /// doesn't require `T: 'static`.
+    Default(fn() -> T),
+    /// Create values using a user-provided factory.
+    Custom(Arc<dyn Fn() -> T + Send + Sync>),
+}
+
+/// A free list which records whether a slot was released to it, so that the
+/// shard's observer can be notified once the slot has been cleared.
+struct Observed<'a, F> {
+    free_list: &'a F,
+    released: Cell<bool>,
 }
 
 #[derive(Debug)]
 struct Ptr<T, C: cfg::Config>(AtomicPtr<alloc::Track<Shard<T, C>>>);
 
+/// A contiguous range of shards in a shard array.
+type Segment<T, C> = Box<[Ptr<T, C>]>;
+
+/// An iterator over the pointers to every shard up to the highest allocated
+/// index.
+struct Ptrs<'a, T, C: cfg::Config> {
+    array: &'a Array<T, C>,
+    next: usize,
+    end: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct Iter<'a, T: 'a, C: cfg::Config + 'a>(Ptrs<'a, T, C>);
+
 #[derive(Debug)]
-pub(crate) struct IterMut<'a, T: 'a, C: cfg::Config + 'a>(slice::IterMut<'a, Ptr<T, C>>);
+pub(crate) struct IterMut<'a, T: 'a, C: cfg::Config + 'a>(Ptrs<'a, T, C>);
 
 // === impl Shard ===
 
@@ -74,19 +234,61 @@ where
         f: impl FnOnce(&'a page::Slot<T, C>) -> Option<U>,
     ) -> Option<U> {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return None;
+        }
         let (addr, page_index) = page::indices::<C>(idx);
 
         test_println!("-> {:?}", addr);
-        if page_index >= self.shared.len() {
-            return None;
+        self.page(page_index)?.with_slot(addr, f)
+    }
+
+    /// Records that the value for `key` was accessed, if `C::TRACK_ACCESS` is
+    /// set.
+    #[inline]
+    pub(crate) fn record_access(&self, key: usize) {
+        #[cfg(feature = "event-log")]
+        self.events.record(crate::EventKind::Get, key);
+        if C::TRACK_ACCESS {
+            let (addr, page_index) = page::indices::<C>(key);
+            if let Some(page) = self.page(page_index) {
+                page.record_access(addr);
+            }
         }
+    }
 
-        self.shared[page_index].with_slot(addr, f)
+    /// Returns when the value in the slot for `key` was inserted, if
+    /// `C::TRACK_INSERTED` is set.
+    ///
+    /// This doesn't check the key's generation.
+    pub(crate) fn inserted_at(&self, key: usize) -> page::timestamps::Tick {
+        let (addr, page_index) = page::indices::<C>(key);
+        self.page(page_index)
+            .map_or(page::timestamps::NEVER, |page| page.inserted_at(addr))
+    }
+
+    /// Returns when the slot for `key` was last used, if `C::TRACK_IDLE` or
+    /// `C::TRACK_ACCESS` is set.
+    ///
+    /// This doesn't check the key's generation.
+    pub(crate) fn last_used_at(&self, key: usize) -> page::timestamps::Tick {
+        let (addr, page_index) = page::indices::<C>(key);
+        self.page(page_index)
+            .map_or(page::timestamps::NEVER, |page| page.last_used_at(addr))
     }
 
-    pub(crate) fn new(tid: usize) -> Self {
-        let mut total_sz = 0;
-        let shared = (0..C::MAX_PAGES)
+    pub(crate) fn new(
+        tid: usize,
+        lifecycle: Option<Arc<dyn Lifecycle>>,
+        reaper: Option<Reaper<T>>,
+        factory: Factory<T>,
+        tag: usize,
+        quota: Arc<std::sync::atomic::AtomicUsize>,
+        vacancies: Arc<Vacancies>,
+    ) -> Self {
+        let first = page::Shared::new(C::page_size(0), 0);
+        let mut total_sz = first.size();
+        let shared = (1..C::MAX_PAGES)
             .map(|page_num| {
                 let sz = C::page_size(page_num);
                 let prev_sz = total_sz;
@@ -95,7 +297,139 @@ where
             })
             .collect();
         let local = (0..C::MAX_PAGES).map(|_| page::Local::new()).collect();
-        Self { tid, local, shared }
+        Self {
+            tid,
+            local,
+            first,
+            shared,
+            lifecycle,
+            reaper,
+            factory,
+            counters: Counters::default(),
+            quota,
+            tag,
+            inserting: AtomicBool::new(false),
+            vacancies,
+            deferred: DeferredTakes::default(),
+            #[cfg(feature = "event-log")]
+            events: Default::default(),
+        }
+    }
+
+    /// Adds this shard's tag to a key for one of its slots.
+    #[inline(always)]
+    pub(crate) fn tag(&self, key: usize) -> usize {
+        key | self.tag
+    }
+
+    /// Returns `true` if `key` was issued by the slab that owns this shard,
+    /// as far as can be told from its tag.
+    ///
+    /// If debug assertions are enabled, this panics if the key was tagged by
+    /// a different slab.
+    #[inline(always)]
+    fn owns(&self, key: usize) -> bool {
+        if C::TAG_BITS == 0 {
+            return true;
+        }
+        let tag = key & C::TAG_MASK;
+        if tag == self.tag {
+            return true;
+        }
+        // Untagged keys weren't issued by any slab, so they are merely
+        // invalid, like any other key that doesn't refer to a value.
+        if cfg!(any(debug_assertions, feature = "strict")) && tag != 0 {
+            panic_in_drop!(
+                "key {:#x} was issued by a different slab (tag {:#x}, expected {:#x})",
+                key,
+                tag >> C::TAG_SHIFT,
+                self.tag >> C::TAG_SHIFT,
+            );
+        }
+        false
+    }
+
+    /// Returns the shared state for the page at `idx`, if one exists.
+    #[inline(always)]
+    pub(crate) fn page(&self, idx: usize) -> Option<&page::Shared<T, C>> {
+        if idx == 0 {
+            Some(&self.first)
+        } else {
+            self.shared.get(idx - 1)
+        }
+    }
+
+    /// Returns an iterator over the shared state of each page in this shard.
+    pub(crate) fn pages(&self) -> Pages<'_, T, C> {
+        Pages {
+            first: Some(&self.first),
+            rest: self.shared.iter(),
+        }
+    }
+
+    /// Notifies this shard's observer, if it has one, that a value was
+    /// inserted at `key`.
+    #[inline]
+    pub(crate) fn on_insert(&self, key: usize) {
+        #[cfg(feature = "event-log")]
+        self.events.record(crate::EventKind::Insert, key);
+        if let Some(ref lifecycle) = self.lifecycle {
+            lifecycle.on_insert(key);
+        }
+    }
+
+    /// Notifies any tasks waiting to take the value at `key` that a guard
+    /// referencing it was dropped, and records the event if the `event-log`
+    /// feature is enabled.
+    #[inline]
+    pub(crate) fn on_release(&self, key: usize) {
+        #[cfg(feature = "event-log")]
+        self.events.record(crate::EventKind::Release, key);
+        self.vacancies.notify_release(C::strip_reserved(key));
+    }
+
+    /// Calls `f` with a wrapper around `free_list`, and notifies this shard's
+    /// observer if `f` marked the value at `key` for removal (as determined by
+    /// `marked`) or cleared it.
+    #[inline]
+    fn observe<F, R>(
+        &self,
+        key: usize,
+        free_list: &F,
+        f: impl FnOnce(&Observed<'_, F>) -> R,
+        marked: impl FnOnce(&R) -> bool,
+    ) -> R {
+        let free_list = Observed {
+            free_list,
+            released: Cell::new(false),
+        };
+        let result = f(&free_list);
+        let released = free_list.released.get();
+        if released {
+            self.vacancies.notify(C::strip_reserved(key));
+        }
+        if cfg!(not(feature = "event-log")) && self.lifecycle.is_none() {
+            return result;
+        }
+        let marked = marked(&result);
+        #[cfg(feature = "event-log")]
+        {
+            if marked {
+                self.events.record(crate::EventKind::Mark, key);
+            }
+            if released {
+                self.events.record(crate::EventKind::Clear, key);
+            }
+        }
+        if let Some(ref lifecycle) = self.lifecycle {
+            if marked {
+                lifecycle.on_mark(key);
+            }
+            if released {
+                lifecycle.on_clear(key);
+            }
+        }
+        result
     }
 }
 
@@ -103,238 +437,1263 @@ impl<T, C> Shard<Option<T>, C>
 where
     C: cfg::Config,
 {
+    /// Returns the number of outstanding references to the value for `key`,
+    /// or `None` if the slot doesn't hold a value at the key's generation.
+    pub(crate) fn ref_count(&self, key: usize) -> Option<usize> {
+        let refs = self.with_slot(key, |slot| slot.ref_count(C::unpack_gen(key)))?;
+        // A referenced slot must hold a value, but an unreferenced slot at
+        // the key's generation may be vacant.
+        if refs == 0 && !self.holds_value(key) {
+            return None;
+        }
+        Some(refs)
+    }
+
+    /// Returns `true` if the slot for `key` holds a value at the key's
+    /// generation.
+    ///
+    /// Unlike acquiring an `Entry`, this doesn't count as an access to the
+    /// value.
+    pub(crate) fn holds_value(&self, key: usize) -> bool {
+        self.with_slot(key, |slot| {
+            let guard = slot.get(C::unpack_gen(key))?;
+            // The guard prevents the slot from being cleared or reused, so
+            // its value may be read.
+            let present = slot.value().is_some();
+            let should_remove = unsafe {
+                // Safety: the guard is not used again after it is released,
+                // and the slot is borrowed from the shard.
+                guard.release()
+            };
+            self.on_release(key);
+            if should_remove {
+                self.clear_after_release(key);
+            }
+            Some(present)
+        })
+        .unwrap_or(false)
+    }
+
     /// Remove an item on the shard's local thread.
     pub(crate) fn take_local(&self, idx: usize) -> Option<T> {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return None;
+        }
         let (addr, page_index) = page::indices::<C>(idx);
 
         test_println!("-> remove_local {:?}", addr);
 
-        self.shared
-            .get(page_index)?
-            .take(addr, C::unpack_gen(idx), self.local(page_index))
+        let page = self.page(page_index)?;
+        self.observe(
+            idx,
+            self.local(page_index),
+            |local| page.take(addr, C::unpack_gen(idx), local),
+            |_| false,
+        )
     }
 
     /// Remove an item, while on a different thread from the shard's local thread.
     pub(crate) fn take_remote(&self, idx: usize) -> Option<T> {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
-        debug_assert!(Tid::<C>::current().as_usize() != self.tid);
+        if !self.owns(idx) {
+            return None;
+        }
+        debug_assert!(C::SHARED_INSERTS || Tid::<C>::current().as_usize() != self.tid);
 
         let (addr, page_index) = page::indices::<C>(idx);
 
         test_println!("-> take_remote {:?}; page {:?}", addr, page_index);
 
-        let shared = self.shared.get(page_index)?;
-        shared.take(addr, C::unpack_gen(idx), shared.free_list())
+        let shared = self.page(page_index)?;
+        self.observe(
+            idx,
+            shared.free_list(),
+            |remote| shared.take(addr, C::unpack_gen(idx), remote),
+            |_| false,
+        )
     }
 
-    pub(crate) fn remove_local(&self, idx: usize) -> bool {
+    /// Removes an item if it isn't referenced, returning `Poll::Pending` if
+    /// it is.
+    pub(crate) fn poll_take(&self, idx: usize) -> std::task::Poll<Option<T>> {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return std::task::Poll::Ready(None);
+        }
         let (addr, page_index) = page::indices::<C>(idx);
 
-        if page_index >= self.shared.len() {
-            return false;
-        }
+        test_println!("-> poll_take {:?}; page {:?}", addr, page_index);
 
-        self.shared[page_index].remove(addr, C::unpack_gen(idx), self.local(page_index))
+        let page = match self.page(page_index) {
+            Some(page) => page,
+            None => return std::task::Poll::Ready(None),
+        };
+        let gen = C::unpack_gen(idx);
+        if Tid::<C>::from_usize(self.tid).is_local() {
+            self.observe(
+                idx,
+                self.local(page_index),
+                |local| page.poll_take(addr, gen, local),
+                |_| false,
+            )
+        } else {
+            self.observe(
+                idx,
+                page.free_list(),
+                |remote| page.poll_take(addr, gen, remote),
+                |_| false,
+            )
+        }
     }
 
-    pub(crate) fn remove_remote(&self, idx: usize) -> bool {
+    /// Marks an item for removal, returning the delivery that it will be
+    /// passed to once it is no longer referenced.
+    pub(crate) fn take_deferred(&self, idx: usize) -> Option<Arc<Delivery<Option<T>>>> {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
-        let (addr, page_index) = page::indices::<C>(idx);
-
-        if page_index >= self.shared.len() {
-            return false;
+        if !self.owns(idx) {
+            return None;
         }
+        let (addr, page_index) = page::indices::<C>(idx);
 
-        let shared = &self.shared[page_index];
-        shared.remove(addr, C::unpack_gen(idx), shared.free_list())
-    }
-
-    pub(crate) fn iter(&self) -> std::slice::Iter<'_, page::Shared<Option<T>, C>> {
-        self.shared.iter()
-    }
-}
-
-impl<T, C> Shard<T, C>
-where
-    T: Clear + Default,
-    C: cfg::Config,
-{
-    pub(crate) fn init_with<U>(
-        &self,
-        mut init: impl FnMut(usize, &page::Slot<T, C>) -> Option<U>,
-    ) -> Option<U> {
-        // Can we fit the value into an exist`ing page?
-        for (page_idx, page) in self.shared.iter().enumerate() {
-            let local = self.local(page_idx);
-
-            test_println!("-> page {}; {:?}; {:?}", page_idx, local, page);
+        test_println!("-> take_deferred {:?}; page {:?}", addr, page_index);
 
-            if let Some(res) = page.init_with(local, &mut init) {
-                return Some(res);
+        let page = self.page(page_index)?;
+        let key = C::strip_reserved(idx);
+        let delivery = self.deferred.register(key)?;
+        // Marking the value doesn't free its slot, so the free list isn't
+        // used, but the mark is observed just as `remove` would be.
+        let marked = self.observe(
+            idx,
+            page.free_list(),
+            |_| page.mark_release(addr, C::unpack_gen(idx)),
+            Option::is_some,
+        );
+        match marked {
+            // The value is referenced, so whichever thread releases the last
+            // reference will claim the take and deliver it.
+            Some(false) => return Some(delivery),
+            Some(true) => {}
+            None => {
+                // There's nothing to take, unless the value was already
+                // marked for removal and has since been delivered to us.
+                return match self.deferred.claim(key) {
+                    Some(_) => None,
+                    None => Some(delivery),
+                };
             }
         }
 
-        None
+        let value = if Tid::<C>::from_usize(self.tid).is_local() {
+            self.take_local(idx)
+        } else {
+            self.take_remote(idx)
+        };
+        match (self.deferred.claim(key), value) {
+            // The value was taken concurrently.
+            (Some(_), None) => None,
+            (Some(_), value) => {
+                delivery.deliver(Some(value));
+                Some(delivery)
+            }
+            // A guard was released concurrently, and has delivered the value.
+            (None, _) => Some(delivery),
+        }
     }
 
-    pub(crate) fn mark_clear_local(&self, idx: usize) -> bool {
+    /// Returns a slot which was reserved by `init_with`, but never given a
+    /// value, to the free list.
+    ///
+    /// The slot's generation is not advanced, since its key was never handed
+    /// out for a value.
+    pub(crate) fn abandon(&self, idx: usize) {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
         let (addr, page_index) = page::indices::<C>(idx);
+        let page = match self.page(page_index) {
+            Some(page) => page,
+            None => return,
+        };
+        if Tid::<C>::from_usize(self.tid).is_local() {
+            page.abandon(addr, self.local(page_index));
+        } else {
+            page.abandon(addr, page.free_list());
+        }
+        self.vacancies.notify(C::strip_reserved(idx));
+    }
 
-        if page_index >= self.shared.len() {
+    pub(crate) fn remove_local(&self, idx: usize) -> bool {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
             return false;
         }
+        let (addr, page_index) = page::indices::<C>(idx);
 
-        self.shared[page_index].mark_clear(addr, C::unpack_gen(idx), self.local(page_index))
+        match self.page(page_index) {
+            Some(page) => self.observe(
+                idx,
+                self.local(page_index),
+                |local| page.remove(addr, C::unpack_gen(idx), local),
+                |&removed| removed,
+            ),
+            None => false,
+        }
     }
 
-    pub(crate) fn mark_clear_remote(&self, idx: usize) -> bool {
+    pub(crate) fn remove_remote(&self, idx: usize) -> bool {
         debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return false;
+        }
         let (addr, page_index) = page::indices::<C>(idx);
 
-        if page_index >= self.shared.len() {
-            return false;
+        match self.page(page_index) {
+            Some(shared) => self.observe(
+                idx,
+                shared.free_list(),
+                |remote| shared.remove(addr, C::unpack_gen(idx), remote),
+                |&removed| removed,
+            ),
+            None => false,
         }
+    }
 
-        let shared = &self.shared[page_index];
-        shared.mark_clear(addr, C::unpack_gen(idx), shared.free_list())
+    pub(crate) fn iter(&self) -> Pages<'_, Option<T>, C> {
+        self.pages()
     }
 
-    pub(crate) fn clear_after_release(&self, idx: usize) {
-        crate::sync::atomic::fence(crate::sync::atomic::Ordering::Acquire);
-        let tid = Tid::<C>::current().as_usize();
-        test_println!(
-            "-> clear_after_release; self.tid={:?}; current.tid={:?};",
-            tid,
-            self.tid
-        );
-        if tid == self.tid {
-            self.clear_local(idx);
-        } else {
-            self.clear_remote(idx);
-        }
+    /// Returns the layout of each of this shard's pages, for
+    /// `Slab::visualize`.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    #[cfg(feature = "debug-tools")]
+    pub(crate) unsafe fn layout(&self) -> (crate::ShardId, Vec<page::Layout>) {
+        let pages = self
+            .pages()
+            .zip(self.local.iter())
+            .map(|(page, local)| page.layout(local))
+            .collect();
+        (Tid::<C>::from_usize(self.tid).into(), pages)
     }
 
-    fn clear_local(&self, idx: usize) -> bool {
-        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
-        let (addr, page_index) = page::indices::<C>(idx);
+    /// Moves the values in this shard into its lowest vacant slots, and
+    /// releases the storage for any pages left empty.
+    ///
+    /// `remap` is called with the old and new keys of each value that is moved.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    pub(crate) unsafe fn compact(&self, remap: &mut impl FnMut(usize, usize)) {
+        let tid = Tid::<C>::from_usize(self.tid);
+        test_println!("-> compact {:?}", tid);
 
-        if page_index >= self.shared.len() {
-            return false;
+        // Move values from the highest occupied slots into the lowest vacant
+        // ones, until every value is below every vacancy.
+        let mut occupied = self
+            .pages()
+            .flat_map(|page| page.occupied())
+            .collect::<Vec<_>>();
+        for to in self.pages().flat_map(|page| page.vacant()) {
+            let from = match occupied.pop() {
+                Some(from) if from > to => from,
+                _ => break,
+            };
+            let src = self
+                .page(from.index())
+                .expect("occupied slot must be in a page");
+            let dst = self
+                .page(to.index())
+                .expect("vacant slot must be in a page");
+            let (old_gen, new_gen) = src.relocate(from, dst, to);
+            remap(
+                self.tag(tid.pack(old_gen.pack(from.as_usize()))),
+                self.tag(tid.pack(new_gen.pack(to.as_usize()))),
+            );
         }
 
-        self.shared[page_index].clear(addr, C::unpack_gen(idx), self.local(page_index))
+        self.reset_free_lists();
     }
 
-    fn clear_remote(&self, idx: usize) -> bool {
-        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
-        let (addr, page_index) = page::indices::<C>(idx);
+    /// Returns each key in this shard whose generation is current.
+    ///
+    /// Since the shard may be modified concurrently, a key may not refer to a
+    /// value.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = usize> + '_ {
+        let tid = Tid::<C>::from_usize(self.tid);
+        self.pages()
+            .flat_map(|page| page.keys())
+            .map(move |key| self.tag(tid.pack(key)))
+    }
+
+    /// Like `keys`, but skips pages which don't contain any values.
+    pub(crate) fn live_keys(&self) -> impl Iterator<Item = usize> + '_ {
+        let tid = Tid::<C>::from_usize(self.tid);
+        self.pages()
+            .filter(|page| page.live() > 0)
+            .flat_map(|page| page.keys())
+            .map(move |key| self.tag(tid.pack(key)))
+    }
+
+    /// Returns the key of every slot in the page at `idx`, or `None` if that
+    /// page hasn't been allocated.
+    ///
+    /// Since the shard may be modified concurrently, a key may not refer to a
+    /// value.
+    pub(crate) fn page_keys(&self, idx: usize) -> Option<Vec<usize>> {
+        let page = self.page(idx)?;
+        if page.is_unallocated() {
+            return None;
+        }
+        let tid = Tid::<C>::from_usize(self.tid);
+        Some(page.keys().map(|key| self.tag(tid.pack(key))).collect())
+    }
 
-        if page_index >= self.shared.len() {
+    /// Stores `value` in the slot for `key`, returning `false` if the key is
+    /// out of range, or that slot already contains a value.
+    ///
+    /// `reset_free_lists` must be called before any more values are inserted.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    pub(crate) unsafe fn restore(&self, key: usize, value: T) -> bool {
+        strict_assert_eq!(Tid::<C>::from_packed(key).as_usize(), self.tid);
+        if !self.owns(key) {
             return false;
         }
+        let (addr, page_index) = page::indices::<C>(key);
+        match self.page(page_index) {
+            Some(page) => page.restore(addr, C::unpack_gen(key), value),
+            None => false,
+        }
+    }
 
-        let shared = &self.shared[page_index];
-        shared.clear(addr, C::unpack_gen(idx), shared.free_list())
+    /// Returns statistics describing this shard's pages.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    pub(crate) unsafe fn stats(&self) -> ShardStats {
+        let mut stats = ShardStats::new(Tid::<C>::from_usize(self.tid).into());
+        stats.failed_inserts = self.counters.failed_inserts.load(Relaxed);
+        for (page, local) in self.pages().zip(self.local.iter()) {
+            stats.generation_wraps += page.generation_wraps();
+            if page.is_unallocated() {
+                continue;
+            }
+            let (local_free, remote_free) = page.free_list_lens(local);
+            stats.allocated_pages += 1;
+            stats.occupied += page.occupied().count();
+            stats.local_free += local_free;
+            stats.remote_free += remote_free;
+        }
+        stats
     }
 
-    #[inline(always)]
-    fn local(&self, i: usize) -> &page::Local {
-        #[cfg(debug_assertions)]
-        debug_assert_eq_in_drop!(
-            Tid::<C>::current().as_usize(),
-            self.tid,
-            "tried to access local data from another thread!"
-        );
+    /// Returns the number of vacant slots in this shard's allocated pages,
+    /// and the number of slots in its unallocated pages, limited by the
+    /// shard's quota.
+    ///
+    /// This must only be called by the thread that owns the shard, unless
+    /// `C::SHARED_INSERTS` is set.
+    pub(crate) fn remaining(&self) -> RemainingCapacity {
+        let _lock = if C::SHARED_INSERTS {
+            Some(self.lock_inserts())
+        } else {
+            None
+        };
 
-        &self.local[i]
+        let mut remaining = RemainingCapacity {
+            vacant: 0,
+            unallocated: 0,
+        };
+        for (page_idx, page) in self.pages().enumerate() {
+            if page.is_unallocated() {
+                remaining.unallocated += page.size();
+                continue;
+            }
+            let (local_free, remote_free) = unsafe {
+                // Safety: only the owning thread (or the thread holding the
+                // insert lock) pops from a page's free lists, so they can't
+                // be shortened while we walk them. Slots pushed to the remote
+                // list concurrently are prepended, and may not be counted.
+                page.free_list_lens(self.local(page_idx))
+            };
+            remaining.vacant += local_free + remote_free;
+        }
+        remaining.clamp(self.remaining_quota())
     }
-}
 
-impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Shard<T, C> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut d = f.debug_struct("Shard");
+    /// Returns how full each of this shard's pages is.
+    ///
+    /// Unlike `stats`, this may be called concurrently.
+    pub(crate) fn page_stats(&self) -> ShardPages {
+        let pages = self
+            .pages()
+            .map(|page| PageStats {
+                occupied: page.live(),
+                size: page.size(),
+                allocated: page.allocated_slots() != 0,
+            })
+            .collect();
+        ShardPages {
+            id: Tid::<C>::from_usize(self.tid).into(),
+            pages,
+        }
+    }
 
-        #[cfg(debug_assertions)]
-        d.field("tid", &self.tid);
-        d.field("shared", &self.shared).finish()
+    /// Rebuilds the free lists for every page in this shard, releasing the
+    /// storage for any empty pages.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    pub(crate) unsafe fn reset_free_lists(&self) {
+        for (page, local) in self.pages().zip(self.local.iter()) {
+            page.reset_free_lists(local);
+        }
     }
 }
 
-// === impl Array ===
-
-impl<T, C> Array<T, C>
+impl<T, C> Shard<T, C>
 where
+    T: Clear,
     C: cfg::Config,
 {
-    pub(crate) fn new() -> Self {
-        let mut shards = Vec::with_capacity(C::MAX_SHARDS);
-        for _ in 0..C::MAX_SHARDS {
-            // XXX(eliza): T_T this could be avoided with maybeuninit or something...
-            shards.push(Ptr::null());
-        }
-        Self {
-            shards: shards.into(),
-            max: AtomicUsize::new(0),
-        }
+    pub(crate) fn init_with<U>(
+        &self,
+        init: impl FnMut(usize, &page::Slot<T, C>) -> Option<U>,
+    ) -> Option<U> {
+        self.try_init_with(init).ok()
     }
 
-    #[inline]
-    pub(crate) fn get(&self, idx: usize) -> Option<&Shard<T, C>> {
-        test_println!("-> get shard={}", idx);
-        self.shards.get(idx)?.load(Acquire)
-    }
+    /// Like `init_with`, but returns the reason no slot could be initialized.
+    pub(crate) fn try_init_with<U>(
+        &self,
+        mut init: impl FnMut(usize, &page::Slot<T, C>) -> Option<U>,
+    ) -> Result<U, InsertError> {
+        let _lock = if C::SHARED_INSERTS {
+            Some(self.lock_inserts())
+        } else {
+            None
+        };
 
-    #[inline]
-    pub(crate) fn current(&self) -> (Tid<C>, &Shard<T, C>) {
-        let tid = Tid::<C>::current();
-        test_println!("current: {:?}", tid);
-        let idx = tid.as_usize();
-        assert!(
-            idx < self.shards.len(),
-            "Thread count overflowed the configured max count. \
-            Thread index = {}, max threads = {}.",
-            idx,
-            C::MAX_SHARDS,
-        );
-        // It's okay for this to be relaxed. The value is only ever stored by
-        // the thread that corresponds to the index, and we are that thread.
-        let shard = self.shards[idx].load(Relaxed).unwrap_or_else(|| {
-            let ptr = Box::into_raw(Box::new(alloc::Track::new(Shard::new(idx))));
-            test_println!("-> allocated new shard for index {} at {:p}", idx, ptr);
-            self.shards[idx].set(ptr);
-            let mut max = self.max.load(Acquire);
-            while max < idx {
-                match self.max.compare_exchange(max, idx, AcqRel, Acquire) {
-                    Ok(_) => break,
-                    Err(actual) => max = actual,
+        // Only this shard's thread inserts into it, unless inserts are shared
+        // and the lock is held, so no other insert can exceed the quota
+        // between checking it and initializing the slot.
+        if self.remaining_quota() == 0 {
+            test_println!("-> shard {} is at its quota", self.tid);
+            self.counters.quota_exceeded.fetch_add(1, Relaxed);
+            return Err(InsertError::QuotaExceeded);
+        }
+
+        // Can we fit the value into an exist`ing page?
+        for (page_idx, page) in self.pages().enumerate() {
+            let local = self.local(page_idx);
+
+            test_println!("-> page {}; {:?}; {:?}", page_idx, local, page);
+
+            let unallocated = self.lifecycle.is_some() && page.is_unallocated();
+            let res = page.init_with_factory(local, || self.new_value(), &mut init);
+            if unallocated && !page.is_unallocated() {
+                if let Some(ref lifecycle) = self.lifecycle {
+                    lifecycle.on_page_alloc(self.tid, page_idx, page.size());
                 }
             }
-            test_println!("-> highest index={}, prev={}", std::cmp::max(max, idx), max);
-            unsafe {
-                // Safety: we just put it there!
-                &*ptr
+            if let Some(res) = res {
+                self.counters.created.fetch_add(1, Relaxed);
+                return Ok(res);
             }
-            .get_ref()
-        });
-        (tid, shard)
-    }
+            if page_idx + 1 < C::MAX_PAGES {
+                self.counters.pages_skipped.fetch_add(1, Relaxed);
+            }
+        }
 
-    pub(crate) fn iter_mut(&mut self) -> IterMut<'_, T, C> {
-        test_println!("Array::iter_mut");
-        let max = self.max.load(Acquire);
-        test_println!("-> highest index={}", max);
-        IterMut(self.shards[0..=max].iter_mut())
+        self.counters.failed_inserts.fetch_add(1, Relaxed);
+        Err(InsertError::Full)
     }
-}
 
-impl<T, C: cfg::Config> Drop for Array<T, C> {
+    /// Like `init_with`, but may be called from any thread, without taking
+    /// the insert lock.
+    ///
+    /// This ignores the shard's quota, and its lifecycle hooks aren't called.
+    ///
+    /// ## Safety
+    ///
+    /// The caller must have exclusive access to the shard.
+    pub(crate) unsafe fn init_with_exclusive<U>(
+        &self,
+        mut init: impl FnMut(usize, &page::Slot<T, C>) -> Option<U>,
+    ) -> Option<U> {
+        let res = self
+            .pages()
+            .zip(self.local.iter())
+            .find_map(|(page, local)| {
+                page.init_with_factory(local, || self.new_value(), &mut init)
+            });
+        if res.is_some() {
+            self.counters.created.fetch_add(1, Relaxed);
+        }
+        res
+    }
+
+    /// Returns how many more values this shard may hold before it reaches
+    /// its quota.
+    ///
+    /// If the shard has no quota, this doesn't count the values it holds.
+    fn remaining_quota(&self) -> usize {
+        let quota = self.quota.load(Relaxed);
+        if quota == !0 {
+            return !0;
+        }
+        let live: usize = self.pages().map(|page| page.live()).sum();
+        quota.saturating_sub(live)
+    }
+
+    /// Like `init_with`, but initializes up to `n` slots in a single pass over
+    /// the shard's pages, pushing the results onto `out`.
+    pub(crate) fn init_many<U>(
+        &self,
+        n: usize,
+        mut init: impl FnMut(usize, &page::Slot<T, C>) -> Option<U>,
+        out: &mut Vec<U>,
+    ) {
+        let _lock = if C::SHARED_INSERTS {
+            Some(self.lock_inserts())
+        } else {
+            None
+        };
+
+        let quota = self.remaining_quota();
+        if quota < n {
+            test_println!("-> shard {} is at its quota", self.tid);
+            self.counters.quota_exceeded.fetch_add(n - quota, Relaxed);
+        }
+        let target = out.len() + cmp::min(n, quota);
+        for (page_idx, page) in self.pages().enumerate() {
+            if out.len() == target {
+                break;
+            }
+
+            let local = self.local(page_idx);
+            test_println!("-> page {}; {:?}; {:?}", page_idx, local, page);
+
+            let unallocated = self.lifecycle.is_some() && page.is_unallocated();
+            while out.len() < target {
+                match page.init_with_factory(local, || self.new_value(), &mut init) {
+                    Some(res) => {
+                        self.counters.created.fetch_add(1, Relaxed);
+                        out.push(res);
+                    }
+                    None => {
+                        if page_idx + 1 < C::MAX_PAGES {
+                            self.counters.pages_skipped.fetch_add(1, Relaxed);
+                        }
+                        break;
+                    }
+                }
+            }
+            if unallocated && !page.is_unallocated() {
+                if let Some(ref lifecycle) = self.lifecycle {
+                    lifecycle.on_page_alloc(self.tid, page_idx, page.size());
+                }
+            }
+        }
+
+        if out.len() < target {
+            self.counters
+                .failed_inserts
+                .fetch_add(target - out.len(), Relaxed);
+        }
+    }
+
+    /// Creates the value for a slot which is being used for the first time.
+    fn new_value(&self) -> T {
+        self.counters.constructed.fetch_add(1, Relaxed);
+        self.factory.create()
+    }
+
+    pub(crate) fn mark_clear_local(&self, idx: usize) -> bool {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return false;
+        }
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        match self.page(page_index) {
+            Some(page) => self.observe(
+                idx,
+                self.local(page_index),
+                |local| page.mark_clear(addr, C::unpack_gen(idx), local),
+                |&marked| marked,
+            ),
+            None => false,
+        }
+    }
+
+    pub(crate) fn mark_clear_remote(&self, idx: usize) -> bool {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return false;
+        }
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        match self.page(page_index) {
+            Some(shared) => self.observe(
+                idx,
+                shared.free_list(),
+                |remote| shared.mark_clear(addr, C::unpack_gen(idx), remote),
+                |&marked| marked,
+            ),
+            None => false,
+        }
+    }
+
+    pub(crate) fn take_storage_local(&self, idx: usize) -> Option<T> {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return None;
+        }
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        let page = self.page(page_index)?;
+        self.observe(
+            idx,
+            self.local(page_index),
+            |local| page.take_storage(addr, C::unpack_gen(idx), local, || self.factory.create()),
+            |_| false,
+        )
+    }
+
+    pub(crate) fn take_storage_remote(&self, idx: usize) -> Option<T> {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        if !self.owns(idx) {
+            return None;
+        }
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        let page = self.page(page_index)?;
+        self.observe(
+            idx,
+            page.free_list(),
+            |remote| page.take_storage(addr, C::unpack_gen(idx), remote, || self.factory.create()),
+            |_| false,
+        )
+    }
+
+    /// Evicts the values of released slots which have been idle for more than
+    /// `max_idle` ticks, returning the number of slots evicted.
+    pub(crate) fn evict_idle(
+        &self,
+        now: page::timestamps::Tick,
+        max_idle: page::timestamps::Tick,
+    ) -> usize {
+        self.pages()
+            .map(|page| page.evict_idle(now, max_idle, || self.factory.create()))
+            .sum()
+    }
+
+    pub(crate) fn clear_after_release(&self, idx: usize) {
+        crate::sync::atomic::fence(crate::sync::atomic::Ordering::Acquire);
+        let tid = Tid::<C>::from_usize(self.tid);
+        test_println!("-> clear_after_release; self.tid={:?};", tid);
+        if let Some(delivery) = self.deferred.claim(C::strip_reserved(idx)) {
+            // The value was taken by `Slab::take_deferred`, so hand it off
+            // rather than clearing it.
+            let value = if tid.is_local() {
+                self.take_storage_local(idx)
+            } else {
+                self.take_storage_remote(idx)
+            };
+            delivery.deliver(value);
+            return;
+        }
+        if let Some(ref reaper) = self.reaper {
+            // Hand the value to the reaper, rather than dropping it on this
+            // thread.
+            let value = if tid.is_local() {
+                self.take_storage_local(idx)
+            } else {
+                self.take_storage_remote(idx)
+            };
+            if let Some(value) = value {
+                reaper(value);
+            }
+            return;
+        }
+        if tid.is_local() {
+            self.clear_local(idx);
+        } else {
+            self.clear_remote(idx);
+        }
+    }
+
+    fn clear_local(&self, idx: usize) -> bool {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        match self.page(page_index) {
+            Some(page) => self.observe(
+                idx,
+                self.local(page_index),
+                |local| page.clear(addr, C::unpack_gen(idx), local),
+                |_| false,
+            ),
+            None => false,
+        }
+    }
+
+    fn clear_remote(&self, idx: usize) -> bool {
+        debug_assert_eq_in_drop!(Tid::<C>::from_packed(idx).as_usize(), self.tid);
+        let (addr, page_index) = page::indices::<C>(idx);
+
+        match self.page(page_index) {
+            Some(shared) => self.observe(
+                idx,
+                shared.free_list(),
+                |remote| shared.clear(addr, C::unpack_gen(idx), remote),
+                |_| false,
+            ),
+            None => false,
+        }
+    }
+
+    #[inline(always)]
+    fn local(&self, i: usize) -> &page::Local {
+        #[cfg(debug_assertions)]
+        if !C::SHARED_INSERTS {
+            debug_assert_eq_in_drop!(
+                Tid::<C>::current().as_usize(),
+                self.tid,
+                "tried to access local data from another thread!"
+            );
+        }
+
+        &self.local[i]
+    }
+
+    /// Locks this shard's local free lists, if `C::SHARED_INSERTS` is set.
+    fn lock_inserts(&self) -> InsertLock<'_> {
+        let mut backoff = 0;
+        while self
+            .inserting
+            .compare_exchange_weak(false, true, Acquire, Relaxed)
+            .is_err()
+        {
+            page::slot::exponential_backoff(&mut backoff);
+        }
+        InsertLock(&self.inserting)
+    }
+}
+
+/// Unlocks a shard's local free lists when dropped.
+struct InsertLock<'a>(&'a AtomicBool);
+
+impl Drop for InsertLock<'_> {
     fn drop(&mut self) {
-        // XXX(eliza): this could be `with_mut` if we wanted to impl a wrapper for std atomics to change `get_mut` to `with_mut`...
+        self.0.store(false, Release);
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Shard<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Shard");
+
+        #[cfg(debug_assertions)]
+        d.field("tid", &self.tid);
+        d.field("first", &self.first)
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+// === impl Observed ===
+
+impl<C: cfg::Config, F: page::FreeList<C>> page::FreeList<C> for Observed<'_, F> {
+    fn push<T>(&self, new_head: usize, slot: &page::Slot<T, C>) {
+        self.released.set(true);
+        self.free_list.push(new_head, slot)
+    }
+
+    fn as_local(&self) -> Option<&page::Local> {
+        self.free_list.as_local()
+    }
+}
+
+// === impl Factory ===
+
+impl<T> Factory<T> {
+    #[inline]
+    pub(crate) fn create(&self) -> T {
+        match self {
+            Factory::Default(default) => default(),
+            Factory::Custom(factory) => factory(),
+        }
+    }
+}
+
+impl<T> Clone for Factory<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Factory::Default(default) => Factory::Default(*default),
+            Factory::Custom(factory) => Factory::Custom(factory.clone()),
+        }
+    }
+}
+
+// === impl Pages ===
+
+impl<T, C> Pages<'_, T, C> {
+    /// Returns an iterator over no pages.
+    pub(crate) fn empty() -> Self {
+        Self {
+            first: None,
+            rest: [].iter(),
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Pages<'a, T, C> {
+    type Item = &'a page::Shared<T, C>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.first.take().or_else(|| self.rest.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.rest.len() + self.first.is_some() as usize;
+        (len, Some(len))
+    }
+}
+
+// === impl Array ===
+
+impl<T, C> Array<T, C>
+where
+    C: cfg::Config,
+{
+    const_fn! {
+        pub(crate) fn new() -> Self
+        where
+            T: Default,
+        {
+            Self::with_factory(Factory::Default(T::default))
+        }
+    }
+
+    const_fn! {
+        /// Returns an array which will create the initial value of each slot
+        /// with `factory`.
+        ///
+        /// Nothing is allocated until the first shard is.
+        pub(crate) fn with_factory(factory: Factory<T>) -> Self {
+            Self {
+                shards: std::sync::atomic::AtomicPtr::new(ptr::null_mut()),
+                len: std::sync::atomic::AtomicUsize::new(0),
+                table: std::sync::atomic::AtomicPtr::new(ptr::null_mut()),
+                max: AtomicUsize::new(0),
+                next_balanced: AtomicUsize::new(0),
+                id: std::sync::atomic::AtomicUsize::new(0),
+                lifecycle: None,
+                reaper: None,
+                factory,
+                tag: None,
+                closed: AtomicBool::new(false),
+                failures: ShardlessFailures::new(),
+            }
+        }
+    }
+
+    /// Sets the tag given to each shard to the tag of `key`.
+    ///
+    /// This must be called before any shards are allocated.
+    pub(crate) fn adopt_tag(&mut self, key: usize) {
+        debug_assert!(self.ptrs().all(|ptr| ptr.0.load(Relaxed).is_null()));
+        if C::TAG_BITS != 0 {
+            self.tag = Some(key & C::TAG_MASK);
+        }
+    }
+
+    /// Sets the observer for every shard in this array, including any which
+    /// have already been allocated.
+    pub(crate) fn set_lifecycle(&mut self, lifecycle: Arc<dyn Lifecycle>) {
+        for shard in self.ptrs() {
+            let ptr = shard.0.load(Acquire);
+            if !ptr.is_null() {
+                unsafe {
+                    // Safety: we have exclusive access to the shard array, so
+                    // no other references to its shards may exist.
+                    (*ptr).get_mut().lifecycle = Some(lifecycle.clone());
+                }
+            }
+        }
+        self.lifecycle = Some(lifecycle);
+    }
+
+    /// Sets the reaper for every shard in this array, including any which
+    /// have already been allocated.
+    pub(crate) fn set_reaper(&mut self, reaper: Reaper<T>) {
+        for shard in self.ptrs() {
+            let ptr = shard.0.load(Acquire);
+            if !ptr.is_null() {
+                unsafe {
+                    // Safety: we have exclusive access to the shard array, so
+                    // no other references to its shards may exist.
+                    (*ptr).get_mut().reaper = Some(reaper.clone());
+                }
+            }
+        }
+        self.reaper = Some(reaper);
+    }
+
+    /// Sets the quota for every shard in this array, or removes it if
+    /// `quota` is `!0`.
+    ///
+    /// Inserts which are in progress may not observe the new quota.
+    pub(crate) fn set_quota(&self, quota: usize) {
+        test_println!("Array::set_quota({})", quota);
+        self.table_or_alloc().quota.store(quota, Relaxed);
+    }
+
+    pub(crate) fn quota(&self) -> usize {
+        self.table().map_or(!0, |table| table.quota.load(Relaxed))
+    }
+
+    /// Returns the tasks waiting for a slot in any shard to be freed.
+    #[cfg(feature = "async")]
+    pub(crate) fn vacancies(&self) -> &Vacancies {
+        &self.table_or_alloc().vacancies
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, idx: usize) -> Option<&Shard<T, C>> {
+        test_println!("-> get shard={}", idx);
+        self.slot(idx)?.load(Acquire)
+    }
+
+    /// Prevents any more values from being inserted into this array's
+    /// shards.
+    pub(crate) fn close(&self) {
+        test_println!("Array::close");
+        self.closed.store(true, Release);
+        // If the table hasn't been allocated, no task can be waiting.
+        if let Some(table) = self.table() {
+            table.vacancies.close();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Acquire)
+    }
+
+    /// Returns the current thread's ID and shard, allocating the shard if it
+    /// doesn't exist.
+    ///
+    /// This returns `None` if the array has been closed, or if the current
+    /// thread's ID can't be accessed because its thread-local storage is
+    /// being destroyed.
+    #[inline]
+    pub(crate) fn current(&self) -> Option<(Tid<C>, &Shard<T, C>)> {
+        if self.is_closed() {
+            test_println!("current: array is closed; no shard");
+            return None;
+        }
+
+        if C::DETERMINISTIC || C::SINGLE_THREADED {
+            return Some((Tid::from_usize(0), self.get_or_alloc(0)));
+        }
+
+        if C::SHARD_HINTS {
+            if let Some(tid) = Tid::<C>::hinted() {
+                test_println!("current: hinted {:?}", tid);
+                return Some((tid, self.get_or_alloc(tid.as_usize())));
+            }
+        }
+
+        if C::BALANCED_INSERTS {
+            return Some(self.next_balanced());
+        }
+
+        let (tid, cached) = Tid::<C>::current_cached(self.id.load(Relaxed));
+        test_println!("current: {:?}; cached={:?}", tid, cached);
+        if let Some(shard) = cached {
+            let shard = unsafe {
+                // Safety: the cached pointer was stored by `current_slow` on
+                // this thread, for this array (IDs are never reused). Shards
+                // are only deallocated when the array is dropped, and the
+                // returned reference borrows the array.
+                &*(shard as *const alloc::Track<Shard<T, C>>)
+            };
+            return Some((tid, shard.get_ref()));
+        }
+
+        self.current_slow(tid)
+    }
+
+    /// Returns the next of the shards in the first segment, in round-robin
+    /// order, along with its ID.
+    ///
+    /// This is used in place of the current thread's shard if
+    /// `C::BALANCED_INSERTS` is set.
+    #[inline]
+    fn next_balanced(&self) -> (Tid<C>, &Shard<T, C>) {
+        let mut shards = self.first();
+        if shards.is_empty() {
+            shards = &self.table_or_alloc().shards;
+        }
+        let idx = self.next_balanced.fetch_add(1, Relaxed) % shards.len();
+        test_println!("-> balanced shard={}", idx);
+        (Tid::from_usize(idx), self.get_or_alloc(idx))
+    }
+
+    #[cold]
+    fn current_slow(&self, tid: Tid<C>) -> Option<(Tid<C>, &Shard<T, C>)> {
+        if tid.is_poisoned() {
+            test_println!("-> thread is exiting; no shard");
+            self.failures.thread_exiting.fetch_add(1, Relaxed);
+            return None;
+        }
+        let idx = tid.as_usize();
+        if idx >= C::MAX_SHARDS {
+            test_println!(
+                "-> thread index {} exceeds max threads {}; no shard",
+                idx,
+                C::MAX_SHARDS
+            );
+            self.failures.max_shards.fetch_add(1, Relaxed);
+            return None;
+        }
+        let ptr = self.get_or_alloc_raw(idx);
+        // Allocating the shard allocated the table, if it hadn't been already,
+        // so the array's ID has been assigned.
+        Tid::<C>::cache_shard(self.id.load(Relaxed), ptr as *const ());
+        let shard = unsafe {
+            // Safety: shards are only deallocated when the array is dropped,
+            // and the returned reference borrows the array.
+            &*ptr
+        };
+        Some((tid, shard.get_ref()))
+    }
+
+    /// Returns the shard at `idx`, allocating it if it doesn't exist.
+    ///
+    /// This must only be called by the thread that corresponds to the index,
+    /// or with exclusive access to the array, unless `C::SHARED_INSERTS` is
+    /// set.
+    pub(crate) fn get_or_alloc(&self, idx: usize) -> &Shard<T, C> {
+        unsafe {
+            // Safety: shards are only deallocated when the array is dropped,
+            // and the returned reference borrows the array.
+            &*self.get_or_alloc_raw(idx)
+        }
+        .get_ref()
+    }
+
+    /// Returns a pointer to the shard at `idx`, allocating it if it doesn't
+    /// exist.
+    ///
+    /// The returned pointer is the one stored in the shard's slot, so it
+    /// carries the provenance of the whole allocation rather than that of a
+    /// shared reference to it. This is the pointer that gets cached in the
+    /// current thread's ID.
+    fn get_or_alloc_raw(&self, idx: usize) -> *const alloc::Track<Shard<T, C>> {
+        let (slot, table) = match self.first().get(idx) {
+            Some(slot) => (slot, None),
+            None => {
+                let table = self.table_or_alloc();
+                (table.slot_or_alloc(idx), Some(table))
+            }
+        };
+        // It's okay for this to be relaxed. The value is only ever stored by
+        // the thread that corresponds to the index, and we are that thread ---
+        // unless inserts are shared, in which case any thread may store it.
+        let order = if C::SHARED_INSERTS { Acquire } else { Relaxed };
+        let existing = slot.load_raw(order);
+        if !existing.is_null() {
+            return existing;
+        }
+
+        let table = table.unwrap_or_else(|| self.table_or_alloc());
+        let ptr = Box::into_raw(Box::new(alloc::Track::new(Shard::new(
+            idx,
+            self.lifecycle.clone(),
+            self.reaper.clone(),
+            self.factory.clone(),
+            self.tag.unwrap_or_else(|| tag_for::<C>(table.id)),
+            table.quota.clone(),
+            table.vacancies.clone(),
+        ))));
+        test_println!("-> allocated new shard for index {} at {:p}", idx, ptr);
+        if let Err(actual) = slot.set(ptr) {
+            test_println!("-> lost race to allocate shard; using {:p}", actual);
+            drop(unsafe {
+                // Safety: another thread allocated the shard first, so
+                // ours was never shared.
+                Box::from_raw(ptr)
+            });
+            return actual;
+        }
+        let mut max = self.max.load(Acquire);
+        while max < idx {
+            match self.max.compare_exchange(max, idx, AcqRel, Acquire) {
+                Ok(_) => break,
+                Err(actual) => max = actual,
+            }
+        }
+        test_println!("-> highest index={}, prev={}", std::cmp::max(max, idx), max);
+        ptr
+    }
+
+    /// Returns an iterator over the shards which have been allocated.
+    ///
+    /// Unlike `iter_mut`, this does not require exclusive access, so shards
+    /// may be allocated concurrently; shards allocated after the iterator was
+    /// created may not be returned.
+    pub(crate) fn iter(&self) -> Iter<'_, T, C> {
+        test_println!("Array::iter");
+        Iter(self.ptrs())
+    }
+
+    /// Returns the number of inserts into this array's shards which failed,
+    /// by cause, totalled across every allocated shard.
+    pub(crate) fn insert_failures(&self) -> InsertFailures {
+        // The counters are always `std` atomics, even when testing with loom.
+        let sum = |counter: fn(&Counters) -> &std::sync::atomic::AtomicUsize| {
+            self.iter()
+                .map(|shard| counter(&shard.counters).load(Relaxed))
+                .sum()
+        };
+        InsertFailures {
+            all_pages_full: sum(|counters| &counters.failed_inserts),
+            quota_exceeded: sum(|counters| &counters.quota_exceeded),
+            max_shards: self.failures.max_shards.load(Relaxed),
+            thread_exiting: self.failures.thread_exiting.load(Relaxed),
+        }
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> IterMut<'_, T, C> {
+        test_println!("Array::iter_mut");
+        IterMut(self.ptrs())
+    }
+
+    /// Returns the pointers to every shard up to the highest index that has
+    /// been allocated.
+    fn ptrs(&self) -> Ptrs<'_, T, C> {
         let max = self.max.load(Acquire);
-        for shard in &self.shards[0..=max] {
+        test_println!("-> highest index={}", max);
+        Ptrs {
+            array: self,
+            next: 0,
+            end: max + 1,
+        }
+    }
+
+    /// Returns the pointer to the shard at `idx`, if the segment containing it
+    /// has been allocated.
+    #[inline]
+    fn slot(&self, idx: usize) -> Option<&Ptr<T, C>> {
+        if let Some(slot) = self.first().get(idx) {
+            return Some(slot);
+        }
+        self.table()?.slot(idx)
+    }
+
+    /// Returns the shards in the table's first segment, or an empty slice if
+    /// the table hasn't been allocated.
+    #[inline]
+    fn first(&self) -> &[Ptr<T, C>] {
+        let shards = self.shards.load(Acquire);
+        if shards.is_null() {
+            return &[];
+        }
+        unsafe {
+            // Safety: `shards` points to the table's first segment, which has
+            // `len` shards, and which is only deallocated when the array is
+            // dropped. `len` was stored before `shards`.
+            slice::from_raw_parts(shards, self.len.load(Relaxed))
+        }
+    }
+
+    #[inline]
+    fn table(&self) -> Option<&Table<T, C>> {
+        let table = self.table.load(Acquire);
+        if table.is_null() {
+            return None;
+        }
+        Some(unsafe {
+            // Safety: the table is only deallocated when the array is
+            // dropped, and the returned reference borrows the array.
+            &*table
+        })
+    }
+
+    /// Returns the table, allocating it if it hasn't been allocated.
+    ///
+    /// Once this returns, the table's first segment may be accessed through
+    /// `first`.
+    #[inline]
+    fn table_or_alloc(&self) -> &Table<T, C> {
+        if self.shards.load(Acquire).is_null() {
+            return self.alloc_table();
+        }
+        unsafe {
+            // Safety: `shards` is only stored after the table, which is only
+            // deallocated when the array is dropped.
+            &*self.table.load(Acquire)
+        }
+    }
+
+    #[cold]
+    fn alloc_table(&self) -> &Table<T, C> {
+        let table = match self.table() {
+            Some(table) => table,
+            None => {
+                let new = Box::into_raw(Box::new(Table::new()));
+                test_println!("-> allocated shard table at {:p}", new);
+                match self
+                    .table
+                    .compare_exchange(ptr::null_mut(), new, AcqRel, Acquire)
+                {
+                    Ok(_) => unsafe {
+                        // Safety: the table is only deallocated when the
+                        // array is dropped.
+                        &*new
+                    },
+                    Err(existing) => unsafe {
+                        // Safety: another thread allocated the table first,
+                        // so ours was never shared.
+                        drop(Box::from_raw(new));
+                        &*existing
+                    },
+                }
+            }
+        };
+        // Every thread that finds the first segment unpublished publishes it
+        // before using the table, so that it can't allocate a shard which
+        // `first` doesn't see. They all store the same values.
+        self.id.store(table.id, Relaxed);
+        self.len.store(table.shards.len(), Relaxed);
+        self.shards
+            .store(table.shards.as_ptr() as *mut Ptr<T, C>, Release);
+        table
+    }
+}
+
+impl<T, C: cfg::Config> Drop for Array<T, C> {
+    fn drop(&mut self) {
+        #[cfg(feature = "event-log")]
+        {
+            if std::thread::panicking() {
+                crate::events::dump(self.iter().map(|shard| &shard.events));
+            }
+        }
+
+        // XXX(eliza): this could be `with_mut` if we wanted to impl a wrapper for std atomics to change `get_mut` to `with_mut`...
+        for shard in self.ptrs() {
             // XXX(eliza): this could be `with_mut` if we wanted to impl a wrapper for std atomics to change `get_mut` to `with_mut`...
             let ptr = shard.0.load(Acquire);
             if ptr.is_null() {
@@ -348,14 +1707,22 @@ impl<T, C: cfg::Config> Drop for Array<T, C> {
             };
             drop(shard)
         }
+
+        let table = *self.table.get_mut();
+        if !table.is_null() {
+            drop(unsafe {
+                // Safety: we have exclusive access to the shard array, and no
+                // shards remain to reference the table's quota or vacancies.
+                Box::from_raw(table)
+            });
+        }
     }
 }
 
 impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Array<T, C> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let max = self.max.load(Acquire);
         let mut set = f.debug_map();
-        for shard in &self.shards[0..=max] {
+        for shard in self.ptrs() {
             let ptr = shard.0.load(Acquire);
             if let Some(shard) = ptr::NonNull::new(ptr) {
                 set.entry(&format_args!("{:p}", ptr), unsafe { shard.as_ref() });
@@ -367,6 +1734,164 @@ impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for Array<T, C> {
     }
 }
 
+// === impl Table ===
+
+impl<T, C: cfg::Config> Table<T, C> {
+    fn new() -> Self {
+        let len = if C::AUTO_SIZE_SHARDS {
+            available_parallelism()
+        } else {
+            FIRST_SEGMENT_LEN
+        };
+        let len = cmp::min(len, C::MAX_SHARDS);
+        // Both lengths are powers of two, so the segments exactly cover the
+        // remaining indices.
+        let segments = (C::MAX_SHARDS / len).trailing_zeros() as usize;
+        Self {
+            shards: Ptr::nulls(len),
+            segments: (0..segments)
+                .map(|_| AtomicPtr::new(ptr::null_mut()))
+                .collect(),
+            id: next_array_id(),
+            quota: Arc::new(std::sync::atomic::AtomicUsize::new(!0)),
+            vacancies: Arc::new(Vacancies::default()),
+        }
+    }
+
+    /// Returns the pointer to the shard at `idx`, if the segment containing it
+    /// has been allocated.
+    fn slot(&self, idx: usize) -> Option<&Ptr<T, C>> {
+        if let Some(slot) = self.shards.get(idx) {
+            return Some(slot);
+        }
+        let (segment, offset) = self.segment_index(idx);
+        let ptr = self.segments.get(segment)?.load(Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+        let segment = unsafe {
+            // Safety: segments are only deallocated when the table is
+            // dropped.
+            &*ptr
+        };
+        segment.get(offset)
+    }
+
+    /// Returns the pointer to the shard at `idx`, allocating the segment
+    /// containing it if it hasn't been allocated.
+    fn slot_or_alloc(&self, idx: usize) -> &Ptr<T, C> {
+        if let Some(slot) = self.shards.get(idx) {
+            return slot;
+        }
+        let (segment, offset) = self.segment_index(idx);
+        &self.segment_or_alloc(segment)[offset]
+    }
+
+    /// Returns the segment containing the shard at `idx`, which must be past
+    /// the end of `self.shards`, and the shard's offset in that segment.
+    #[inline]
+    fn segment_index(&self, idx: usize) -> (usize, usize) {
+        debug_assert!(idx >= self.shards.len());
+        let n = idx >> self.shards.len().trailing_zeros();
+        let segment = cfg::WIDTH - 1 - n.leading_zeros() as usize;
+        (segment, idx - (self.shards.len() << segment))
+    }
+
+    #[cold]
+    fn segment_or_alloc(&self, segment: usize) -> &[Ptr<T, C>] {
+        let ptr = &self.segments[segment];
+        let mut current = ptr.load(Acquire);
+        if current.is_null() {
+            let new = Box::into_raw(Box::new(Ptr::nulls(self.shards.len() << segment)));
+            test_println!("-> allocated shard segment {} at {:p}", segment, new);
+            current = match ptr.compare_exchange(ptr::null_mut(), new, AcqRel, Acquire) {
+                Ok(_) => new,
+                Err(actual) => {
+                    drop(unsafe {
+                        // Safety: another thread allocated the segment first,
+                        // so ours was never shared.
+                        Box::from_raw(new)
+                    });
+                    actual
+                }
+            };
+        }
+        unsafe {
+            // Safety: segments are only deallocated when the table is
+            // dropped.
+            &*current
+        }
+    }
+}
+
+impl<T, C: cfg::Config> Drop for Table<T, C> {
+    fn drop(&mut self) {
+        for segment in self.segments.iter() {
+            let segment = segment.load(Acquire);
+            if !segment.is_null() {
+                drop(unsafe {
+                    // Safety: we have exclusive access to the table.
+                    Box::from_raw(segment)
+                });
+            }
+        }
+    }
+}
+
+/// The number of shards in the first segment of a shard array, unless
+/// `C::AUTO_SIZE_SHARDS` is set.
+const FIRST_SEGMENT_LEN: usize = 32;
+
+/// Returns the number of threads that may run in parallel, rounded up to a
+/// power of two.
+///
+/// This is only determined once, since it may require reading several files.
+fn available_parallelism() -> usize {
+    // This is a plain `std` atomic even when running under loom: it only
+    // caches a value that is the same for every thread.
+    static CACHED: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    #[cfg(slab_available_parallelism)]
+    #[allow(clippy::incompatible_msrv)] // only built on Rust 1.59+; see build.rs
+    fn detect() -> usize {
+        std::thread::available_parallelism().map_or(!0, |n| n.get().next_power_of_two())
+    }
+
+    #[cfg(not(slab_available_parallelism))]
+    fn detect() -> usize {
+        !0
+    }
+
+    match CACHED.load(Relaxed) {
+        0 => {
+            let n = detect();
+            CACHED.store(n, Relaxed);
+            n
+        }
+        n => n,
+    }
+}
+
+/// Returns the tag for the shard array with the given ID, shifted into place.
+///
+/// Tags are never zero, so that keys which weren't issued by any slab aren't
+/// mistaken for keys issued by a different one.
+fn tag_for<C: cfg::Config>(id: usize) -> usize {
+    if C::TAG_BITS == 0 {
+        return 0;
+    }
+    let tags = (1 << C::TAG_BITS) - 1;
+    (id % tags + 1) << C::TAG_SHIFT
+}
+
+/// Returns a new, unique, nonzero ID for a shard array.
+fn next_array_id() -> usize {
+    // This is a plain `std` atomic even when running under loom: it only hands
+    // out unique IDs and does not synchronize any other memory.
+    static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+    NEXT_ID.fetch_add(1, Relaxed)
+}
+
 // === impl Ptr ===
 
 impl<T, C: cfg::Config> Ptr<T, C> {
@@ -375,10 +1900,20 @@ impl<T, C: cfg::Config> Ptr<T, C> {
         Self(AtomicPtr::new(ptr::null_mut()))
     }
 
+    fn nulls(len: usize) -> Box<[Self]> {
+        (0..len).map(|_| Self::null()).collect()
+    }
+
     #[inline]
-    fn load(&self, order: Ordering) -> Option<&Shard<T, C>> {
+    fn load_raw(&self, order: Ordering) -> *const alloc::Track<Shard<T, C>> {
         let ptr = self.0.load(order);
         test_println!("---> loaded={:p} (order={:?})", ptr, order);
+        ptr
+    }
+
+    #[inline]
+    fn load(&self, order: Ordering) -> Option<&Shard<T, C>> {
+        let ptr = self.load_raw(order);
         if ptr.is_null() {
             test_println!("---> null");
             return None;
@@ -398,16 +1933,71 @@ impl<T, C: cfg::Config> Ptr<T, C> {
         Some(track.get_ref())
     }
 
+    /// Sets the shard, returning the existing shard if one was already set.
     #[inline]
-    fn set(&self, new: *mut alloc::Track<Shard<T, C>>) {
+    fn set(
+        &self,
+        new: *mut alloc::Track<Shard<T, C>>,
+    ) -> Result<(), *mut alloc::Track<Shard<T, C>>> {
         self.0
             .compare_exchange(ptr::null_mut(), new, AcqRel, Acquire)
-            .expect("a shard can only be inserted by the thread that owns it, this is a bug!");
+            .map(|_| ())
+            .map_err(|actual| {
+                assert!(
+                    C::SHARED_INSERTS,
+                    "a shard can only be inserted by the thread that owns it, this is a bug!"
+                );
+                actual
+            })
     }
 }
 
 // === Iterators ===
 
+impl<'a, T, C: cfg::Config> Iterator for Ptrs<'a, T, C> {
+    type Item = &'a Ptr<T, C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.end {
+            let idx = self.next;
+            self.next += 1;
+            // If a shard has been allocated, so has its segment, and `max` is
+            // only increased after the shard is allocated; so every segment
+            // before `end` is visible here.
+            if let Some(slot) = self.array.slot(idx) {
+                return Some(slot);
+            }
+        }
+        None
+    }
+}
+
+impl<T, C: cfg::Config> fmt::Debug for Ptrs<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ptrs")
+            .field("next", &self.next)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+impl<'a, T, C> Iterator for Iter<'a, T, C>
+where
+    T: 'a,
+    C: cfg::Config + 'a,
+{
+    type Item = &'a Shard<T, C>;
+    fn next(&mut self) -> Option<Self::Item> {
+        test_println!("Iter::next");
+        loop {
+            // Skip over shards which have not been allocated (see `IterMut`).
+            if let Some(shard) = self.0.next()?.load(Acquire) {
+                return Some(shard);
+            }
+        }
+    }
+}
+
 impl<'a, T, C> Iterator for IterMut<'a, T, C>
 where
     T: 'a,
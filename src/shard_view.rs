@@ -0,0 +1,236 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    page, Pack, Shard, ShardId, Slab, Tid,
+};
+use std::fmt;
+
+/// Exclusive access to the values in one of a [`Slab`]'s shards.
+///
+/// This is returned by [`Slab::shards_mut`]. Since each view has exclusive
+/// access to a different shard, the views may be sent to different threads,
+/// such as scoped worker threads, to access or remove the values in each
+/// shard in parallel.
+///
+/// Values may not be inserted through a `ShardViewMut`. A value removed
+/// through a `ShardViewMut` is removed as though by [`Slab::take`], and its
+/// slot may be reused once the slab is shared again.
+pub struct ShardViewMut<'a, T, C: cfg::Config = DefaultConfig> {
+    shard: &'a Shard<Option<T>, C>,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Returns an exclusive view of each of the slab's shards.
+    ///
+    /// This allows values to be mutated or removed in bulk, by a separate
+    /// thread for each shard. Because this exclusively borrows the slab, no
+    /// other operations may occur until the views have been dropped.
+    ///
+    /// Only shards which have been allocated are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let mut slab = Arc::new(Slab::new());
+    /// let keys = (0..4)
+    ///     .map(|i| {
+    ///         let slab = slab.clone();
+    ///         thread::spawn(move || slab.insert(i).unwrap())
+    ///     })
+    ///     .collect::<Vec<_>>()
+    ///     .into_iter()
+    ///     .map(|t| t.join().unwrap())
+    ///     .collect::<Vec<_>>();
+    ///
+    /// // Hand each shard to a different thread.
+    /// let slab_mut = Arc::get_mut(&mut slab).unwrap();
+    /// thread::scope(|scope| {
+    ///     for mut shard in slab_mut.shards_mut() {
+    ///         scope.spawn(move || {
+    ///             for (_, value) in shard.iter_mut() {
+    ///                 *value *= 10;
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    ///
+    /// for (i, key) in keys.into_iter().enumerate() {
+    ///     assert_eq!(slab.get(key).unwrap(), i * 10);
+    /// }
+    /// ```
+    pub fn shards_mut(&mut self) -> impl Iterator<Item = ShardViewMut<'_, T, C>> {
+        self.shards.iter_mut().map(|shard| ShardViewMut { shard })
+    }
+}
+
+// === impl ShardViewMut ===
+
+impl<'a, T, C: cfg::Config> ShardViewMut<'a, T, C> {
+    /// Returns the ID of the shard.
+    pub fn id(&self) -> ShardId {
+        Tid::<C>::from_usize(self.shard.tid).into()
+    }
+
+    /// Returns an iterator over the key and value of each entry in the shard.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        let shard = self.shard;
+        shard.keys().filter_map(move |key| {
+            shard.with_slot(key, |slot| slot.value().as_ref().map(|value| (key, value)))
+        })
+    }
+
+    /// Returns an iterator over the key and a mutable reference to the value
+    /// of each entry in the shard.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        let shard = self.shard;
+        shard.keys().filter_map(move |key| {
+            shard.with_slot(key, |slot| {
+                let value = unsafe {
+                    // Safety: the view has exclusive access to the shard, and
+                    // each key refers to a different slot.
+                    (*slot.value_mut()).as_mut()
+                };
+                value.map(|value| (key, value))
+            })
+        })
+    }
+
+    /// Returns the number of values in the shard.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns `true` if the shard contains no values.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Returns a reference to the value associated with the given key, if it
+    /// is in this shard.
+    pub fn get(&self, key: usize) -> Option<&T> {
+        self.slot(key)?.value().as_ref()
+    }
+
+    /// Returns a mutable reference to the value associated with the given
+    /// key, if it is in this shard.
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        let slot = self.slot(key)?;
+        unsafe {
+            // Safety: the view has exclusive access to the shard.
+            (*slot.value_mut()).as_mut()
+        }
+    }
+
+    /// Removes the value associated with the given key from the shard,
+    /// returning it.
+    pub fn take(&mut self, key: usize) -> Option<T> {
+        self.slot(key)?;
+        // Since the view has exclusive access, no guards to the value exist,
+        // so this will not wait.
+        if Tid::<C>::from_usize(self.shard.tid).is_local() {
+            self.shard.take_local(key)
+        } else {
+            self.shard.take_remote(key)
+        }
+    }
+
+    /// Retains only the values for which `f` returns `true`, removing the
+    /// rest.
+    pub fn retain(&mut self, mut f: impl FnMut(usize, &mut T) -> bool) {
+        let remove = self
+            .iter_mut()
+            .filter_map(|(key, value)| if f(key, value) { None } else { Some(key) })
+            .collect::<Vec<_>>();
+        for key in remove {
+            drop(self.take(key));
+        }
+    }
+
+    /// Returns the slot for `key`, if the key's generation is current and it
+    /// belongs to this shard.
+    fn slot(&self, key: usize) -> Option<&'a page::Slot<Option<T>, C>> {
+        if C::unpack_tid(key).as_usize() != self.shard.tid {
+            return None;
+        }
+        self.shard.with_slot(key, |slot| {
+            Some(slot).filter(|slot| slot.generation() == C::unpack_gen(key))
+        })
+    }
+}
+
+impl<T: fmt::Debug, C: cfg::Config> fmt::Debug for ShardViewMut<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Each view has exclusive access to the values in its shard, like a
+// `&mut [T]`.
+unsafe impl<T: Send, C: cfg::Config> Send for ShardViewMut<'_, T, C> {}
+unsafe impl<T: Sync, C: cfg::Config> Sync for ShardViewMut<'_, T, C> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::Slab;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn views_are_disjoint() {
+        let mut slab = Arc::new(Slab::new());
+        let keys = (0..4)
+            .map(|i| {
+                let slab = slab.clone();
+                thread::spawn(move || (slab.insert(i).unwrap(), slab.insert(i + 10).unwrap()))
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+
+        let slab_mut = Arc::get_mut(&mut slab).unwrap();
+        let mut total = 0;
+        for mut view in slab_mut.shards_mut() {
+            // Each key is accessible only through the view of its own shard.
+            let mine = keys
+                .iter()
+                .filter(|&&(small, large)| {
+                    let found = view.get(small).is_some();
+                    assert_eq!(found, view.get(large).is_some());
+                    found
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            assert_eq!(view.len(), mine.len() * 2);
+            total += view.len();
+
+            for &(small, _) in &mine {
+                *view.get_mut(small).unwrap() += 100;
+            }
+            view.retain(|_, value| *value >= 100);
+            assert_eq!(view.len(), mine.len());
+        }
+        assert_eq!(total, 8);
+
+        for &(small, large) in &keys {
+            assert!(*slab.get(small).unwrap() >= 100);
+            assert!(slab.get(large).is_none());
+        }
+    }
+
+    #[test]
+    fn take_frees_slot() {
+        let mut slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let id = slab.shard_id(key);
+        let mut view = slab.shards_mut().next().unwrap();
+        assert_eq!(view.id(), id);
+        assert_eq!(view.take(key), Some(1));
+        assert_eq!(view.take(key), None);
+        assert!(view.is_empty());
+
+        assert!(slab.get(key).is_none());
+        slab.insert(2).unwrap();
+    }
+}
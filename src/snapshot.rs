@@ -0,0 +1,296 @@
+//! Persisting a [`Slab`]'s contents, with their keys.
+//!
+//! A snapshot is laid out as follows, with all integers stored as
+//! little-endian `u64`s:
+//!
+//! * the bytes `b"SSLB"`, followed by a format version byte;
+//! * a fingerprint of the [`Config`] that determines how keys are packed;
+//! * for each entry, a `1` byte, the entry's key, and the value as written
+//!   by the caller's encoder;
+//! * a `0` byte.
+//!
+//! [`Config`]: crate::Config
+use crate::{
+    cfg::{self, CfgPrivate},
+    shard, watch, Entry, Pack, Slab, Tid,
+};
+use std::{
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+const MAGIC: &[u8; 4] = b"SSLB";
+const VERSION: u8 = 2;
+
+const ENTRY: u8 = 1;
+const END: u8 = 0;
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Writes every value in the slab, along with its key, to `writer`.
+    ///
+    /// Each value is written by calling `encode` with a reference to the value
+    /// and the writer. The snapshot also records the slab's [`Config`], so
+    /// that it will only be [restored] into a slab whose keys are packed the
+    /// same way.
+    ///
+    /// This does not prevent the slab from being modified concurrently. Values
+    /// that are inserted or removed while the snapshot is being written may or
+    /// may not be included, but every value that is written was present at
+    /// some point during the snapshot, and is not written while it is being
+    /// modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::io::{Read, Write};
+    ///
+    /// let slab = Slab::new();
+    /// let key = slab.insert(42u32).unwrap();
+    ///
+    /// let mut bytes = Vec::new();
+    /// slab.snapshot(&mut bytes, |value, w| w.write_all(&value.to_le_bytes()))
+    ///     .unwrap();
+    ///
+    /// let restored = Slab::<u32>::restore(&bytes[..], |r| {
+    ///     let mut buf = [0; 4];
+    ///     r.read_exact(&mut buf)?;
+    ///     Ok(u32::from_le_bytes(buf))
+    /// })
+    /// .unwrap();
+    /// assert_eq!(restored.get(key).unwrap(), 42);
+    /// ```
+    ///
+    /// [`Config`]: crate::Config
+    /// [restored]: Slab::restore
+    pub fn snapshot<W: Write>(
+        &self,
+        mut writer: W,
+        mut encode: impl FnMut(&T, &mut W) -> io::Result<()>,
+    ) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for field in &fingerprint::<C>() {
+            write_u64(&mut writer, *field)?;
+        }
+
+        for shard in self.shards.iter() {
+            for key in shard.keys() {
+                if let Some(entry) = Entry::scanned(shard, key) {
+                    test_println!("snapshot: {:#x}", key);
+                    writer.write_all(&[ENTRY])?;
+                    write_u64(&mut writer, key as u64)?;
+                    encode(&entry, &mut writer)?;
+                }
+            }
+        }
+
+        writer.write_all(&[END])?;
+        writer.flush()
+    }
+
+    /// Returns a new slab containing the values in a snapshot written by
+    /// [`Slab::snapshot`], with the same keys.
+    ///
+    /// Each value is read by calling `decode` with the reader.
+    ///
+    /// Keys encode the index of the thread that inserted the value, so
+    /// restored values are owned by whichever thread currently has that
+    /// index. This only affects which thread's free lists their slots return
+    /// to when they are removed.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error of kind [`InvalidData`] if the snapshot was taken
+    /// from a slab with a [`Config`] that packs keys differently, or is
+    /// otherwise malformed, along with any errors returned by `reader` or
+    /// `decode`.
+    ///
+    /// [`InvalidData`]: io::ErrorKind::InvalidData
+    /// [`Config`]: crate::Config
+    pub fn restore<R: Read>(
+        mut reader: R,
+        mut decode: impl FnMut(&mut R) -> io::Result<T>,
+    ) -> io::Result<Self> {
+        C::validate();
+
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic)?;
+        if &magic[..4] != MAGIC || magic[4] != VERSION {
+            return Err(invalid_data("not a slab snapshot"));
+        }
+        for field in &fingerprint::<C>() {
+            if read_u64(&mut reader)? != *field {
+                return Err(invalid_data(
+                    "snapshot was taken from a slab with an incompatible `Config`",
+                ));
+            }
+        }
+
+        let mut slab = Slab {
+            shards: shard::Array::new(),
+            watchers: watch::Watchers::new(),
+            _cfg: PhantomData,
+        };
+        let mut first = true;
+        loop {
+            match read_u8(&mut reader)? {
+                ENTRY => {}
+                END => break,
+                _ => return Err(invalid_data("malformed slab snapshot")),
+            }
+            let key = read_u64(&mut reader)? as usize;
+            let value = decode(&mut reader)?;
+            test_println!("restore: {:#x}", key);
+
+            // The restored slab keeps the tag of the slab the snapshot was
+            // taken from, so that its keys remain valid.
+            if first {
+                slab.shards.adopt_tag(key);
+                first = false;
+            }
+
+            let tid = Tid::<C>::from_packed(key).as_usize();
+            if tid >= C::MAX_SHARDS {
+                return Err(invalid_data("snapshot key out of range"));
+            }
+            let restored = unsafe {
+                // Safety: nothing else can access the slab until we return it.
+                slab.shards.get_or_alloc(tid).restore(key, value)
+            };
+            if !restored {
+                return Err(invalid_data("snapshot key out of range or repeated"));
+            }
+        }
+
+        for shard in slab.shards.iter_mut() {
+            unsafe {
+                // Safety: we have exclusive access to the slab.
+                shard.reset_free_lists();
+            }
+        }
+        Ok(slab)
+    }
+}
+
+/// The configuration parameters which determine how keys are packed.
+fn fingerprint<C: cfg::Config>() -> [u64; 7] {
+    [
+        cfg::WIDTH as u64,
+        cfg::LIFECYCLE_WIDTH as u64,
+        C::INITIAL_SZ as u64,
+        C::MAX_PAGES as u64,
+        C::MAX_SHARDS as u64,
+        C::RESERVED_BITS as u64,
+        C::TAG_BITS as u64,
+    ]
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    fn encode(value: &usize, w: &mut impl Write) -> io::Result<()> {
+        write_u64(w, *value as u64)
+    }
+
+    fn decode(r: &mut &[u8]) -> io::Result<usize> {
+        read_u64(r).map(|value| value as usize)
+    }
+
+    #[test]
+    fn restore_preserves_keys() {
+        let slab = Arc::new(Slab::new());
+        let mut keys = (0..100)
+            .map(|i| (slab.insert(i).unwrap(), i))
+            .collect::<Vec<_>>();
+        // Values inserted by other threads are stored in other shards.
+        let remote = thread::spawn({
+            let slab = slab.clone();
+            move || {
+                (100..110)
+                    .map(|i| (slab.insert(i).unwrap(), i))
+                    .collect::<Vec<_>>()
+            }
+        });
+        keys.extend(remote.join().unwrap());
+        // Leave some gaps, and advance some slots' generations.
+        for (key, _) in keys.drain(10..20) {
+            slab.remove(key);
+        }
+        let reused = slab.insert(1000).unwrap();
+        keys.push((reused, 1000));
+
+        let mut bytes = Vec::new();
+        slab.snapshot(&mut bytes, encode).unwrap();
+        let restored = Slab::<usize>::restore(&bytes[..], decode).unwrap();
+
+        for &(key, value) in &keys {
+            assert_eq!(restored.get(key).unwrap(), value);
+        }
+
+        // New values don't clobber restored ones.
+        let new_keys = (0..100)
+            .map(|i| restored.insert(2000 + i).unwrap())
+            .collect::<Vec<_>>();
+        for &(key, value) in &keys {
+            assert!(!new_keys.contains(&key));
+            assert_eq!(restored.get(key).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn restore_rejects_incompatible_config() {
+        struct Other;
+        impl crate::Config for Other {
+            const INITIAL_PAGE_SIZE: usize = 64;
+        }
+
+        let slab = Slab::new();
+        slab.insert(1).unwrap();
+        let mut bytes = Vec::new();
+        slab.snapshot(&mut bytes, encode).unwrap();
+
+        let err = Slab::<usize, Other>::restore(&bytes[..], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Slab::<usize>::restore(&bytes[..], decode).is_ok());
+    }
+
+    #[test]
+    fn restore_rejects_repeated_keys() {
+        let slab = Slab::new();
+        slab.insert(1).unwrap();
+        let mut bytes = Vec::new();
+        slab.snapshot(&mut bytes, encode).unwrap();
+
+        // Write the same entry twice.
+        let end = bytes.pop().unwrap();
+        let entry = bytes[bytes.len() - 17..].to_vec();
+        bytes.extend(entry);
+        bytes.push(end);
+
+        let err = Slab::<usize>::restore(&bytes[..], decode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
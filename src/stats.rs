@@ -0,0 +1,884 @@
+use crate::{cfg, page, ShardId, Slab};
+use std::{
+    cmp, fmt, mem,
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+/// Statistics describing one of a [`Slab`]'s shards.
+///
+/// These are returned by [`Slab::shard_stats`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ShardStats {
+    pub(crate) id: ShardId,
+    pub(crate) allocated_pages: usize,
+    pub(crate) occupied: usize,
+    pub(crate) local_free: usize,
+    pub(crate) remote_free: usize,
+    pub(crate) generation_wraps: usize,
+    pub(crate) failed_inserts: usize,
+}
+
+/// How full each of the pages in one of a [`Slab`]'s shards is.
+///
+/// These are returned by [`Slab::page_stats`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShardPages {
+    pub(crate) id: ShardId,
+    pub(crate) pages: Vec<PageStats>,
+}
+
+/// How full one of the pages in a [`Slab`]'s shard is.
+///
+/// This formats as `occupied/size`, or `-/size` if the page's storage isn't
+/// allocated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PageStats {
+    pub(crate) occupied: usize,
+    pub(crate) size: usize,
+    pub(crate) allocated: bool,
+}
+
+/// Totals describing a whole [`Slab`].
+///
+/// These are returned by [`Slab::stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlabStats {
+    pub(crate) occupied: usize,
+    pub(crate) allocated_slots: usize,
+    pub(crate) allocated_bytes: usize,
+    pub(crate) active_shards: usize,
+    pub(crate) insert_failures: InsertFailures,
+    pub(crate) pages_skipped: usize,
+    pub(crate) pending_removals: usize,
+}
+
+/// How many more values the current thread's shard of a [`Slab`] can hold.
+///
+/// This is returned by [`Slab::remaining_on_current_shard`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RemainingCapacity {
+    pub(crate) vacant: usize,
+    pub(crate) unallocated: usize,
+}
+
+/// Counts of the inserts into a [`Slab`] or [`Pool`] which failed, by cause.
+///
+/// These are returned by [`SlabStats::insert_failures`] and
+/// [`PoolMetrics::insert_failures`].
+///
+/// [`Pool`]: crate::Pool
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InsertFailures {
+    pub(crate) all_pages_full: usize,
+    pub(crate) quota_exceeded: usize,
+    pub(crate) max_shards: usize,
+    pub(crate) thread_exiting: usize,
+}
+
+/// Counts of how a [`Pool`]'s objects have been used.
+///
+/// These are returned by [`Pool::metrics`].
+///
+/// [`Pool`]: crate::Pool
+/// [`Pool::metrics`]: crate::Pool::metrics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    pub(crate) created: usize,
+    pub(crate) constructed: usize,
+    pub(crate) cleared: usize,
+    pub(crate) taken: usize,
+    pub(crate) generation_wraps: usize,
+    pub(crate) insert_failures: InsertFailures,
+    pub(crate) pages_skipped: usize,
+}
+
+/// Counts operations on a shard's slots.
+///
+/// Since these are only statistics, and don't synchronize access to anything,
+/// they are always `std` atomics, even when testing with loom, so that they
+/// don't add to the state space loom explores.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    pub(crate) created: AtomicUsize,
+    pub(crate) constructed: AtomicUsize,
+    pub(crate) cleared: AtomicUsize,
+    pub(crate) taken: AtomicUsize,
+    /// The number of values which couldn't be inserted because the shard was
+    /// full.
+    pub(crate) failed_inserts: AtomicUsize,
+    /// The number of times an insert found a page other than the last full,
+    /// and moved on to the next page.
+    pub(crate) pages_skipped: AtomicUsize,
+    /// The number of values which couldn't be inserted because the shard
+    /// held as many values as its quota allows.
+    pub(crate) quota_exceeded: AtomicUsize,
+}
+
+/// Counts inserts which failed because the inserting thread had no shard.
+///
+/// Like `Counters`, these are always `std` atomics.
+#[derive(Debug, Default)]
+pub(crate) struct ShardlessFailures {
+    pub(crate) max_shards: AtomicUsize,
+    pub(crate) thread_exiting: AtomicUsize,
+}
+
+impl ShardlessFailures {
+    const_fn! {
+        pub(crate) fn new() -> Self {
+            Self {
+                max_shards: AtomicUsize::new(0),
+                thread_exiting: AtomicUsize::new(0),
+            }
+        }
+    }
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Returns statistics describing each of the slab's shards.
+    ///
+    /// This is intended to help diagnose imbalances between threads: for
+    /// example, a shard whose thread has exited but which still holds most
+    /// of the slab's values, or one whose slots are mostly being freed by
+    /// other threads.
+    ///
+    /// Because a shard's local free list may only be accessed by the thread
+    /// that owns it, this requires exclusive access to the slab.
+    ///
+    /// Only shards which have been allocated are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let mut slab = Slab::new();
+    /// let key = slab.insert("hello").unwrap();
+    /// slab.insert("world").unwrap();
+    /// slab.remove(key);
+    ///
+    /// let stats = slab.shard_stats();
+    /// assert_eq!(stats.len(), 1);
+    /// assert_eq!(stats[0].id(), slab.shard_id(key));
+    /// assert_eq!(stats[0].allocated_pages(), 1);
+    /// assert_eq!(stats[0].occupied(), 1);
+    /// ```
+    pub fn shard_stats(&mut self) -> Vec<ShardStats> {
+        self.shards
+            .iter_mut()
+            .map(|shard| unsafe {
+                // Safety: we have exclusive access to the slab.
+                shard.stats()
+            })
+            .collect()
+    }
+
+    /// Returns how full each page of each of the slab's shards is.
+    ///
+    /// Since each page is twice the size of the previous one, and values are
+    /// inserted into the first page with a vacant slot, a shard whose later
+    /// pages are nearly empty is a good candidate for [compaction]. Many
+    /// partly-full pages may instead indicate that values are being removed
+    /// by other threads faster than the shard's own thread reuses their slots.
+    ///
+    /// Like [`stats`], this doesn't require exclusive access to the slab, and
+    /// may not reflect operations which are in progress.
+    ///
+    /// Only shards which have been allocated are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello").unwrap();
+    /// slab.insert("world").unwrap();
+    /// slab.remove(key);
+    ///
+    /// let shards = slab.page_stats();
+    /// assert_eq!(shards.len(), 1);
+    /// assert_eq!(shards[0].id(), slab.shard_id(key));
+    ///
+    /// let first = shards[0].pages()[0];
+    /// assert_eq!(first.occupied(), 1);
+    /// assert_eq!(first.to_string(), "1/32");
+    /// assert!(!shards[0].pages()[1].is_allocated());
+    /// ```
+    ///
+    /// [compaction]: Slab::compact
+    /// [`stats`]: Slab::stats
+    pub fn page_stats(&self) -> Vec<ShardPages> {
+        self.shards.iter().map(|shard| shard.page_stats()).collect()
+    }
+
+    /// Returns totals describing the slab as a whole.
+    ///
+    /// Unlike [`shard_stats`], this doesn't require exclusive access to the
+    /// slab, and doesn't block any other operations, so it may be called
+    /// periodically to log the slab's state. However, the totals may not
+    /// reflect operations which are in progress.
+    ///
+    /// Counting the removals which are waiting for their values to be
+    /// released requires checking every slot in the slab, so this is not
+    /// especially cheap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello").unwrap();
+    /// slab.insert("world").unwrap();
+    ///
+    /// let entry = slab.get(key).unwrap();
+    /// slab.remove(key);
+    ///
+    /// let stats = slab.stats();
+    /// assert_eq!(stats.occupied(), 2);
+    /// assert_eq!(stats.pending_removals(), 1);
+    /// assert_eq!(stats.active_shards(), 1);
+    ///
+    /// drop(entry);
+    /// let stats = slab.stats();
+    /// assert_eq!(stats.occupied(), 1);
+    /// assert_eq!(stats.pending_removals(), 0);
+    /// ```
+    ///
+    /// [`shard_stats`]: Slab::shard_stats
+    pub fn stats(&self) -> SlabStats {
+        let mut stats = self
+            .shards
+            .iter()
+            .fold(SlabStats::default(), |mut stats, shard| {
+                stats.active_shards += 1;
+                stats.pages_skipped += shard.counters.pages_skipped.load(Relaxed);
+                for page in shard.pages() {
+                    stats.occupied += page.live();
+                    stats.allocated_slots += page.allocated_slots();
+                    stats.pending_removals += page.deferred_releases();
+                }
+                stats
+            });
+        stats.allocated_bytes = stats.allocated_slots * mem::size_of::<page::Slot<Option<T>, C>>();
+        stats.insert_failures = self.shards.insert_failures();
+        stats
+    }
+}
+
+// === impl ShardPages ===
+
+impl ShardPages {
+    /// Returns the ID of the shard, which is also the ID of the thread that
+    /// owns it.
+    pub fn id(&self) -> ShardId {
+        self.id
+    }
+
+    /// Returns how full each of the shard's pages is, in order.
+    ///
+    /// Every page the shard may use is included, whether or not its storage
+    /// has been allocated.
+    pub fn pages(&self) -> &[PageStats] {
+        &self.pages[..]
+    }
+}
+
+// === impl PageStats ===
+
+impl PageStats {
+    /// Returns the number of slots in the page which hold values.
+    pub fn occupied(&self) -> usize {
+        self.occupied
+    }
+
+    /// Returns the total number of slots in the page.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if storage for the page's slots has been allocated.
+    pub fn is_allocated(&self) -> bool {
+        self.allocated
+    }
+}
+
+impl fmt::Display for PageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.allocated {
+            write!(f, "{}/{}", self.occupied, self.size)
+        } else {
+            write!(f, "-/{}", self.size)
+        }
+    }
+}
+
+// === impl SlabStats ===
+
+impl SlabStats {
+    /// Returns the number of values in the slab, including those which have
+    /// been removed but not yet released.
+    pub fn occupied(&self) -> usize {
+        self.occupied
+    }
+
+    /// Returns the number of slots for which storage has been allocated,
+    /// whether or not they hold values.
+    pub fn allocated_slots(&self) -> usize {
+        self.allocated_slots
+    }
+
+    /// Returns the number of bytes of storage allocated for the slab's slots.
+    ///
+    /// This only counts the slots themselves, not any heap allocations owned
+    /// by the values stored in them, or the slab's other bookkeeping.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated_bytes
+    }
+
+    /// Returns the number of shards which have been allocated.
+    ///
+    /// A shard is allocated the first time its thread inserts a value, and
+    /// isn't released until the slab is dropped.
+    pub fn active_shards(&self) -> usize {
+        self.active_shards
+    }
+
+    /// Returns the number of values which couldn't be inserted, for any
+    /// reason.
+    ///
+    /// This is the total of the counts returned by [`insert_failures`].
+    ///
+    /// [`insert_failures`]: SlabStats::insert_failures
+    pub fn failed_inserts(&self) -> usize {
+        self.insert_failures.total()
+    }
+
+    /// Returns the number of values which couldn't be inserted, by the reason
+    /// the insert failed.
+    pub fn insert_failures(&self) -> InsertFailures {
+        self.insert_failures
+    }
+
+    /// Returns the number of times an insert found a page in the inserting
+    /// thread's shard full, and had to try the next page.
+    ///
+    /// This isn't a failure, since the insert may well have succeeded on a
+    /// later page. A count which grows quickly relative to the number of
+    /// inserts means that values are often spilling onto later, larger pages,
+    /// and that [`Config::INITIAL_PAGE_SIZE`] may be too small.
+    ///
+    /// [`Config::INITIAL_PAGE_SIZE`]: crate::Config::INITIAL_PAGE_SIZE
+    pub fn pages_skipped(&self) -> usize {
+        self.pages_skipped
+    }
+
+    /// Returns the number of values which have been [removed], but are still
+    /// being accessed, and so will only be released when the last [`Entry`]
+    /// referencing them is dropped.
+    ///
+    /// [removed]: crate::Slab::remove
+    /// [`Entry`]: crate::Entry
+    pub fn pending_removals(&self) -> usize {
+        self.pending_removals
+    }
+}
+
+// === impl RemainingCapacity ===
+
+impl RemainingCapacity {
+    /// Returns the number of values which can be inserted into slots that
+    /// are already allocated.
+    pub fn without_allocating(&self) -> usize {
+        self.vacant
+    }
+
+    /// Returns the number of values which can be inserted before inserts
+    /// start failing, including those which will require new pages to be
+    /// allocated.
+    pub fn with_allocating(&self) -> usize {
+        self.vacant + self.unallocated
+    }
+
+    /// Limits the remaining capacity to `max` values, preferring slots which
+    /// are already allocated.
+    pub(crate) fn clamp(self, max: usize) -> Self {
+        let vacant = cmp::min(self.vacant, max);
+        Self {
+            vacant,
+            unallocated: cmp::min(self.unallocated, max - vacant),
+        }
+    }
+}
+
+// === impl InsertFailures ===
+
+impl InsertFailures {
+    /// Returns the number of inserts which failed because every page in the
+    /// inserting thread's shard was full.
+    ///
+    /// Since each shard's capacity is fixed by [`Config::MAX_PAGES`] and
+    /// [`Config::INITIAL_PAGE_SIZE`], this indicates that the configuration
+    /// is too small for the thread's share of the values.
+    ///
+    /// [`Config::MAX_PAGES`]: crate::Config::MAX_PAGES
+    /// [`Config::INITIAL_PAGE_SIZE`]: crate::Config::INITIAL_PAGE_SIZE
+    pub fn all_pages_full(&self) -> usize {
+        self.all_pages_full
+    }
+
+    /// Returns the number of inserts which failed because the inserting
+    /// thread's shard already held as many values as its quota allows.
+    ///
+    /// See [`Slab::with_shard_quota`].
+    ///
+    /// [`Slab::with_shard_quota`]: crate::Slab::with_shard_quota
+    pub fn quota_exceeded(&self) -> usize {
+        self.quota_exceeded
+    }
+
+    /// Returns the number of inserts which failed because the inserting
+    /// thread's index was greater than [`Config::MAX_THREADS`], so it had no
+    /// shard.
+    ///
+    /// [`Config::MAX_THREADS`]: crate::Config::MAX_THREADS
+    pub fn max_shards(&self) -> usize {
+        self.max_shards
+    }
+
+    /// Returns the number of inserts which failed because the inserting
+    /// thread was exiting, and its thread-local storage had already been
+    /// destroyed.
+    pub fn thread_exiting(&self) -> usize {
+        self.thread_exiting
+    }
+
+    /// Returns the total number of inserts which failed.
+    pub fn total(&self) -> usize {
+        self.all_pages_full + self.quota_exceeded + self.max_shards + self.thread_exiting
+    }
+}
+
+// === impl PoolMetrics ===
+
+impl PoolMetrics {
+    /// Returns the number of objects which have been created in the pool.
+    pub fn created(&self) -> usize {
+        self.created
+    }
+
+    /// Returns the number of objects which were created in slots that had
+    /// never been used, and so had to be newly constructed.
+    pub fn constructed(&self) -> usize {
+        self.constructed
+    }
+
+    /// Returns the number of objects which were created by reusing the
+    /// storage of a previously cleared object.
+    pub fn reused(&self) -> usize {
+        self.created.saturating_sub(self.constructed)
+    }
+
+    /// Returns the fraction of created objects which reused the storage of a
+    /// previously cleared object, from 0.0 to 1.0.
+    ///
+    /// If no objects have been created, this is 0.0.
+    pub fn reuse_rate(&self) -> f64 {
+        if self.created == 0 {
+            return 0.0;
+        }
+        self.reused() as f64 / self.created as f64
+    }
+
+    /// Returns the number of objects which have been [cleared].
+    ///
+    /// [cleared]: crate::Pool::clear
+    pub fn cleared(&self) -> usize {
+        self.cleared
+    }
+
+    /// Returns the number of objects which have been [taken] out of the pool.
+    ///
+    /// [taken]: crate::Pool::take
+    pub fn taken(&self) -> usize {
+        self.taken
+    }
+
+    /// Returns the number of times a slot's generation has wrapped around.
+    ///
+    /// This is only counted if [`Config::GENERATION_WRAP`] is not `Ignore`;
+    /// otherwise, it is always 0.
+    ///
+    /// [`Config::GENERATION_WRAP`]: crate::Config::GENERATION_WRAP
+    pub fn generation_wraps(&self) -> usize {
+        self.generation_wraps
+    }
+
+    /// Returns the number of objects which couldn't be created, by the reason
+    /// the pool was unable to create them.
+    pub fn insert_failures(&self) -> InsertFailures {
+        self.insert_failures
+    }
+
+    /// Returns the number of times creating an object found a page in the
+    /// creating thread's shard full, and had to try the next page.
+    ///
+    /// See [`SlabStats::pages_skipped`].
+    pub fn pages_skipped(&self) -> usize {
+        self.pages_skipped
+    }
+}
+
+// === impl ShardStats ===
+
+impl ShardStats {
+    pub(crate) fn new(id: ShardId) -> Self {
+        Self {
+            id,
+            allocated_pages: 0,
+            occupied: 0,
+            local_free: 0,
+            remote_free: 0,
+            generation_wraps: 0,
+            failed_inserts: 0,
+        }
+    }
+
+    /// Returns the ID of the shard, which is also the ID of the thread that
+    /// owns it.
+    pub fn id(&self) -> ShardId {
+        self.id
+    }
+
+    /// Returns the number of pages for which the shard has allocated storage.
+    pub fn allocated_pages(&self) -> usize {
+        self.allocated_pages
+    }
+
+    /// Returns the number of slots in the shard which hold values.
+    pub fn occupied(&self) -> usize {
+        self.occupied
+    }
+
+    /// Returns the number of vacant slots on the shard's local free lists.
+    ///
+    /// These are slots which were freed by the shard's own thread, or have
+    /// never been used. They are reused before any slots on the remote free
+    /// lists.
+    pub fn local_free(&self) -> usize {
+        self.local_free
+    }
+
+    /// Returns the number of vacant slots on the shard's remote free lists.
+    ///
+    /// These are slots which were freed by other threads, and have not yet
+    /// been taken by the shard's thread.
+    pub fn remote_free(&self) -> usize {
+        self.remote_free
+    }
+
+    /// Returns the number of times the generation of one of the shard's
+    /// slots has wrapped around.
+    ///
+    /// This is only counted if [`Config::GENERATION_WRAP`] is not `Ignore`;
+    /// otherwise, it is always 0.
+    ///
+    /// [`Config::GENERATION_WRAP`]: crate::Config::GENERATION_WRAP
+    pub fn generation_wraps(&self) -> usize {
+        self.generation_wraps
+    }
+
+    /// Returns the number of values which couldn't be inserted because every
+    /// page in the shard was full.
+    pub fn failed_inserts(&self) -> usize {
+        self.failed_inserts
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::InsertFailures;
+    use crate::{Config, DefaultConfig, InsertError, Pool, Slab};
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn counts_local_and_remote_frees() {
+        let mut slab = Arc::new(Slab::new());
+        let keys = (0..4).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        slab.remove(keys[0]);
+        let remote = {
+            let slab = slab.clone();
+            let keys = keys.clone();
+            thread::spawn(move || {
+                slab.remove(keys[1]);
+                slab.remove(keys[2]);
+            })
+        };
+        remote.join().unwrap();
+
+        let stats = Arc::get_mut(&mut slab).unwrap().shard_stats();
+        let stats = stats
+            .into_iter()
+            .find(|stats| stats.id() == slab.shard_id(keys[0]))
+            .unwrap();
+        let page_size = DefaultConfig::INITIAL_PAGE_SIZE;
+        assert_eq!(stats.allocated_pages(), 1);
+        assert_eq!(stats.occupied(), 1);
+        assert_eq!(stats.remote_free(), 2);
+        assert_eq!(stats.local_free(), page_size - 3);
+    }
+
+    #[test]
+    fn slab_stats() {
+        struct TinyConfig;
+
+        impl Config for TinyConfig {
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 1;
+        }
+
+        let slab = Arc::new(Slab::new_with_config::<TinyConfig>());
+        assert_eq!(slab.stats(), Default::default());
+
+        let keys = (0..2).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        assert!(slab.insert(2).is_none());
+        let remote = {
+            let slab = slab.clone();
+            thread::spawn(move || slab.insert(3).unwrap())
+        };
+        remote.join().unwrap();
+
+        let entry = slab.get(keys[0]).unwrap();
+        assert!(slab.remove(keys[0]));
+        assert!(slab.remove(keys[1]));
+
+        let stats = slab.stats();
+        assert_eq!(stats.occupied(), 2);
+        assert_eq!(stats.allocated_slots(), 4);
+        assert_eq!(
+            stats.allocated_bytes(),
+            4 * std::mem::size_of::<crate::page::Slot<Option<i32>, TinyConfig>>()
+        );
+        assert_eq!(stats.active_shards(), 2);
+        assert_eq!(stats.failed_inserts(), 1);
+        assert_eq!(stats.pending_removals(), 1);
+
+        drop(entry);
+        let stats = slab.stats();
+        assert_eq!(stats.occupied(), 1);
+        assert_eq!(stats.pending_removals(), 0);
+    }
+
+    #[test]
+    fn insert_failures() {
+        use std::sync::Barrier;
+
+        struct OneThread;
+
+        impl Config for OneThread {
+            const MAX_THREADS: usize = 1;
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 1;
+        }
+
+        let slab = Arc::new(Slab::new_with_config::<OneThread>());
+        let barrier = Arc::new(Barrier::new(2));
+        // While both threads are running, they have different IDs, so at most
+        // one of them has a shard.
+        let threads = (0..2)
+            .map(|_| {
+                let slab = slab.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    let inserted = slab.insert(1).is_some();
+                    if inserted {
+                        assert!(slab.insert(2).is_some());
+                        assert!(slab.insert(3).is_none());
+                    }
+                    barrier.wait();
+                    inserted
+                })
+            })
+            .collect::<Vec<_>>();
+        let inserted = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(|&inserted| inserted)
+            .count();
+
+        let failures = slab.stats().insert_failures();
+        assert_eq!(failures.all_pages_full(), inserted);
+        assert_eq!(failures.max_shards(), 2 - inserted);
+        assert_eq!(failures.thread_exiting(), 0);
+        assert_eq!(failures.total(), 2);
+    }
+
+    #[test]
+    fn pages_skipped_and_all_pages_full() {
+        struct TwoPages;
+
+        impl Config for TwoPages {
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 2;
+        }
+
+        let slab = Slab::new_with_config::<TwoPages>();
+        let _keys = (0..2).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        assert_eq!(slab.stats().insert_failures(), InsertFailures::default());
+
+        // Each insert into the second page finds the first one full.
+        let _more = (2..6).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        let stats = slab.stats();
+        assert_eq!(stats.pages_skipped(), 4);
+        assert_eq!(stats.insert_failures(), InsertFailures::default());
+
+        assert!(slab.insert(6).is_none());
+        let stats = slab.stats();
+        assert_eq!(stats.pages_skipped(), 5);
+        assert_eq!(stats.insert_failures().all_pages_full(), 1);
+        assert_eq!(stats.insert_failures().total(), 1);
+    }
+
+    #[test]
+    fn quota_per_shard() {
+        let slab = Arc::new(Slab::new().with_shard_quota(2));
+        let keys = (0..2).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        assert_eq!(slab.try_insert(2), Err(InsertError::QuotaExceeded));
+
+        // Another thread's shard has its own quota.
+        let slab2 = slab.clone();
+        thread::spawn(move || {
+            assert!(slab2.insert(3).is_some());
+            assert!(slab2.insert(4).is_some());
+            assert!(slab2.insert(5).is_none());
+        })
+        .join()
+        .unwrap();
+
+        assert!(slab.remove(keys[0]));
+        assert!(slab.insert(6).is_some());
+
+        let failures = slab.stats().insert_failures();
+        assert_eq!(failures.quota_exceeded(), 2);
+        assert_eq!(failures.all_pages_full(), 0);
+        assert_eq!(slab.stats().pages_skipped(), 0);
+        assert_eq!(failures.total(), 2);
+    }
+
+    #[test]
+    fn remaining_capacity() {
+        struct TinyConfig;
+
+        impl Config for TinyConfig {
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 2;
+        }
+
+        let slab = Arc::new(Slab::new_with_config::<TinyConfig>());
+        let keys = (0..3).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        let remaining = slab.remaining_on_current_shard().unwrap();
+        assert_eq!(remaining.without_allocating(), 3);
+        assert_eq!(remaining.with_allocating(), 3);
+
+        // Slots freed by other threads are counted too.
+        let remote = {
+            let slab = slab.clone();
+            let key = keys[0];
+            thread::spawn(move || {
+                let remaining = slab.remaining_on_current_shard().unwrap();
+                assert_eq!(remaining.without_allocating(), 0);
+                assert_eq!(remaining.with_allocating(), 6);
+                assert!(slab.remove(key));
+            })
+        };
+        remote.join().unwrap();
+        assert!(slab.remove(keys[1]));
+        let remaining = slab.remaining_on_current_shard().unwrap();
+        assert_eq!(remaining.without_allocating(), 5);
+    }
+
+    #[test]
+    fn remaining_capacity_with_quota() {
+        let slab = Arc::new(Slab::new().with_shard_quota(3));
+        let remaining = slab.remaining_on_current_shard().unwrap();
+        assert_eq!(remaining.without_allocating(), 0);
+        assert_eq!(remaining.with_allocating(), 3);
+
+        slab.insert(1).unwrap();
+        slab.insert(2).unwrap();
+        let remaining = slab.remaining_on_current_shard().unwrap();
+        assert_eq!(remaining.without_allocating(), 1);
+        assert_eq!(remaining.with_allocating(), 1);
+
+        // A shard which hasn't been allocated is limited by the quota too.
+        let remote = slab.clone();
+        thread::spawn(move || {
+            let remaining = remote.remaining_on_current_shard().unwrap();
+            assert_eq!(remaining.without_allocating(), 0);
+            assert_eq!(remaining.with_allocating(), 3);
+        })
+        .join()
+        .unwrap();
+
+        slab.insert(3).unwrap();
+        let remaining = slab.remaining_on_current_shard().unwrap();
+        assert_eq!(remaining.with_allocating(), 0);
+        assert_eq!(slab.try_insert(4), Err(crate::InsertError::QuotaExceeded));
+    }
+
+    #[test]
+    fn page_stats() {
+        struct TinyConfig;
+
+        impl Config for TinyConfig {
+            const INITIAL_PAGE_SIZE: usize = 2;
+            const MAX_PAGES: usize = 3;
+        }
+
+        let slab = Slab::new_with_config::<TinyConfig>();
+        let keys = (0..5).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        for &key in &keys[1..4] {
+            assert!(slab.remove(key));
+        }
+
+        let shards = slab.page_stats();
+        assert_eq!(shards.len(), 1);
+        let pages = shards[0]
+            .pages()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(pages, ["1/2", "1/4", "-/8"]);
+    }
+
+    #[test]
+    fn pool_metrics() {
+        let pool = Arc::new(Pool::<String>::new());
+        let keys = (0..4)
+            .map(|_| pool.create_with(|s| s.push_str("hello")).unwrap())
+            .collect::<Vec<_>>();
+        assert!(pool.clear(keys[0]));
+        assert!(!pool.clear(keys[0]));
+        assert_eq!(pool.take(keys[1]).unwrap(), "hello");
+        let remote = {
+            let pool = pool.clone();
+            let key = keys[2];
+            thread::spawn(move || assert!(pool.clear(key)))
+        };
+        remote.join().unwrap();
+
+        // Reuses the slots cleared and taken by this thread. The slot cleared
+        // by the other thread is on the remote free list, so it isn't reused
+        // until the page's never-used slots run out.
+        for _ in 0..4 {
+            pool.create().unwrap();
+        }
+
+        let metrics = pool.metrics();
+        assert_eq!(metrics.created(), 8);
+        assert_eq!(metrics.constructed(), 6);
+        assert_eq!(metrics.reused(), 2);
+        assert_eq!(metrics.cleared(), 2);
+        assert_eq!(metrics.taken(), 1);
+        assert!((metrics.reuse_rate() - 2.0 / 8.0).abs() < f64::EPSILON);
+    }
+}
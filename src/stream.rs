@@ -0,0 +1,311 @@
+#[cfg(feature = "async")]
+use crate::cfg::CfgPrivate;
+use crate::{cfg, shard, DefaultConfig, Shard, Slab};
+#[cfg(feature = "async")]
+use std::task::Waker;
+use std::{
+    fmt,
+    task::{Context, Poll},
+    vec,
+};
+
+/// A stream which takes each value out of a [`Slab`], a page at a time.
+///
+/// This is returned by [`Slab::drain_stream`]. It yields the key and value of
+/// each value in the slab, taking it out of the slab as it goes. After
+/// finishing each page, it yields to the executor, so that draining a large
+/// slab doesn't starve other tasks running on the same thread.
+///
+/// Values may be received with [`poll_take`]. If the `futures-core` feature
+/// is enabled, `DrainStream` also implements [`Stream`].
+///
+/// [`poll_take`]: DrainStream::poll_take
+/// [`Stream`]: https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html
+pub struct DrainStream<'a, T, C: cfg::Config = DefaultConfig> {
+    slab: &'a Slab<T, C>,
+    shards: shard::Iter<'a, Option<T>, C>,
+    shard: Option<&'a Shard<Option<T>, C>>,
+    /// The index of the next page to visit in `shard`.
+    page: usize,
+    /// The keys of the slots on the current page which haven't been visited.
+    keys: vec::IntoIter<usize>,
+    /// The key of a value which was being accessed when the stream last
+    /// tried to take it.
+    waiting: Option<usize>,
+    /// The waker registered to be woken when a guard referencing the value
+    /// at `waiting` is released.
+    #[cfg(feature = "async")]
+    watching: Option<(usize, Waker)>,
+    /// Whether any page has been visited yet.
+    started: bool,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Returns a [`DrainStream`] which takes each value out of the slab.
+    ///
+    /// The stream visits each allocated page of each shard in turn, taking
+    /// every value on the page, and yields to the executor between pages.
+    /// Like [`take`], taking a value which is being accessed waits for the
+    /// guards referencing it to be dropped, but rather than blocking the
+    /// current thread, the stream returns `Poll::Pending` until they are.
+    /// If the `async` feature is enabled, the task is woken when a guard is
+    /// released; otherwise, it is woken immediately, to be polled again.
+    /// Values which are inserted into a page after the stream has visited it
+    /// are not yielded.
+    ///
+    /// This is intended for pipelines which hand the entries in a slab off to
+    /// asynchronous consumers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// use std::task::{Context, Poll, Waker};
+    /// # fn noop_waker() -> Waker {
+    /// #     use std::task::{RawWaker, RawWakerVTable};
+    /// #     static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    /// #     unsafe fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+    /// #     unsafe fn noop(_: *const ()) {}
+    /// #     unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    /// # }
+    /// let slab = Slab::new();
+    /// let key = slab.insert("hello world").unwrap();
+    ///
+    /// let waker = noop_waker();
+    /// let mut cx = Context::from_waker(&waker);
+    /// let mut drain = slab.drain_stream();
+    /// assert_eq!(drain.poll_take(&mut cx), Poll::Ready(Some((key, "hello world"))));
+    /// assert_eq!(drain.poll_take(&mut cx), Poll::Ready(None));
+    /// assert!(!slab.contains(key));
+    /// ```
+    ///
+    /// [`take`]: Slab::take
+    pub fn drain_stream(&self) -> DrainStream<'_, T, C> {
+        DrainStream {
+            slab: self,
+            shards: self.shards.iter(),
+            shard: None,
+            page: 0,
+            keys: Vec::new().into_iter(),
+            waiting: None,
+            #[cfg(feature = "async")]
+            watching: None,
+            started: false,
+        }
+    }
+}
+
+// === impl DrainStream ===
+
+impl<T, C: cfg::Config> DrainStream<'_, T, C> {
+    /// Polls for the next value in the slab, taking it out of the slab.
+    ///
+    /// This returns `Poll::Ready(None)` once every page has been visited.
+    /// Otherwise, if it has just finished a page, or the next value is being
+    /// accessed, it returns `Poll::Pending`, so that the executor may run
+    /// other tasks (such as those holding guards) before it is polled again.
+    pub fn poll_take(&mut self, cx: &mut Context<'_>) -> Poll<Option<(usize, T)>> {
+        loop {
+            loop {
+                let key = match self.waiting.take() {
+                    // We already know this key held a value. Checking again
+                    // would briefly reference the value, and releasing that
+                    // reference would wake this task.
+                    Some(key) => key,
+                    None => match self.keys.next() {
+                        // A vacant slot's current generation was never issued
+                        // as a key, so it must not be taken.
+                        Some(key) if !self.slab.contains(key) => continue,
+                        Some(key) => key,
+                        None => break,
+                    },
+                };
+                match self.poll_take_key(key, cx) {
+                    Poll::Ready(Some(value)) => return Poll::Ready(Some((key, value))),
+                    Poll::Ready(None) => {}
+                    Poll::Pending => {
+                        test_println!("drain_stream: waiting for {:#x}", key);
+                        self.waiting = Some(key);
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            if !self.next_page() {
+                return Poll::Ready(None);
+            }
+            if self.started {
+                test_println!("drain_stream: yielding before page {}", self.page - 1);
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.started = true;
+        }
+    }
+
+    /// Tries to take the value at `key`, registering to be woken when a guard
+    /// referencing it is released if it is being accessed.
+    fn poll_take_key(&mut self, key: usize, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let shard = match self.shard {
+            Some(shard) => shard,
+            None => return Poll::Ready(None),
+        };
+        if let Poll::Ready(value) = shard.poll_take(key) {
+            self.unwatch();
+            return Poll::Ready(value);
+        }
+
+        #[cfg(feature = "async")]
+        {
+            let stripped = C::strip_reserved(key);
+            let registered = match self.watching {
+                Some((k, ref waker)) => k == stripped && waker.will_wake(cx.waker()),
+                None => false,
+            };
+            if !registered {
+                self.unwatch();
+                self.slab
+                    .shards
+                    .vacancies()
+                    .watch_release(stripped, cx.waker());
+                self.watching = Some((stripped, cx.waker().clone()));
+            }
+
+            // A guard may have been released since we last tried.
+            let poll = shard.poll_take(key);
+            if poll.is_ready() {
+                self.unwatch();
+            }
+            poll
+        }
+
+        #[cfg(not(feature = "async"))]
+        {
+            // Without the `async` feature, releasing a guard doesn't wake
+            // anything, so try again the next time we're polled.
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    /// Stops watching for a guard to be released, if we were.
+    fn unwatch(&mut self) {
+        #[cfg(feature = "async")]
+        {
+            if let Some((key, waker)) = self.watching.take() {
+                self.slab.shards.vacancies().unwatch_release(key, &waker);
+            }
+        }
+    }
+
+    /// Advances to the next allocated page, returning `false` if there are no
+    /// more pages.
+    fn next_page(&mut self) -> bool {
+        loop {
+            if let Some(shard) = self.shard {
+                while self.page < C::MAX_PAGES {
+                    let page = self.page;
+                    self.page += 1;
+                    if let Some(keys) = shard.page_keys(page) {
+                        self.keys = keys.into_iter();
+                        return true;
+                    }
+                }
+            }
+            self.shard = match self.shards.next() {
+                Some(shard) => Some(shard),
+                None => return false,
+            };
+            self.page = 0;
+        }
+    }
+}
+
+#[cfg(feature = "futures-core")]
+impl<T, C: cfg::Config> futures_core::Stream for DrainStream<'_, T, C> {
+    type Item = (usize, T);
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.poll_take(cx)
+    }
+}
+
+impl<T, C: cfg::Config> Drop for DrainStream<'_, T, C> {
+    fn drop(&mut self) {
+        self.unwatch();
+    }
+}
+
+impl<T, C: cfg::Config> fmt::Debug for DrainStream<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrainStream")
+            .field("page", &self.page)
+            .field("remaining_on_page", &self.keys.len())
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{tests::util::counting_waker, Config};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct TinyConfig;
+
+    impl Config for TinyConfig {
+        const INITIAL_PAGE_SIZE: usize = 2;
+    }
+
+    #[test]
+    fn yields_between_pages() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = Slab::new_with_config::<TinyConfig>();
+        // Fill the first page (2 slots) and part of the second (4 slots).
+        let keys = (0..3).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        slab.remove(keys[1]);
+
+        let mut drain = slab.drain_stream();
+        assert_eq!(drain.poll_take(&mut cx), Poll::Ready(Some((keys[0], 0))));
+        assert_eq!(drain.poll_take(&mut cx), Poll::Pending);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert_eq!(drain.poll_take(&mut cx), Poll::Ready(Some((keys[2], 2))));
+        assert_eq!(drain.poll_take(&mut cx), Poll::Ready(None));
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+
+        assert!(keys.iter().all(|&key| !slab.contains(key)));
+    }
+
+    #[test]
+    fn waits_for_guards_without_blocking() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let guard = slab.get(key).unwrap();
+
+        let mut drain = slab.drain_stream();
+        assert_eq!(drain.poll_take(&mut cx), Poll::Pending);
+        assert_eq!(drain.poll_take(&mut cx), Poll::Pending);
+        assert_eq!(*guard, 1);
+
+        // With the `async` feature, the task is woken once the guard is
+        // released; otherwise, it was woken when the stream returned
+        // `Pending`.
+        #[cfg(feature = "async")]
+        assert_eq!(WAKES.load(Ordering::SeqCst), 0);
+        drop(guard);
+        assert!(WAKES.load(Ordering::SeqCst) >= 1);
+        assert_eq!(drain.poll_take(&mut cx), Poll::Ready(Some((key, 1))));
+        assert_eq!(drain.poll_take(&mut cx), Poll::Ready(None));
+        assert!(!slab.contains(key));
+    }
+}
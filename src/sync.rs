@@ -10,6 +10,10 @@ mod inner {
         cell::UnsafeCell, hint, lazy_static, sync::Mutex, thread::yield_now, thread_local,
     };
 
+    /// Prefetching is a no-op under loom, since it can't observe caches.
+    #[inline(always)]
+    pub(crate) fn prefetch_read<T>(_: *const T) {}
+
     pub(crate) mod alloc {
         #![allow(dead_code)]
         use loom::alloc;
@@ -79,6 +83,33 @@ mod inner {
         }
     }
 
+    /// Hint to the CPU that the cache line containing `ptr` will be read soon.
+    ///
+    /// This is only a hint: it never faults, even if `ptr` is dangling, and it
+    /// compiles to nothing on targets without a prefetch instruction (or under
+    /// Miri, which doesn't support the intrinsics).
+    #[inline(always)]
+    pub(crate) fn prefetch_read<T>(ptr: *const T) {
+        #[cfg(all(target_arch = "x86_64", target_feature = "sse", not(miri)))]
+        unsafe {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
+        }
+        #[cfg(all(target_arch = "x86", target_feature = "sse", not(miri)))]
+        unsafe {
+            use std::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+            _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
+        }
+        #[cfg(any(
+            not(all(
+                any(target_arch = "x86", target_arch = "x86_64"),
+                target_feature = "sse"
+            )),
+            miri
+        ))]
+        let _ = ptr;
+    }
+
     #[derive(Debug)]
     pub(crate) struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
 
@@ -0,0 +1,421 @@
+//! A randomized-operation harness for checking a [`Slab`] against a model.
+//!
+//! This module is only available when the `testing` feature is enabled. It
+//! lets downstream crates and fuzzers drive a slab with a sequence of
+//! [`Op`]s, checking the result of each operation against a simple reference
+//! model of which keys are live and what values they hold.
+//!
+//! The harness is deterministic: it never spawns threads. Instead, the
+//! [`Op::Thread`] operation changes the thread ID that subsequent operations
+//! are performed as, so operations on other threads' shards are exercised
+//! without introducing any nondeterminism. A failing sequence of operations
+//! will fail the same way every time it is applied, which makes it suitable
+//! for shrinking by property-testing libraries.
+//!
+//! # Examples
+//!
+//! ```
+//! use sharded_slab::testing::{self, Op};
+//!
+//! let ops = [
+//!     Op::Insert,
+//!     Op::Insert,
+//!     Op::Thread(1),
+//!     Op::RemoveLive(0),
+//!     Op::GetLive(0),
+//!     Op::Take(12345),
+//! ];
+//! testing::apply(&ops).expect("slab should agree with the model");
+//! ```
+//!
+//! Driving the harness from a fuzzer's raw input:
+//!
+//! ```
+//! use sharded_slab::testing::{self, Op};
+//!
+//! fn fuzz_target(data: &[u8]) {
+//!     let ops = Op::decode(data);
+//!     if let Err(failure) = testing::apply(&ops) {
+//!         panic!("{}", failure);
+//!     }
+//! }
+//! # fuzz_target(b"\x00\x00\x01\x07\x02\x03\x00");
+//! ```
+//!
+//! [`Slab`]: crate::Slab
+use crate::{
+    cfg::{self, DefaultConfig},
+    tid, Slab,
+};
+use std::{fmt, mem};
+
+/// An operation applied to a slab by the [`Harness`].
+///
+/// Operations which take a key come in two forms. The "live" forms (such as
+/// [`Op::GetLive`]) use their argument to pick one of the keys that the model
+/// currently considers live, so that they almost always refer to an existing
+/// entry. The other forms use their argument as a key directly, so they
+/// almost always refer to an entry that doesn't exist.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// Insert a new value into the slab.
+    Insert,
+    /// Get the entry for a key.
+    Get(usize),
+    /// Get the entry for a live key, chosen by the argument.
+    GetLive(usize),
+    /// Remove the entry for a key.
+    Remove(usize),
+    /// Remove the entry for a live key, chosen by the argument.
+    RemoveLive(usize),
+    /// Take the value for a key out of the slab.
+    Take(usize),
+    /// Take the value for a live key out of the slab, chosen by the argument.
+    TakeLive(usize),
+    /// Perform subsequent operations as if on the thread with the given ID.
+    ///
+    /// The ID is wrapped to the configured maximum number of threads.
+    Thread(usize),
+}
+
+/// Applies a sequence of operations to a new slab with the default
+/// configuration, checking each against the model.
+///
+/// This is equivalent to creating a new [`Harness`], calling
+/// [`Harness::apply`], and then [`Harness::check`].
+pub fn apply(ops: &[Op]) -> Result<(), Failure> {
+    let mut harness = Harness::new();
+    harness.apply(ops)?;
+    harness.check()
+}
+
+/// Drives a [`Slab`] with a sequence of [`Op`]s, checking each result
+/// against a reference model.
+///
+/// [`Slab`]: crate::Slab
+pub struct Harness<C: cfg::Config = DefaultConfig> {
+    slab: Slab<u64, C>,
+    /// The entries the model considers live, in the order they were inserted
+    /// (except where a removal moved the last entry into its place).
+    live: Vec<(usize, u64)>,
+    next_value: u64,
+    thread: usize,
+    steps: usize,
+}
+
+/// The reason the slab disagreed with the model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    step: Option<usize>,
+    op: Option<Op>,
+    reason: String,
+}
+
+// === impl Op ===
+
+impl Op {
+    /// Decodes a sequence of operations from arbitrary bytes.
+    ///
+    /// Every input decodes to some (possibly empty) sequence of operations,
+    /// so this is suitable for turning a fuzzer's raw input into operations.
+    /// Each operation is encoded as a byte selecting the kind of operation,
+    /// followed by its argument (if it has one) as up to eight little-endian
+    /// bytes. An argument cut short by the end of the input is padded with
+    /// zeroes.
+    pub fn decode(mut bytes: &[u8]) -> Vec<Op> {
+        let mut ops = Vec::new();
+        while let Some((&kind, rest)) = bytes.split_first() {
+            bytes = rest;
+            if kind % 8 == 0 {
+                ops.push(Op::Insert);
+                continue;
+            }
+
+            let len = std::cmp::min(bytes.len(), mem::size_of::<usize>());
+            let (arg, rest) = bytes.split_at(len);
+            bytes = rest;
+            let arg = arg
+                .iter()
+                .rev()
+                .fold(0usize, |acc, &byte| (acc << 8) | byte as usize);
+            ops.push(match kind % 8 {
+                1 => Op::Get(arg),
+                2 => Op::GetLive(arg),
+                3 => Op::Remove(arg),
+                4 => Op::RemoveLive(arg),
+                5 => Op::Take(arg),
+                6 => Op::TakeLive(arg),
+                _ => Op::Thread(arg),
+            });
+        }
+        ops
+    }
+}
+
+// === impl Harness ===
+
+impl Harness {
+    /// Returns a new harness driving a slab with the default configuration.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: cfg::Config> Harness<C> {
+    /// Returns a new harness driving a slab with the provided configuration.
+    pub fn new_with_config() -> Self {
+        Self {
+            slab: Slab::new_with_config::<C>(),
+            live: Vec::new(),
+            next_value: 0,
+            thread: 0,
+            steps: 0,
+        }
+    }
+
+    /// Returns a reference to the slab being driven.
+    pub fn slab(&self) -> &Slab<u64, C> {
+        &self.slab
+    }
+
+    /// Applies a sequence of operations, checking the result of each against
+    /// the model.
+    ///
+    /// This stops at the first operation whose result disagrees with the
+    /// model. Operations applied by previous calls count towards the step
+    /// numbers reported in failures.
+    pub fn apply(&mut self, ops: &[Op]) -> Result<(), Failure> {
+        for &op in ops {
+            let step = self.steps;
+            self.steps += 1;
+            self.step(op).map_err(|reason| Failure {
+                step: Some(step),
+                op: Some(op),
+                reason,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks that every entry the model considers live is present in the
+    /// slab with the expected value, and that the slab contains no other
+    /// entries.
+    pub fn check(&mut self) -> Result<(), Failure> {
+        let fail = |reason| Failure {
+            step: None,
+            op: None,
+            reason,
+        };
+        let slab = &self.slab;
+        let thread = self.thread;
+        for &(key, value) in &self.live {
+            let actual = tid::with(thread, || slab.get(key).map(|entry| *entry));
+            if actual != Some(value) {
+                return Err(fail(format!(
+                    "expected key {:#x} to have value {}, but got {:?}",
+                    key, value, actual
+                )));
+            }
+        }
+
+        let mut expected = self
+            .live
+            .iter()
+            .map(|&(_, value)| value)
+            .collect::<Vec<_>>();
+        let mut actual = self.slab.unique_iter().copied().collect::<Vec<_>>();
+        expected.sort_unstable();
+        actual.sort_unstable();
+        if actual != expected {
+            return Err(fail(format!(
+                "expected the slab to contain {:?}, but it contains {:?}",
+                expected, actual
+            )));
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, op: Op) -> Result<(), String> {
+        let thread = self.thread;
+        match op {
+            Op::Insert => {
+                let value = self.next_value;
+                self.next_value += 1;
+                let key = tid::with(thread, || self.slab.insert(value))
+                    .ok_or_else(|| "slab unexpectedly exhausted".to_string())?;
+                if let Some(&(_, prev)) = self.live.iter().find(|&&(k, _)| k == key) {
+                    return Err(format!(
+                        "inserted key {:#x} is already live with value {}",
+                        key, prev
+                    ));
+                }
+                self.live.push((key, value));
+            }
+            Op::Get(key) => {
+                let actual = tid::with(thread, || self.slab.get(key).map(|entry| *entry));
+                expect_eq("get", key, self.lookup(key).map(|(_, v)| v), actual)?;
+            }
+            Op::GetLive(seed) => {
+                if let Some((key, value)) = self.pick(seed) {
+                    let actual = tid::with(thread, || self.slab.get(key).map(|entry| *entry));
+                    expect_eq("get", key, Some(value), actual)?;
+                }
+            }
+            Op::Remove(key) => {
+                let expected = self.remove_model(key);
+                let removed = tid::with(thread, || self.slab.remove(key));
+                if removed != expected.is_some() {
+                    return Err(format!(
+                        "remove({:#x}) returned {}, but the model expected {}",
+                        key,
+                        removed,
+                        expected.is_some()
+                    ));
+                }
+            }
+            Op::RemoveLive(seed) => {
+                if let Some(i) = self.pick_index(seed) {
+                    let (key, _) = self.live.swap_remove(i);
+                    if !tid::with(thread, || self.slab.remove(key)) {
+                        return Err(format!("remove({:#x}) failed for a live key", key));
+                    }
+                }
+            }
+            Op::Take(key) => {
+                let expected = self.remove_model(key);
+                let actual = tid::with(thread, || self.slab.take(key));
+                expect_eq("take", key, expected, actual)?;
+            }
+            Op::TakeLive(seed) => {
+                if let Some(i) = self.pick_index(seed) {
+                    let (key, value) = self.live.swap_remove(i);
+                    let actual = tid::with(thread, || self.slab.take(key));
+                    expect_eq("take", key, Some(value), actual)?;
+                }
+            }
+            Op::Thread(thread) => {
+                self.thread = thread % C::MAX_THREADS;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the index and value of the live entry for `key`, ignoring any
+    /// bits the slab would ignore.
+    fn lookup(&self, key: usize) -> Option<(usize, u64)> {
+        let key = key & (!0 >> C::RESERVED_BITS);
+        self.live
+            .iter()
+            .position(|&(k, _)| k == key)
+            .map(|i| (i, self.live[i].1))
+    }
+
+    /// Removes the live entry for `key` from the model, returning its value.
+    fn remove_model(&mut self, key: usize) -> Option<u64> {
+        let (i, _) = self.lookup(key)?;
+        Some(self.live.swap_remove(i).1)
+    }
+
+    fn pick_index(&self, seed: usize) -> Option<usize> {
+        if self.live.is_empty() {
+            return None;
+        }
+        Some(seed % self.live.len())
+    }
+
+    fn pick(&self, seed: usize) -> Option<(usize, u64)> {
+        self.pick_index(seed).map(|i| self.live[i])
+    }
+}
+
+impl<C: cfg::Config> fmt::Debug for Harness<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Harness")
+            .field("slab", &self.slab)
+            .field("live", &self.live.len())
+            .field("thread", &self.thread)
+            .field("steps", &self.steps)
+            .finish()
+    }
+}
+
+fn expect_eq(
+    what: &str,
+    key: usize,
+    expected: Option<u64>,
+    actual: Option<u64>,
+) -> Result<(), String> {
+    if expected == actual {
+        return Ok(());
+    }
+    Err(format!(
+        "{}({:#x}) returned {:?}, but the model expected {:?}",
+        what, key, actual, expected
+    ))
+}
+
+// === impl Failure ===
+
+impl Failure {
+    /// Returns the index of the operation that failed, or `None` if the
+    /// failure was found by [`Harness::check`].
+    pub fn step(&self) -> Option<usize> {
+        self.step
+    }
+
+    /// Returns the operation that failed, or `None` if the failure was found
+    /// by [`Harness::check`].
+    pub fn op(&self) -> Option<Op> {
+        self.op
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.step, self.op) {
+            (Some(step), Some(op)) => write!(f, "step {} ({:?}): {}", step, op, self.reason),
+            _ => write!(f, "final check: {}", self.reason),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agrees_with_model() {
+        let mut ops = Vec::new();
+        for i in 0..200 {
+            ops.push(Op::Thread(i % 3));
+            ops.push(Op::Insert);
+            ops.push(Op::Insert);
+            ops.push(Op::GetLive(i * 7));
+            ops.push(Op::Get(i));
+            match i % 3 {
+                0 => ops.push(Op::RemoveLive(i * 13)),
+                1 => ops.push(Op::TakeLive(i * 5)),
+                _ => ops.push(Op::Take(i)),
+            }
+        }
+        apply(&ops).unwrap();
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(Op::decode(&[]), vec![]);
+        assert_eq!(
+            Op::decode(&[0, 8, 3, 1, 2, 7, 5]),
+            vec![Op::Insert, Op::Insert, Op::Remove(0x0507_0201)],
+        );
+        assert_eq!(Op::decode(&[15]), vec![Op::Thread(0)]);
+    }
+}
@@ -1,7 +1,10 @@
 //! Ensures that a custom config behaves as the default config, until limits are reached.
 //! Prevents regression after #80.
 
-use crate::{cfg::CfgPrivate, Config, Slab};
+use crate::{
+    cfg::{self, CfgPrivate},
+    Config, Slab,
+};
 
 struct CustomConfig;
 
@@ -76,3 +79,513 @@ fn double_get() {
 
     slab_eq(custom_slab, default_slab);
 }
+
+/// Threads with IDs past the space initially reserved in the shard array can
+/// still use the slab.
+#[test]
+fn shard_array_grows() {
+    struct AutoSize;
+
+    impl Config for AutoSize {
+        const AUTO_SIZE_SHARDS: bool = true;
+    }
+
+    fn check<C: Config>() {
+        let mut slab = Slab::new_with_config::<C>();
+        let tids = [0, 31, 32, 33, 64, C::MAX_SHARDS / 2, C::MAX_SHARDS - 1];
+        let keys = tids
+            .iter()
+            .map(|&tid| crate::tid::with(tid, || slab.insert(tid).unwrap()))
+            .collect::<Vec<_>>();
+
+        for (&tid, &key) in tids.iter().zip(&keys) {
+            assert_eq!(slab.get(key).unwrap(), tid);
+        }
+        assert_eq!(
+            slab.active_shard_ids()
+                .map(|id| id.as_usize())
+                .collect::<Vec<_>>(),
+            tids
+        );
+        assert_eq!(slab.unique_iter().count(), tids.len());
+    }
+
+    check::<crate::DefaultConfig>();
+    check::<AutoSize>();
+}
+
+/// Values inserted by one thread are spread across shards, and may be removed
+/// from any thread.
+#[test]
+fn balanced_inserts() {
+    use std::{sync::Arc, thread};
+
+    struct Balanced;
+
+    impl Config for Balanced {
+        const BALANCED_INSERTS: bool = true;
+    }
+
+    let slab = Arc::new(Slab::new_with_config::<Balanced>());
+    let keys = (0..64).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    assert_eq!(slab.active_shards(), 32);
+
+    let s = slab.clone();
+    let (removed, kept) = keys.split_at(32);
+    let removed = removed.to_vec();
+    thread::spawn(move || {
+        for key in removed {
+            assert!(s.remove(key));
+        }
+    })
+    .join()
+    .unwrap();
+
+    for (i, &key) in kept.iter().enumerate() {
+        assert_eq!(slab.get(key).unwrap(), i + 32);
+    }
+    for i in 0..64 {
+        let key = slab.insert(i).unwrap();
+        assert_eq!(slab.get(key).unwrap(), i);
+    }
+}
+
+/// Values inserted with a shard hint are stored in the hinted shard,
+/// regardless of which thread inserts them.
+#[test]
+fn shard_hints() {
+    use crate::{tests::util::noop_waker, with_shard_hint, ShardHinted, ShardId};
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        thread,
+    };
+
+    struct Hinted;
+
+    impl Config for Hinted {
+        const SHARD_HINTS: bool = true;
+    }
+
+    /// Inserts a value each time it is polled.
+    struct InsertOnPoll<'a>(&'a Slab<usize, Hinted>, Vec<usize>);
+
+    impl Future for InsertOnPoll<'_> {
+        type Output = Vec<usize>;
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Vec<usize>> {
+            let key = self.0.insert(1).unwrap();
+            self.1.push(key);
+            if self.1.len() < 2 {
+                return Poll::Pending;
+            }
+            Poll::Ready(std::mem::take(&mut self.1))
+        }
+    }
+
+    let slab = Arc::new(Slab::new_with_config::<Hinted>());
+    let hint = ShardId::new(5);
+
+    let mut keys = (0..4)
+        .map(|_| {
+            let slab = slab.clone();
+            thread::spawn(move || with_shard_hint(hint, || slab.insert(1).unwrap()))
+                .join()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // A hinted future uses the same shard on whichever thread polls it.
+    let mut future = Box::pin(ShardHinted::new(hint, InsertOnPoll(&slab, Vec::new())));
+    let waker = noop_waker();
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut cx = Context::from_waker(&waker);
+            assert!(future.as_mut().poll(&mut cx).is_pending());
+        });
+    });
+    let mut cx = Context::from_waker(&waker);
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(polled) => keys.extend(polled),
+        Poll::Pending => panic!("the future should complete on the second poll"),
+    }
+    assert_eq!(keys.len(), 6);
+    assert!(keys.iter().all(|&key| slab.shard_id(key) == hint));
+
+    // Hints nest, and are unset afterwards.
+    let own = slab.current_shard_id().unwrap();
+    with_shard_hint(ShardId::new(1), || {
+        with_shard_hint(ShardId::new(2), || {
+            assert_eq!(slab.current_shard_id(), Some(ShardId::new(2)));
+        });
+        assert_eq!(slab.current_shard_id(), Some(ShardId::new(1)));
+    });
+    let key = slab.insert(2).unwrap();
+    assert_eq!(slab.shard_id(key), own);
+
+    // Hinted values may be removed from any thread.
+    for key in keys {
+        assert!(slab.remove(key));
+    }
+}
+
+/// A slot whose generation wraps around is counted, and quarantined if the
+/// config asks for it.
+#[test]
+fn generation_wrap() {
+    use crate::{cfg::WIDTH, page::slot::Generation, GenerationWrap, Pack};
+
+    // Leave only a few bits for the generation, so that it wraps quickly.
+    struct Count;
+
+    impl Config for Count {
+        const INITIAL_PAGE_SIZE: usize = 4;
+        const MAX_PAGES: usize = 2;
+        const MAX_THREADS: usize = 4;
+        const RESERVED_BITS: usize = WIDTH - 12;
+        const GENERATION_WRAP: GenerationWrap = GenerationWrap::Count;
+    }
+
+    struct Quarantine;
+
+    impl Config for Quarantine {
+        const INITIAL_PAGE_SIZE: usize = 4;
+        const MAX_PAGES: usize = 2;
+        const MAX_THREADS: usize = 4;
+        const RESERVED_BITS: usize = WIDTH - 12;
+        const GENERATION_WRAP: GenerationWrap = GenerationWrap::Quarantine;
+    }
+
+    fn cycle<C: Config>(slab: &Slab<usize, C>) -> Vec<usize> {
+        (0..=Generation::<C>::BITS)
+            .map(|i| {
+                let key = slab.insert(i).unwrap();
+                assert!(slab.remove(key));
+                key & ((1 << Generation::<C>::SHIFT) - 1)
+            })
+            .collect()
+    }
+
+    let mut slab = Slab::new_with_config::<Count>();
+    let indices = cycle(&slab);
+    // Without quarantine, the same slot is reused every time.
+    assert!(indices.iter().all(|&idx| idx == indices[0]));
+    assert_eq!(slab.shard_stats()[0].generation_wraps(), 1);
+
+    let mut slab = Slab::new_with_config::<Quarantine>();
+    let indices = cycle(&slab);
+    // Once the first slot's generation wraps, it is never reused.
+    let (last, rest) = indices.split_last().unwrap();
+    assert!(rest.iter().all(|&idx| idx == indices[0]));
+    assert_ne!(*last, indices[0]);
+    assert_eq!(slab.shard_stats()[0].generation_wraps(), 1);
+
+    // Compacting the slab doesn't reuse it, either, unless its page is
+    // released.
+    let key = slab.insert(1).unwrap();
+    slab.compact(|_, _| panic!("no values should be moved"));
+    assert_eq!(slab.get(key).unwrap(), 1);
+    let key = slab.insert(2).unwrap();
+    assert_ne!(
+        key & ((1 << Generation::<Quarantine>::SHIFT) - 1),
+        indices[0]
+    );
+}
+
+/// Keys are tagged with the slab that issued them, and rejected by others.
+#[test]
+fn tagged_keys() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    struct Tagged;
+
+    impl Config for Tagged {
+        const TAG_BITS: usize = 4;
+    }
+
+    let a = Slab::new_with_config::<Tagged>();
+    let b = Slab::new_with_config::<Tagged>();
+    let key_a = a.insert(1).unwrap();
+    let key_b = b.insert(2).unwrap();
+    assert_ne!(key_a, key_b);
+    assert_eq!(a.get(key_a).unwrap(), 1);
+    assert_eq!(b.get(key_b).unwrap(), 2);
+
+    // Using a key with the wrong slab panics in debug (or strict) builds, and
+    // otherwise doesn't find anything.
+    let foreign = catch_unwind(AssertUnwindSafe(|| b.get(key_a).map(|entry| *entry)));
+    if cfg!(any(debug_assertions, feature = "strict")) {
+        assert!(foreign.is_err());
+    } else {
+        assert_eq!(foreign.unwrap(), None);
+    }
+
+    // Untagged keys are just invalid.
+    let untagged = key_a & !Tagged::TAG_MASK;
+    assert!(a.get(untagged).is_none());
+    assert!(!a.remove(untagged));
+    assert!(a.remove(key_a));
+}
+
+/// The same operations produce the same keys, regardless of which threads
+/// perform them.
+#[test]
+fn deterministic_keys() {
+    use std::{sync::Arc, thread};
+
+    struct Deterministic;
+
+    impl Config for Deterministic {
+        const DETERMINISTIC: bool = true;
+    }
+
+    fn run(slab: &Slab<usize, Deterministic>) -> Vec<usize> {
+        let mut keys = Vec::new();
+        for i in 0..8 {
+            keys.push(slab.insert(i).unwrap());
+        }
+        assert!(slab.remove(keys[2]));
+        assert_eq!(slab.take(keys[5]), Some(5));
+        keys.push(slab.insert(8).unwrap());
+        keys.push(slab.insert(9).unwrap());
+        keys
+    }
+
+    let slab = Slab::new_with_config::<Deterministic>();
+    let expected = run(&slab);
+    assert_eq!(slab.current_shard_id().unwrap().as_usize(), 0);
+
+    let actual = thread::spawn(|| run(&Slab::new_with_config::<Deterministic>()))
+        .join()
+        .unwrap();
+    assert_eq!(actual, expected);
+
+    // Values inserted on one thread and removed on another are freed just
+    // the same as if one thread had done both.
+    let local = Slab::new_with_config::<Deterministic>();
+    let key = local.insert(0).unwrap();
+    assert!(!local.is_local(key));
+    assert!(local.remove(key));
+    let reused = local.insert(1).unwrap();
+
+    let slab = Arc::new(Slab::new_with_config::<Deterministic>());
+    let s = slab.clone();
+    let key = thread::spawn(move || s.insert(0).unwrap()).join().unwrap();
+    let s = slab.clone();
+    thread::spawn(move || assert!(s.remove(key)))
+        .join()
+        .unwrap();
+    assert_eq!(slab.insert(1).unwrap(), reused);
+}
+
+/// A single-threaded config's keys are just slot indices, on any thread.
+#[test]
+fn single_threaded_keys() {
+    use crate::ConfigError;
+    use std::{sync::Arc, thread};
+
+    struct SingleThreaded;
+
+    impl Config for SingleThreaded {
+        const MAX_THREADS: usize = 1;
+        const SINGLE_THREADED: bool = true;
+    }
+
+    struct Invalid;
+
+    impl Config for Invalid {
+        const SINGLE_THREADED: bool = true;
+    }
+
+    let slab = Arc::new(Slab::new_with_config::<SingleThreaded>());
+    let keys = (0..4).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    assert_eq!(keys, vec![0, 1, 2, 3]);
+    assert_eq!(slab.current_shard_id().unwrap().as_usize(), 0);
+
+    let s = slab.clone();
+    let key = thread::spawn(move || {
+        assert!(s.remove(1));
+        s.insert(4).unwrap()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(key, 4);
+    assert_eq!(slab.get(key).unwrap(), 4);
+    assert!(slab.get(1).is_none());
+
+    assert!(matches!(
+        Slab::<usize>::try_new_with_config::<Invalid>(),
+        Err(ConfigError::TooManyThreads { max_threads }) if max_threads == Invalid::MAX_THREADS
+    ));
+}
+
+#[test]
+fn timestamps() {
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    struct Timestamped;
+
+    impl Config for Timestamped {
+        const TRACK_INSERTED: bool = true;
+        const TRACK_ACCESS: bool = true;
+        const INITIAL_PAGE_SIZE: usize = 2;
+    }
+
+    let start = Instant::now();
+    let mut slab = Arc::new(Slab::new_with_config::<Timestamped>());
+    let keys = (0..3).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    let inserted_at = slab.get(keys[2]).unwrap().inserted_at().unwrap();
+    // Timestamps are rounded down to the millisecond.
+    assert!(inserted_at + Duration::from_millis(1) >= start);
+
+    thread::sleep(Duration::from_millis(20));
+    let entry = slab.clone().get_owned(keys[2]).unwrap();
+    assert_eq!(entry.inserted_at(), Some(inserted_at));
+    assert!(entry.last_accessed().unwrap() >= inserted_at + Duration::from_millis(20));
+    drop(entry);
+
+    let mut old = slab.keys_older_than(Duration::from_millis(10));
+    old.sort_unstable();
+    assert_eq!(old, keys);
+
+    // Removing a value clears its insertion time.
+    assert!(slab.remove(keys[0]));
+    assert_eq!(slab.keys_older_than(Duration::from_millis(10)).len(), 2);
+    let key = slab.insert(3).unwrap();
+    assert!(slab.get(key).unwrap().inserted_at().unwrap() > inserted_at);
+    assert!(slab.remove(key));
+
+    // Compacting the slab keeps each value's insertion time.
+    let mut remapped = Vec::new();
+    Arc::get_mut(&mut slab)
+        .unwrap()
+        .compact(|old, new| remapped.push((old, new)));
+    assert_eq!(remapped.len(), 1);
+    let (_, new) = remapped[0];
+    assert_eq!(slab.get(new).unwrap().inserted_at(), Some(inserted_at));
+}
+
+struct TrackAccess;
+
+impl Config for TrackAccess {
+    const TRACK_ACCESS: bool = true;
+}
+
+/// Cloning every value out of the slab doesn't count as accessing them.
+#[test]
+fn to_vec_doesnt_record_access() {
+    use std::{thread, time::Duration};
+
+    let slab = Slab::new_with_config::<TrackAccess>();
+    let key = slab.insert(1).unwrap();
+    let entry = slab.get(key).unwrap();
+    let accessed = entry.last_accessed().unwrap();
+
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(slab.to_vec(), vec![(key, 1)]);
+    assert_eq!(entry.last_accessed(), Some(accessed));
+}
+
+/// Only the value returned by `find` counts as accessed, not every value it
+/// passes to the predicate.
+#[test]
+fn find_records_access_to_match() {
+    use std::{thread, time::Duration};
+
+    let slab = Slab::new_with_config::<TrackAccess>();
+    let skipped = slab.get(slab.insert(1).unwrap()).unwrap();
+    let found = slab.get(slab.insert(2).unwrap()).unwrap();
+    let skipped_at = skipped.last_accessed().unwrap();
+    let found_at = found.last_accessed().unwrap();
+
+    thread::sleep(Duration::from_millis(20));
+    let (key, entry) = slab.find(|&value| value == 2).unwrap();
+    assert_eq!(key, found.key());
+    assert_eq!(*entry, 2);
+    assert_eq!(skipped.last_accessed(), Some(skipped_at));
+    assert!(found.last_accessed().unwrap() >= found_at + Duration::from_millis(20));
+}
+
+/// Scanning a range of the slab doesn't count as accessing the values in it.
+#[test]
+fn scan_range_doesnt_record_access() {
+    use std::{thread, time::Duration};
+
+    let slab = Slab::new_with_config::<TrackAccess>();
+    let entry = slab.get(slab.insert(1).unwrap()).unwrap();
+    let accessed = entry.last_accessed().unwrap();
+
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(slab.scan_range(.., ..).map(|entry| *entry).sum::<i32>(), 1);
+    assert_eq!(entry.last_accessed(), Some(accessed));
+}
+
+/// Scanning the slab for a value doesn't stop the other values from being
+/// reported as idle.
+#[test]
+fn idle_keys_after_scan() {
+    use std::{thread, time::Duration};
+
+    let slab = Slab::new_with_config::<TrackAccess>();
+    let idle = slab.insert(1).unwrap();
+    let found = slab.insert(2).unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    assert_eq!(slab.to_vec().len(), 2);
+    assert_eq!(slab.find(|&value| value == 2).unwrap().0, found);
+    assert_eq!(slab.idle_keys(Duration::from_millis(20)), vec![idle]);
+}
+
+/// Migrating moves every value, unless the new config can't hold them all.
+#[test]
+fn migrate() {
+    struct Tiny;
+
+    impl Config for Tiny {
+        const INITIAL_PAGE_SIZE: usize = 2;
+        const MAX_PAGES: usize = 1;
+        const MAX_THREADS: usize = 1;
+        // Leaves room for the reference count, with so few address bits.
+        const RESERVED_BITS: usize = cfg::WIDTH / 2;
+        const SINGLE_THREADED: bool = true;
+    }
+
+    let slab = Slab::new_with_config::<CustomConfig>();
+    let keys = (0..3).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+    slab.remove(keys[0]);
+
+    let slab = slab.migrate::<Tiny>(|_, _| {}).unwrap();
+    // The values are moved into shard 0, which another thread may own.
+    let (slab, moved) = std::thread::spawn(move || {
+        let mut moved = Vec::new();
+        let slab = slab
+            .migrate::<CustomConfig>(|old, new| moved.push((old, new)))
+            .unwrap();
+        (slab, moved)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(moved.len(), 2);
+    for (_, new) in moved {
+        assert!(slab.contains(new));
+    }
+
+    slab.insert(3).unwrap();
+    slab.insert(4).unwrap();
+    let slab = slab
+        .migrate::<Tiny>(|_, _| panic!("nothing is moved"))
+        .unwrap_err();
+    let mut values = slab
+        .to_vec()
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, vec![1, 2, 3, 4]);
+}
@@ -512,6 +512,38 @@ fn create_owned_mut_guard_send() {
     });
 }
 
+#[test]
+fn create_owned_mut_guard_cleared_after_send() {
+    run_model("create_owned_mut_guard_cleared_after_send", || {
+        let pool = Arc::new(Pool::<String>::new_with_config::<TinyConfig>());
+        let mut guard = pool.clone().create_owned().unwrap();
+        let key: usize = guard.key();
+
+        let t1 = thread::spawn(move || {
+            guard.push_str("Hello world");
+            guard.downgrade()
+        });
+        let guard = t1.join().unwrap();
+        assert!(pool.clear(key));
+
+        // The last guard is dropped by a thread that doesn't own the slot's
+        // shard, so the slot must be freed to the remote free list.
+        let t2 = thread::spawn(move || drop(guard));
+        t2.join().unwrap();
+        assert!(pool.get(key).is_none());
+
+        // Once the rest of the first page is used, the slot is reused, and its
+        // value was cleared.
+        let addr = |key| <crate::page::Addr<TinyConfig> as Pack<TinyConfig>>::from_packed(key);
+        for _ in 0..3 {
+            pool.create().unwrap();
+        }
+        let guard = pool.clone().create_owned().unwrap();
+        assert_eq!(addr(guard.key()), addr(key));
+        assert!(guard.is_empty());
+    });
+}
+
 #[test]
 fn create_owned_mut_guard_2() {
     run_model("create_owned_mut_guard_2", || {
@@ -639,3 +671,186 @@ fn create_mut_downgrade_during_clear_by_other_thead() {
         t2.join().unwrap();
     });
 }
+
+#[test]
+fn evict_idle_races_reuse() {
+    struct TrackIdle;
+    impl crate::Config for TrackIdle {
+        const INITIAL_PAGE_SIZE: usize = 4;
+        const TRACK_IDLE: bool = true;
+    }
+
+    run_model("evict_idle_races_reuse", || {
+        let pool: Arc<Pool<String, TrackIdle>> = Arc::new(Pool::new_with_config());
+
+        let key = pool
+            .create_with(|item| item.push_str("hello"))
+            .expect("create");
+        assert!(pool.clear(key));
+
+        let p = pool.clone();
+        let t1 = thread::spawn(move || {
+            // Pretend that a long time has passed, so that every released slot
+            // is evicted.
+            p.evict_idle_at(crate::page::timestamps::now().wrapping_add(1000), 0);
+        });
+
+        // Reusing the slot must never observe (or be clobbered by) an
+        // eviction in progress.
+        let key = pool
+            .create_with(|item| {
+                assert_eq!(item, "");
+                item.push_str("world");
+            })
+            .expect("create");
+        assert_eq!(pool.get(key).unwrap(), String::from("world"));
+
+        t1.join().unwrap();
+        assert_eq!(pool.get(key).unwrap(), String::from("world"));
+    });
+}
+
+#[test]
+fn create_many_spans_pages() {
+    run_model("create_many_spans_pages", || {
+        let pool = Arc::new(Pool::new_with_config::<TinyConfig>());
+        let key = pool
+            .create_with(|item: &mut String| item.push_str("hello"))
+            .expect("create_with");
+
+        let p = pool.clone();
+        let t1 = thread::spawn(move || {
+            assert!(p.clear(key));
+        });
+
+        // Enough items to fill the first page and spill onto the next, while
+        // the other thread may concurrently free a slot in the first.
+        let mut refs = pool.create_many(4);
+        assert_eq!(refs.len(), 4);
+        for (i, item) in refs.iter_mut().enumerate() {
+            assert_eq!(item.as_str(), "");
+            item.push_str(&i.to_string());
+        }
+        let keys = refs.iter().map(|item| item.key()).collect::<Vec<_>>();
+        drop(refs);
+
+        t1.join().unwrap();
+        for (i, key) in keys.into_iter().enumerate() {
+            assert_eq!(pool.get(key).unwrap(), i.to_string());
+        }
+    });
+}
+
+#[test]
+fn take_waits_for_guards() {
+    run_model("take_waits_for_guards", || {
+        let pool = Arc::new(Pool::<String>::new());
+        let key = pool
+            .create_with(|item| item.push_str("hello world"))
+            .expect("create_with");
+
+        let p = pool.clone();
+        let t1 = thread::spawn(move || {
+            if let Some(item) = p.get(key) {
+                assert_eq!(item, String::from("hello world"));
+            }
+        });
+
+        assert_eq!(pool.take(key), Some(String::from("hello world")));
+        assert!(pool.get(key).is_none());
+        t1.join().unwrap();
+
+        let key = pool.create_with(|_| {}).expect("create_with");
+        assert_eq!(pool.get(key).unwrap().capacity(), 0);
+    });
+}
+
+#[test]
+fn factory_creates_vacant_objects() {
+    run_model("factory_creates_vacant_objects", || {
+        let pool = Arc::new(Pool::with_factory_and_config::<TinyConfig>(|| {
+            String::with_capacity(16)
+        }));
+
+        let p = pool.clone();
+        let t1 = thread::spawn(move || {
+            let key = p.create_with(|item| item.push_str("hello")).unwrap();
+            assert!(p.get(key).unwrap().capacity() >= 16);
+            assert_eq!(p.take(key), Some(String::from("hello")));
+        });
+
+        let key = pool.create_with(|item| item.push_str("world")).unwrap();
+        assert!(pool.get(key).unwrap().capacity() >= 16);
+        t1.join().unwrap();
+
+        // The taken object is replaced using the factory, too.
+        assert_eq!(pool.take(key), Some(String::from("world")));
+        let key = pool.create_with(|_| {}).unwrap();
+        assert!(pool.get(key).unwrap().capacity() >= 16);
+    });
+}
+
+#[test]
+fn is_cleared_waits_for_guards() {
+    run_model("is_cleared_waits_for_guards", || {
+        let pool = Arc::new(Pool::<String>::new());
+        let key = pool
+            .create_with(|item| item.push_str("hello world"))
+            .expect("create_with");
+        let guard = pool.clone().get_owned(key).unwrap();
+
+        let t1 = thread::spawn(move || drop(guard));
+
+        assert!(pool.clear(key));
+        t1.join().unwrap();
+
+        // Once the guard has been dropped, the clear must have happened.
+        assert!(pool.is_cleared(key));
+        let key = pool.create_with(|_| {}).unwrap();
+        assert_eq!(pool.get(key).unwrap(), String::new());
+    });
+}
+
+#[test]
+fn mapped_mut_guard_prevents_access() {
+    run_model("mapped_mut_guard_prevents_access", || {
+        let pool = Arc::new(Pool::<String>::new());
+        let guard = pool.create().unwrap();
+        let key: usize = guard.key();
+        let mut mapped = crate::pool::RefMut::map(guard, |s| s);
+        mapped.push_str("hello");
+
+        let pool2 = pool.clone();
+        thread::spawn(move || {
+            assert!(pool2.get(key).is_none());
+        })
+        .join()
+        .unwrap();
+
+        drop(mapped);
+        let value = crate::pool::Ref::map(pool.get(key).unwrap(), |s| s.as_str());
+        assert_eq!(value, *"hello");
+    });
+}
+
+#[test]
+fn create_with_panic_clears() {
+    run_model("create_with_panic_clears", || {
+        let pool = Arc::new(Pool::<String>::new());
+
+        let p = pool.clone();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            p.create_with(|item| {
+                item.push_str("half-initialized");
+                panic!("initializer panicked");
+            })
+        }));
+        assert!(panicked.is_err());
+
+        // The half-initialized object was cleared and its slot reused.
+        let key = pool.create_with(|_| {}).unwrap();
+        assert_eq!(pool.get(key).unwrap(), String::new());
+        assert_eq!(pool.metrics().created(), 2);
+        assert_eq!(pool.metrics().constructed(), 1);
+    });
+}
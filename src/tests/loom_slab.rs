@@ -758,3 +758,476 @@ fn owned_entry_drop_from_other_threads() {
         assert!(slab.get(key2).is_none());
     });
 }
+
+#[test]
+fn weak_entry_upgrade_races_reuse() {
+    run_model("weak_entry_upgrade_races_reuse", || {
+        let slab = Arc::new(Slab::new_with_config::<TinyConfig>());
+        let key = slab.insert(1).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            let weak = s.downgrade(key);
+            if let Some(entry) = weak.upgrade() {
+                // A weak entry must never upgrade to a later value stored in
+                // the same slot.
+                assert_eq!(entry, 1);
+            }
+        });
+
+        slab.remove(key);
+        let key2 = slab.insert(2).expect("insert");
+        t1.join().expect("thread 1 should not panic");
+
+        assert!(slab.downgrade(key).upgrade().is_none());
+        assert_eq!(slab.downgrade(key2).upgrade().unwrap(), 2);
+    });
+}
+
+#[test]
+fn arc_entry_last_drop_removes() {
+    struct SetOnDrop(Arc<AtomicBool>);
+
+    impl Drop for SetOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    crate::sync::lazy_static! {
+        static ref SLAB: crate::ArcSlab<SetOnDrop> = crate::ArcSlab::new();
+    }
+
+    run_model("arc_entry_last_drop_removes", || {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let entry = SLAB.insert(SetOnDrop(dropped.clone())).expect("insert");
+        let entry2 = entry.clone();
+
+        let t1 = thread::spawn(move || {
+            // The value must not be dropped while a handle still exists.
+            assert!(!entry2.0.load(Ordering::Acquire));
+            drop(entry2);
+        });
+
+        drop(entry);
+        t1.join().expect("thread 1 should not panic");
+
+        assert!(
+            dropped.load(Ordering::Acquire),
+            "value must be dropped when the last handle is dropped"
+        );
+    });
+}
+
+#[test]
+fn fixed_slab_remote_remove_reuses_slot() {
+    struct FewThreads;
+    impl crate::Config for FewThreads {
+        const MAX_THREADS: usize = 4;
+    }
+
+    run_model("fixed_slab_remote_remove_reuses_slot", || {
+        let slab = Arc::new(crate::FixedSlab::new_with_config::<FewThreads>(1));
+
+        let key = slab.insert(alloc::Track::new(1)).expect("insert");
+        assert!(slab.insert(alloc::Track::new(2)).is_none(), "shard is full");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            assert!(s.remove(key));
+        });
+
+        // While the value is being removed remotely, it may still be accessed.
+        if let Some(entry) = slab.get(key) {
+            assert_eq!(entry.get_ref(), &1);
+        }
+        t1.join().expect("thread 1 should not panic");
+
+        // The remotely-freed slot can be reused by the owning thread.
+        let key2 = slab.insert(alloc::Track::new(3)).expect("insert");
+        assert!(slab.get(key).is_none());
+        assert_eq!(slab.get(key2).unwrap().get_ref(), &3);
+    });
+}
+
+#[test]
+fn locked_slab_writers_are_exclusive() {
+    run_model("locked_slab_writers_are_exclusive", || {
+        let slab = Arc::new(crate::LockedSlab::new());
+        let key = slab.insert(0usize).expect("insert");
+
+        let threads = (0..2)
+            .map(|_| {
+                let s = slab.clone();
+                thread::spawn(move || {
+                    // Writers never wait for each other, so this may fail if
+                    // the other thread holds the lock.
+                    let mut entry = s.get_mut(key)?;
+                    let value = *entry;
+                    thread::yield_now();
+                    *entry = value + 1;
+                    Some(())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(entry) = slab.get(key) {
+            assert!(slab.get_mut(key).is_none());
+            assert!(*entry <= 2);
+        }
+
+        let writes = threads
+            .into_iter()
+            .filter_map(|t| t.join().expect("thread should not panic"))
+            .count();
+
+        // No increments may be lost.
+        assert_eq!(slab.get(key).unwrap(), writes);
+    });
+}
+
+#[test]
+fn locked_slab_remove_while_writing() {
+    run_model("locked_slab_remove_while_writing", || {
+        let slab = Arc::new(crate::LockedSlab::new());
+        let key = slab.insert(alloc::Track::new(1)).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            if let Some(mut entry) = s.get_mut(key) {
+                *entry.get_mut() += 1;
+                assert_eq!(entry.get_ref(), &2);
+            }
+        });
+
+        assert!(slab.remove(key));
+        t1.join().expect("thread 1 should not panic");
+
+        assert!(slab.get(key).is_none());
+        assert!(slab.get_mut(key).is_none());
+    });
+}
+
+/// Columns allocate a page table for every possible shard, so use a smaller
+/// configuration to keep the number of atomics loom has to track down.
+struct FewThreads;
+impl crate::Config for FewThreads {
+    const MAX_THREADS: usize = 4;
+    const MAX_PAGES: usize = 2;
+}
+
+#[test]
+fn column_remove_while_referenced_and_reuse() {
+    run_model("column_remove_while_referenced_and_reuse", || {
+        let slab = Slab::new_with_config::<FewThreads>();
+        let column = Arc::new(slab.column());
+        let key = slab.insert(()).expect("insert");
+        assert!(column.insert(key, alloc::Track::new(1)).is_ok());
+
+        let c = column.clone();
+        let t1 = thread::spawn(move || {
+            if let Some(entry) = c.get(key) {
+                assert_eq!(entry.get_ref(), &1);
+            }
+        });
+
+        assert!(column.remove(key));
+        slab.remove(key);
+        let key2 = slab.insert(()).expect("insert");
+        // If the old value is still referenced, the slot can't be reused yet.
+        let value = column.insert(key2, alloc::Track::new(2)).err();
+
+        t1.join().expect("thread 1 should not panic");
+
+        if let Some(value) = value {
+            assert!(column.insert(key2, value).is_ok());
+        }
+        assert!(column.get(key).is_none());
+        assert_eq!(column.get(key2).unwrap().get_ref(), &2);
+    });
+}
+
+#[test]
+fn column_concurrent_page_alloc() {
+    run_model("column_concurrent_page_alloc", || {
+        let slab = Slab::new_with_config::<FewThreads>();
+        let column = Arc::new(slab.column());
+        let keys = [slab.insert(()).unwrap(), slab.insert(()).unwrap()];
+
+        let threads = keys
+            .iter()
+            .enumerate()
+            .map(|(i, &key)| {
+                let column = column.clone();
+                thread::spawn(move || {
+                    assert!(column.insert(key, alloc::Track::new(i)).is_ok());
+                })
+            })
+            .collect::<Vec<_>>();
+        for t in threads {
+            t.join().expect("thread should not panic");
+        }
+
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(column.get(key).unwrap().get_ref(), &i);
+        }
+    });
+}
+
+struct TrackOccupancy;
+impl crate::Config for TrackOccupancy {
+    const INITIAL_PAGE_SIZE: usize = 2;
+    const MAX_PAGES: usize = 2;
+    const MAX_THREADS: usize = 4;
+    const TRACK_OCCUPANCY: bool = true;
+}
+
+#[test]
+fn occupancy_remote_remove_and_reuse() {
+    run_model("occupancy_remote_remove_and_reuse", || {
+        let mut slab = Arc::new(Slab::new_with_config::<TrackOccupancy>());
+        let key1 = slab.insert(1).expect("insert");
+        slab.insert(2).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            assert!(s.remove(key1));
+        });
+        // This may or may not reuse the slot freed by the other thread.
+        slab.insert(3).expect("insert");
+        t1.join().expect("thread 1 should not panic");
+        slab.insert(4).expect("insert");
+
+        let slab = Arc::get_mut(&mut slab).expect("other arcs should be dropped");
+        let mut items: Vec<_> = slab.unique_iter().copied().collect();
+        items.sort_unstable();
+        assert_eq!(items, [2, 3, 4]);
+    });
+}
+
+#[test]
+fn checkout_remote_remove() {
+    run_model("checkout_remote_remove", || {
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(alloc::Track::new(1)).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            s.remove(key);
+        });
+
+        if let Some(mut checkout) = slab.checkout(key) {
+            let value = checkout.take().expect("value should be checked out");
+            assert_eq!(value.get_ref(), &1);
+            checkout.checkin(alloc::Track::new(2));
+        }
+        t1.join().expect("thread 1 should not panic");
+
+        // Whether or not the value was checked out before it was removed, it
+        // (and any replacement) has now been dropped.
+        assert!(slab.get(key).is_none());
+    });
+}
+
+struct PreferLow;
+impl crate::Config for PreferLow {
+    const INITIAL_PAGE_SIZE: usize = 4;
+    const MAX_PAGES: usize = 1;
+    const MAX_THREADS: usize = 4;
+    const PREFER_LOW_ADDRESSES: bool = true;
+}
+
+#[test]
+fn prefer_low_addresses_merges_remote_frees() {
+    run_model("prefer_low_addresses_merges_remote_frees", || {
+        let slab = Arc::new(Slab::new_with_config::<PreferLow>());
+        let key1 = slab.insert(1).expect("insert");
+        let key2 = slab.insert(2).expect("insert");
+        slab.insert(3).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            assert!(s.remove(key1));
+        });
+        assert!(slab.remove(key2));
+        t1.join().expect("thread 1 should not panic");
+
+        // Both freed slots are lower than the never-used one, and the lowest
+        // is reused first.
+        let addr = |key| <crate::page::Addr<PreferLow> as crate::Pack<PreferLow>>::from_packed(key);
+        let key = slab.insert(4).expect("insert");
+        assert_eq!(addr(key), addr(key1));
+        let key = slab.insert(5).expect("insert");
+        assert_eq!(addr(key), addr(key2));
+    });
+}
+
+#[derive(Default)]
+struct CountClears {
+    marks: std::sync::atomic::AtomicUsize,
+    clears: std::sync::atomic::AtomicUsize,
+}
+
+impl crate::Lifecycle for CountClears {
+    fn on_mark(&self, _: usize) {
+        self.marks.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn on_clear(&self, _: usize) {
+        self.clears.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn lifecycle_clears_once_with_remote_guard() {
+    run_model("lifecycle_clears_once_with_remote_guard", || {
+        let counts = Arc::new(CountClears::default());
+        let slab = Arc::new(Slab::new().with_lifecycle(counts.clone()));
+        let key = slab.insert(alloc::Track::new(1)).expect("insert");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            if let Some(entry) = s.get(key) {
+                assert_eq!(entry.get_ref(), &1);
+            }
+        });
+
+        assert!(slab.remove(key));
+        t1.join().expect("thread 1 should not panic");
+
+        assert!(!slab.contains(key));
+        assert_eq!(counts.marks.load(Ordering::SeqCst), 1);
+        assert_eq!(counts.clears.load(Ordering::SeqCst), 1);
+    });
+}
+
+struct Balanced;
+impl crate::Config for Balanced {
+    const INITIAL_PAGE_SIZE: usize = 2;
+    const MAX_PAGES: usize = 1;
+    // A single shard, so that every insert contends for it.
+    const MAX_THREADS: usize = 2;
+    const BALANCED_INSERTS: bool = true;
+}
+
+#[test]
+fn balanced_concurrent_insert() {
+    run_model("balanced_concurrent_insert", || {
+        let slab = Arc::new(Slab::new_with_config::<Balanced>());
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || s.insert(1).expect("insert"));
+        let key2 = slab.insert(2).expect("insert");
+        let key1 = t1.join().expect("thread 1 should not panic");
+
+        assert_ne!(key1, key2);
+        assert_eq!(slab.get(key1).unwrap(), 1);
+        assert_eq!(slab.get(key2).unwrap(), 2);
+
+        // Slots freed by any thread may be reused by any other.
+        let s = slab.clone();
+        let t1 = thread::spawn(move || assert!(s.remove(key1)));
+        t1.join().expect("thread 1 should not panic");
+        let key3 = slab.insert(3).expect("insert");
+        assert_eq!(slab.get(key3).unwrap(), 3);
+        assert!(slab.get(key1).is_none());
+    });
+}
+
+#[test]
+fn get_detailed_while_removing() {
+    use crate::LookupError;
+
+    run_model("get_detailed_while_removing", || {
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(1).expect("insert");
+        let guard = slab.get(key).expect("get");
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            // Depending on how far the main thread has got, the value is
+            // still present, being cleared, or gone.
+            match s.get_detailed(key) {
+                Ok(entry) => assert_eq!(entry, 1),
+                Err(error) => assert!(
+                    error == LookupError::Clearing || error == LookupError::Stale,
+                    "unexpected error: {:?}",
+                    error
+                ),
+            }
+        });
+
+        assert!(slab.remove(key));
+        assert_eq!(slab.get_detailed(key).unwrap_err(), LookupError::Clearing);
+        drop(guard);
+        t1.join().expect("thread 1 should not panic");
+
+        assert_eq!(slab.get_detailed(key).unwrap_err(), LookupError::Stale);
+        assert_eq!(
+            slab.get_detailed(key + (1 << 20)).unwrap_err(),
+            LookupError::OutOfRange
+        );
+    });
+}
+
+#[test]
+fn waker_slab_register_wake() {
+    use crate::WakerSlab;
+    use loom::sync::atomic::AtomicBool;
+
+    run_model("waker_slab_register_wake", || {
+        // Declared first, so that it outlives the wakers referencing it.
+        let woken = AtomicBool::new(false);
+        let wakers = Arc::new(WakerSlab::new());
+        let key = wakers.insert().expect("insert");
+        let done = Arc::new(AtomicBool::new(false));
+
+        let w = wakers.clone();
+        let d = done.clone();
+        let t1 = thread::spawn(move || {
+            d.store(true, Ordering::Release);
+            w.wake(key);
+        });
+
+        // Register, then check whether the operation completed, as a future
+        // would when polled.
+        let waker = flag_waker(&woken);
+        assert!(wakers.register(key, &waker));
+        let done_before = done.load(Ordering::Acquire);
+        t1.join().expect("thread 1 should not panic");
+
+        // If the operation hadn't completed, the wakeup must not be lost.
+        assert!(done_before || woken.load(Ordering::Acquire));
+        assert!(wakers.remove(key));
+    });
+}
+
+#[test]
+fn dense_slab_never_hides_free_handles() {
+    use crate::DenseSlab;
+
+    run_model("dense_slab_never_hides_free_handles", || {
+        let slab = Arc::new(DenseSlab::new());
+        let a = slab.insert(1).expect("insert");
+        let b = slab.insert(2).expect("insert");
+        // Reusing `a` leaves the bitmap's summary claiming that its word has
+        // free handles, so the next insert will find the word empty.
+        assert!(slab.remove(a));
+        assert_eq!(slab.insert(3), Some(a));
+
+        let s = slab.clone();
+        let t1 = thread::spawn(move || {
+            assert!(s.remove(b));
+        });
+
+        // Racing with the other thread's release, this may or may not reuse
+        // its handle.
+        let c = slab.insert(4).expect("insert");
+        t1.join().expect("thread 1 should not panic");
+
+        // If it didn't, the released handle must not have been hidden.
+        let d = slab.insert(5).expect("insert");
+        assert!(c == b || d == b, "handle {} was hidden", b);
+        assert!(slab.handle_bound() <= 3);
+    });
+}
@@ -8,7 +8,7 @@ mod idx {
 
     proptest! {
         #[test]
-        #[cfg_attr(loom, ignore)]
+        #[cfg_attr(any(loom, miri), ignore)]
         fn tid_roundtrips(tid in 0usize..Tid::<cfg::DefaultConfig>::BITS) {
             let tid = Tid::<cfg::DefaultConfig>::from_usize(tid);
             let packed = tid.pack(0);
@@ -16,7 +16,7 @@ mod idx {
         }
 
         #[test]
-        #[cfg_attr(loom, ignore)]
+        #[cfg_attr(any(loom, miri), ignore)]
         fn idx_roundtrips(
             tid in 0usize..Tid::<cfg::DefaultConfig>::BITS,
             gen in 0usize..slot::Generation::<cfg::DefaultConfig>::BITS,
@@ -33,9 +33,101 @@ mod idx {
     }
 }
 
+#[cfg(not(loom))]
+mod ref_count {
+    use crate::{
+        cfg::DefaultConfig,
+        page::{self, slot},
+        tid::Tid,
+        Pack, Slab,
+    };
+
+    type Addr = page::Addr<DefaultConfig>;
+    type Generation = slot::Generation<DefaultConfig>;
+
+    #[test]
+    fn vacant_slot_has_no_ref_count() {
+        let slab = Slab::new();
+        let key = slab.insert(1).unwrap();
+        let tid = Tid::<DefaultConfig>::from_packed(key);
+        let gen = Generation::from_packed(key);
+        let addr = Addr::from_packed(key);
+        let key_for = |gen: Generation, addr: Addr| tid.pack(gen.pack(addr.pack(0)));
+
+        // The next slot in the page is allocated, but has never held a value.
+        let vacant = key_for(gen, Addr::from_usize(addr.as_usize() + 1));
+        assert_eq!(slab.ref_count(vacant), None);
+
+        // Once the value is removed, its slot is vacant at the next
+        // generation.
+        assert!(slab.remove(key));
+        let next = key_for(Generation::from_usize(gen.as_usize() + 1), addr);
+        assert_eq!(slab.ref_count(key), None);
+        assert_eq!(slab.ref_count(next), None);
+
+        let key = slab.insert(2).unwrap();
+        assert_eq!(slab.ref_count(key), Some(0));
+        let _guard = slab.get(key).unwrap();
+        assert_eq!(slab.ref_count(key), Some(1));
+    }
+}
+
+#[cfg(not(loom))]
+mod insert_with {
+    use crate::{cfg, DefaultConfig, Slab};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    struct TrackIdle;
+
+    impl cfg::Config for TrackIdle {
+        const TRACK_IDLE: bool = true;
+    }
+
+    fn panicking_constructor_frees_slot<C: cfg::Config>() {
+        let slab = Slab::<usize>::new_with_config::<C>();
+        let first = slab.insert(1).unwrap();
+        let stats = slab.stats();
+
+        let mut reserved = None;
+        let panicked = catch_unwind(AssertUnwindSafe(|| {
+            slab.insert_with(|key| {
+                reserved = Some(key);
+                panic!("constructor failed");
+            })
+        }));
+        assert!(panicked.is_err());
+        let reserved = reserved.unwrap();
+        assert!(!slab.contains(reserved));
+        assert_eq!(slab.stats().occupied(), stats.occupied());
+        assert_eq!(slab.stats().allocated_slots(), stats.allocated_slots());
+
+        // The slot is reused at the same generation.
+        let key = slab.insert_with(|key| key).unwrap();
+        assert_eq!(key, reserved);
+        assert_eq!(*slab.get(key).unwrap(), key);
+        assert_eq!(slab.stats().occupied(), stats.occupied() + 1);
+        assert_eq!(slab.stats().allocated_slots(), stats.allocated_slots());
+        assert_eq!(*slab.get(first).unwrap(), 1);
+    }
+
+    #[test]
+    fn panicking_constructor() {
+        panicking_constructor_frees_slot::<DefaultConfig>();
+    }
+
+    #[test]
+    fn panicking_constructor_while_tracking_idle() {
+        panicking_constructor_frees_slot::<TrackIdle>();
+    }
+}
+
 pub(crate) mod util {
     #[cfg(loom)]
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use loom::sync::atomic::AtomicBool;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{RawWaker, RawWakerVTable, Waker},
+    };
     pub(crate) struct TinyConfig;
 
     impl crate::Config for TinyConfig {
@@ -63,6 +155,47 @@ pub(crate) mod util {
             f()
         });
     }
+
+    /// Returns a waker that does nothing when it is woken.
+    #[cfg(not(loom))]
+    pub(crate) fn noop_waker() -> Waker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        unsafe fn noop(_: *const ()) {}
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    /// Returns a waker that increments `wakes` when it is woken.
+    #[cfg(not(loom))]
+    pub(crate) fn counting_waker(wakes: &'static AtomicUsize) -> Waker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+        unsafe fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+        unsafe fn wake(data: *const ()) {
+            (*(data as *const AtomicUsize)).fetch_add(1, Ordering::SeqCst);
+        }
+        unsafe fn drop(_: *const ()) {}
+        let data = wakes as *const AtomicUsize as *const ();
+        unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+    }
+
+    /// Returns a waker that sets `woken` when it is woken.
+    #[cfg(loom)]
+    pub(crate) fn flag_waker(woken: &AtomicBool) -> Waker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+        unsafe fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+        unsafe fn wake(data: *const ()) {
+            (*(data as *const AtomicBool)).store(true, Ordering::Release);
+        }
+        unsafe fn drop(_: *const ()) {}
+        let data = woken as *const AtomicBool as *const ();
+        unsafe { Waker::from_raw(RawWaker::new(data, &VTABLE)) }
+    }
 }
 
 #[cfg(not(loom))]
@@ -73,3 +206,5 @@ mod loom_pool;
 mod loom_slab;
 #[cfg(not(loom))]
 mod properties;
+#[cfg(all(not(loom), feature = "serde"))]
+mod serialize;
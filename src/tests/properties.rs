@@ -3,7 +3,8 @@
 //! * Active entries cannot be overridden until removed.
 //! * The slab doesn't produce overlapping keys.
 //! * The slab doesn't leave "lost" keys.
-//! * `get()`, `get_owned`, and `contains()` are consistent.
+//! * `get()`, `get_detailed()`, `get_owned`, and `contains()` are consistent.
+//! * `compact()` reports every key it changes.
 //! * `RESERVED_BITS` are actually not used.
 //!
 //! The test is supposed to be deterministic, so it doesn't spawn real threads
@@ -35,6 +36,7 @@ enum ActionKind {
     TakeExistent(usize),   // seed
     GetRandom(usize),      // key
     GetExistent(usize),    // seed
+    Compact,
 }
 
 prop_compose! {
@@ -54,6 +56,7 @@ fn action_kind_strategy() -> impl Strategy<Value = ActionKind> {
         // Produce `GetRandom` and `GetExistent` more often.
         5 => prop::num::usize::ANY.prop_map(ActionKind::GetRandom),
         5 => prop::num::usize::ANY.prop_map(ActionKind::GetExistent),
+        1 => Just(ActionKind::Compact),
     ]
 }
 
@@ -119,7 +122,7 @@ fn used_bits<C: Config>(key: usize) -> usize {
 }
 
 fn apply_action<C: Config>(
-    slab: &Arc<Slab<u32, C>>,
+    slab: &mut Arc<Slab<u32, C>>,
     active: &mut Active,
     action: ActionKind,
 ) -> Result<(), TestCaseError> {
@@ -164,6 +167,10 @@ fn apply_action<C: Config>(
             let used_key = used_bits::<C>(key);
             prop_assert_eq!(slab.get(key).map(|e| *e), slab.get(used_key).map(|e| *e));
             prop_assert_eq!(slab.get(key).map(|e| *e), active.get(used_key));
+            prop_assert_eq!(
+                slab.get_detailed(key).ok().map(|e| *e),
+                active.get(used_key)
+            );
             prop_assert_eq!(
                 slab.clone().get_owned(key).map(|e| *e),
                 active.get(used_key)
@@ -176,6 +183,20 @@ fn apply_action<C: Config>(
                 prop_assert_eq!(slab.clone().get_owned(key).map(|e| *e), Some(value));
             }
         }
+        ActionKind::Compact => {
+            let mut moved = Vec::new();
+            Arc::get_mut(slab)
+                .unwrap()
+                .compact(|old_key, new_key| moved.push((old_key, new_key)));
+            let values = moved
+                .iter()
+                .map(|&(old_key, _)| active.remove(old_key).expect("moved inactive key"))
+                .collect::<Vec<_>>();
+            for (&(_, new_key), value) in moved.iter().zip(values) {
+                prop_assert_eq!(used_bits::<C>(new_key), new_key);
+                active.insert(new_key, value);
+            }
+        }
     }
 
     Ok(())
@@ -191,7 +212,7 @@ fn run<C: Config>(actions: Vec<Action>) -> Result<(), TestCaseError> {
         // to preserve determinism. We're not checking concurrency issues here, they should be
         // covered by loom tests anyway. Thus, it's fine to run all actions consequently.
         tid::with(action.tid, || {
-            apply_action::<C>(&slab, &mut active, action.kind)
+            apply_action::<C>(&mut slab, &mut active, action.kind)
         })?;
     }
 
@@ -216,11 +237,13 @@ fn run<C: Config>(actions: Vec<Action>) -> Result<(), TestCaseError> {
 
 proptest! {
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn default_config(actions in prop::collection::vec(action_strategy(), ACTIONS)) {
         run::<DefaultConfig>(actions)?;
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn custom_config(actions in prop::collection::vec(action_strategy(), ACTIONS)) {
         run::<CustomConfig>(actions)?;
     }
@@ -234,6 +257,7 @@ impl Config for CustomConfig {
     const MAX_PAGES: usize = 15;
     const MAX_THREADS: usize = 256;
     const RESERVED_BITS: usize = 24;
+    const TRACK_OCCUPANCY: bool = true;
 }
 #[cfg(target_pointer_width = "32")]
 impl Config for CustomConfig {
@@ -241,4 +265,5 @@ impl Config for CustomConfig {
     const MAX_PAGES: usize = 6;
     const MAX_THREADS: usize = 128;
     const RESERVED_BITS: usize = 12;
+    const TRACK_OCCUPANCY: bool = true;
 }
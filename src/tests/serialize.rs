@@ -0,0 +1,97 @@
+//! Guards serialize as the value they reference.
+
+use crate::{pool, ArcSlab, DynSlab, FixedSlab, LockedSlab, PartitionedSlab, Pool, PoolSet, Slab};
+use std::sync::Arc;
+
+#[test]
+fn slab_entries() {
+    let slab = Arc::new(Slab::new());
+    let key = slab.insert(vec![1, 2, 3]).unwrap();
+
+    let entry = slab.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+    let entry = slab.clone().get_owned(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+}
+
+#[test]
+fn pool_refs() {
+    let pool = Arc::new(Pool::<String>::new());
+
+    let mut value = pool.create().unwrap();
+    value.push_str("hello");
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+    let key = value.key();
+    drop(value);
+    let value = pool.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+    let value = pool.clone().get_owned(key).unwrap();
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+
+    let mut value = pool.clone().create_owned().unwrap();
+    value.push_str("world");
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""world""#);
+}
+
+#[test]
+fn pool_mapped_refs() {
+    let pool = Pool::<String>::new();
+
+    let mut value = pool.create().unwrap();
+    value.push_str("hello");
+    let key = value.key();
+    let value = pool::RefMut::map(value, |s| s.as_mut_str());
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+    drop(value);
+    let value = pool::Ref::map(pool.get(key).unwrap(), |s| s.as_str());
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+}
+
+#[test]
+fn pool_set_refs() {
+    let pools = PoolSet::<String>::new(vec![16, 64]);
+
+    let mut value = pools.create(8).unwrap();
+    value.push_str("hello");
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+    let value = value.downgrade();
+    assert_eq!(serde_json::to_string(&value).unwrap(), r#""hello""#);
+}
+
+#[test]
+fn other_slab_entries() {
+    let slab = ArcSlab::new();
+    let entry = slab.insert(vec![1, 2, 3]).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+
+    let slab = Slab::new();
+    let key = slab.insert(()).unwrap();
+    let column = slab.column();
+    column.insert(key, vec![1, 2, 3]).unwrap();
+    let entry = column.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+
+    let slab = DynSlab::<str>::new();
+    let key = slab.insert(String::from("hello"), |s| s.as_str()).unwrap();
+    let entry = slab.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), r#""hello""#);
+
+    let slab = PartitionedSlab::new();
+    let partition = slab.partition(1);
+    let key = partition.insert(vec![1, 2, 3]).unwrap();
+    let entry = partition.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+
+    let slab = FixedSlab::new(8);
+    let key = slab.insert(vec![1, 2, 3]).unwrap();
+    let entry = slab.get(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+}
+
+#[test]
+fn locked_entries() {
+    let slab = LockedSlab::new();
+    let key = slab.insert(vec![1, 2, 3]).unwrap();
+    let entry = slab.get_mut(key).unwrap();
+    assert_eq!(serde_json::to_string(&entry).unwrap(), "[1,2,3]");
+}
@@ -11,7 +11,10 @@ use std::{
     cell::{Cell, UnsafeCell},
     collections::VecDeque,
     fmt,
+    future::Future,
     marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
 };
 
 /// Uniquely identifies a thread.
@@ -21,8 +24,48 @@ pub(crate) struct Tid<C> {
     _cfg: PhantomData<fn(C)>,
 }
 
+/// Identifies one of the shards in a [`Slab`] or [`Pool`].
+///
+/// Each thread that inserts into a slab does so in its own shard, so a
+/// `ShardId` also identifies the thread that owns the shard (or owned it, if
+/// that thread has since exited). IDs are assigned to threads process-wide,
+/// so a thread uses the shard with the same ID in every slab and pool with
+/// the same [`Config`].
+///
+/// A key's shard is returned by [`Slab::shard_id`], and the current thread's
+/// by [`Slab::current_shard_id`].
+///
+/// [`Slab`]: crate::Slab
+/// [`Pool`]: crate::Pool
+/// [`Config`]: crate::Config
+/// [`Slab::shard_id`]: crate::Slab::shard_id
+/// [`Slab::current_shard_id`]: crate::Slab::current_shard_id
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShardId(usize);
+
+#[derive(Debug)]
+struct Registration {
+    id: Cell<Option<usize>>,
+    /// The most recently accessed shard on this thread, tagged with the
+    /// unique ID of the shard array that owns it.
+    ///
+    /// This allows `shard::Array::current` to skip indexing the shard array
+    /// on the hot path when a thread repeatedly inserts into the same slab.
+    shard: Cell<Option<(usize, *const ())>>,
+    /// The shard hint set by `with_shard_hint`, if any.
+    hint: Cell<Option<usize>>,
+}
+
+/// A future which sets a shard hint each time it is polled.
+///
+/// This is returned by [`ShardHinted::new`]. See [`with_shard_hint`] for
+/// details.
 #[derive(Debug)]
-struct Registration(Cell<Option<usize>>);
+#[must_use = "futures do nothing unless polled"]
+pub struct ShardHinted<F> {
+    hint: ShardId,
+    future: F,
+}
 
 struct Registry {
     next: AtomicUsize,
@@ -43,7 +86,10 @@ thread_local! {
 // === impl Tid ===
 
 impl<C: cfg::Config> Pack<C> for Tid<C> {
-    const LEN: usize = C::MAX_SHARDS.trailing_zeros() as usize + 1;
+    // A single-threaded config leaves the thread ID out of keys entirely.
+    const LEN: usize =
+        (C::MAX_SHARDS.trailing_zeros() as usize + 1) * (!C::SINGLE_THREADED) as usize;
+    const BITS: usize = (1 << Self::LEN) - 1;
 
     type Prev = page::Addr<C>;
 
@@ -70,6 +116,69 @@ impl<C: cfg::Config> Tid<C> {
             .unwrap_or_else(|_| Self::poisoned())
     }
 
+    /// Returns the current thread's ID, along with the cached pointer to its
+    /// shard in the shard array identified by `array_id`, if one has been
+    /// cached by [`Tid::cache_shard`].
+    #[inline]
+    pub(crate) fn current_cached(array_id: usize) -> (Self, Option<*const ()>) {
+        REGISTRATION
+            .try_with(|r| {
+                let shard = match r.shard.get() {
+                    Some((id, shard)) if id == array_id => Some(shard),
+                    _ => None,
+                };
+                (r.current(), shard)
+            })
+            .unwrap_or_else(|_| (Self::poisoned(), None))
+    }
+
+    /// Caches a pointer to the current thread's shard in the shard array
+    /// identified by `array_id`.
+    ///
+    /// Only one shard is cached per thread, so this replaces any shard
+    /// previously cached for a different shard array.
+    #[inline]
+    pub(crate) fn cache_shard(array_id: usize, shard: *const ()) {
+        let _ = REGISTRATION.try_with(|r| r.shard.set(Some((array_id, shard))));
+    }
+
+    /// Binds the current thread to the given ID, returning `false` if it is
+    /// already in use by another thread, or the current thread's ID can't be
+    /// accessed.
+    pub(crate) fn bind_current(id: usize) -> bool {
+        REGISTRATION.try_with(|r| r.bind(id)).unwrap_or(false)
+    }
+
+    /// Binds the current thread to the first ID in `ids` that is not in use
+    /// by any thread, returning it.
+    ///
+    /// IDs which have never been assigned are skipped, so this only returns
+    /// an ID that was released by an exited (or rebound) thread.
+    pub(crate) fn adopt_current(ids: impl IntoIterator<Item = usize>) -> Option<Self> {
+        REGISTRATION
+            .try_with(|r| r.adopt(ids))
+            .ok()
+            .flatten()
+            .map(Self::new)
+    }
+
+    /// Returns `true` if the current thread may free slots in this ID's shard
+    /// to the shard's local free lists.
+    ///
+    /// If `C::BALANCED_INSERTS`, `C::DETERMINISTIC`, `C::SHARD_HINTS`, or
+    /// `C::SINGLE_THREADED` is set, threads insert into shards other than
+    /// their own, so slots are always freed to the remote free lists.
+    pub(crate) fn is_local(self) -> bool {
+        !C::SHARED_INSERTS && self.is_current()
+    }
+
+    /// Returns the shard hinted by `with_shard_hint`, if the current thread
+    /// has set one.
+    pub(crate) fn hinted() -> Option<Self> {
+        let hint = REGISTRATION.try_with(|r| r.hint.get()).ok()??;
+        Some(Self::new(hint % C::MAX_SHARDS))
+    }
+
     pub(crate) fn is_current(self) -> bool {
         REGISTRATION
             .try_with(|r| self == r.current::<C>())
@@ -86,7 +195,7 @@ impl<C> Tid<C> {
     #[cold]
     fn poisoned() -> Self {
         Self {
-            id: std::usize::MAX,
+            id: !0,
             _not_send: PhantomData,
             _cfg: PhantomData,
         }
@@ -94,7 +203,7 @@ impl<C> Tid<C> {
 
     /// Returns true if the local thread ID was accessed while unwinding.
     pub(crate) fn is_poisoned(&self) -> bool {
-        self.id == std::usize::MAX
+        self.id == !0
     }
 }
 
@@ -112,6 +221,177 @@ impl<C> fmt::Debug for Tid<C> {
     }
 }
 
+impl<C> From<Tid<C>> for ShardId {
+    fn from(tid: Tid<C>) -> Self {
+        Self(tid.id)
+    }
+}
+
+// === impl ShardId ===
+
+impl ShardId {
+    /// Returns the ID of the shard at the given index.
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    /// Returns the index of the shard.
+    ///
+    /// Shards are numbered from 0. When a thread exits, its ID may be reused
+    /// by a thread that is spawned later.
+    pub fn as_usize(self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for ShardId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+// === thread registration ===
+
+/// Assigns the current thread a [`ShardId`], if it has not already been
+/// assigned one, and returns it.
+///
+/// Threads are otherwise assigned a shard the first time they access any slab
+/// or pool. Registering worker threads as they start, before they handle any
+/// work, keeps the cost of registration out of the first operation each
+/// worker performs.
+///
+/// This returns `None` if the current thread's ID can't be accessed because
+/// its thread-local storage is being destroyed.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// let id = sharded_slab::register_thread().unwrap();
+///
+/// let slab = Slab::new();
+/// let key = slab.insert("hello").unwrap();
+/// assert_eq!(slab.shard_id(key), id);
+/// ```
+pub fn register_thread() -> Option<ShardId> {
+    let tid = Tid::<cfg::DefaultConfig>::current();
+    if tid.is_poisoned() {
+        return None;
+    }
+    Some(tid.into())
+}
+
+/// Releases the current thread's [`ShardId`], so that it may be assigned to
+/// another thread, returning `true` if the current thread had one.
+///
+/// A thread's shard is released automatically when the thread exits. Thread
+/// pools which reuse a thread for a different purpose, or which park threads
+/// that may not access a slab for a long time, may release its shard earlier,
+/// so that a thread that is spawned later can use it, and reuse the slots
+/// that it frees.
+///
+/// If the current thread accesses a slab or pool again, it is assigned a new
+/// shard (which may or may not be the same one). Values that the thread
+/// inserted remain in the slab, and may still be accessed by their keys.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::Slab;
+/// let slab = Slab::new();
+/// let key = slab.insert("hello").unwrap();
+///
+/// assert!(sharded_slab::unregister_thread());
+/// assert!(!sharded_slab::unregister_thread());
+/// assert_eq!(slab.get(key).unwrap(), "hello");
+/// assert!(slab.remove(key));
+/// ```
+pub fn unregister_thread() -> bool {
+    REGISTRATION
+        .try_with(Registration::unregister)
+        .unwrap_or(false)
+}
+
+/// Calls `f` with the current thread's shard hint set to `hint`, returning
+/// its result.
+///
+/// Slabs and pools whose [`Config`] sets [`SHARD_HINTS`] insert values into
+/// the hinted shard, rather than the current thread's shard, while `f` runs.
+/// Other slabs ignore the hint. The previous hint, if any, is restored when
+/// `f` returns (or panics), so hints may be nested.
+///
+/// To set a hint each time a future is polled, such as for every operation
+/// performed by a task on a work-stealing runtime, wrap it in a
+/// [`ShardHinted`].
+///
+/// # Examples
+///
+/// ```
+/// use sharded_slab::{Config, ShardId, Slab};
+///
+/// struct Hinted;
+///
+/// impl Config for Hinted {
+///     const SHARD_HINTS: bool = true;
+/// }
+///
+/// let slab = Slab::new_with_config::<Hinted>();
+/// let hint = ShardId::new(3);
+///
+/// let key = sharded_slab::with_shard_hint(hint, || slab.insert("hello").unwrap());
+/// assert_eq!(slab.shard_id(key), hint);
+/// ```
+///
+/// [`Config`]: crate::Config
+/// [`SHARD_HINTS`]: crate::Config::SHARD_HINTS
+pub fn with_shard_hint<R>(hint: ShardId, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous hint when dropped.
+    struct Restore(Option<usize>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let _ = REGISTRATION.try_with(|r| r.hint.set(self.0));
+        }
+    }
+
+    let _restore = REGISTRATION
+        .try_with(|r| Restore(r.hint.replace(Some(hint.0))))
+        .ok();
+    f()
+}
+
+// === impl ShardHinted ===
+
+impl<F> ShardHinted<F> {
+    /// Wraps `future` so that the current thread's shard hint is set to
+    /// `hint` each time it is polled.
+    ///
+    /// See [`with_shard_hint`] for details.
+    pub fn new(hint: ShardId, future: F) -> Self {
+        Self { hint, future }
+    }
+
+    /// Returns the hint set while the future is polled.
+    pub fn hint(&self) -> ShardId {
+        self.hint
+    }
+}
+
+impl<F: Future> Future for ShardHinted<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let hint = self.hint;
+        let future = unsafe {
+            // Safety: `future` is pinned whenever `self` is, since it is
+            // never moved out of `self`, and `ShardHinted` doesn't implement
+            // `Drop` or `Unpin` itself.
+            self.map_unchecked_mut(|this| &mut this.future)
+        };
+        with_shard_hint(hint, || future.poll(cx))
+    }
+}
+
 impl<C> PartialEq for Tid<C> {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -132,12 +412,16 @@ impl<C: cfg::Config> Copy for Tid<C> {}
 
 impl Registration {
     fn new() -> Self {
-        Self(Cell::new(None))
+        Self {
+            id: Cell::new(None),
+            shard: Cell::new(None),
+            hint: Cell::new(None),
+        }
     }
 
     #[inline(always)]
     fn current<C: cfg::Config>(&self) -> Tid<C> {
-        if let Some(tid) = self.0.get().map(Tid::new) {
+        if let Some(tid) = self.id.get().map(Tid::new) {
             return tid;
         }
 
@@ -172,11 +456,109 @@ impl Registration {
                 id
             });
 
-        self.0.set(Some(id));
+        self.id.set(Some(id));
         Tid::new(id)
     }
 }
 
+impl Registration {
+    /// Assigns `id` to this thread, if no other thread is using it.
+    ///
+    /// The thread's previous ID, if it had one, is released for reuse, just as
+    /// if the thread had exited.
+    fn bind(&self, id: usize) -> bool {
+        if self.id.get() == Some(id) {
+            return true;
+        }
+
+        let mut free = REGISTRY
+            .free
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if Self::take_free(&mut free, id) {
+            test_println!("bind: reusing free ID {}", id);
+        } else {
+            // If the ID hasn't been assigned yet, claim it, along with every
+            // ID before it; those are added to the free list so that they may
+            // still be assigned to other threads.
+            let mut next = REGISTRY.next.load(Ordering::Acquire);
+            loop {
+                if id < next {
+                    test_println!("bind: ID {} is in use", id);
+                    return false;
+                }
+                match REGISTRY.next.compare_exchange(
+                    next,
+                    id + 1,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => next = actual,
+                }
+            }
+            test_println!("bind: claimed new ID {}; freeing {}..{}", id, next, id);
+            free.extend(next..id);
+        }
+
+        self.assign(&mut free, id);
+        true
+    }
+
+    /// Assigns the first ID in `ids` that was released by the thread it was
+    /// previously assigned to, returning it.
+    fn adopt(&self, ids: impl IntoIterator<Item = usize>) -> Option<usize> {
+        let mut free = REGISTRY
+            .free
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let id = ids.into_iter().find(|&id| Self::take_free(&mut free, id))?;
+        test_println!("adopt: reusing free ID {}", id);
+        self.assign(&mut free, id);
+        Some(id)
+    }
+
+    /// Releases this thread's ID, returning `false` if it didn't have one.
+    fn unregister(&self) -> bool {
+        let id = match self.id.take() {
+            Some(id) => id,
+            None => return false,
+        };
+        test_println!("unregister: freeing ID {}", id);
+        // Any cached shard belongs to the released ID.
+        self.shard.set(None);
+        REGISTRY
+            .free
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push_back(id);
+        true
+    }
+
+    /// Removes `id` from the free list, returning `false` if it wasn't free.
+    fn take_free(free: &mut VecDeque<usize>, id: usize) -> bool {
+        match free.iter().position(|&free| free == id) {
+            Some(idx) => {
+                free.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces this thread's ID with `id`, releasing the previous one.
+    ///
+    /// Since `free` is locked, the previous ID can't be reused until the new
+    /// one has been assigned.
+    fn assign(&self, free: &mut VecDeque<usize>, id: usize) {
+        if let Some(prev) = self.id.replace(Some(id)) {
+            free.push_back(prev);
+        }
+        // Any cached shard belongs to the previous ID.
+        self.shard.set(None);
+    }
+}
+
 // Reusing thread IDs doesn't work under loom, since this `Drop` impl results in
 // an access to a `loom` lazy_static while the test is shutting down, which
 // panics. T_T
@@ -187,24 +569,223 @@ impl Drop for Registration {
     fn drop(&mut self) {
         use std::sync::PoisonError;
 
-        if let Some(id) = self.0.get() {
+        if let Some(id) = self.id.get() {
             let mut free_list = REGISTRY.free.lock().unwrap_or_else(PoisonError::into_inner);
             free_list.push_back(id);
         }
     }
 }
 
-#[cfg(all(test, not(loom)))]
+#[cfg(all(any(test, feature = "testing"), not(loom)))]
 pub(crate) fn with<R>(tid: usize, f: impl FnOnce() -> R) -> R {
     struct Guard(Option<usize>);
 
     impl Drop for Guard {
         fn drop(&mut self) {
-            REGISTRATION.with(|r| r.0.set(self.0.take()));
+            REGISTRATION.with(|r| {
+                r.id.set(self.0.take());
+                r.shard.set(None);
+            });
         }
     }
 
-    let prev = REGISTRATION.with(|r| r.0.replace(Some(tid)));
+    // Overriding the thread ID invalidates any cached shard, which belongs to
+    // the real thread ID.
+    let prev = REGISTRATION.with(|r| {
+        r.shard.set(None);
+        r.id.replace(Some(tid))
+    });
     let _guard = Guard(prev);
     f()
 }
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::{ShardId, Slab};
+    use std::{
+        sync::{mpsc, Arc},
+        thread,
+    };
+
+    #[test]
+    fn bind_current_thread() {
+        let slab = Arc::new(Slab::new());
+        let shard = ShardId::new(1000);
+
+        let (bound_tx, bound_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let s = slab.clone();
+        let bound = thread::spawn(move || {
+            assert!(s.bind_current_thread_to(shard));
+            assert_eq!(s.current_shard_id(), Some(shard));
+            let key = s.insert(1).unwrap();
+            assert_eq!(s.shard_id(key), shard);
+            bound_tx.send(()).unwrap();
+            let _ = done_rx.recv();
+        });
+
+        // While the first thread is bound to the shard, no other thread may
+        // be.
+        bound_rx.recv().unwrap();
+        thread::spawn(move || {
+            let prev = slab.current_shard_id();
+            assert!(!slab.bind_current_thread_to(shard));
+            assert_eq!(slab.current_shard_id(), prev);
+            assert!(!slab.bind_current_thread_to(ShardId::new(!0)));
+        })
+        .join()
+        .unwrap();
+
+        drop(done_tx);
+        bound.join().unwrap();
+    }
+
+    #[test]
+    fn adopt_orphaned_shard() {
+        let slab = Arc::new(Slab::new());
+        let s = slab.clone();
+        let (orphan, freed) = thread::spawn(move || {
+            // Use a shard that no other test uses, and which is behind many
+            // free IDs, so that threads spawned by other tests won't be
+            // assigned it once this thread exits.
+            assert!(s.bind_current_thread_to(ShardId::new(2000)));
+            s.insert(1).unwrap();
+            let freed = s.insert(2).unwrap();
+            assert!(s.remove(freed));
+            (s.shard_id(freed), freed)
+        })
+        .join()
+        .unwrap();
+
+        // The owning thread has exited, so its shard is the only orphan.
+        thread::spawn(move || {
+            let shard = slab
+                .adopt_orphaned_shard()
+                .expect("the owning thread has exited");
+            assert_eq!(shard, orphan);
+            assert_eq!(slab.current_shard_id(), Some(orphan));
+
+            // The slot freed by the exited thread is reused.
+            let key = slab.insert(3).unwrap();
+            assert_eq!(slab.shard_id(key), orphan);
+            assert_eq!(addr(key), addr(freed));
+            assert_ne!(key, freed);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn insert_during_tls_destruction() {
+        struct InsertOnDrop(Arc<Slab<usize>>, mpsc::Sender<(bool, Option<usize>)>);
+
+        impl Drop for InsertOnDrop {
+            fn drop(&mut self) {
+                let registered = self.0.current_shard_id().is_some();
+                let _ = self.1.send((registered, self.0.insert(1)));
+            }
+        }
+
+        std::thread_local! {
+            static GUARD: std::cell::RefCell<Option<InsertOnDrop>> = std::cell::RefCell::new(None);
+        }
+
+        let slab = Arc::new(Slab::new());
+        let (tx, rx) = mpsc::channel();
+        let s = slab.clone();
+        thread::spawn(move || {
+            // Thread-locals are typically destroyed in the reverse of the
+            // order in which they were initialized, so initializing the guard
+            // before the thread's ID makes it likely that the guard is dropped
+            // after the ID has been released.
+            GUARD.with(|guard| *guard.borrow_mut() = Some(InsertOnDrop(s.clone(), tx)));
+            s.insert(0).unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let (registered, key) = rx.recv().unwrap();
+        assert_eq!(registered, key.is_some());
+        if let Some(key) = key {
+            assert_eq!(slab.get(key).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn use_during_tls_destruction() {
+        use crate::{FixedSlab, Pool};
+
+        struct Slabs {
+            slab: Arc<Slab<usize>>,
+            pool: Pool<String>,
+            fixed: FixedSlab<usize>,
+            key: usize,
+        }
+
+        struct UseOnDrop(Option<Slabs>, mpsc::Sender<Vec<bool>>);
+
+        impl Drop for UseOnDrop {
+            fn drop(&mut self) {
+                let Slabs {
+                    slab,
+                    pool,
+                    fixed,
+                    key,
+                } = self.0.take().unwrap();
+                let registered = crate::register_thread().is_some();
+                // None of these may panic, since a panic in a thread-local's
+                // destructor aborts the process.
+                let results = vec![
+                    registered,
+                    slab.current_shard_id().is_some(),
+                    slab.insert(1).is_some(),
+                    slab.vacant_entry().is_some(),
+                    pool.create_with(|s| s.push_str("hello")).is_some(),
+                    fixed.insert(1).is_some(),
+                    slab.get(key).is_some(),
+                    !slab.is_local(key) || registered,
+                    slab.remove(key),
+                ];
+                let _ = self.1.send(results);
+            }
+        }
+
+        std::thread_local! {
+            static GUARD: std::cell::RefCell<Option<UseOnDrop>> = std::cell::RefCell::new(None);
+        }
+
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(0).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let s = slab.clone();
+        thread::spawn(move || {
+            let slabs = Slabs {
+                slab: s.clone(),
+                pool: Pool::new(),
+                fixed: FixedSlab::new(32),
+                key,
+            };
+            // As above, initialize the guard before the thread's ID, so that
+            // it is likely to be dropped after the ID has been released.
+            GUARD.with(|guard| *guard.borrow_mut() = Some(UseOnDrop(Some(slabs), tx)));
+            s.insert(0).unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let results = rx.recv().unwrap();
+        let registered = results[0];
+        // Every operation that needs the current thread's shard agrees on
+        // whether the thread still has one...
+        for &result in &results[1..6] {
+            assert_eq!(result, registered, "{:?}", results);
+        }
+        // ...while operations on an existing key always work.
+        assert!(results[6..].iter().all(|&result| result), "{:?}", results);
+        assert!(!slab.contains(key));
+    }
+
+    fn addr(key: usize) -> crate::page::Addr {
+        crate::page::indices::<crate::DefaultConfig>(key).0
+    }
+}
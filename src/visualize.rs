@@ -0,0 +1,283 @@
+use crate::{
+    cfg,
+    page::{FreeChain, Layout, SlotState},
+    ShardId, Slab,
+};
+use std::fmt::Write;
+
+/// The format of the diagram returned by [`Slab::visualize`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiagramFormat {
+    /// A plain-text diagram, suitable for logs and terminals.
+    Ascii,
+    /// A [Graphviz] `digraph`, which can be rendered with `dot`.
+    ///
+    /// [Graphviz]: https://graphviz.org/
+    Dot,
+}
+
+/// The number of slots shown on each line of an ASCII slot map.
+const ROW_LEN: usize = 64;
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Renders a diagram of the slab's shards, their allocated pages, and the
+    /// free lists in each page.
+    ///
+    /// This is intended to help debug problems with the slab's free lists:
+    /// for each page, the diagram shows which slots are occupied, and the
+    /// order of the slots on the page's local and remote free lists, starting
+    /// from their heads. A vacant slot which isn't on either list has been
+    /// leaked, and a list which links back to one of its own slots is marked
+    /// as a cycle.
+    ///
+    /// Slots are identified by their index in the shard, rather than by key,
+    /// since a vacant slot has no key.
+    ///
+    /// Because a shard's local free list may only be accessed by the thread
+    /// that owns it, this requires exclusive access to the slab.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sharded_slab::{DiagramFormat, Slab};
+    ///
+    /// let mut slab = Slab::new();
+    /// let key = slab.insert("hello").unwrap();
+    /// slab.insert("world").unwrap();
+    /// slab.remove(key);
+    ///
+    /// let diagram = slab.visualize(DiagramFormat::Ascii);
+    /// assert!(diagram.contains("local:  0 -> 2..31 (never used)"));
+    ///
+    /// let dot = slab.visualize(DiagramFormat::Dot);
+    /// assert!(dot.starts_with("digraph slab {"));
+    /// ```
+    pub fn visualize(&mut self, format: DiagramFormat) -> String {
+        let shards = self
+            .shards
+            .iter_mut()
+            .map(|shard| unsafe {
+                // Safety: we have exclusive access to the slab.
+                shard.layout()
+            })
+            .collect::<Vec<_>>();
+        let mut out = String::new();
+        match format {
+            DiagramFormat::Ascii => ascii(&mut out, &shards),
+            DiagramFormat::Dot => dot(&mut out, &shards),
+        }
+        .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+fn ascii(out: &mut String, shards: &[(ShardId, Vec<Layout>)]) -> std::fmt::Result {
+    writeln!(
+        out,
+        "slots: # occupied, . vacant, q quarantined, _ never used"
+    )?;
+    for (id, pages) in shards {
+        writeln!(out, "shard {}", id)?;
+        let allocated = pages.iter().filter(|page| page.allocated).count();
+        let pages = pages.iter().enumerate().filter(|(_, page)| page.allocated);
+        for (n, (idx, page)) in pages.enumerate() {
+            let (branch, stem) = if n + 1 == allocated {
+                ("`-", "  ")
+            } else {
+                ("|-", "| ")
+            };
+            let occupied = page
+                .states
+                .iter()
+                .filter(|&&state| state == SlotState::Occupied)
+                .count();
+            writeln!(
+                out,
+                "{} page {} (slots {}..{}): {}/{} occupied",
+                branch,
+                idx,
+                page.prev_sz,
+                page.prev_sz + page.size,
+                occupied,
+                page.size
+            )?;
+            for row in (0..page.size).step_by(ROW_LEN) {
+                let map = (row..page.size.min(row + ROW_LEN))
+                    .map(|offset| match page.states.get(offset) {
+                        Some(SlotState::Occupied) => '#',
+                        Some(SlotState::Vacant) => '.',
+                        Some(SlotState::Quarantined) => 'q',
+                        None => '_',
+                    })
+                    .collect::<String>();
+                let label = if row == 0 { "slots: " } else { "       " };
+                writeln!(out, "{} {} {}", stem, label, map)?;
+            }
+            writeln!(out, "{} local:  {}", stem, ascii_chain(page, &page.local))?;
+            writeln!(out, "{} remote: {}", stem, ascii_chain(page, &page.remote))?;
+        }
+        if allocated == 0 {
+            writeln!(out, "`- (no pages allocated)")?;
+        }
+    }
+    Ok(())
+}
+
+fn ascii_chain(page: &Layout, chain: &FreeChain) -> String {
+    if chain.head >= page.size {
+        return "(empty)".to_string();
+    }
+    let mut links = chain
+        .offsets
+        .iter()
+        .map(|offset| (page.prev_sz + offset).to_string())
+        .collect::<Vec<_>>();
+    if chain.cycle {
+        links.push(format!("{} (cycle!)", page.prev_sz + chain.end));
+    } else if chain.end < page.size {
+        links.push(format!(
+            "{}..{} (never used)",
+            page.prev_sz + chain.end,
+            page.prev_sz + page.size - 1
+        ));
+    }
+    links.join(" -> ")
+}
+
+fn dot(out: &mut String, shards: &[(ShardId, Vec<Layout>)]) -> std::fmt::Result {
+    writeln!(out, "digraph slab {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+    writeln!(out, "    node [shape=record, fontname=monospace];")?;
+    for (id, pages) in shards {
+        let shard = format!("shard_{}", id);
+        writeln!(out, "    {} [label=\"shard {}\"];", shard, id)?;
+        for (idx, page) in pages.iter().enumerate().filter(|(_, page)| page.allocated) {
+            let node = format!("{}_page_{}", shard, idx);
+            let occupied = page
+                .states
+                .iter()
+                .filter(|&&state| state == SlotState::Occupied)
+                .count();
+            writeln!(
+                out,
+                "    {} [label=\"{{page {}|slots {}..{}|{}/{} occupied}}\"];",
+                node,
+                idx,
+                page.prev_sz,
+                page.prev_sz + page.size,
+                occupied,
+                page.size
+            )?;
+            writeln!(out, "    {} -> {};", shard, node)?;
+            dot_chain(out, &node, "local", page, &page.local)?;
+            dot_chain(out, &node, "remote", page, &page.remote)?;
+        }
+    }
+    writeln!(out, "}}")
+}
+
+fn dot_chain(
+    out: &mut String,
+    page_node: &str,
+    name: &str,
+    page: &Layout,
+    chain: &FreeChain,
+) -> std::fmt::Result {
+    if chain.head >= page.size {
+        return Ok(());
+    }
+    let slot = |offset: usize| format!("{}_slot_{}", page_node, page.prev_sz + offset);
+    let mut prev = page_node.to_string();
+    let mut label = format!(" [label=\"{}\"]", name);
+    for &offset in &chain.offsets {
+        let node = slot(offset);
+        writeln!(
+            out,
+            "    {} [label=\"{}\", shape=box];",
+            node,
+            page.prev_sz + offset
+        )?;
+        writeln!(out, "    {} -> {}{};", prev, node, label)?;
+        prev = node;
+        label = String::new();
+    }
+    if chain.cycle {
+        writeln!(out, "    {} -> {} [color=red];", prev, slot(chain.end))?;
+    } else if chain.end < page.size {
+        let node = format!("{}_{}_unused", page_node, name);
+        writeln!(
+            out,
+            "    {} [label=\"{}..{} (never used)\", shape=box, style=dashed];",
+            node,
+            page.prev_sz + chain.end,
+            page.prev_sz + page.size - 1
+        )?;
+        writeln!(out, "    {} -> {}{};", prev, node, label)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    struct TinyConfig;
+
+    impl Config for TinyConfig {
+        const INITIAL_PAGE_SIZE: usize = 4;
+        const MAX_PAGES: usize = 2;
+    }
+
+    #[test]
+    fn ascii() {
+        let mut slab = Slab::new_with_config::<TinyConfig>();
+        let keys = (0..5).map(|i| slab.insert(i).unwrap()).collect::<Vec<_>>();
+        slab.remove(keys[1]);
+        slab.remove(keys[3]);
+        let id = slab.shard_id(keys[0]);
+
+        let expected = format!(
+            "\
+slots: # occupied, . vacant, q quarantined, _ never used
+shard {}
+|- page 0 (slots 0..4): 2/4 occupied
+|  slots:  #.#.
+|  local:  3 -> 1
+|  remote: (empty)
+`- page 1 (slots 4..12): 1/8 occupied
+   slots:  #_______
+   local:  5..11 (never used)
+   remote: (empty)
+",
+            id
+        );
+        assert_eq!(slab.visualize(DiagramFormat::Ascii), expected);
+    }
+
+    #[test]
+    fn dot() {
+        let mut slab = Slab::new_with_config::<TinyConfig>();
+        let key = slab.insert(1).unwrap();
+        slab.remove(key);
+        let id = slab.shard_id(key);
+
+        let expected = format!(
+            "\
+digraph slab {{
+    rankdir=LR;
+    node [shape=record, fontname=monospace];
+    shard_{id} [label=\"shard {id}\"];
+    shard_{id}_page_0 [label=\"{{page 0|slots 0..4|0/4 occupied}}\"];
+    shard_{id} -> shard_{id}_page_0;
+    shard_{id}_page_0_slot_0 [label=\"0\", shape=box];
+    shard_{id}_page_0 -> shard_{id}_page_0_slot_0 [label=\"local\"];
+    shard_{id}_page_0_local_unused [label=\"1..3 (never used)\", shape=box, style=dashed];
+    shard_{id}_page_0_slot_0 -> shard_{id}_page_0_local_unused;
+}}
+",
+            id = id
+        );
+        assert_eq!(slab.visualize(DiagramFormat::Dot), expected);
+    }
+}
@@ -0,0 +1,697 @@
+#[cfg(feature = "async")]
+use crate::{
+    cfg::{self, CfgPrivate},
+    DefaultConfig, Pack, Slab,
+};
+#[cfg(feature = "async")]
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{fence, AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// The tasks waiting for a slot in a shard array to be freed, or for a guard
+/// referencing a value in the array to be released.
+///
+/// This is shared by every shard in the array, so that a task waiting to
+/// insert is woken when a slot in any shard is freed, rather than just the
+/// one it last tried to insert into.
+///
+/// Without the `async` feature, nothing can wait, so this is empty and
+/// notifying it does nothing.
+#[derive(Debug, Default)]
+pub(crate) struct Vacancies {
+    /// Whether there may be any waiting tasks.
+    ///
+    /// This is checked every time a slot is freed, so that the lock is only
+    /// acquired if someone is waiting.
+    #[cfg(feature = "async")]
+    waiting: AtomicBool,
+    #[cfg(feature = "async")]
+    waiters: Mutex<Waiters>,
+}
+
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+struct Waiters {
+    /// Tasks waiting for any slot to be freed, so that they can insert.
+    inserts: Vec<Waker>,
+    /// Removals waiting for the slot with a given key to be freed, by key.
+    removals: HashMap<usize, Vec<Arc<Removal>>>,
+    /// Tasks waiting for a guard referencing the value with a given key to be
+    /// released, so that they can take or lock it, by key.
+    releases: HashMap<usize, Vec<Waker>>,
+}
+
+/// The state shared between a [`RemoveAsync`] future and the [`Vacancies`]
+/// that will complete it.
+#[cfg(feature = "async")]
+#[derive(Debug, Default)]
+struct Removal {
+    cleared: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// A future which resolves once a value removed from a [`Slab`] has been
+/// dropped.
+///
+/// This is returned by [`Slab::remove_async`].
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[must_use = "futures do nothing unless polled"]
+pub struct RemoveAsync<'a> {
+    vacancies: &'a Vacancies,
+    /// The key being removed, without any reserved bits.
+    key: usize,
+    removal: Option<Arc<Removal>>,
+}
+
+/// A future which takes a value out of a [`Slab`] once it is no longer being
+/// accessed.
+///
+/// This is returned by [`Slab::take_async`].
+#[cfg(feature = "async")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+#[must_use = "futures do nothing unless polled"]
+pub struct TakeAsync<'a, T, C: cfg::Config = DefaultConfig> {
+    slab: &'a Slab<T, C>,
+    key: usize,
+    /// The waker registered for the last time this was polled, if any.
+    waker: Option<Waker>,
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Removes the value associated with the given key from the slab,
+    /// returning a future which resolves once the value has been dropped.
+    ///
+    /// The value is marked for removal immediately, just as if [`remove`]
+    /// were called. If the value is not being accessed, it is dropped before
+    /// this returns, and the future is already complete. Otherwise, it is
+    /// dropped when the last [`Entry`] or [`OwnedEntry`] referencing it is
+    /// dropped, possibly on another thread, and the future resolves after
+    /// that. This allows code which must be sure that some state has been
+    /// destroyed (such as during a graceful shutdown) to wait for it without
+    /// polling the slab.
+    ///
+    /// The future resolves to `true` if a value was removed, or `false` if
+    /// the slab didn't contain a value for the given key.
+    ///
+    /// If the slab has a [reaper], the value is passed to the reaper rather
+    /// than being dropped by the slab, and the future resolves once the value
+    /// has been moved out of the slab to be passed to it. Since the reaper
+    /// may send the value elsewhere to be dropped, the future may resolve
+    /// before the value has been dropped, or even before the reaper is called.
+    ///
+    /// If the slab is dropped before the value is, the future never
+    /// completes, so it must not outlive the slab --- which it borrows.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Slab;
+    /// async fn close(slab: &Slab<String>, key: usize) {
+    ///     // Once this completes, the value has been dropped, even if other
+    ///     // tasks were still accessing it when it was removed.
+    ///     slab.remove_async(key).await;
+    /// }
+    /// ```
+    ///
+    /// [`remove`]: Slab::remove
+    /// [`Entry`]: crate::Entry
+    /// [`OwnedEntry`]: crate::OwnedEntry
+    /// [reaper]: Slab::with_reaper
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn remove_async(&self, key: usize) -> RemoveAsync<'_> {
+        let vacancies = self.shards.vacancies();
+        let stripped = C::strip_reserved(key);
+        // Start watching before removing the value, so that we don't miss it
+        // being cleared by another thread.
+        let removal = vacancies.watch_removal(stripped);
+        if !self.remove(key) {
+            vacancies.unwatch_removal(stripped, &removal);
+            return RemoveAsync {
+                vacancies,
+                key: stripped,
+                removal: None,
+            };
+        }
+        RemoveAsync {
+            vacancies,
+            key: stripped,
+            removal: Some(removal),
+        }
+    }
+
+    /// Removes the value associated with the given key from the slab,
+    /// returning a future which resolves to the value once it is no longer
+    /// being accessed.
+    ///
+    /// This is like [`take`], but rather than blocking the current thread
+    /// until every guard referencing the value has been dropped, the future
+    /// waits to be woken when a guard is dropped, so that it doesn't block an
+    /// executor thread. If the slab doesn't contain a value for the given
+    /// key, or the value is [removed] by another thread while waiting, the
+    /// future resolves to `None`.
+    ///
+    /// Unlike [`take`], the value isn't removed until it is no longer being
+    /// accessed, so new guards referencing it may be acquired while the
+    /// future is waiting. A value which is constantly being accessed may
+    /// therefore never be taken.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use sharded_slab::Slab;
+    /// async fn finish(slab: &Slab<String>, key: usize) -> Option<String> {
+    ///     // Other tasks may still be reading the value; wait for them to
+    ///     // finish without blocking this thread.
+    ///     slab.take_async(key).await
+    /// }
+    /// ```
+    ///
+    /// [`take`]: Slab::take
+    /// [removed]: Slab::remove
+    #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+    pub fn take_async(&self, key: usize) -> TakeAsync<'_, T, C> {
+        TakeAsync {
+            slab: self,
+            key,
+            waker: None,
+        }
+    }
+}
+
+// === impl Vacancies ===
+
+impl Vacancies {
+    /// Wakes every task waiting for a slot to be freed, after the slot for
+    /// `key` was freed.
+    ///
+    /// `key` must not include any reserved bits.
+    #[inline]
+    pub(crate) fn notify(&self, key: usize) {
+        let _ = key;
+        #[cfg(feature = "async")]
+        {
+            // Pairs with the fence in `register`: either the waiting task
+            // sees the slot we just freed when it tries again, or we see
+            // that it's waiting.
+            fence(Ordering::SeqCst);
+            if self.waiting.load(Ordering::Relaxed) {
+                self.notify_slow(key);
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[cold]
+    fn notify_slow(&self, key: usize) {
+        // Take the waiters under the lock, but wake them after releasing it,
+        // since a waker may run code which frees a slot and notifies again.
+        let (inserts, removals) = {
+            let mut waiters = lock(&self.waiters);
+            let inserts = std::mem::take(&mut waiters.inserts);
+            let removals = waiters.removals.remove(&key).unwrap_or_default();
+            self.waiting.store(!waiters.is_empty(), Ordering::Relaxed);
+            (inserts, removals)
+        };
+        test_println!(
+            "vacancies: freed {:#x}; waking {} inserts and {} removals",
+            key,
+            inserts.len(),
+            removals.len()
+        );
+        for waker in inserts {
+            waker.wake();
+        }
+        for removal in removals {
+            removal.cleared.store(true, Ordering::Release);
+            let waker = lock(&removal.waker).take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Wakes every task waiting for a guard referencing the value at `key` to
+    /// be released, after one was.
+    ///
+    /// `key` must not include any reserved bits.
+    #[inline]
+    pub(crate) fn notify_release(&self, key: usize) {
+        let _ = key;
+        #[cfg(feature = "async")]
+        {
+            // Pairs with the fence in `watch_release`.
+            fence(Ordering::SeqCst);
+            if self.waiting.load(Ordering::Relaxed) {
+                self.notify_release_slow(key);
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[cold]
+    fn notify_release_slow(&self, key: usize) {
+        let wakers = {
+            let mut waiters = lock(&self.waiters);
+            let wakers = waiters.releases.remove(&key).unwrap_or_default();
+            self.waiting.store(!waiters.is_empty(), Ordering::Relaxed);
+            wakers
+        };
+        test_println!(
+            "vacancies: released {:#x}; waking {} tasks",
+            key,
+            wakers.len()
+        );
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Registers `waker` to be woken the next time a guard referencing the
+    /// value at `key` is released.
+    ///
+    /// `key` must not include any reserved bits. The caller must try to access
+    /// the value again after this returns.
+    #[cfg(feature = "async")]
+    pub(crate) fn watch_release(&self, key: usize, waker: &Waker) {
+        {
+            let mut waiters = lock(&self.waiters);
+            let wakers = waiters.releases.entry(key).or_default();
+            if !wakers.iter().any(|w| w.will_wake(waker)) {
+                wakers.push(waker.clone());
+            }
+            self.waiting.store(true, Ordering::Relaxed);
+        }
+        fence(Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn unwatch_release(&self, key: usize, waker: &Waker) {
+        let mut waiters = lock(&self.waiters);
+        if let Some(wakers) = waiters.releases.get_mut(&key) {
+            wakers.retain(|w| !w.will_wake(waker));
+            if wakers.is_empty() {
+                waiters.releases.remove(&key);
+            }
+        }
+    }
+
+    /// Wakes every task waiting for a slot to be freed, after the shard array
+    /// was closed.
+    ///
+    /// Once the array is closed, no slot will ever become available to them,
+    /// so they must be woken to observe that it was closed.
+    #[inline]
+    pub(crate) fn close(&self) {
+        #[cfg(feature = "async")]
+        {
+            let inserts = std::mem::take(&mut lock(&self.waiters).inserts);
+            test_println!("vacancies: closed; waking {} inserts", inserts.len());
+            for waker in inserts {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Registers `waker` to be woken the next time a slot is freed.
+    ///
+    /// The caller must try to insert again after this returns, since a slot
+    /// may have been freed after its last attempt, but before the waker was
+    /// registered.
+    #[cfg(feature = "async")]
+    pub(crate) fn register(&self, waker: &Waker) {
+        {
+            let mut waiters = lock(&self.waiters);
+            if !waiters.inserts.iter().any(|w| w.will_wake(waker)) {
+                waiters.inserts.push(waker.clone());
+            }
+            self.waiting.store(true, Ordering::Relaxed);
+        }
+        fence(Ordering::SeqCst);
+    }
+
+    /// Starts watching for the slot for `key` to be freed.
+    ///
+    /// `key` must not include any reserved bits.
+    #[cfg(feature = "async")]
+    fn watch_removal(&self, key: usize) -> Arc<Removal> {
+        let removal = Arc::new(Removal::default());
+        {
+            let mut waiters = lock(&self.waiters);
+            waiters
+                .removals
+                .entry(key)
+                .or_default()
+                .push(removal.clone());
+            self.waiting.store(true, Ordering::Relaxed);
+        }
+        fence(Ordering::SeqCst);
+        removal
+    }
+
+    #[cfg(feature = "async")]
+    fn unwatch_removal(&self, key: usize, removal: &Arc<Removal>) {
+        let mut waiters = lock(&self.waiters);
+        if let Some(removals) = waiters.removals.get_mut(&key) {
+            removals.retain(|other| !Arc::ptr_eq(other, removal));
+            if removals.is_empty() {
+                waiters.removals.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Waiters {
+    fn is_empty(&self) -> bool {
+        self.inserts.is_empty() && self.removals.is_empty() && self.releases.is_empty()
+    }
+}
+
+// === impl RemoveAsync ===
+
+#[cfg(feature = "async")]
+impl Future for RemoveAsync<'_> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let removal = match self.removal {
+            Some(ref removal) => removal,
+            None => return Poll::Ready(false),
+        };
+        if removal.cleared.load(Ordering::Acquire) {
+            return Poll::Ready(true);
+        }
+        *lock(&removal.waker) = Some(cx.waker().clone());
+        // The value may have been cleared before we stored the waker.
+        if removal.cleared.load(Ordering::Acquire) {
+            return Poll::Ready(true);
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "async")]
+impl Drop for RemoveAsync<'_> {
+    fn drop(&mut self) {
+        if let Some(ref removal) = self.removal {
+            if !removal.cleared.load(Ordering::Acquire) {
+                self.vacancies.unwatch_removal(self.key, removal);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl fmt::Debug for RemoveAsync<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cleared = self
+            .removal
+            .as_ref()
+            .map(|removal| removal.cleared.load(Ordering::Acquire));
+        f.debug_struct("RemoveAsync")
+            .field("cleared", &cleared)
+            .finish()
+    }
+}
+
+// === impl TakeAsync ===
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> Future for TakeAsync<'_, T, C> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let tid = C::unpack_tid(self.key);
+        let shard = match self.slab.shards.get(tid.as_usize()) {
+            Some(shard) => shard,
+            None => return Poll::Ready(None),
+        };
+        if let Poll::Ready(value) = shard.poll_take(self.key) {
+            return Poll::Ready(value);
+        }
+
+        let vacancies = self.slab.shards.vacancies();
+        let key = C::strip_reserved(self.key);
+        if let Some(ref waker) = self.waker {
+            if !waker.will_wake(cx.waker()) {
+                vacancies.unwatch_release(key, waker);
+            }
+        }
+        vacancies.watch_release(key, cx.waker());
+        self.waker = Some(cx.waker().clone());
+
+        // A guard may have been released since we last tried.
+        shard.poll_take(self.key)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> Drop for TakeAsync<'_, T, C> {
+    fn drop(&mut self) {
+        if let Some(ref waker) = self.waker {
+            let key = C::strip_reserved(self.key);
+            self.slab.shards.vacancies().unwatch_release(key, waker);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T, C: cfg::Config> fmt::Debug for TakeAsync<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TakeAsync")
+            .field("key", &format_args!("{:#x}", self.key))
+            .finish()
+    }
+}
+
+/// Locks `mutex`, ignoring poisoning.
+///
+/// No user code runs while this lock is held, so a panic can't leave the
+/// protected state inconsistent.
+#[cfg(feature = "async")]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, not(loom), feature = "async"))]
+mod tests {
+    use crate::{tests::util::counting_waker, AsyncLockedSlab, Config, Pool, Slab};
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+        thread,
+    };
+
+    struct TinyConfig;
+
+    impl Config for TinyConfig {
+        const INITIAL_PAGE_SIZE: usize = 2;
+        const MAX_PAGES: usize = 1;
+    }
+
+    #[test]
+    fn create_async_waits_for_clear() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let pool = Arc::new(Pool::<String>::new_with_config::<TinyConfig>());
+        let key1 = pool.create().unwrap().key();
+        let key2 = pool.create().unwrap().key();
+
+        let mut create = pool.create_async();
+        assert!(Pin::new(&mut create).poll(&mut cx).is_pending());
+        assert_eq!(WAKES.load(Ordering::SeqCst), 0);
+
+        // Clearing a value on another thread wakes the task.
+        let pool2 = pool.clone();
+        thread::spawn(move || assert!(pool2.clear(key1)))
+            .join()
+            .unwrap();
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+
+        let guard = match Pin::new(&mut create).poll(&mut cx) {
+            Poll::Ready(Some(guard)) => guard,
+            _ => panic!("a slot was cleared, so creating should succeed"),
+        };
+        assert_ne!(guard.key(), key2);
+    }
+
+    #[test]
+    fn close_wakes_create_async() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let pool = Pool::<String>::new_with_config::<TinyConfig>();
+        let _key1 = pool.create().unwrap().key();
+        let _key2 = pool.create().unwrap().key();
+
+        let mut create = pool.create_async();
+        assert!(Pin::new(&mut create).poll(&mut cx).is_pending());
+
+        pool.close();
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            Pin::new(&mut create).poll(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+
+    #[test]
+    fn remove_async_waits_for_drop() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        struct CountDrops;
+
+        impl Drop for CountDrops {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(CountDrops).unwrap();
+        let guard = slab.clone().get_owned(key).unwrap();
+
+        let mut remove = slab.remove_async(key);
+        assert!(Pin::new(&mut remove).poll(&mut cx).is_pending());
+        assert_eq!(DROPS.load(Ordering::SeqCst), 0);
+
+        // Dropping the last guard on another thread drops the value, and
+        // completes the removal.
+        thread::spawn(move || drop(guard)).join().unwrap();
+        assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert_eq!(Pin::new(&mut remove).poll(&mut cx), Poll::Ready(true));
+
+        // Removing a value that's already gone completes immediately.
+        let mut remove = slab.remove_async(key);
+        assert_eq!(Pin::new(&mut remove).poll(&mut cx), Poll::Ready(false));
+
+        // So does removing a value that isn't being accessed.
+        let key = slab.insert(CountDrops).unwrap();
+        let mut remove = slab.remove_async(key);
+        assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+        assert_eq!(Pin::new(&mut remove).poll(&mut cx), Poll::Ready(true));
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn remove_async_wakes_only_its_key() {
+        static WAKES1: AtomicUsize = AtomicUsize::new(0);
+        static WAKES2: AtomicUsize = AtomicUsize::new(0);
+        let waker1 = counting_waker(&WAKES1);
+        let waker2 = counting_waker(&WAKES2);
+
+        let slab = Slab::new();
+        let key1 = slab.insert(1).unwrap();
+        let key2 = slab.insert(2).unwrap();
+        let guard1 = slab.get(key1).unwrap();
+        let guard2 = slab.get(key2).unwrap();
+
+        let mut remove1 = slab.remove_async(key1);
+        let mut remove2 = slab.remove_async(key2);
+        let mut cx1 = Context::from_waker(&waker1);
+        let mut cx2 = Context::from_waker(&waker2);
+        assert!(Pin::new(&mut remove1).poll(&mut cx1).is_pending());
+        assert!(Pin::new(&mut remove2).poll(&mut cx2).is_pending());
+
+        drop(guard2);
+        assert_eq!(WAKES1.load(Ordering::SeqCst), 0);
+        assert_eq!(WAKES2.load(Ordering::SeqCst), 1);
+        assert_eq!(Pin::new(&mut remove2).poll(&mut cx2), Poll::Ready(true));
+
+        // Dropping a pending removal stops watching its key.
+        drop(remove1);
+        let vacancies = slab.shards.vacancies();
+        assert!(super::lock(&vacancies.waiters).removals.is_empty());
+        drop(guard1);
+        assert_eq!(WAKES1.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn take_async_waits_for_release() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = Arc::new(Slab::new());
+        let key = slab.insert(String::from("hello")).unwrap();
+        let guard = slab.clone().get_owned(key).unwrap();
+
+        let mut take = slab.take_async(key);
+        assert!(Pin::new(&mut take).poll(&mut cx).is_pending());
+        // The value isn't removed while waiting.
+        assert!(slab.contains(key));
+
+        // Dropping the guard on another thread wakes the task.
+        thread::spawn(move || drop(guard)).join().unwrap();
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            Pin::new(&mut take).poll(&mut cx),
+            Poll::Ready(Some(String::from("hello")))
+        );
+        assert!(!slab.contains(key));
+
+        // Taking a value that's already gone completes immediately.
+        let mut take = slab.take_async(key);
+        assert_eq!(Pin::new(&mut take).poll(&mut cx), Poll::Ready(None));
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn lock_waits_for_unlock() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = AsyncLockedSlab::new();
+        let key = slab.insert(1).unwrap();
+
+        let mut guard = slab.try_lock(key).unwrap();
+        let mut lock = slab.lock(key);
+        assert!(Pin::new(&mut lock).poll(&mut cx).is_pending());
+        assert!(slab.try_lock(key).is_none());
+
+        // Releasing the lock on another thread wakes the task.
+        thread::scope(|s| {
+            s.spawn(move || *guard += 1);
+        });
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        let guard = match Pin::new(&mut lock).poll(&mut cx) {
+            Poll::Ready(guard) => guard.unwrap(),
+            Poll::Pending => panic!("the lock was released, so locking should succeed"),
+        };
+        assert_eq!(*guard, 2);
+
+        // Removing a locked value completes waiting tasks once it's unlocked.
+        let mut lock = slab.lock(key);
+        assert!(Pin::new(&mut lock).poll(&mut cx).is_pending());
+        assert!(slab.remove(key));
+        drop(guard);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 2);
+        assert!(matches!(
+            Pin::new(&mut lock).poll(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{
+    cfg::{self, CfgPrivate, DefaultConfig},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        UnsafeCell,
+    },
+    Slab,
+};
+use std::{fmt, task::Waker};
+
+/// A slab of [`Waker`]s.
+///
+/// Async runtimes and protocol libraries frequently need to store a waker for
+/// each of a set of pending operations, so that whichever thread completes an
+/// operation can wake the task waiting on it. A `WakerSlab` provides exactly
+/// this: each entry is a slot which a task may [`register`] its waker in, and
+/// which may be [woken] from any thread.
+///
+/// Rather than wrapping each waker in a `Mutex`, each entry uses a small
+/// state machine, so registering and waking never block. If an entry is
+/// woken while a task is registering its waker, the registering task is
+/// responsible for waking it, so the wakeup is never lost.
+///
+/// Since a task is woken at most once for each time it registers, a task
+/// must check whether the operation it is waiting on has completed _after_
+/// registering its waker, as with other wakeup primitives.
+///
+/// # Examples
+///
+/// ```
+/// # use sharded_slab::WakerSlab;
+/// use std::{
+///     future::Future,
+///     pin::Pin,
+///     sync::atomic::{AtomicBool, Ordering},
+///     task::{Context, Poll},
+/// };
+///
+/// struct Wait<'a> {
+///     wakers: &'a WakerSlab,
+///     key: usize,
+///     done: &'a AtomicBool,
+/// }
+///
+/// impl Future for Wait<'_> {
+///     type Output = ();
+///
+///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         self.wakers.register(self.key, cx.waker());
+///         // Check the condition after registering, so that a wakeup
+///         // between the check and the registration isn't missed.
+///         if self.done.load(Ordering::Acquire) {
+///             self.wakers.remove(self.key);
+///             return Poll::Ready(());
+///         }
+///         Poll::Pending
+///     }
+/// }
+///
+/// fn complete(wakers: &WakerSlab, key: usize, done: &AtomicBool) {
+///     done.store(true, Ordering::Release);
+///     wakers.wake(key);
+/// }
+/// ```
+///
+/// [`register`]: WakerSlab::register
+/// [woken]: WakerSlab::wake
+pub struct WakerSlab<C: cfg::Config = DefaultConfig> {
+    slab: Slab<AtomicWaker, C>,
+}
+
+/// A slot for a waker, which may be registered and taken concurrently.
+///
+/// This is the same protocol as `futures::task::AtomicWaker`: the waker may
+/// only be accessed by whoever moves the state out of `WAITING`.
+struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+/// No one is accessing the waker.
+const WAITING: usize = 0;
+/// A task is storing a new waker.
+const REGISTERING: usize = 0b01;
+/// Someone is taking the waker to wake it.
+const WAKING: usize = 0b10;
+
+impl WakerSlab {
+    /// Returns a new `WakerSlab` with the default configuration parameters.
+    pub fn new() -> Self {
+        Self::new_with_config()
+    }
+
+    /// Returns a new `WakerSlab` with the provided configuration parameters.
+    pub fn new_with_config<C: cfg::Config>() -> WakerSlab<C> {
+        WakerSlab {
+            slab: Slab::new_with_config(),
+        }
+    }
+}
+
+impl<C: cfg::Config> WakerSlab<C> {
+    /// Inserts an entry with no registered waker, returning its key.
+    ///
+    /// If this function returns `None`, then the shard for the current thread
+    /// is full and no entries can be added until some are removed, or the
+    /// maximum number of shards has been reached.
+    pub fn insert(&self) -> Option<usize> {
+        self.slab.insert(AtomicWaker::new())
+    }
+
+    /// Registers `waker` to be woken the next time the entry for `key` is
+    /// [woken], replacing any previously registered waker.
+    ///
+    /// If `waker` would wake the same task as the registered waker, the
+    /// registered waker is kept, so that polling a future repeatedly doesn't
+    /// clone its waker each time. If the entry is woken concurrently with
+    /// this call, `waker` is woken immediately.
+    ///
+    /// Returns `false` if the slab does not contain an entry for `key`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::WakerSlab;
+    /// # fn noop_waker() -> std::task::Waker {
+    /// #     use std::task::{RawWaker, RawWakerVTable, Waker};
+    /// #     static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    /// #     unsafe fn clone(_: *const ()) -> RawWaker { RawWaker::new(std::ptr::null(), &VTABLE) }
+    /// #     unsafe fn noop(_: *const ()) {}
+    /// #     unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    /// # }
+    /// let wakers = WakerSlab::new();
+    /// let key = wakers.insert().unwrap();
+    ///
+    /// assert!(wakers.register(key, &noop_waker()));
+    /// assert!(wakers.take(key).is_some());
+    /// assert!(wakers.take(key).is_none());
+    ///
+    /// wakers.remove(key);
+    /// assert!(!wakers.register(key, &noop_waker()));
+    /// ```
+    ///
+    /// [woken]: WakerSlab::wake
+    pub fn register(&self, key: usize, waker: &Waker) -> bool {
+        match self.slab.get(key) {
+            Some(entry) => {
+                entry.register(waker);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the waker registered for the entry for `key`, if there is one.
+    ///
+    /// This returns `None` if no waker is registered, if the entry is
+    /// currently being woken by another thread, or if the slab does not
+    /// contain an entry for `key`.
+    pub fn take(&self, key: usize) -> Option<Waker> {
+        self.slab.get(key)?.take()
+    }
+
+    /// Wakes the waker registered for the entry for `key`, returning `true`
+    /// if a waker was woken.
+    ///
+    /// The waker is removed from the entry, so a task must [register] again
+    /// to be woken again.
+    ///
+    /// [register]: WakerSlab::register
+    pub fn wake(&self, key: usize) -> bool {
+        match self.take(key) {
+            Some(waker) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the entry for `key`, dropping any registered waker, and
+    /// returns `true` if an entry was removed.
+    pub fn remove(&self, key: usize) -> bool {
+        self.slab.remove(key)
+    }
+
+    /// Returns `true` if the slab contains an entry for `key`.
+    pub fn contains(&self, key: usize) -> bool {
+        self.slab.contains(key)
+    }
+}
+
+impl Default for WakerSlab {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: cfg::Config> fmt::Debug for WakerSlab<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakerSlab")
+            .field("shards", &self.slab.shards)
+            .field("config", &C::debug())
+            .finish()
+    }
+}
+
+// === impl AtomicWaker ===
+
+impl AtomicWaker {
+    fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|actual| actual)
+        {
+            WAITING => {
+                self.waker.with_mut(|registered| unsafe {
+                    // Safety: moving the state out of `WAITING` gives us
+                    // exclusive access to the waker.
+                    match *registered {
+                        Some(ref registered) if registered.will_wake(waker) => {}
+                        _ => *registered = Some(waker.clone()),
+                    }
+                });
+                let res = self.state.compare_exchange(
+                    REGISTERING,
+                    WAITING,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                );
+                if let Err(actual) = res {
+                    // Someone tried to wake the entry while we were storing
+                    // the waker, and left it to us.
+                    debug_assert_eq!(actual, REGISTERING | WAKING);
+                    let waker = self.waker.with_mut(|registered| unsafe {
+                        // Safety: the state is still `REGISTERING`, so we
+                        // still have exclusive access.
+                        (*registered).take()
+                    });
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            WAKING => {
+                // The entry is being woken right now, so this wakeup would be
+                // missed; wake the new waker instead.
+                test_println!("-> register: concurrent wake");
+                waker.wake_by_ref();
+            }
+            state => {
+                // Another task is registering concurrently, which would be a
+                // bug in the caller; one of the two wakers wins.
+                debug_assert!(state == REGISTERING || state == REGISTERING | WAKING);
+            }
+        }
+    }
+
+    fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                let waker = self.waker.with_mut(|registered| unsafe {
+                    // Safety: moving the state out of `WAITING` gives us
+                    // exclusive access to the waker.
+                    (*registered).take()
+                });
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            state => {
+                // Either a task is registering, in which case it will wake
+                // itself when it sees `WAKING`, or another thread is already
+                // taking the waker.
+                debug_assert!(
+                    state == REGISTERING || state == REGISTERING | WAKING || state == WAKING
+                );
+                None
+            }
+        }
+    }
+}
+
+// Safety: the waker is only accessed by whoever moves the state out of
+// `WAITING`, and `Waker` is itself `Send` and `Sync`.
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl fmt::Debug for AtomicWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicWaker")
+            .field("state", &self.state.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::tests::util::counting_waker;
+
+    #[test]
+    fn register_and_wake() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+
+        let wakers = WakerSlab::new();
+        let key = wakers.insert().unwrap();
+        assert!(!wakers.wake(key));
+
+        assert!(wakers.register(key, &waker));
+        assert!(wakers.wake(key));
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+
+        // Waking takes the waker, so it isn't woken twice.
+        assert!(!wakers.wake(key));
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+
+        // Registering while the entry is being woken wakes the new waker.
+        let entry = wakers.slab.get(key).unwrap();
+        entry.state.store(WAKING, Ordering::SeqCst);
+        entry.register(&waker);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 2);
+        entry.state.store(WAITING, Ordering::SeqCst);
+        drop(entry);
+
+        assert!(wakers.remove(key));
+        assert!(!wakers.contains(key));
+        assert!(!wakers.register(key, &waker));
+    }
+}
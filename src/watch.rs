@@ -0,0 +1,404 @@
+use crate::{cfg, Slab};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, Weak,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// A stream of the keys of values inserted into a [`Slab`].
+///
+/// This is returned by [`Slab::watch_inserts`]. It receives the key of every
+/// value inserted into the slab after it was created, in the order in which
+/// the insertions were observed, until the slab is dropped.
+///
+/// Keys are buffered until they are received, so a `WatchInserts` that is
+/// never polled will grow without bound. Keys are reported once the value has
+/// been inserted, but a value may have been removed again by the time its key
+/// is received.
+///
+/// Keys may be received with [`try_recv`] or [`poll_recv`]. If the
+/// `futures-core` feature is enabled, `WatchInserts` also implements
+/// [`Stream`].
+///
+/// [`try_recv`]: WatchInserts::try_recv
+/// [`poll_recv`]: WatchInserts::poll_recv
+/// [`Stream`]: https://docs.rs/futures-core/0.3/futures_core/stream/trait.Stream.html
+pub struct WatchInserts {
+    channel: Arc<Channel>,
+}
+
+/// The subscribers to a slab's insertions.
+///
+/// Every subscriber is registered in each of a fixed number of stripes, and
+/// an insertion only notifies the stripe for the shard it was made on, so
+/// that threads inserting into different shards don't contend for one lock.
+pub(crate) struct Watchers {
+    stripes: [Stripe; STRIPES],
+}
+
+/// The number of stripes in a [`Watchers`].
+const STRIPES: usize = 8;
+
+/// The subscribers notified of insertions into some of a slab's shards.
+struct Stripe {
+    /// Whether there may be any subscribers.
+    ///
+    /// This is checked on every insertion, so that the lock is only acquired
+    /// if someone is watching.
+    active: AtomicBool,
+    subscribers: Mutex<Vec<Weak<Channel>>>,
+}
+
+struct Channel {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    keys: VecDeque<usize>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+impl<T, C: cfg::Config> Slab<T, C> {
+    /// Returns a [`WatchInserts`] that receives the key of every value
+    /// inserted into the slab from now on.
+    ///
+    /// This is useful when some other component must know about every entry
+    /// in the slab, without having to notify it at every site where a value
+    /// is inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use sharded_slab::Slab;
+    /// let slab = Slab::new();
+    /// let before = slab.insert("before").unwrap();
+    ///
+    /// let inserts = slab.watch_inserts();
+    /// let key1 = slab.insert("hello").unwrap();
+    /// let key2 = slab.insert("world").unwrap();
+    ///
+    /// assert_eq!(inserts.try_recv(), Some(key1));
+    /// assert_eq!(inserts.try_recv(), Some(key2));
+    /// assert_eq!(inserts.try_recv(), None);
+    /// # drop(before);
+    /// ```
+    pub fn watch_inserts(&self) -> WatchInserts {
+        self.watchers.subscribe()
+    }
+}
+
+// === impl Watchers ===
+
+impl Watchers {
+    const_fn! {
+        pub(crate) fn new() -> Self {
+            Self {
+                stripes: [
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                    Stripe::new(),
+                ],
+            }
+        }
+    }
+
+    fn subscribe(&self) -> WatchInserts {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State::default()),
+        });
+        for stripe in &self.stripes {
+            lock(&stripe.subscribers).push(Arc::downgrade(&channel));
+            stripe.active.store(true, Ordering::Release);
+        }
+        WatchInserts { channel }
+    }
+
+    /// Sends `key`, which was inserted into the shard with index `shard`, to
+    /// every subscriber.
+    #[inline]
+    pub(crate) fn notify(&self, shard: usize, key: usize) {
+        let stripe = &self.stripes[shard % STRIPES];
+        if stripe.active.load(Ordering::Acquire) {
+            stripe.notify_slow(key);
+        }
+    }
+}
+
+impl Drop for Watchers {
+    fn drop(&mut self) {
+        for stripe in &mut self.stripes {
+            let subscribers = stripe
+                .subscribers
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner);
+            for channel in subscribers.drain(..) {
+                if let Some(channel) = channel.upgrade() {
+                    channel.close();
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Watchers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let active = self
+            .stripes
+            .iter()
+            .any(|stripe| stripe.active.load(Ordering::Relaxed));
+        f.debug_struct("Watchers").field("active", &active).finish()
+    }
+}
+
+// === impl Stripe ===
+
+impl Stripe {
+    const_fn! {
+        fn new() -> Self {
+            Self {
+                active: AtomicBool::new(false),
+                subscribers: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[cold]
+    fn notify_slow(&self, key: usize) {
+        test_println!("watch: notify {:#x}", key);
+        // Collect the live channels under the lock, but send to them after
+        // releasing it, since sending wakes the subscriber's task.
+        let channels = {
+            let mut subscribers = lock(&self.subscribers);
+            let mut channels = Vec::with_capacity(subscribers.len());
+            subscribers.retain(|channel| match channel.upgrade() {
+                Some(channel) => {
+                    channels.push(channel);
+                    true
+                }
+                None => false,
+            });
+            self.active
+                .store(!subscribers.is_empty(), Ordering::Release);
+            channels
+        };
+        for channel in channels {
+            channel.send(key);
+        }
+    }
+}
+
+// === impl Channel ===
+
+impl Channel {
+    fn send(&self, key: usize) {
+        let waker = {
+            let mut state = lock(&self.state);
+            state.keys.push_back(key);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn close(&self) {
+        let waker = {
+            let mut state = lock(&self.state);
+            state.closed = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+// === impl WatchInserts ===
+
+impl WatchInserts {
+    /// Returns the key of the next inserted value, if one has been received.
+    ///
+    /// This returns `None` if no keys are buffered, whether or not the slab
+    /// has been dropped.
+    pub fn try_recv(&self) -> Option<usize> {
+        lock(&self.channel.state).keys.pop_front()
+    }
+
+    /// Polls for the key of the next inserted value.
+    ///
+    /// This returns `Poll::Ready(None)` once the slab has been dropped and
+    /// every buffered key has been received. Otherwise, if no keys are
+    /// buffered, the current task is woken when one is.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<usize>> {
+        let mut state = lock(&self.channel.state);
+        if let Some(key) = state.keys.pop_front() {
+            return Poll::Ready(Some(key));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Returns `true` if the slab being watched has been dropped.
+    ///
+    /// Keys received before the slab was dropped may still be buffered.
+    pub fn is_closed(&self) -> bool {
+        lock(&self.channel.state).closed
+    }
+}
+
+#[cfg(feature = "futures-core")]
+impl futures_core::Stream for WatchInserts {
+    type Item = usize;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl fmt::Debug for WatchInserts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = lock(&self.channel.state);
+        f.debug_struct("WatchInserts")
+            .field("buffered", &state.keys.len())
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+/// Locks `mutex`, ignoring poisoning.
+///
+/// No user code runs while these locks are held, so a panic can't leave the
+/// protected state inconsistent.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{tests::util::counting_waker, DefaultConfig, Pack, Tid};
+    use std::{sync::atomic::AtomicUsize, thread};
+
+    #[test]
+    fn receives_inserts_from_all_threads() {
+        let slab = Arc::new(Slab::new());
+        let inserts = slab.watch_inserts();
+
+        let mut keys = (0..10)
+            .map(|i| {
+                let slab = slab.clone();
+                thread::spawn(move || slab.insert(i).unwrap())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect::<Vec<_>>();
+        keys.push(
+            slab.vacant_entry()
+                .map(|entry| {
+                    let key = entry.key();
+                    entry.insert(10);
+                    key
+                })
+                .unwrap(),
+        );
+
+        let mut received = std::iter::from_fn(|| inserts.try_recv()).collect::<Vec<_>>();
+        keys.sort_unstable();
+        received.sort_unstable();
+        assert_eq!(keys, received);
+    }
+
+    #[test]
+    fn wakes_after_unlocking() {
+        use std::task::{RawWaker, RawWakerVTable};
+
+        // A waker which inserts into the slab it points to when it is woken.
+        // If it were woken while the stripe's lock is held, this would
+        // deadlock.
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake, drop);
+        static REENTERED: AtomicUsize = AtomicUsize::new(0);
+
+        fn clone(data: *const ()) -> RawWaker {
+            RawWaker::new(data, &VTABLE)
+        }
+        fn wake(data: *const ()) {
+            if REENTERED.fetch_add(1, Ordering::SeqCst) == 0 {
+                let slab = unsafe {
+                    // Safety: the waker points to the slab below, which
+                    // outlives it.
+                    &*(data as *const Slab<usize>)
+                };
+                slab.insert(2).unwrap();
+            }
+        }
+        fn drop(_: *const ()) {}
+
+        let slab = Slab::<usize>::new();
+        let waker = unsafe {
+            // Safety: the vtable's functions uphold the `RawWaker` contract.
+            Waker::from_raw(RawWaker::new(
+                &slab as *const Slab<usize> as *const (),
+                &VTABLE,
+            ))
+        };
+        let mut cx = Context::from_waker(&waker);
+        let mut inserts = slab.watch_inserts();
+        assert_eq!(inserts.poll_recv(&mut cx), Poll::Pending);
+
+        let key1 = slab.insert(1).unwrap();
+        assert_eq!(REENTERED.load(Ordering::SeqCst), 1);
+        assert_eq!(inserts.try_recv(), Some(key1));
+        assert!(inserts.try_recv().is_some());
+    }
+
+    #[test]
+    fn poll_wakes_and_closes() {
+        static WAKES: AtomicUsize = AtomicUsize::new(0);
+        let waker = counting_waker(&WAKES);
+        let mut cx = Context::from_waker(&waker);
+
+        let slab = Slab::new();
+        let mut inserts = slab.watch_inserts();
+        assert_eq!(inserts.poll_recv(&mut cx), Poll::Pending);
+
+        let key = slab.insert(1).unwrap();
+        assert_eq!(WAKES.load(Ordering::SeqCst), 1);
+        assert_eq!(inserts.poll_recv(&mut cx), Poll::Ready(Some(key)));
+        assert_eq!(inserts.poll_recv(&mut cx), Poll::Pending);
+
+        drop(slab);
+        assert_eq!(WAKES.load(Ordering::SeqCst), 2);
+        assert!(inserts.is_closed());
+        assert_eq!(inserts.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn dropped_watchers_are_pruned() {
+        let slab = Slab::new();
+        let inserts = slab.watch_inserts();
+        drop(inserts);
+
+        let key = slab.insert(1).unwrap();
+        let shard = Tid::<DefaultConfig>::from_packed(key).as_usize();
+        let stripe = &slab.watchers.stripes[shard % STRIPES];
+        assert!(!stripe.active.load(Ordering::Relaxed));
+    }
+}
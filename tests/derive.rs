@@ -0,0 +1,65 @@
+#![cfg(feature = "derive")]
+use sharded_slab::{Clear, Pool};
+use std::collections::HashMap;
+
+#[derive(Clear, Default)]
+struct Named {
+    map: HashMap<u32, String>,
+    name: String,
+    #[clear(default)]
+    count: usize,
+    #[clear(skip)]
+    id: usize,
+}
+
+#[derive(Clear, Default)]
+struct Tuple<T>(Vec<T>, #[clear(default)] Option<T>, #[clear(skip)] u8);
+
+#[derive(Clear)]
+struct Unit;
+
+#[test]
+fn clears_named_fields() {
+    let mut named = Named::default();
+    named.map.insert(1, "one".to_string());
+    named.name.push_str("hello");
+    named.count = 5;
+    named.id = 7;
+    let capacity = named.name.capacity();
+
+    named.clear();
+    assert!(named.map.is_empty());
+    assert!(named.name.is_empty());
+    assert_eq!(named.name.capacity(), capacity);
+    assert_eq!(named.count, 0);
+    assert_eq!(named.id, 7);
+}
+
+#[test]
+fn clears_tuple_fields() {
+    let mut tuple = Tuple(vec![1, 2, 3], Some(4), 5);
+    tuple.clear();
+    assert!(tuple.0.is_empty());
+    assert_eq!(tuple.1, None);
+    assert_eq!(tuple.2, 5);
+
+    Unit.clear();
+}
+
+#[test]
+fn derived_in_pool() {
+    let pool: Pool<Named> = Pool::new();
+    let key = pool
+        .create_with(|named| {
+            named.name.push_str("hello world");
+            named.count = 1;
+        })
+        .unwrap();
+    assert_eq!(pool.get(key).unwrap().name, "hello world");
+    assert!(pool.clear(key));
+
+    let key = pool.create_with(|_| {}).unwrap();
+    let named = pool.get(key).unwrap();
+    assert!(named.name.is_empty());
+    assert_eq!(named.count, 0);
+}